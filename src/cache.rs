@@ -1,22 +1,38 @@
+mod clear;
+mod export;
+mod gc;
+mod import;
+mod remote;
+mod stats;
+mod verify;
+
 use crate::{
+    cli::cache::{Args, Command},
+    config::Config,
+    context::cx,
     exec::{self, exec, sandbox_exec},
     hashing::WithHashingExt as _,
     io::read_file,
-    lint::HlintHint,
+    lint::{HlintHint, ShellcheckHint},
+    packages::{self, Package},
 };
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 use camino::{Utf8Path, Utf8PathBuf};
 use color_eyre::eyre::{self, ContextCompat as _};
 use const_random::const_random;
 use dashmap::DashMap;
 use etcetera::app_strategy::{AppStrategy as _, AppStrategyArgs, Xdg};
 use saphyr::{LoadableYamlNode as _, Yaml};
-use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqliteSynchronous};
+use sqlx::{
+    Connection as _,
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqliteSynchronous},
+};
 use std::{
+    collections::HashMap,
     hash::Hasher as _,
     str::{self, FromStr as _},
+    time::Duration,
 };
-use tempfile::tempdir;
 use tokio::{
     fs::{self, File},
     io::AsyncReadExt as _,
@@ -25,64 +41,92 @@ use tokio::{
 use twox_hash::XxHash3_64;
 use which::{which_global, which_in_global};
 
+/// Tool runs slower than this get a warning
+const SLOW_FILE_THRESHOLD: Duration = Duration::from_secs(10);
+
 // TODO: Only re-generated when this file is rebuilt
 const BE_BINARY_ID: u64 = const_random!(u64);
 
 pub struct Cache {
     sqlite: SqlitePool,
+    dir: Utf8PathBuf,
+    /// Skip reading and writing cached results, for `--no-cache`
+    disabled: bool,
     git_root: OnceCell<Utf8PathBuf>,
+    config: OnceCell<Config>,
+    packages: OnceCell<Vec<Package>>,
+    remote: OnceCell<Option<remote::Remote>>,
     which: DashMap<&'static str, Utf8PathBuf>,
     fourmolu_version: OnceCell<String>,
     fourmolu_config: OnceCell<(Utf8PathBuf, u64)>,
     fourmolu_extensions: OnceCell<(Vec<String>, u64)>,
-    nixfmt_version: OnceCell<String>,
+    nix_formatter_version: OnceCell<String>,
+    dhall_version: OnceCell<String>,
     hlint_version: OnceCell<String>,
     hlint_configs: OnceCell<(Vec<Utf8PathBuf>, u64)>,
+    ghc_version: OnceCell<String>,
+    weeder_version: OnceCell<String>,
+    stan_version: OnceCell<String>,
+    typos_version: OnceCell<String>,
+    typos_config: OnceCell<(Option<Utf8PathBuf>, u64)>,
+    shfmt_version: OnceCell<String>,
+    shellcheck_version: OnceCell<String>,
+    statix_version: OnceCell<String>,
+    deadnix_version: OnceCell<String>,
+    cabal_formatter: OnceCell<&'static str>,
+    cabal_formatter_version: OnceCell<String>,
 }
 
 impl Cache {
+    /// Open the cache in `dir`, or else the XDG cache directory
     #[tracing::instrument]
-    pub async fn new() -> eyre::Result<Self> {
-        let xdg = Xdg::new(AppStrategyArgs {
-            top_level_domain: String::from("com"),
-            author: String::from("Evan Relf"),
-            app_name: String::from("Be"),
-        })?;
-
-        let xdg_cache_dir = xdg.cache_dir();
-
-        fs::create_dir_all(&xdg_cache_dir).await?;
-
-        let sqlite_path = xdg.in_cache_dir("cache.sqlite");
-        let sqlite_path = sqlite_path.to_str().unwrap();
-
-        let sqlite_url = format!("sqlite://{sqlite_path}");
+    pub async fn new(dir: Option<&Utf8Path>, disabled: bool) -> eyre::Result<Self> {
+        let dir = match dir {
+            Some(dir) => dir.to_path_buf(),
+            None => default_dir()?,
+        };
 
-        let sqlite_opts = SqliteConnectOptions::from_str(&sqlite_url)?
-            .journal_mode(SqliteJournalMode::Wal)
-            .synchronous(SqliteSynchronous::Normal)
-            // .pragma("mmap_size", u32::MAX.to_string())
-            .create_if_missing(true);
+        fs::create_dir_all(&dir).await?;
 
-        let sqlite = SqlitePool::connect_with(sqlite_opts).await?;
+        let sqlite_path = dir.join("cache.sqlite");
 
-        if sqlite_valid(&sqlite).await? {
-            tracing::debug!("Using existing SQLite cache (exists and has same `be` binary ID)");
-        } else {
-            tracing::debug!("Creating new SQLite cache (missing or different `be` binary ID)");
-            sqlite_reset(&sqlite).await?;
-        }
+        let sqlite = match sqlite_open(&sqlite_path).await {
+            Ok(sqlite) => sqlite,
+            Err(error) => {
+                // Nothing in the cache is precious, so start over rather than failing every run
+                tracing::warn!("Rebuilding corrupt SQLite cache: {error}");
+                sqlite_remove(&sqlite_path).await?;
+                sqlite_open(&sqlite_path).await?
+            }
+        };
 
         Ok(Self {
             sqlite,
+            dir,
+            disabled,
             git_root: OnceCell::new(),
+            config: OnceCell::new(),
+            packages: OnceCell::new(),
+            remote: OnceCell::new(),
             which: DashMap::new(),
             fourmolu_version: OnceCell::new(),
             fourmolu_config: OnceCell::new(),
             fourmolu_extensions: OnceCell::new(),
-            nixfmt_version: OnceCell::new(),
+            nix_formatter_version: OnceCell::new(),
+            dhall_version: OnceCell::new(),
             hlint_version: OnceCell::new(),
             hlint_configs: OnceCell::new(),
+            ghc_version: OnceCell::new(),
+            weeder_version: OnceCell::new(),
+            stan_version: OnceCell::new(),
+            typos_version: OnceCell::new(),
+            typos_config: OnceCell::new(),
+            shfmt_version: OnceCell::new(),
+            shellcheck_version: OnceCell::new(),
+            statix_version: OnceCell::new(),
+            deadnix_version: OnceCell::new(),
+            cabal_formatter: OnceCell::new(),
+            cabal_formatter_version: OnceCell::new(),
         })
     }
 
@@ -97,10 +141,42 @@ impl Cache {
             .await
     }
 
+    /// The repo which cached results are kept for, so results from other repos aren't mixed in
+    async fn repo(&self) -> eyre::Result<&str> {
+        Ok(self.git_root().await?.as_str())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn config(&self) -> eyre::Result<&Config> {
+        self.config
+            .get_or_try_init(|| async {
+                let git_root = self.git_root().await?;
+                Config::load(git_root).await
+            })
+            .await
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn packages(&self) -> eyre::Result<&[Package]> {
+        self.packages
+            .get_or_try_init(|| async {
+                let git_root = self.git_root().await?;
+                packages::discover(git_root).await
+            })
+            .await
+            .map(|x| x.as_ref())
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn which(&self, binary: &'static str) -> eyre::Result<Utf8PathBuf> {
         let git_root = self.git_root().await?;
+        let config = self.config().await?;
         let bin = git_root.join(".bin");
+        let which_config = || {
+            let path = config.tool(binary).and_then(|tool| tool.path.as_ref());
+            let path = path.ok_or(which::Error::CannotFindBinaryPath)?;
+            eyre::Ok(git_root.join(path).into_std_path_buf())
+        };
         let which_path = || eyre::Ok(which_global(binary)?);
         let which_bin = || {
             let mut iter = which_in_global(binary, Some(bin))?;
@@ -108,7 +184,10 @@ impl Cache {
             eyre::Ok(path)
         };
         let path = self.which.entry(binary).or_try_insert_with(|| {
-            let path = which_path().or_else(|_| which_bin())?.canonicalize()?;
+            let path = which_config()
+                .or_else(|_| which_path())
+                .or_else(|_| which_bin())?
+                .canonicalize()?;
             let utf8_path = Utf8PathBuf::try_from(path)?;
             eyre::Ok(utf8_path)
         })?;
@@ -120,7 +199,9 @@ impl Cache {
         self.fourmolu_version
             .get_or_try_init(|| async {
                 let fourmolu = self.which("fourmolu").await?;
-                let stdout = sandbox_exec(exec::FOURMOLU_PROFILE, fourmolu, ["--version"]).await?;
+                let stdout =
+                    sandbox_exec("fourmolu", exec::FOURMOLU_PROFILE, fourmolu, ["--version"])
+                        .await?;
                 let version = String::from(str::from_utf8(&stdout)?.trim_end());
                 Ok(version)
             })
@@ -133,15 +214,13 @@ impl Cache {
         self.fourmolu_config
             .get_or_try_init(|| async {
                 let git_root = self.git_root().await?;
-                let path = git_root.join("fourmolu.yaml");
-                let temp_dir = tempdir()?;
-                let temp_path = Utf8PathBuf::try_from(temp_dir.path().join("fourmolu.yaml"))?;
+                let config = self.config().await?;
+                let path = git_root.join(config.tool_configs.fourmolu());
+                let temp_path = cx().temp_store.dir()?.join("fourmolu.yaml");
                 let copy_handle = tokio::spawn(fs::copy(path.clone(), temp_path.clone()));
                 let hash_handle = tokio::spawn(async move { file_hash(&path).await });
                 copy_handle.await??;
                 let hash = hash_handle.await??;
-                // TODO: gross
-                std::mem::forget(temp_dir);
                 Ok((temp_path, hash))
             })
             .await
@@ -152,7 +231,8 @@ impl Cache {
         self.fourmolu_extensions
             .get_or_try_init(|| async {
                 let git_root = self.git_root().await?;
-                let path = git_root.join("hpack-common/default-extensions.yaml");
+                let config = self.config().await?;
+                let path = git_root.join(config.tool_configs.default_extensions());
                 let (bytes, _) = read_file(&path).await?;
                 let str = str::from_utf8(&bytes)?;
                 let yaml = Yaml::load_from_str(str)?;
@@ -178,12 +258,20 @@ impl Cache {
             .await
     }
 
+    /// Version of the configured Nix formatter
     #[tracing::instrument(skip(self))]
-    pub async fn nixfmt_version(&self) -> eyre::Result<&str> {
-        self.nixfmt_version
+    pub async fn nix_formatter_version(&self) -> eyre::Result<&str> {
+        self.nix_formatter_version
             .get_or_try_init(|| async {
-                let fourmolu = self.which("nixfmt").await?;
-                let stdout = sandbox_exec(exec::NIXFMT_PROFILE, fourmolu, ["--version"]).await?;
+                let formatter = self.config().await?.format.nix_formatter;
+                let binary = self.which(formatter.binary()).await?;
+                let stdout = sandbox_exec(
+                    formatter.binary(),
+                    formatter.profile(),
+                    binary,
+                    ["--version"],
+                )
+                .await?;
                 let version = String::from(str::from_utf8(&stdout)?.trim_end());
                 Ok(version)
             })
@@ -191,15 +279,35 @@ impl Cache {
             .map(|x| x.as_ref())
     }
 
+    /// Directory holding `cache.sqlite` and other cached data
+    pub fn dir(&self) -> &Utf8Path {
+        &self.dir
+    }
+
+    /// Whether cached results for a tool may be used, which `--no-cache` or `cache = false` in the
+    /// tool's config turn off
+    #[tracing::instrument(skip(self))]
+    pub async fn is_enabled(&self, tool: &str) -> eyre::Result<bool> {
+        if self.disabled {
+            return Ok(false);
+        }
+        let config = self.config().await?;
+        Ok(config.tool(tool).and_then(|tool| tool.cache) != Some(false))
+    }
+
     #[tracing::instrument(skip_all)]
     pub async fn is_haskell_formatted(&self, source_hash: u64) -> eyre::Result<bool> {
+        if !self.is_enabled("fourmolu").await? {
+            return Ok(false);
+        }
+
         let version = self.fourmolu_version().await?;
 
         let (_, config_hash) = self.fourmolu_config().await?;
 
         let (_, extensions_hash) = self.fourmolu_extensions().await?;
 
-        let is_formatted = sqlx::query_scalar(
+        let mut is_formatted: bool = sqlx::query_scalar(
             "
             select exists(
                 select *
@@ -208,6 +316,7 @@ impl Cache {
                   and config_hash = $2
                   and extensions_hash = $3
                   and source_hash = $4
+                  and repo = $5
             )
             ",
         )
@@ -215,72 +324,255 @@ impl Cache {
         .bind(config_hash.to_string())
         .bind(extensions_hash.to_string())
         .bind(source_hash.to_string())
+        .bind(self.repo().await?)
         .fetch_one(&self.sqlite)
         .await?;
 
+        let key = [
+            version,
+            &config_hash.to_string(),
+            &extensions_hash.to_string(),
+            &source_hash.to_string(),
+        ];
+        if !is_formatted && self.remote_has("fourmolu", &key).await? {
+            self.mark_haskell_formatted(source_hash).await?;
+            is_formatted = true;
+        }
+
+        cx().metrics.cache_lookup("fourmolu", is_formatted);
+
         Ok(is_formatted)
     }
 
     #[tracing::instrument(skip_all)]
     pub async fn mark_haskell_formatted(&self, source_hash: u64) -> eyre::Result<()> {
+        if !self.is_enabled("fourmolu").await? {
+            return Ok(());
+        }
+
         let version = self.fourmolu_version().await?;
 
         let (_, config_hash) = self.fourmolu_config().await?;
 
         let (_, extensions_hash) = self.fourmolu_extensions().await?;
 
-        sqlx::query("insert or ignore into fourmolu values ($1, $2, $3, $4)")
+        sqlx::query("insert or ignore into fourmolu values ($1, $2, $3, $4, $5, unixepoch())")
             .bind(version)
             .bind(config_hash.to_string())
             .bind(extensions_hash.to_string())
             .bind(source_hash.to_string())
+            .bind(self.repo().await?)
             .execute(&self.sqlite)
             .await?;
 
+        let key = [
+            version,
+            &config_hash.to_string(),
+            &extensions_hash.to_string(),
+            &source_hash.to_string(),
+        ];
+        self.remote_put("fourmolu", &key, Bytes::new()).await?;
+
+        Ok(())
+    }
+
+    /// Output of `fourmolu` for contents it changed, so formatting them again doesn't run it
+    #[tracing::instrument(skip_all)]
+    pub async fn haskell_formatted_output(&self, source_hash: u64) -> eyre::Result<Option<Bytes>> {
+        if !self.is_enabled("fourmolu").await? {
+            return Ok(None);
+        }
+
+        let version = self.fourmolu_version().await?;
+
+        let (_, config_hash) = self.fourmolu_config().await?;
+
+        let (_, extensions_hash) = self.fourmolu_extensions().await?;
+
+        let output: Option<Vec<u8>> = sqlx::query_scalar(
+            "
+            select output
+            from fourmolu_outputs
+            where version = $1
+              and config_hash = $2
+              and extensions_hash = $3
+              and source_hash = $4
+              and repo = $5
+            ",
+        )
+        .bind(version)
+        .bind(config_hash.to_string())
+        .bind(extensions_hash.to_string())
+        .bind(source_hash.to_string())
+        .bind(self.repo().await?)
+        .fetch_optional(&self.sqlite)
+        .await?;
+
+        Ok(output.map(Bytes::from))
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn cache_haskell_formatted_output(
+        &self,
+        source_hash: u64,
+        output: &[u8],
+    ) -> eyre::Result<()> {
+        if !self.is_enabled("fourmolu").await? {
+            return Ok(());
+        }
+
+        let version = self.fourmolu_version().await?;
+
+        let (_, config_hash) = self.fourmolu_config().await?;
+
+        let (_, extensions_hash) = self.fourmolu_extensions().await?;
+
+        sqlx::query(
+            "insert or ignore into fourmolu_outputs values ($1, $2, $3, $4, $5, $6, unixepoch())",
+        )
+        .bind(version)
+        .bind(config_hash.to_string())
+        .bind(extensions_hash.to_string())
+        .bind(source_hash.to_string())
+        .bind(output)
+        .bind(self.repo().await?)
+        .execute(&self.sqlite)
+        .await?;
+
         Ok(())
     }
 
     #[tracing::instrument(skip_all)]
     pub async fn is_nix_formatted(&self, source_hash: u64) -> eyre::Result<bool> {
-        let version = self.nixfmt_version().await?;
+        let formatter = self.config().await?.format.nix_formatter.binary();
 
-        let is_formatted = sqlx::query_scalar(
+        if !self.is_enabled(formatter).await? {
+            return Ok(false);
+        }
+
+        let version = self.nix_formatter_version().await?;
+
+        let mut is_formatted: bool = sqlx::query_scalar(
             "
             select exists(
                 select *
-                from nixfmt
-                where version = $1
+                from nix_formatter
+                where formatter = $1
+                  and version = $2
                   and source_hash = $3
+                  and repo = $4
             )
             ",
         )
+        .bind(formatter)
         .bind(version)
         .bind(source_hash.to_string())
+        .bind(self.repo().await?)
         .fetch_one(&self.sqlite)
         .await?;
 
+        let key = [formatter, version, &source_hash.to_string()];
+        if !is_formatted && self.remote_has("nix_formatter", &key).await? {
+            self.mark_nix_formatted(source_hash).await?;
+            is_formatted = true;
+        }
+
+        cx().metrics.cache_lookup(formatter, is_formatted);
+
         Ok(is_formatted)
     }
 
     #[tracing::instrument(skip_all)]
     pub async fn mark_nix_formatted(&self, source_hash: u64) -> eyre::Result<()> {
-        let version = self.nixfmt_version().await?;
+        let formatter = self.config().await?.format.nix_formatter.binary();
+
+        if !self.is_enabled(formatter).await? {
+            return Ok(());
+        }
+
+        let version = self.nix_formatter_version().await?;
 
-        sqlx::query("insert or ignore into nixfmt values ($1, $2)")
+        sqlx::query("insert or ignore into nix_formatter values ($1, $2, $3, $4, unixepoch())")
+            .bind(formatter)
             .bind(version)
             .bind(source_hash.to_string())
+            .bind(self.repo().await?)
             .execute(&self.sqlite)
             .await?;
 
+        let key = [formatter, version, &source_hash.to_string()];
+        self.remote_put("nix_formatter", &key, Bytes::new()).await?;
+
+        Ok(())
+    }
+
+    /// Output of the Nix formatter for contents it changed, so formatting them again doesn't run
+    /// it
+    #[tracing::instrument(skip_all)]
+    pub async fn nix_formatted_output(&self, source_hash: u64) -> eyre::Result<Option<Bytes>> {
+        let formatter = self.config().await?.format.nix_formatter.binary();
+
+        if !self.is_enabled(formatter).await? {
+            return Ok(None);
+        }
+
+        let version = self.nix_formatter_version().await?;
+
+        let output: Option<Vec<u8>> = sqlx::query_scalar(
+            "
+            select output
+            from nix_formatter_outputs
+            where formatter = $1
+              and version = $2
+              and source_hash = $3
+              and repo = $4
+            ",
+        )
+        .bind(formatter)
+        .bind(version)
+        .bind(source_hash.to_string())
+        .bind(self.repo().await?)
+        .fetch_optional(&self.sqlite)
+        .await?;
+
+        Ok(output.map(Bytes::from))
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn cache_nix_formatted_output(
+        &self,
+        source_hash: u64,
+        output: &[u8],
+    ) -> eyre::Result<()> {
+        let formatter = self.config().await?.format.nix_formatter.binary();
+
+        if !self.is_enabled(formatter).await? {
+            return Ok(());
+        }
+
+        let version = self.nix_formatter_version().await?;
+
+        sqlx::query(
+            "insert or ignore into nix_formatter_outputs values ($1, $2, $3, $4, $5, unixepoch())",
+        )
+        .bind(formatter)
+        .bind(version)
+        .bind(source_hash.to_string())
+        .bind(output)
+        .bind(self.repo().await?)
+        .execute(&self.sqlite)
+        .await?;
+
         Ok(())
     }
 
     #[tracing::instrument(skip(self))]
-    pub async fn hlint_version(&self) -> eyre::Result<&str> {
-        self.hlint_version
+    pub async fn dhall_version(&self) -> eyre::Result<&str> {
+        self.dhall_version
             .get_or_try_init(|| async {
-                let fourmolu = self.which("hlint").await?;
-                let stdout = sandbox_exec(exec::HLINT_PROFILE, fourmolu, ["--version"]).await?;
+                let dhall = self.which("dhall").await?;
+                let stdout =
+                    sandbox_exec("dhall", exec::DHALL_PROFILE, dhall, ["--version"]).await?;
                 let version = String::from(str::from_utf8(&stdout)?.trim_end());
                 Ok(version)
             })
@@ -288,205 +580,2088 @@ impl Cache {
             .map(|x| x.as_ref())
     }
 
-    // TODO: Refactor this, it's too long and verbose
+    #[tracing::instrument(skip_all)]
+    pub async fn is_dhall_formatted(&self, source_hash: u64) -> eyre::Result<bool> {
+        if !self.is_enabled("dhall").await? {
+            return Ok(false);
+        }
+
+        let version = self.dhall_version().await?;
+
+        let mut is_formatted: bool = sqlx::query_scalar(
+            "
+            select exists(
+                select *
+                from dhall
+                where version = $1
+                  and source_hash = $2
+                  and repo = $3
+            )
+            ",
+        )
+        .bind(version)
+        .bind(source_hash.to_string())
+        .bind(self.repo().await?)
+        .fetch_one(&self.sqlite)
+        .await?;
+
+        let key = [version, &source_hash.to_string()];
+        if !is_formatted && self.remote_has("dhall", &key).await? {
+            self.mark_dhall_formatted(source_hash).await?;
+            is_formatted = true;
+        }
+
+        cx().metrics.cache_lookup("dhall", is_formatted);
+
+        Ok(is_formatted)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn mark_dhall_formatted(&self, source_hash: u64) -> eyre::Result<()> {
+        if !self.is_enabled("dhall").await? {
+            return Ok(());
+        }
+
+        let version = self.dhall_version().await?;
+
+        sqlx::query("insert or ignore into dhall values ($1, $2, $3, unixepoch())")
+            .bind(version)
+            .bind(source_hash.to_string())
+            .bind(self.repo().await?)
+            .execute(&self.sqlite)
+            .await?;
+
+        let key = [version, &source_hash.to_string()];
+        self.remote_put("dhall", &key, Bytes::new()).await?;
+
+        Ok(())
+    }
+
     #[tracing::instrument(skip(self))]
-    pub async fn hlint_configs(&self) -> eyre::Result<&(Vec<Utf8PathBuf>, u64)> {
-        self.hlint_configs
+    pub async fn shfmt_version(&self) -> eyre::Result<&str> {
+        self.shfmt_version
             .get_or_try_init(|| async {
-                let git_root = self.git_root().await?;
-                let temp_dir = tempdir()?;
-                let mut paths = Vec::new();
-                let mut copy_handles = Vec::new();
-                let mut hasher = XxHash3_64::default();
+                let shfmt = self.which("shfmt").await?;
+                let stdout =
+                    sandbox_exec("shfmt", exec::SHFMT_PROFILE, shfmt, ["--version"]).await?;
+                let version = String::from(str::from_utf8(&stdout)?.trim_end());
+                Ok(version)
+            })
+            .await
+            .map(|x| x.as_ref())
+    }
 
-                let hlint_yaml = git_root.join(".hlint.yaml");
-                if fs::metadata(&hlint_yaml).await.is_ok() {
-                    let hash = file_hash(&hlint_yaml).await?;
-                    hasher.write(&hash.to_le_bytes());
-                    let file_name = hlint_yaml.file_name().unwrap();
-                    let temp_path = Utf8PathBuf::try_from(temp_dir.path().join(file_name))?;
-                    let copy_handle = tokio::spawn(fs::copy(hlint_yaml, temp_path.clone()));
-                    copy_handles.push(copy_handle);
-                    paths.push(temp_path);
-                }
+    #[tracing::instrument(skip_all)]
+    pub async fn is_shell_formatted(&self, source_hash: u64) -> eyre::Result<bool> {
+        if !self.is_enabled("shfmt").await? {
+            return Ok(false);
+        }
 
-                let hlint_rules_dir = git_root.join("hlint-rules");
-                if let Ok(mut dir) = fs::read_dir(&hlint_rules_dir).await {
-                    while let Ok(Some(entry)) = dir.next_entry().await {
-                        let Ok(file_type) = entry.file_type().await else {
-                            continue;
-                        };
-                        if !file_type.is_file() {
-                            continue;
-                        }
-                        let path = entry.path();
-                        let Some(extension) = path.extension() else {
-                            continue;
-                        };
-                        if extension != "yaml" {
-                            continue;
-                        }
-                        let path = Utf8PathBuf::try_from(path)?;
-                        let hash = file_hash(&path).await?;
-                        hasher.write(&hash.to_le_bytes());
-                        let file_name = path.file_name().unwrap();
-                        let temp_path = Utf8PathBuf::try_from(temp_dir.path().join(file_name))?;
-                        let copy_handle = tokio::spawn(fs::copy(path, temp_path.clone()));
-                        copy_handles.push(copy_handle);
-                        paths.push(temp_path);
-                    }
-                }
+        let version = self.shfmt_version().await?;
 
-                for copy_handle in copy_handles {
-                    copy_handle.await??;
-                }
+        let mut is_formatted: bool = sqlx::query_scalar(
+            "
+            select exists(
+                select *
+                from shfmt
+                where version = $1
+                  and source_hash = $2
+                  and repo = $3
+            )
+            ",
+        )
+        .bind(version)
+        .bind(source_hash.to_string())
+        .bind(self.repo().await?)
+        .fetch_one(&self.sqlite)
+        .await?;
 
-                let hash = hasher.finish();
+        let key = [version, &source_hash.to_string()];
+        if !is_formatted && self.remote_has("shfmt", &key).await? {
+            self.mark_shell_formatted(source_hash).await?;
+            is_formatted = true;
+        }
 
-                // TODO: gross
-                std::mem::forget(temp_dir);
+        cx().metrics.cache_lookup("shfmt", is_formatted);
 
-                Ok((paths, hash))
+        Ok(is_formatted)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn mark_shell_formatted(&self, source_hash: u64) -> eyre::Result<()> {
+        if !self.is_enabled("shfmt").await? {
+            return Ok(());
+        }
+
+        let version = self.shfmt_version().await?;
+
+        sqlx::query("insert or ignore into shfmt values ($1, $2, $3, unixepoch())")
+            .bind(version)
+            .bind(source_hash.to_string())
+            .bind(self.repo().await?)
+            .execute(&self.sqlite)
+            .await?;
+
+        let key = [version, &source_hash.to_string()];
+        self.remote_put("shfmt", &key, Bytes::new()).await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn shellcheck_version(&self) -> eyre::Result<&str> {
+        self.shellcheck_version
+            .get_or_try_init(|| async {
+                let shellcheck = self.which("shellcheck").await?;
+                let stdout = sandbox_exec(
+                    "shellcheck",
+                    exec::SHELLCHECK_PROFILE,
+                    shellcheck,
+                    ["--version"],
+                )
+                .await?;
+                let version = String::from(str::from_utf8(&stdout)?.trim_end());
+                Ok(version)
             })
             .await
+            .map(|x| x.as_ref())
     }
 
     #[tracing::instrument(skip_all)]
-    pub async fn is_haskell_linted(
+    pub async fn is_shell_linted(
         &self,
         source_hash: u64,
-    ) -> eyre::Result<Option<Vec<HlintHint>>> {
-        let version = self.hlint_version().await?;
+    ) -> eyre::Result<Option<Vec<ShellcheckHint>>> {
+        if !self.is_enabled("shellcheck").await? {
+            return Ok(None);
+        }
 
-        let (_, configs_hash) = self.hlint_configs().await?;
+        let version = self.shellcheck_version().await?;
 
         let hints_bytes: Option<Vec<u8>> = sqlx::query_scalar(
             "
             select hints
-            from hlint
+            from shellcheck
             where version = $1
-              and configs_hash = $2
-              and source_hash = $3
+              and source_hash = $2
+              and repo = $3
             ",
         )
         .bind(version)
-        .bind(configs_hash.to_string())
         .bind(source_hash.to_string())
+        .bind(self.repo().await?)
         .fetch_optional(&self.sqlite)
         .await?;
 
         if let Some(hints_bytes) = hints_bytes {
+            cx().metrics.cache_lookup("shellcheck", true);
             let hints = serde_json::from_slice(&hints_bytes)?;
-            Ok(Some(hints))
-        } else {
-            Ok(None)
+            return Ok(Some(hints));
         }
+
+        let key = [version, &source_hash.to_string()];
+        let hints = self
+            .remote_hints::<ShellcheckHint>("shellcheck", &key)
+            .await?;
+        if let Some(hints) = &hints {
+            self.mark_shell_linted(source_hash, hints).await?;
+        }
+
+        cx().metrics.cache_lookup("shellcheck", hints.is_some());
+
+        Ok(hints)
     }
 
     #[tracing::instrument(skip_all)]
-    pub async fn mark_haskell_linted(
+    pub async fn mark_shell_linted(
         &self,
         source_hash: u64,
-        hints: &[HlintHint],
+        hints: &[ShellcheckHint],
     ) -> eyre::Result<()> {
-        let version = self.hlint_version().await?;
+        if !self.is_enabled("shellcheck").await? {
+            return Ok(());
+        }
 
-        let (_, configs_hash) = self.hlint_configs().await?;
+        let version = self.shellcheck_version().await?;
 
         let hints = serde_json::to_vec(hints)?;
 
-        sqlx::query("insert or ignore into hlint values ($1, $2, $3, $4)")
+        sqlx::query("insert or ignore into shellcheck values ($1, $2, $3, $4, unixepoch())")
             .bind(version)
-            .bind(configs_hash.to_string())
             .bind(source_hash.to_string())
-            .bind(hints)
+            .bind(hints.as_slice())
+            .bind(self.repo().await?)
             .execute(&self.sqlite)
             .await?;
 
+        let key = [version, &source_hash.to_string()];
+        self.remote_put("shellcheck", &key, Bytes::from(hints))
+            .await?;
+
         Ok(())
     }
-}
-
-#[tracing::instrument(skip_all)]
-async fn sqlite_valid(sqlite: &SqlitePool) -> eyre::Result<bool> {
-    sqlx::raw_sql(
-        "
-        create table if not exists be_binary_id (
-            be_binary_id text primary key
-        ) strict
-        ",
-    )
-    .execute(sqlite)
-    .await?;
-
-    let id_count: i64 = sqlx::query_scalar("select count(*) from be_binary_id")
-        .fetch_one(sqlite)
-        .await?;
 
-    let has_id: bool = sqlx::query_scalar(
-        "
-        select exists(
-            select be_binary_id from be_binary_id where be_binary_id = $1
-        )
-        ",
-    )
-    .bind(BE_BINARY_ID.to_string())
-    .fetch_one(sqlite)
-    .await?;
+    /// `cabal-gild` if it's installed and enabled, or else `cabal-fmt`
+    #[tracing::instrument(skip(self))]
+    pub async fn cabal_formatter(&self) -> eyre::Result<&'static str> {
+        self.cabal_formatter
+            .get_or_try_init(|| async {
+                let config = self.config().await?;
+                for formatter in ["cabal-gild", "cabal-fmt"] {
+                    if config.is_tool_enabled(formatter) && self.which(formatter).await.is_ok() {
+                        return Ok(formatter);
+                    }
+                }
+                eyre::bail!("Install `cabal-gild` or `cabal-fmt` to format `.cabal` files")
+            })
+            .await
+            .copied()
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn cabal_formatter_version(&self) -> eyre::Result<&str> {
+        self.cabal_formatter_version
+            .get_or_try_init(|| async {
+                let formatter = self.cabal_formatter().await?;
+                let binary = self.which(formatter).await?;
+                let profile = if formatter == "cabal-gild" {
+                    exec::CABAL_GILD_PROFILE
+                } else {
+                    exec::CABAL_FMT_PROFILE
+                };
+                let stdout = sandbox_exec(formatter, profile, binary, ["--version"]).await?;
+                let version = String::from(str::from_utf8(&stdout)?.trim_end());
+                Ok(version)
+            })
+            .await
+            .map(|x| x.as_ref())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn is_cabal_formatted(&self, source_hash: u64) -> eyre::Result<bool> {
+        let formatter = self.cabal_formatter().await?;
+
+        if !self.is_enabled(formatter).await? {
+            return Ok(false);
+        }
+
+        let version = self.cabal_formatter_version().await?;
+
+        let mut is_formatted: bool = sqlx::query_scalar(
+            "
+            select exists(
+                select *
+                from cabal_formatter
+                where formatter = $1
+                  and version = $2
+                  and source_hash = $3
+                  and repo = $4
+            )
+            ",
+        )
+        .bind(formatter)
+        .bind(version)
+        .bind(source_hash.to_string())
+        .bind(self.repo().await?)
+        .fetch_one(&self.sqlite)
+        .await?;
+
+        let key = [formatter, version, &source_hash.to_string()];
+        if !is_formatted && self.remote_has("cabal_formatter", &key).await? {
+            self.mark_cabal_formatted(source_hash).await?;
+            is_formatted = true;
+        }
+
+        cx().metrics.cache_lookup(formatter, is_formatted);
+
+        Ok(is_formatted)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn mark_cabal_formatted(&self, source_hash: u64) -> eyre::Result<()> {
+        let formatter = self.cabal_formatter().await?;
+
+        if !self.is_enabled(formatter).await? {
+            return Ok(());
+        }
+
+        let version = self.cabal_formatter_version().await?;
+
+        sqlx::query("insert or ignore into cabal_formatter values ($1, $2, $3, $4, unixepoch())")
+            .bind(formatter)
+            .bind(version)
+            .bind(source_hash.to_string())
+            .bind(self.repo().await?)
+            .execute(&self.sqlite)
+            .await?;
+
+        let key = [formatter, version, &source_hash.to_string()];
+        self.remote_put("cabal_formatter", &key, Bytes::new())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Version of `statix` or `deadnix`
+    #[tracing::instrument(skip(self))]
+    pub async fn nix_linter_version(&self, linter: &'static str) -> eyre::Result<&str> {
+        let (cell, profile) = if linter == "statix" {
+            (&self.statix_version, exec::STATIX_PROFILE)
+        } else {
+            (&self.deadnix_version, exec::DEADNIX_PROFILE)
+        };
+        cell.get_or_try_init(|| async {
+            let binary = self.which(linter).await?;
+            let stdout = sandbox_exec(linter, profile, binary, ["--version"]).await?;
+            let version = String::from(str::from_utf8(&stdout)?.trim_end());
+            Ok(version)
+        })
+        .await
+        .map(|x| x.as_ref())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn is_nix_linted(
+        &self,
+        linter: &'static str,
+        source_hash: u64,
+    ) -> eyre::Result<Option<Vec<HlintHint>>> {
+        if !self.is_enabled(linter).await? {
+            return Ok(None);
+        }
+
+        let version = self.nix_linter_version(linter).await?;
+
+        let hints_bytes: Option<Vec<u8>> = sqlx::query_scalar(
+            "
+            select hints
+            from nix_linter
+            where linter = $1
+              and version = $2
+              and source_hash = $3
+              and repo = $4
+            ",
+        )
+        .bind(linter)
+        .bind(version)
+        .bind(source_hash.to_string())
+        .bind(self.repo().await?)
+        .fetch_optional(&self.sqlite)
+        .await?;
+
+        if let Some(hints_bytes) = hints_bytes {
+            cx().metrics.cache_lookup(linter, true);
+            let hints = serde_json::from_slice(&hints_bytes)?;
+            return Ok(Some(hints));
+        }
+
+        let key = [linter, version, &source_hash.to_string()];
+        let hints = self.remote_hints::<HlintHint>("nix_linter", &key).await?;
+        if let Some(hints) = &hints {
+            self.mark_nix_linted(linter, source_hash, hints).await?;
+        }
+
+        cx().metrics.cache_lookup(linter, hints.is_some());
+
+        Ok(hints)
+    }
+
+    #[tracing::instrument(skip(self, hints))]
+    pub async fn mark_nix_linted(
+        &self,
+        linter: &'static str,
+        source_hash: u64,
+        hints: &[HlintHint],
+    ) -> eyre::Result<()> {
+        if !self.is_enabled(linter).await? {
+            return Ok(());
+        }
+
+        let version = self.nix_linter_version(linter).await?;
+
+        let hints = serde_json::to_vec(hints)?;
+
+        sqlx::query("insert or ignore into nix_linter values ($1, $2, $3, $4, $5, unixepoch())")
+            .bind(linter)
+            .bind(version)
+            .bind(source_hash.to_string())
+            .bind(hints.as_slice())
+            .bind(self.repo().await?)
+            .execute(&self.sqlite)
+            .await?;
+
+        let key = [linter, version, &source_hash.to_string()];
+        self.remote_put("nix_linter", &key, Bytes::from(hints))
+            .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn ghc_version(&self) -> eyre::Result<&str> {
+        self.ghc_version
+            .get_or_try_init(|| async {
+                let ghc = self.which("ghc").await?;
+                let stdout =
+                    sandbox_exec("ghc", exec::GHC_PROFILE, ghc, ["--numeric-version"]).await?;
+                let version = String::from(str::from_utf8(&stdout)?.trim_end());
+                Ok(version)
+            })
+            .await
+            .map(|x| x.as_ref())
+    }
+
+    /// Warnings `ghc` found with the same flags and extensions. Only the module's own contents are
+    /// part of the key, so a change to a module it imports doesn't invalidate them.
+    #[tracing::instrument(skip(self))]
+    pub async fn is_ghc_linted(
+        &self,
+        flags_hash: u64,
+        source_hash: u64,
+    ) -> eyre::Result<Option<Vec<HlintHint>>> {
+        if !self.is_enabled("ghc").await? {
+            return Ok(None);
+        }
+
+        let version = self.ghc_version().await?;
+        let (_, extensions_hash) = self.fourmolu_extensions().await?;
+
+        let hints_bytes: Option<Vec<u8>> = sqlx::query_scalar(
+            "
+            select hints
+            from ghc
+            where version = $1
+              and flags_hash = $2
+              and extensions_hash = $3
+              and source_hash = $4
+              and repo = $5
+            ",
+        )
+        .bind(version)
+        .bind(flags_hash.to_string())
+        .bind(extensions_hash.to_string())
+        .bind(source_hash.to_string())
+        .bind(self.repo().await?)
+        .fetch_optional(&self.sqlite)
+        .await?;
+
+        if let Some(hints_bytes) = hints_bytes {
+            cx().metrics.cache_lookup("ghc", true);
+            let hints = serde_json::from_slice(&hints_bytes)?;
+            return Ok(Some(hints));
+        }
+
+        let key = [
+            version,
+            &flags_hash.to_string(),
+            &extensions_hash.to_string(),
+            &source_hash.to_string(),
+        ];
+        let hints = self.remote_hints::<HlintHint>("ghc", &key).await?;
+        if let Some(hints) = &hints {
+            self.mark_ghc_linted(flags_hash, source_hash, hints).await?;
+        }
+
+        cx().metrics.cache_lookup("ghc", hints.is_some());
+
+        Ok(hints)
+    }
+
+    #[tracing::instrument(skip(self, hints))]
+    pub async fn mark_ghc_linted(
+        &self,
+        flags_hash: u64,
+        source_hash: u64,
+        hints: &[HlintHint],
+    ) -> eyre::Result<()> {
+        if !self.is_enabled("ghc").await? {
+            return Ok(());
+        }
+
+        let version = self.ghc_version().await?;
+        let (_, extensions_hash) = self.fourmolu_extensions().await?;
+
+        let hints = serde_json::to_vec(hints)?;
+
+        sqlx::query("insert or ignore into ghc values ($1, $2, $3, $4, $5, $6, unixepoch())")
+            .bind(version)
+            .bind(flags_hash.to_string())
+            .bind(extensions_hash.to_string())
+            .bind(source_hash.to_string())
+            .bind(hints.as_slice())
+            .bind(self.repo().await?)
+            .execute(&self.sqlite)
+            .await?;
+
+        let key = [
+            version,
+            &flags_hash.to_string(),
+            &extensions_hash.to_string(),
+            &source_hash.to_string(),
+        ];
+        self.remote_put("ghc", &key, Bytes::from(hints)).await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn weeder_version(&self) -> eyre::Result<&str> {
+        self.weeder_version
+            .get_or_try_init(|| async {
+                let weeder = self.which("weeder").await?;
+                let stdout =
+                    sandbox_exec("weeder", exec::WEEDER_PROFILE, weeder, ["--version"]).await?;
+                let version = String::from(str::from_utf8(&stdout)?.trim_end());
+                Ok(version)
+            })
+            .await
+            .map(|x| x.as_ref())
+    }
+
+    /// Unused code `weeder` found in the same `.hie` files with the same config
+    #[tracing::instrument(skip(self))]
+    pub async fn is_weeder_checked(
+        &self,
+        config_hash: u64,
+        hie_hash: u64,
+    ) -> eyre::Result<Option<Vec<HlintHint>>> {
+        if !self.is_enabled("weeder").await? {
+            return Ok(None);
+        }
+
+        let version = self.weeder_version().await?;
+
+        let hints_bytes: Option<Vec<u8>> = sqlx::query_scalar(
+            "
+            select hints
+            from weeder
+            where version = $1
+              and config_hash = $2
+              and hie_hash = $3
+              and repo = $4
+            ",
+        )
+        .bind(version)
+        .bind(config_hash.to_string())
+        .bind(hie_hash.to_string())
+        .bind(self.repo().await?)
+        .fetch_optional(&self.sqlite)
+        .await?;
+
+        if let Some(hints_bytes) = hints_bytes {
+            cx().metrics.cache_lookup("weeder", true);
+            let hints = serde_json::from_slice(&hints_bytes)?;
+            return Ok(Some(hints));
+        }
+
+        // Not looked up remotely, since paths in `.hie` files are specific to where they were built
+        cx().metrics.cache_lookup("weeder", false);
+
+        Ok(None)
+    }
+
+    #[tracing::instrument(skip(self, hints))]
+    pub async fn mark_weeder_checked(
+        &self,
+        config_hash: u64,
+        hie_hash: u64,
+        hints: &[HlintHint],
+    ) -> eyre::Result<()> {
+        if !self.is_enabled("weeder").await? {
+            return Ok(());
+        }
+
+        let version = self.weeder_version().await?;
+
+        let hints = serde_json::to_vec(hints)?;
+
+        sqlx::query("insert or ignore into weeder values ($1, $2, $3, $4, $5, unixepoch())")
+            .bind(version)
+            .bind(config_hash.to_string())
+            .bind(hie_hash.to_string())
+            .bind(hints.as_slice())
+            .bind(self.repo().await?)
+            .execute(&self.sqlite)
+            .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn stan_version(&self) -> eyre::Result<&str> {
+        self.stan_version
+            .get_or_try_init(|| async {
+                let stan = self.which("stan").await?;
+                let stdout = sandbox_exec("stan", exec::STAN_PROFILE, stan, ["--version"]).await?;
+                let version = String::from(str::from_utf8(&stdout)?.trim_end());
+                Ok(version)
+            })
+            .await
+            .map(|x| x.as_ref())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn is_stan_checked(
+        &self,
+        config_hash: u64,
+        source_hash: u64,
+    ) -> eyre::Result<Option<Vec<HlintHint>>> {
+        if !self.is_enabled("stan").await? {
+            return Ok(None);
+        }
+
+        let version = self.stan_version().await?;
+
+        let hints_bytes: Option<Vec<u8>> = sqlx::query_scalar(
+            "
+            select hints
+            from stan
+            where version = $1
+              and config_hash = $2
+              and source_hash = $3
+              and repo = $4
+            ",
+        )
+        .bind(version)
+        .bind(config_hash.to_string())
+        .bind(source_hash.to_string())
+        .bind(self.repo().await?)
+        .fetch_optional(&self.sqlite)
+        .await?;
+
+        if let Some(hints_bytes) = hints_bytes {
+            cx().metrics.cache_lookup("stan", true);
+            let hints = serde_json::from_slice(&hints_bytes)?;
+            return Ok(Some(hints));
+        }
+
+        let key = [version, &config_hash.to_string(), &source_hash.to_string()];
+        let hints = self.remote_hints::<HlintHint>("stan", &key).await?;
+        if let Some(hints) = &hints {
+            self.mark_stan_checked(config_hash, source_hash, hints)
+                .await?;
+        }
+
+        cx().metrics.cache_lookup("stan", hints.is_some());
+
+        Ok(hints)
+    }
+
+    #[tracing::instrument(skip(self, hints))]
+    pub async fn mark_stan_checked(
+        &self,
+        config_hash: u64,
+        source_hash: u64,
+        hints: &[HlintHint],
+    ) -> eyre::Result<()> {
+        if !self.is_enabled("stan").await? {
+            return Ok(());
+        }
+
+        let version = self.stan_version().await?;
+
+        let hints = serde_json::to_vec(hints)?;
+
+        sqlx::query("insert or ignore into stan values ($1, $2, $3, $4, $5, unixepoch())")
+            .bind(version)
+            .bind(config_hash.to_string())
+            .bind(source_hash.to_string())
+            .bind(hints.as_slice())
+            .bind(self.repo().await?)
+            .execute(&self.sqlite)
+            .await?;
+
+        let key = [version, &config_hash.to_string(), &source_hash.to_string()];
+        self.remote_put("stan", &key, Bytes::from(hints)).await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn hlint_version(&self) -> eyre::Result<&str> {
+        self.hlint_version
+            .get_or_try_init(|| async {
+                let fourmolu = self.which("hlint").await?;
+                let stdout =
+                    sandbox_exec("hlint", exec::HLINT_PROFILE, fourmolu, ["--version"]).await?;
+                let version = String::from(str::from_utf8(&stdout)?.trim_end());
+                Ok(version)
+            })
+            .await
+            .map(|x| x.as_ref())
+    }
+
+    /// Copies of the repo's `hlint` configs, and their combined hash
+    #[tracing::instrument(skip(self))]
+    pub async fn hlint_configs(&self) -> eyre::Result<&(Vec<Utf8PathBuf>, u64)> {
+        self.hlint_configs
+            .get_or_try_init(|| async {
+                let git_root = self.git_root().await?;
+                let config = self.config().await?;
+
+                let mut sources = Vec::new();
+                for path in config.tool_configs.hlint() {
+                    let path = git_root.join(path);
+                    let Ok(metadata) = fs::metadata(&path).await else {
+                        continue;
+                    };
+                    if metadata.is_file() {
+                        sources.push(path);
+                        continue;
+                    }
+                    let mut dir_sources = Vec::new();
+                    let mut dir = fs::read_dir(&path).await?;
+                    while let Some(entry) = dir.next_entry().await? {
+                        let path = Utf8PathBuf::try_from(entry.path())?;
+                        if path.extension() == Some("yaml") && entry.file_type().await?.is_file() {
+                            dir_sources.push(path);
+                        }
+                    }
+                    // Keep the hash the same regardless of directory order
+                    dir_sources.sort();
+                    sources.extend(dir_sources);
+                }
+
+                let temp_dir = cx().temp_store.dir()?;
+                let mut paths = Vec::with_capacity(sources.len());
+                let mut hasher = XxHash3_64::default();
+
+                for (i, source) in sources.into_iter().enumerate() {
+                    let hash = file_hash(&source).await?;
+                    hasher.write(&hash.to_le_bytes());
+                    // Configs from different directories may have the same name
+                    let file_name = format!("{i}-{}", source.file_name().unwrap_or("hlint.yaml"));
+                    let temp_path = temp_dir.join(file_name);
+                    fs::copy(&source, &temp_path).await?;
+                    paths.push(temp_path);
+                }
+
+                let hash = hasher.finish();
+
+                Ok((paths, hash))
+            })
+            .await
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn is_haskell_linted(
+        &self,
+        source_hash: u64,
+    ) -> eyre::Result<Option<Vec<HlintHint>>> {
+        if !self.is_enabled("hlint").await? {
+            return Ok(None);
+        }
+
+        let version = self.hlint_version().await?;
+
+        let (_, configs_hash) = self.hlint_configs().await?;
+
+        let hints_bytes: Option<Vec<u8>> = sqlx::query_scalar(
+            "
+            select hints
+            from hlint
+            where version = $1
+              and configs_hash = $2
+              and source_hash = $3
+              and repo = $4
+            ",
+        )
+        .bind(version)
+        .bind(configs_hash.to_string())
+        .bind(source_hash.to_string())
+        .bind(self.repo().await?)
+        .fetch_optional(&self.sqlite)
+        .await?;
+
+        if let Some(hints_bytes) = hints_bytes {
+            cx().metrics.cache_lookup("hlint", true);
+            let hints = serde_json::from_slice(&hints_bytes)?;
+            return Ok(Some(hints));
+        }
+
+        let key = [version, &configs_hash.to_string(), &source_hash.to_string()];
+        let hints = self.remote_hints::<HlintHint>("hlint", &key).await?;
+        if let Some(hints) = &hints {
+            self.mark_haskell_linted(source_hash, hints).await?;
+        }
+
+        cx().metrics.cache_lookup("hlint", hints.is_some());
+
+        Ok(hints)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn mark_haskell_linted(
+        &self,
+        source_hash: u64,
+        hints: &[HlintHint],
+    ) -> eyre::Result<()> {
+        if !self.is_enabled("hlint").await? {
+            return Ok(());
+        }
+
+        let version = self.hlint_version().await?;
+
+        let (_, configs_hash) = self.hlint_configs().await?;
+
+        let hints = serde_json::to_vec(hints)?;
+
+        sqlx::query("insert or ignore into hlint values ($1, $2, $3, $4, $5, unixepoch())")
+            .bind(version)
+            .bind(configs_hash.to_string())
+            .bind(source_hash.to_string())
+            .bind(hints.as_slice())
+            .bind(self.repo().await?)
+            .execute(&self.sqlite)
+            .await?;
+
+        let key = [version, &configs_hash.to_string(), &source_hash.to_string()];
+        self.remote_put("hlint", &key, Bytes::from(hints)).await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn typos_version(&self) -> eyre::Result<&str> {
+        self.typos_version
+            .get_or_try_init(|| async {
+                let typos = self.which("typos").await?;
+                let stdout =
+                    sandbox_exec("typos", exec::TYPOS_PROFILE, typos, ["--version"]).await?;
+                let version = String::from(str::from_utf8(&stdout)?.trim_end());
+                Ok(version)
+            })
+            .await
+            .map(|x| x.as_ref())
+    }
+
+    /// Copy of the repo's `typos` config, if there is one, and its hash
+    #[tracing::instrument(skip(self))]
+    pub async fn typos_config(&self) -> eyre::Result<&(Option<Utf8PathBuf>, u64)> {
+        self.typos_config
+            .get_or_try_init(|| async {
+                let git_root = self.git_root().await?;
+                let config = self.config().await?;
+                for candidate in config.tool_configs.typos() {
+                    let path = git_root.join(candidate);
+                    if fs::metadata(&path).await.is_err() {
+                        continue;
+                    }
+                    let file_name = candidate.file_name().unwrap_or("typos.toml");
+                    let (bytes, hash) = read_file(&path).await?;
+                    let temp_path = cx().temp_store.dir()?.join(file_name);
+                    fs::write(&temp_path, bytes).await?;
+                    return Ok((Some(temp_path), hash));
+                }
+                Ok((None, 0))
+            })
+            .await
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn is_typos_checked(&self, source_hash: u64) -> eyre::Result<Option<Vec<HlintHint>>> {
+        if !self.is_enabled("typos").await? {
+            return Ok(None);
+        }
+
+        let version = self.typos_version().await?;
+
+        let (_, config_hash) = self.typos_config().await?;
+
+        let hints_bytes: Option<Vec<u8>> = sqlx::query_scalar(
+            "
+            select hints
+            from typos
+            where version = $1
+              and config_hash = $2
+              and source_hash = $3
+              and repo = $4
+            ",
+        )
+        .bind(version)
+        .bind(config_hash.to_string())
+        .bind(source_hash.to_string())
+        .bind(self.repo().await?)
+        .fetch_optional(&self.sqlite)
+        .await?;
+
+        if let Some(hints_bytes) = hints_bytes {
+            cx().metrics.cache_lookup("typos", true);
+            let hints = serde_json::from_slice(&hints_bytes)?;
+            return Ok(Some(hints));
+        }
+
+        let key = [version, &config_hash.to_string(), &source_hash.to_string()];
+        let hints = self.remote_hints::<HlintHint>("typos", &key).await?;
+        if let Some(hints) = &hints {
+            self.mark_typos_checked(source_hash, hints).await?;
+        }
+
+        cx().metrics.cache_lookup("typos", hints.is_some());
+
+        Ok(hints)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn mark_typos_checked(
+        &self,
+        source_hash: u64,
+        hints: &[HlintHint],
+    ) -> eyre::Result<()> {
+        if !self.is_enabled("typos").await? {
+            return Ok(());
+        }
+
+        let version = self.typos_version().await?;
+
+        let (_, config_hash) = self.typos_config().await?;
+
+        let hints = serde_json::to_vec(hints)?;
+
+        sqlx::query("insert or ignore into typos values ($1, $2, $3, $4, $5, unixepoch())")
+            .bind(version)
+            .bind(config_hash.to_string())
+            .bind(source_hash.to_string())
+            .bind(hints.as_slice())
+            .bind(self.repo().await?)
+            .execute(&self.sqlite)
+            .await?;
+
+        let key = [version, &config_hash.to_string(), &source_hash.to_string()];
+        self.remote_put("typos", &key, Bytes::from(hints)).await?;
+
+        Ok(())
+    }
 
-    Ok(id_count == 1 && has_id)
+    /// Hints from one of `be`'s own lints, with default severities
+    #[tracing::instrument(skip(self))]
+    pub async fn builtin_hints(
+        &self,
+        lint: &str,
+        source_hash: u64,
+    ) -> eyre::Result<Option<Vec<HlintHint>>> {
+        if self.disabled {
+            return Ok(None);
+        }
+
+        let hints_bytes: Option<Vec<u8>> = sqlx::query_scalar(
+            "
+            select hints
+            from builtin_lints
+            where lint = $1
+              and source_hash = $2
+              and repo = $3
+            ",
+        )
+        .bind(lint)
+        .bind(source_hash.to_string())
+        .bind(self.repo().await?)
+        .fetch_optional(&self.sqlite)
+        .await?;
+
+        cx().metrics.cache_lookup(
+            lint.split(':').next().unwrap_or(lint),
+            hints_bytes.is_some(),
+        );
+
+        if let Some(hints_bytes) = hints_bytes {
+            let hints = serde_json::from_slice(&hints_bytes)?;
+            Ok(Some(hints))
+        } else {
+            Ok(None)
+        }
+    }
+
+    #[tracing::instrument(skip(self, hints))]
+    pub async fn mark_builtin_hints(
+        &self,
+        lint: &str,
+        source_hash: u64,
+        hints: &[HlintHint],
+    ) -> eyre::Result<()> {
+        if self.disabled {
+            return Ok(());
+        }
+
+        let hints = serde_json::to_vec(hints)?;
+
+        sqlx::query("insert or ignore into builtin_lints values ($1, $2, $3, $4, unixepoch())")
+            .bind(lint)
+            .bind(source_hash.to_string())
+            .bind(hints)
+            .bind(self.repo().await?)
+            .execute(&self.sqlite)
+            .await?;
+
+        Ok(())
+    }
+
+    /// The cache from `[remote-cache]`, unless there isn't one or `--no-cache` is set
+    async fn remote(&self) -> eyre::Result<Option<&remote::Remote>> {
+        if self.disabled {
+            return Ok(None);
+        }
+        self.remote
+            .get_or_try_init(|| async {
+                let config = self.config().await?;
+                remote::Remote::new(&config.remote_cache)
+            })
+            .await
+            .map(Option::as_ref)
+    }
+
+    /// Whether the remote cache has a result for a table without any columns besides its key
+    async fn remote_has(&self, table: &str, key: &[&str]) -> eyre::Result<bool> {
+        let Some(remote) = self.remote().await? else {
+            return Ok(false);
+        };
+        Ok(remote.get(table, key).await.is_some())
+    }
+
+    /// Hints from the remote cache, or `None` if they're missing or can't be parsed
+    async fn remote_hints<T: serde::de::DeserializeOwned>(
+        &self,
+        table: &str,
+        key: &[&str],
+    ) -> eyre::Result<Option<Vec<T>>> {
+        let Some(remote) = self.remote().await? else {
+            return Ok(None);
+        };
+        let Some(bytes) = remote.get(table, key).await else {
+            return Ok(None);
+        };
+        match serde_json::from_slice(&bytes) {
+            Ok(hints) => Ok(Some(hints)),
+            Err(error) => {
+                tracing::warn!("Ignoring invalid `{table}` entry in remote cache: {error}");
+                Ok(None)
+            }
+        }
+    }
+
+    /// Upload a result to the remote cache in the background
+    async fn remote_put(&self, table: &str, key: &[&str], value: Bytes) -> eyre::Result<()> {
+        if let Some(remote) = self.remote().await? {
+            remote.put(table, key, value);
+        }
+        Ok(())
+    }
+
+    /// Wait for uploads to the remote cache to finish, before `be` exits
+    pub async fn finish_uploads(&self) {
+        if let Some(Some(remote)) = self.remote.get() {
+            remote.finish().await;
+        }
+    }
+
+    /// Add this run's cache hits and misses to the totals for `be cache stats`, before `be` exits
+    #[tracing::instrument(skip_all)]
+    pub async fn record_lookups(&self, lookups: &[(String, usize, usize)]) -> eyre::Result<()> {
+        if self.disabled || lookups.is_empty() {
+            return Ok(());
+        }
+        let mut transaction = self.sqlite.begin().await?;
+        for (tool, hits, misses) in lookups {
+            sqlx::query(
+                "
+                insert into lookups values ($1, $2, $3)
+                on conflict (tool) do update
+                set hits = hits + excluded.hits, misses = misses + excluded.misses
+                ",
+            )
+            .bind(tool)
+            .bind(i64::try_from(*hits)?)
+            .bind(i64::try_from(*misses)?)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn record_duration(
+        &self,
+        tool: &str,
+        source_hash: u64,
+        path: &Utf8Path,
+        duration: Duration,
+    ) -> eyre::Result<()> {
+        // Nothing ran for this file alone, like when it was part of a batch
+        if duration.is_zero() {
+            return Ok(());
+        }
+
+        if duration > SLOW_FILE_THRESHOLD {
+            tracing::warn!(
+                "`{tool}` took {:.1}s on `{path}`, which may dominate run time",
+                duration.as_secs_f64(),
+            );
+        }
+
+        if !self.is_enabled(tool).await? {
+            return Ok(());
+        }
+
+        let duration_ms = i64::try_from(duration.as_millis())?;
+
+        sqlx::query("insert or replace into durations values ($1, $2, $3, $4, $5, unixepoch())")
+            .bind(tool)
+            .bind(source_hash.to_string())
+            .bind(path.as_str())
+            .bind(duration_ms)
+            .bind(self.repo().await?)
+            .execute(&self.sqlite)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Longest recorded duration for each path, across all tools and versions of the file
+    #[tracing::instrument(skip_all)]
+    pub async fn durations_by_path(&self) -> eyre::Result<HashMap<Utf8PathBuf, Duration>> {
+        let rows: Vec<(String, i64)> = sqlx::query_as(
+            "
+            select path, max(duration_ms)
+            from durations
+            where repo = $1
+            group by path
+            ",
+        )
+        .bind(self.repo().await?)
+        .fetch_all(&self.sqlite)
+        .await?;
+
+        let durations = rows
+            .into_iter()
+            .map(|(path, duration_ms)| {
+                let duration = Duration::from_millis(u64::try_from(duration_ms).unwrap_or(0));
+                (Utf8PathBuf::from(path), duration)
+            })
+            .collect();
+
+        Ok(durations)
+    }
+
+    /// Number of recorded tool runs in this repo, and their total duration
+    #[tracing::instrument(skip_all)]
+    pub async fn duration_totals(&self) -> eyre::Result<(u64, Duration)> {
+        let (count, total_ms): (i64, i64) = sqlx::query_as(
+            "select count(*), coalesce(sum(duration_ms), 0) from durations where repo = $1",
+        )
+        .bind(self.repo().await?)
+        .fetch_one(&self.sqlite)
+        .await?;
+        let count = u64::try_from(count)?;
+        let total = Duration::from_millis(u64::try_from(total_ms)?);
+        Ok((count, total))
+    }
+
+    /// Slowest recorded tool runs in this repo, slowest first
+    #[tracing::instrument(skip(self))]
+    pub async fn slowest(
+        &self,
+        limit: usize,
+    ) -> eyre::Result<Vec<(String, Utf8PathBuf, Duration)>> {
+        let rows: Vec<(String, String, i64)> = sqlx::query_as(
+            "
+            select tool, path, duration_ms
+            from durations
+            where repo = $1
+            order by duration_ms desc
+            limit $2
+            ",
+        )
+        .bind(self.repo().await?)
+        .bind(i64::try_from(limit)?)
+        .fetch_all(&self.sqlite)
+        .await?;
+
+        let slowest = rows
+            .into_iter()
+            .map(|(tool, path, duration_ms)| {
+                let duration = Duration::from_millis(u64::try_from(duration_ms).unwrap_or(0));
+                (tool, Utf8PathBuf::from(path), duration)
+            })
+            .collect();
+
+        Ok(slowest)
+    }
+
+    /// Bytes used by `cache.sqlite` and its write-ahead log
+    #[tracing::instrument(skip_all)]
+    pub async fn size(&self) -> eyre::Result<u64> {
+        let mut size = 0;
+        for suffix in ["", "-wal"] {
+            match fs::metadata(self.dir.join(format!("cache.sqlite{suffix}"))).await {
+                Ok(metadata) => size += metadata.len(),
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+        Ok(size)
+    }
+
+    /// Bytes used by rows, not counting free pages which `vacuum` hasn't returned yet
+    #[tracing::instrument(skip_all)]
+    async fn used_size(&self) -> eyre::Result<u64> {
+        let size: i64 = sqlx::query_scalar(
+            "
+            select (page_count - freelist_count) * page_size
+            from pragma_page_count(), pragma_freelist_count(), pragma_page_size()
+            ",
+        )
+        .fetch_one(&self.sqlite)
+        .await?;
+        Ok(u64::try_from(size)?)
+    }
+
+    /// Number of rows in each table of cached results
+    #[tracing::instrument(skip_all)]
+    pub async fn row_counts(&self) -> eyre::Result<Vec<(&'static str, usize)>> {
+        let mut counts = Vec::with_capacity(TABLES.len());
+        for (table, _) in TABLES {
+            let count: i64 = sqlx::query_scalar(&format!("select count(*) from {table}"))
+                .fetch_one(&self.sqlite)
+                .await?;
+            counts.push((*table, usize::try_from(count)?));
+        }
+        Ok(counts)
+    }
+
+    /// Cache hits and misses for each tool, most used first
+    #[tracing::instrument(skip_all)]
+    pub async fn lookups(&self) -> eyre::Result<Vec<(String, u64, u64)>> {
+        let rows: Vec<(String, i64, i64)> = sqlx::query_as(
+            "
+            select tool, hits, misses
+            from lookups
+            order by hits + misses desc, tool
+            ",
+        )
+        .fetch_all(&self.sqlite)
+        .await?;
+        rows.into_iter()
+            .map(|(tool, hits, misses)| Ok((tool, u64::try_from(hits)?, u64::try_from(misses)?)))
+            .collect()
+    }
+
+    /// Delete cached results, only from `tool` and `repo` if they're given. Returns how many rows
+    /// were deleted.
+    #[tracing::instrument(skip(self))]
+    pub async fn clear(&self, tool: Option<&str>, repo: Option<&str>) -> eyre::Result<u64> {
+        let mut transaction = self.sqlite.begin().await?;
+        let mut deleted = 0;
+
+        for (table, owner) in TABLES {
+            let tool_filter = match (tool, owner) {
+                (Some(tool), Owner::Tool(owner)) if tool != *owner => continue,
+                // Built-in lints are cached under their name and settings, like `module-size:400`
+                (Some(_), Owner::Column(column)) => {
+                    format!("({column} = $1 or {column} like $1 || ':%')")
+                }
+                _ => String::from("true"),
+            };
+            deleted += sqlx::query(&format!(
+                "delete from {table} where {tool_filter} and ($2 is null or repo = $2)"
+            ))
+            .bind(tool)
+            .bind(repo)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+        }
+
+        // Hit ratios aren't kept for each repo, so only reset them when clearing every repo
+        if repo.is_none() {
+            sqlx::query("delete from lookups where $1 is null or tool = $1")
+                .bind(tool)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        transaction.commit().await?;
+
+        self.vacuum().await?;
+
+        Ok(deleted)
+    }
+
+    /// Delete cached results older than `max_age`, and then the oldest ones until the cache fits in
+    /// `max_size` bytes. Returns how many rows were deleted.
+    #[tracing::instrument(skip(self))]
+    pub async fn gc(&self, max_age: Option<Duration>, max_size: Option<u64>) -> eyre::Result<u64> {
+        let mut deleted = 0;
+
+        if let Some(max_age) = max_age {
+            let max_age = i64::try_from(max_age.as_secs())?;
+            for (table, _) in TABLES {
+                deleted += sqlx::query(&format!(
+                    "delete from {table} where created_at < unixepoch() - $1"
+                ))
+                .bind(max_age)
+                .execute(&self.sqlite)
+                .await?
+                .rows_affected();
+            }
+        }
+
+        if let Some(max_size) = max_size {
+            let all_rows = TABLES
+                .iter()
+                .map(|(table, _)| format!("select created_at from {table}"))
+                .collect::<Vec<_>>()
+                .join(" union all ");
+
+            loop {
+                let used_size = self.used_size().await?;
+                if used_size <= max_size {
+                    break;
+                }
+
+                let row_count: i64 =
+                    sqlx::query_scalar(&format!("select count(*) from ({all_rows})"))
+                        .fetch_one(&self.sqlite)
+                        .await?;
+                if row_count == 0 {
+                    break;
+                }
+
+                // Rows are roughly the same size, so delete the share of them that's over the limit
+                let excess = i64::try_from((used_size - max_size) * 100 / used_size)?;
+                let offset = (row_count * excess / 100).max(1) - 1;
+
+                let cutoff: i64 = sqlx::query_scalar(&format!(
+                    "select created_at from ({all_rows}) order by created_at limit 1 offset $1"
+                ))
+                .bind(offset)
+                .fetch_one(&self.sqlite)
+                .await?;
+
+                for (table, _) in TABLES {
+                    deleted += sqlx::query(&format!("delete from {table} where created_at <= $1"))
+                        .bind(cutoff)
+                        .execute(&self.sqlite)
+                        .await?
+                        .rows_affected();
+                }
+            }
+        }
+
+        self.vacuum().await?;
+
+        Ok(deleted)
+    }
+
+    /// Copy this repo's cached results to a new database at `path`, like for CI to save and
+    /// restore between runs. Returns how many rows were exported.
+    #[tracing::instrument(skip(self))]
+    pub async fn export(&self, path: &Utf8Path) -> eyre::Result<u64> {
+        sqlite_remove(path).await?;
+
+        let repo = self.repo().await?;
+
+        // Attached databases belong to one connection
+        let mut connection = self.sqlite.acquire().await?;
+
+        sqlx::query("attach database $1 as export")
+            .bind(path.as_str())
+            .execute(&mut *connection)
+            .await?;
+
+        let result = async {
+            let mut exported = 0;
+
+            for (table, _) in TABLES {
+                sqlx::query(&format!(
+                    "create table export.{table} as select * from main.{table} where repo = $1"
+                ))
+                .bind(repo)
+                .execute(&mut *connection)
+                .await?;
+
+                let count: i64 =
+                    sqlx::query_scalar(&format!("select count(*) from export.{table}"))
+                        .fetch_one(&mut *connection)
+                        .await?;
+                exported += u64::try_from(count)?;
+            }
+
+            // Pragmas can't take bound parameters
+            sqlx::raw_sql(&format!(
+                "
+                create table export.be_binary_id as select * from main.be_binary_id;
+
+                pragma export.user_version = {};
+                ",
+                MIGRATIONS.len(),
+            ))
+            .execute(&mut *connection)
+            .await?;
+
+            Ok(exported)
+        }
+        .await;
+
+        sqlx::query("detach database export")
+            .execute(&mut *connection)
+            .await?;
+
+        result
+    }
+
+    /// Add cached results from a database made by `export`, as if they were cached in this repo.
+    /// Returns how many rows were imported, and how many were in the database.
+    #[tracing::instrument(skip(self))]
+    pub async fn import(&self, path: &Utf8Path) -> eyre::Result<(u64, u64)> {
+        if fs::metadata(path).await.is_err() {
+            eyre::bail!("`{path}` doesn't exist");
+        }
+
+        let repo = self.repo().await?;
+
+        // Attached databases belong to one connection
+        let mut connection = self.sqlite.acquire().await?;
+
+        sqlx::query("attach database $1 as import")
+            .bind(path.as_str())
+            .execute(&mut *connection)
+            .await?;
+
+        let result = async {
+            let version: i64 = sqlx::query_scalar("pragma import.user_version")
+                .fetch_one(&mut *connection)
+                .await?;
+            if usize::try_from(version)? != MIGRATIONS.len() {
+                eyre::bail!(
+                    "`{path}` has schema version {version}, but this `be` uses schema version {}",
+                    MIGRATIONS.len(),
+                );
+            }
+
+            let id: Option<String> =
+                sqlx::query_scalar("select be_binary_id from import.be_binary_id")
+                    .fetch_optional(&mut *connection)
+                    .await?;
+
+            let mut transaction = connection.begin().await?;
+            let mut imported = 0;
+            let mut total = 0;
+
+            for (table, _) in TABLES {
+                // Results of built-in lints are only valid for the binary which found them
+                if *table == "builtin_lints" && id != Some(BE_BINARY_ID.to_string()) {
+                    tracing::debug!("Skipping results of built-in lints from a different binary");
+                    continue;
+                }
+
+                let columns: Vec<String> =
+                    sqlx::query_scalar("select name from pragma_table_info($1, 'main')")
+                        .bind(table)
+                        .fetch_all(&mut *transaction)
+                        .await?;
+
+                // Results are moved to this repo, since CI usually checks it out somewhere else
+                let values = columns
+                    .iter()
+                    .map(|column| if column == "repo" { "$1" } else { column })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                imported += sqlx::query(&format!(
+                    "insert or ignore into main.{table} select {values} from import.{table}"
+                ))
+                .bind(repo)
+                .execute(&mut *transaction)
+                .await?
+                .rows_affected();
+
+                let count: i64 =
+                    sqlx::query_scalar(&format!("select count(*) from import.{table}"))
+                        .fetch_one(&mut *transaction)
+                        .await?;
+                total += u64::try_from(count)?;
+            }
+
+            transaction.commit().await?;
+
+            Ok((imported, total))
+        }
+        .await;
+
+        sqlx::query("detach database import")
+            .execute(&mut *connection)
+            .await?;
+
+        result
+    }
+
+    /// Give free pages back to the file system, so deleting rows shrinks the cache on disk
+    #[tracing::instrument(skip_all)]
+    async fn vacuum(&self) -> eyre::Result<()> {
+        sqlx::raw_sql("vacuum; pragma wal_checkpoint(truncate);")
+            .execute(&self.sqlite)
+            .await?;
+        Ok(())
+    }
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &Args) -> eyre::Result<()> {
+    match &args.command {
+        Command::Stats(args) => stats::run(args).await,
+        Command::Clear(args) => clear::run(args).await,
+        Command::Gc(args) => gc::run(args).await,
+        Command::Export(args) => export::run(args).await,
+        Command::Import(args) => import::run(args).await,
+        Command::Verify(args) => verify::run(args).await,
+    }
+}
+
+/// Connect to the cache, checking that it isn't corrupt (e.g. after power loss) and migrating it if
+/// it's from an older `be`
+#[tracing::instrument]
+async fn sqlite_open(sqlite_path: &Utf8Path) -> eyre::Result<SqlitePool> {
+    let sqlite_url = format!("sqlite://{sqlite_path}");
+
+    let sqlite_opts = SqliteConnectOptions::from_str(&sqlite_url)?
+        .journal_mode(SqliteJournalMode::Wal)
+        .synchronous(SqliteSynchronous::Normal)
+        // .pragma("mmap_size", u32::MAX.to_string())
+        .create_if_missing(true);
+
+    let sqlite = SqlitePool::connect_with(sqlite_opts).await?;
+
+    let result = async {
+        let problems: Vec<String> = sqlx::query_scalar("pragma quick_check")
+            .fetch_all(&sqlite)
+            .await?;
+
+        if problems != ["ok"] {
+            eyre::bail!("Integrity check failed: {}", problems.join("; "));
+        }
+
+        sqlite_migrate(&sqlite).await?;
+
+        sqlite_forget_builtin_hints(&sqlite).await?;
+
+        Ok(())
+    };
+
+    if let Err(error) = result.await {
+        sqlite.close().await;
+        return Err(error);
+    }
+
+    Ok(sqlite)
+}
+
+/// Delete the cache database along with its write-ahead log
+#[tracing::instrument]
+async fn sqlite_remove(sqlite_path: &Utf8Path) -> eyre::Result<()> {
+    for suffix in ["", "-wal", "-shm"] {
+        match fs::remove_file(format!("{sqlite_path}{suffix}")).await {
+            Ok(()) => {}
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+            Err(error) => return Err(error.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Bring the cache's schema up to date, tracking how far it got in `pragma user_version`
+#[tracing::instrument(skip_all)]
+async fn sqlite_migrate(sqlite: &SqlitePool) -> eyre::Result<()> {
+    let version: i64 = sqlx::query_scalar("pragma user_version")
+        .fetch_one(sqlite)
+        .await?;
+    let mut version = usize::try_from(version)?;
+
+    if version == MIGRATIONS.len() {
+        tracing::debug!("Using existing SQLite cache (schema version {version})");
+        return Ok(());
+    }
+
+    let mut transaction = sqlite.begin().await?;
+
+    if version > MIGRATIONS.len() {
+        // There's no way to undo migrations from a newer `be`, so start over. Its tables are
+        // dropped first, since replaying the migrations would otherwise create them again.
+        tracing::debug!(
+            "Downgrading SQLite cache from a newer `be` (schema version {version}) by resetting it",
+        );
+        let tables: Vec<String> = sqlx::query_scalar(
+            "select name from sqlite_master where type = 'table' and name not like 'sqlite_%'",
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        for table in tables {
+            sqlx::raw_sql(&format!("drop table \"{}\"", table.replace('"', "\"\"")))
+                .execute(&mut *transaction)
+                .await?;
+        }
+        version = 0;
+    }
+
+    tracing::debug!(
+        "Migrating SQLite cache from schema version {version} to {}",
+        MIGRATIONS.len(),
+    );
+
+    for migration in &MIGRATIONS[version..] {
+        sqlx::raw_sql(migration).execute(&mut *transaction).await?;
+    }
+
+    // Pragmas can't take bound parameters
+    sqlx::raw_sql(&format!("pragma user_version = {}", MIGRATIONS.len()))
+        .execute(&mut *transaction)
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(())
 }
 
+/// `be`'s own lints change along with `be`, unlike results from other tools which are keyed by
+/// their version, so only keep them for the binary which found them
 #[tracing::instrument(skip_all)]
-async fn sqlite_reset(sqlite: &SqlitePool) -> eyre::Result<()> {
-    sqlx::raw_sql(
-        "
-        drop table if exists be_binary_id;
-
-        drop table if exists fourmolu;
-
-        drop table if exists nixfmt;
-
-        drop table if exists hlint;
-
-        create table be_binary_id (
-            be_binary_id text primary key not null
-        ) strict;
-
-        create table fourmolu (
-            version text not null,
-            config_hash text not null,
-            extensions_hash text not null,
-            source_hash text not null,
-            unique (version, config_hash, source_hash)
-        ) strict;
-
-        create table nixfmt (
-            version text not null,
-            source_hash text not null,
-            unique (version, source_hash)
-        ) strict;
-
-        create table hlint (
-            version text not null,
-            configs_hash text not null,
-            source_hash text not null,
-            hints blob not null,
-            unique (version, configs_hash, source_hash)
-        ) strict;
-        ",
-    )
-    .execute(sqlite)
-    .await?;
+async fn sqlite_forget_builtin_hints(sqlite: &SqlitePool) -> eyre::Result<()> {
+    let id: Option<String> = sqlx::query_scalar("select be_binary_id from be_binary_id")
+        .fetch_optional(sqlite)
+        .await?;
+
+    if id == Some(BE_BINARY_ID.to_string()) {
+        return Ok(());
+    }
+
+    tracing::debug!("Forgetting results of built-in lints from a different `be` binary");
+
+    let mut transaction = sqlite.begin().await?;
+
+    for table in ["builtin_lints", "be_binary_id"] {
+        sqlx::query(&format!("delete from {table}"))
+            .execute(&mut *transaction)
+            .await?;
+    }
 
     sqlx::query("insert into be_binary_id values ($1)")
         .bind(BE_BINARY_ID.to_string())
-        .execute(sqlite)
+        .execute(&mut *transaction)
         .await?;
 
+    transaction.commit().await?;
+
     Ok(())
 }
 
+/// Which tool's results a table holds
+enum Owner {
+    /// Every row is from this tool
+    Tool(&'static str),
+    /// Rows are from the tool named in this column
+    Column(&'static str),
+}
+
+/// Tables of cached results, which `be cache clear` and `be cache gc` delete from
+const TABLES: &[(&str, Owner)] = &[
+    ("fourmolu", Owner::Tool("fourmolu")),
+    ("fourmolu_outputs", Owner::Tool("fourmolu")),
+    ("nix_formatter", Owner::Column("formatter")),
+    ("nix_formatter_outputs", Owner::Column("formatter")),
+    ("dhall", Owner::Tool("dhall")),
+    ("shfmt", Owner::Tool("shfmt")),
+    ("cabal_formatter", Owner::Column("formatter")),
+    ("hlint", Owner::Tool("hlint")),
+    ("shellcheck", Owner::Tool("shellcheck")),
+    ("nix_linter", Owner::Column("linter")),
+    ("ghc", Owner::Tool("ghc")),
+    ("weeder", Owner::Tool("weeder")),
+    ("stan", Owner::Tool("stan")),
+    ("typos", Owner::Tool("typos")),
+    ("builtin_lints", Owner::Column("lint")),
+    ("durations", Owner::Column("tool")),
+];
+
+/// Changes to the schema, in order, which each bring a cache from the version before it up to date.
+/// Add a migration rather than editing one, and have it delete any results which are no longer
+/// valid, like hints serialized in a different shape.
+const MIGRATIONS: &[&str] = &[
+    SCHEMA,
+    REPO_SCHEMA,
+    GHC_SCHEMA,
+    WEEDER_SCHEMA,
+    STAN_SCHEMA,
+    FOURMOLU_EXTENSIONS_SCHEMA,
+];
+
+/// The first schema, which drops tables from before caches had a schema version
+const SCHEMA: &str = "
+    drop table if exists be_binary_id;
+
+    drop table if exists fourmolu;
+
+    drop table if exists nixfmt;
+
+    drop table if exists fourmolu_outputs;
+
+    drop table if exists nix_formatter;
+
+    drop table if exists nix_formatter_outputs;
+
+    drop table if exists dhall;
+
+    drop table if exists shfmt;
+
+    drop table if exists cabal_formatter;
+
+    drop table if exists hlint;
+
+    drop table if exists shellcheck;
+
+    drop table if exists nix_linter;
+
+    drop table if exists typos;
+
+    drop table if exists builtin_lints;
+
+    drop table if exists durations;
+
+    drop table if exists lookups;
+
+    create table be_binary_id (
+        be_binary_id text primary key not null
+    ) strict;
+
+    create table fourmolu (
+        version text not null,
+        config_hash text not null,
+        extensions_hash text not null,
+        source_hash text not null,
+        created_at integer not null,
+        unique (version, config_hash, source_hash)
+    ) strict;
+
+    create table fourmolu_outputs (
+        version text not null,
+        config_hash text not null,
+        extensions_hash text not null,
+        source_hash text not null,
+        output blob not null,
+        created_at integer not null,
+        unique (version, config_hash, extensions_hash, source_hash)
+    ) strict;
+
+    create table nix_formatter (
+        formatter text not null,
+        version text not null,
+        source_hash text not null,
+        created_at integer not null,
+        unique (formatter, version, source_hash)
+    ) strict;
+
+    create table nix_formatter_outputs (
+        formatter text not null,
+        version text not null,
+        source_hash text not null,
+        output blob not null,
+        created_at integer not null,
+        unique (formatter, version, source_hash)
+    ) strict;
+
+    create table dhall (
+        version text not null,
+        source_hash text not null,
+        created_at integer not null,
+        unique (version, source_hash)
+    ) strict;
+
+    create table shfmt (
+        version text not null,
+        source_hash text not null,
+        created_at integer not null,
+        unique (version, source_hash)
+    ) strict;
+
+    create table cabal_formatter (
+        formatter text not null,
+        version text not null,
+        source_hash text not null,
+        created_at integer not null,
+        unique (formatter, version, source_hash)
+    ) strict;
+
+    create table hlint (
+        version text not null,
+        configs_hash text not null,
+        source_hash text not null,
+        hints blob not null,
+        created_at integer not null,
+        unique (version, configs_hash, source_hash)
+    ) strict;
+
+    create table shellcheck (
+        version text not null,
+        source_hash text not null,
+        hints blob not null,
+        created_at integer not null,
+        unique (version, source_hash)
+    ) strict;
+
+    create table nix_linter (
+        linter text not null,
+        version text not null,
+        source_hash text not null,
+        hints blob not null,
+        created_at integer not null,
+        unique (linter, version, source_hash)
+    ) strict;
+
+    create table typos (
+        version text not null,
+        config_hash text not null,
+        source_hash text not null,
+        hints blob not null,
+        created_at integer not null,
+        unique (version, config_hash, source_hash)
+    ) strict;
+
+    create table builtin_lints (
+        lint text not null,
+        source_hash text not null,
+        hints blob not null,
+        created_at integer not null,
+        unique (lint, source_hash)
+    ) strict;
+
+    create table durations (
+        tool text not null,
+        source_hash text not null,
+        path text not null,
+        duration_ms integer not null,
+        created_at integer not null,
+        unique (tool, source_hash)
+    ) strict;
+
+    create table lookups (
+        tool text primary key not null,
+        hits integer not null,
+        misses integer not null
+    ) strict;
+    ";
+
+/// Keeps results for each repo separately, dropping older results since they don't say which repo
+/// they're from
+const REPO_SCHEMA: &str = "
+    drop table fourmolu;
+
+    create table fourmolu (
+        version text not null,
+        config_hash text not null,
+        extensions_hash text not null,
+        source_hash text not null,
+        repo text not null,
+        created_at integer not null,
+        unique (version, config_hash, source_hash, repo)
+    ) strict;
+
+    drop table fourmolu_outputs;
+
+    create table fourmolu_outputs (
+        version text not null,
+        config_hash text not null,
+        extensions_hash text not null,
+        source_hash text not null,
+        output blob not null,
+        repo text not null,
+        created_at integer not null,
+        unique (version, config_hash, extensions_hash, source_hash, repo)
+    ) strict;
+
+    drop table nix_formatter;
+
+    create table nix_formatter (
+        formatter text not null,
+        version text not null,
+        source_hash text not null,
+        repo text not null,
+        created_at integer not null,
+        unique (formatter, version, source_hash, repo)
+    ) strict;
+
+    drop table nix_formatter_outputs;
+
+    create table nix_formatter_outputs (
+        formatter text not null,
+        version text not null,
+        source_hash text not null,
+        output blob not null,
+        repo text not null,
+        created_at integer not null,
+        unique (formatter, version, source_hash, repo)
+    ) strict;
+
+    drop table dhall;
+
+    create table dhall (
+        version text not null,
+        source_hash text not null,
+        repo text not null,
+        created_at integer not null,
+        unique (version, source_hash, repo)
+    ) strict;
+
+    drop table shfmt;
+
+    create table shfmt (
+        version text not null,
+        source_hash text not null,
+        repo text not null,
+        created_at integer not null,
+        unique (version, source_hash, repo)
+    ) strict;
+
+    drop table cabal_formatter;
+
+    create table cabal_formatter (
+        formatter text not null,
+        version text not null,
+        source_hash text not null,
+        repo text not null,
+        created_at integer not null,
+        unique (formatter, version, source_hash, repo)
+    ) strict;
+
+    drop table hlint;
+
+    create table hlint (
+        version text not null,
+        configs_hash text not null,
+        source_hash text not null,
+        hints blob not null,
+        repo text not null,
+        created_at integer not null,
+        unique (version, configs_hash, source_hash, repo)
+    ) strict;
+
+    drop table shellcheck;
+
+    create table shellcheck (
+        version text not null,
+        source_hash text not null,
+        hints blob not null,
+        repo text not null,
+        created_at integer not null,
+        unique (version, source_hash, repo)
+    ) strict;
+
+    drop table nix_linter;
+
+    create table nix_linter (
+        linter text not null,
+        version text not null,
+        source_hash text not null,
+        hints blob not null,
+        repo text not null,
+        created_at integer not null,
+        unique (linter, version, source_hash, repo)
+    ) strict;
+
+    drop table typos;
+
+    create table typos (
+        version text not null,
+        config_hash text not null,
+        source_hash text not null,
+        hints blob not null,
+        repo text not null,
+        created_at integer not null,
+        unique (version, config_hash, source_hash, repo)
+    ) strict;
+
+    drop table builtin_lints;
+
+    create table builtin_lints (
+        lint text not null,
+        source_hash text not null,
+        hints blob not null,
+        repo text not null,
+        created_at integer not null,
+        unique (lint, source_hash, repo)
+    ) strict;
+
+    drop table durations;
+
+    create table durations (
+        tool text not null,
+        source_hash text not null,
+        path text not null,
+        duration_ms integer not null,
+        repo text not null,
+        created_at integer not null,
+        unique (tool, source_hash, repo)
+    ) strict;
+    ";
+
+/// Adds warnings from `be lint ghc`
+const GHC_SCHEMA: &str = "
+    create table ghc (
+        version text not null,
+        flags_hash text not null,
+        extensions_hash text not null,
+        source_hash text not null,
+        hints blob not null,
+        repo text not null,
+        created_at integer not null,
+        unique (version, flags_hash, extensions_hash, source_hash, repo)
+    ) strict;
+    ";
+
+/// Adds unused code found by `be lint weeder`
+const WEEDER_SCHEMA: &str = "
+    create table weeder (
+        version text not null,
+        config_hash text not null,
+        hie_hash text not null,
+        hints blob not null,
+        repo text not null,
+        created_at integer not null,
+        unique (version, config_hash, hie_hash, repo)
+    ) strict;
+    ";
+
+/// Adds observations from `be lint stan`
+const STAN_SCHEMA: &str = "
+    create table stan (
+        version text not null,
+        config_hash text not null,
+        source_hash text not null,
+        hints blob not null,
+        repo text not null,
+        created_at integer not null,
+        unique (version, config_hash, source_hash, repo)
+    ) strict;
+    ";
+
+/// Adds `extensions_hash` to `fourmolu`'s unique key. Without it, once a file was formatted with
+/// some extensions, `insert or ignore` skipped recording it as formatted with others.
+const FOURMOLU_EXTENSIONS_SCHEMA: &str = "
+    create table fourmolu_new (
+        version text not null,
+        config_hash text not null,
+        extensions_hash text not null,
+        source_hash text not null,
+        repo text not null,
+        created_at integer not null,
+        unique (version, config_hash, extensions_hash, source_hash, repo)
+    ) strict;
+
+    insert into fourmolu_new select * from fourmolu;
+
+    drop table fourmolu;
+
+    alter table fourmolu_new rename to fourmolu;
+    ";
+
 // TODO: This might be incorrect? Hashing the `be` binary wasn't working.
 #[tracing::instrument]
 async fn file_hash(path: &Utf8Path) -> eyre::Result<u64> {
@@ -502,7 +2677,17 @@ async fn file_hash(path: &Utf8Path) -> eyre::Result<u64> {
     Ok(file.hash())
 }
 
+/// Where cached data is kept without `--cache-dir`
 #[tracing::instrument(skip_all)]
+pub fn default_dir() -> eyre::Result<Utf8PathBuf> {
+    let xdg = Xdg::new(AppStrategyArgs {
+        top_level_domain: String::from("com"),
+        author: String::from("Evan Relf"),
+        app_name: String::from("Be"),
+    })?;
+    Ok(Utf8PathBuf::try_from(xdg.cache_dir())?)
+}
+
 async fn git_root(git: &Utf8Path) -> eyre::Result<Utf8PathBuf> {
     let stdout = exec(git, ["rev-parse", "--show-toplevel"]).await?;
     let root = Utf8PathBuf::from(str::from_utf8(&stdout)?.trim_end());
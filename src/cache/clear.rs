@@ -0,0 +1,37 @@
+use crate::{cli::cache::ClearArgs, context::cx, events::message, git};
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &ClearArgs) -> eyre::Result<()> {
+    let cx = cx();
+
+    let repo = match &args.repo {
+        Some(path) => Some(git::root(path).await?),
+        None => None,
+    };
+
+    let deleted = cx
+        .cache
+        .clear(
+            args.tool.as_deref(),
+            repo.as_ref().map(|repo| repo.as_str()),
+        )
+        .await?;
+
+    message!(
+        "Deleted {deleted} cached {results}{from}{repo}",
+        deleted = deleted.to_formatted_string(&Locale::en),
+        results = if deleted == 1 { "result" } else { "results" },
+        from = match &args.tool {
+            Some(tool) => format!(" from `{tool}`"),
+            None => String::new(),
+        },
+        repo = match &repo {
+            Some(repo) => format!(" in `{repo}`"),
+            None => String::new(),
+        },
+    );
+
+    Ok(())
+}
@@ -0,0 +1,23 @@
+use crate::{cli::cache::ExportArgs, context::cx, events::message, utils::kilobytes};
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use tokio::fs;
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &ExportArgs) -> eyre::Result<()> {
+    let cx = cx();
+
+    let exported = cx.cache.export(&args.path).await?;
+
+    let size = fs::metadata(&args.path).await?.len();
+
+    message!(
+        "Exported {exported} cached {results} to `{path}` ({size})",
+        exported = exported.to_formatted_string(&Locale::en),
+        results = if exported == 1 { "result" } else { "results" },
+        path = args.path,
+        size = kilobytes(size),
+    );
+
+    Ok(())
+}
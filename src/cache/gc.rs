@@ -0,0 +1,34 @@
+use crate::{cli::cache::GcArgs, context::cx, events::message, utils::kilobytes};
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use std::time::Duration;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &GcArgs) -> eyre::Result<()> {
+    let cx = cx();
+
+    let size_before = cx.cache.size().await?;
+
+    let deleted = cx
+        .cache
+        .gc(
+            args.max_age
+                .map(|days| Duration::from_secs(days * SECONDS_PER_DAY)),
+            args.max_size.map(|megabytes| megabytes * 1_000_000),
+        )
+        .await?;
+
+    let size_after = cx.cache.size().await?;
+
+    message!(
+        "Deleted {deleted} cached {results}, shrinking the cache from {} to {}",
+        kilobytes(size_before),
+        kilobytes(size_after),
+        deleted = deleted.to_formatted_string(&Locale::en),
+        results = if deleted == 1 { "result" } else { "results" },
+    );
+
+    Ok(())
+}
@@ -0,0 +1,20 @@
+use crate::{cli::cache::ImportArgs, context::cx, events::message};
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &ImportArgs) -> eyre::Result<()> {
+    let cx = cx();
+
+    let (imported, total) = cx.cache.import(&args.path).await?;
+
+    message!(
+        "Imported {imported} of {total} cached {results} from `{path}`",
+        imported = imported.to_formatted_string(&Locale::en),
+        total = total.to_formatted_string(&Locale::en),
+        results = if total == 1 { "result" } else { "results" },
+        path = args.path,
+    );
+
+    Ok(())
+}
@@ -0,0 +1,298 @@
+use crate::{config::RemoteCacheConfig, context::cx, exec};
+use bytes::Bytes;
+use color_eyre::eyre;
+use dashmap::DashSet;
+use hmac::{Hmac, Mac as _};
+use sha2::Sha256;
+use std::{
+    env,
+    fmt::Write as _,
+    io::Write as _,
+    process::Stdio,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+use tokio::{io::AsyncWriteExt as _, process::Command, task::JoinSet};
+use twox_hash::XxHash3_64;
+
+/// Environment variable holding the bearer token, unless `token-env` names another
+const DEFAULT_TOKEN_ENV: &str = "BE_REMOTE_CACHE_TOKEN";
+
+/// Environment variable holding the signing key, unless `signing-key-env` names another
+const DEFAULT_SIGNING_KEY_ENV: &str = "BE_REMOTE_CACHE_SIGNING_KEY";
+
+/// Length of the HMAC-SHA256 signature at the start of a signed entry
+const SIGNATURE_LEN: usize = 32;
+
+/// A cache shared over HTTP with `curl`, which results are read through from on a local miss and
+/// written back to in the background.
+///
+/// Failing to reach it logs one warning and turns it off for the rest of the run, since it's never
+/// needed for correctness.
+///
+/// With a signing key, entries start with an HMAC of where they're stored and what's stored there,
+/// and entries without a valid one are ignored. Otherwise one compromised machine with `write`
+/// enabled could mark unformatted or unlinted files as clean for everyone else.
+pub struct Remote {
+    url: String,
+    write: bool,
+    signing_key: Option<Hmac<Sha256>>,
+    request: Request,
+    /// Keys which were just read, so storing them locally doesn't upload them again
+    fetched: DashSet<String>,
+    uploads: Mutex<JoinSet<()>>,
+}
+
+/// A value in a curl config file, which is read with backslash escapes inside quotes
+fn quoted(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for char in value.chars() {
+        match char {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            _ => quoted.push(char),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+impl Remote {
+    pub fn new(config: &RemoteCacheConfig) -> eyre::Result<Option<Self>> {
+        let Some(url) = &config.url else {
+            return Ok(None);
+        };
+
+        // Writing to a `String` can't fail
+        let mut curl_config = String::new();
+
+        let token_env = config.token_env.as_deref().unwrap_or(DEFAULT_TOKEN_ENV);
+        if let Ok(token) = env::var(token_env) {
+            let _ = writeln!(
+                curl_config,
+                "header = {}",
+                quoted(&format!("Authorization: Bearer {token}")),
+            );
+        }
+
+        if let Some(region) = &config.s3_region {
+            let (Ok(key_id), Ok(secret)) = (
+                env::var("AWS_ACCESS_KEY_ID"),
+                env::var("AWS_SECRET_ACCESS_KEY"),
+            ) else {
+                eyre::bail!(
+                    "Set `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY` to use the S3 remote cache"
+                );
+            };
+            let _ = writeln!(
+                curl_config,
+                "aws-sigv4 = {}",
+                quoted(&format!("aws:amz:{region}:s3")),
+            );
+            let _ = writeln!(
+                curl_config,
+                "user = {}",
+                quoted(&format!("{key_id}:{secret}"))
+            );
+            if let Ok(session_token) = env::var("AWS_SESSION_TOKEN") {
+                let _ = writeln!(
+                    curl_config,
+                    "header = {}",
+                    quoted(&format!("x-amz-security-token: {session_token}")),
+                );
+            }
+        }
+
+        let signing_key_env = config
+            .signing_key_env
+            .as_deref()
+            .unwrap_or(DEFAULT_SIGNING_KEY_ENV);
+        let signing_key = match env::var(signing_key_env) {
+            Ok(key) if !key.is_empty() => Some(Hmac::new_from_slice(key.as_bytes())?),
+            _ => None,
+        };
+
+        Ok(Some(Self {
+            url: String::from(url.trim_end_matches('/')),
+            // Unsigned entries can't be checked, so uploading them is opt-in
+            write: config.write.unwrap_or(signing_key.is_some()),
+            signing_key,
+            request: Request {
+                curl_config: Arc::from(curl_config),
+                unreachable: Arc::new(AtomicBool::new(false)),
+            },
+            fetched: DashSet::new(),
+            uploads: Mutex::new(JoinSet::new()),
+        }))
+    }
+
+    /// Where a result is stored under the base URL: the table it's cached in locally, and a hash of
+    /// the columns it's looked up by
+    fn path(table: &str, key: &[&str]) -> String {
+        let hash = XxHash3_64::oneshot(key.join("\0").as_bytes());
+        format!("{table}/{hash:016x}")
+    }
+
+    /// An HMAC of an entry's path and value, so an entry can't be copied to another path either
+    fn signature(signing_key: &Hmac<Sha256>, path: &str, value: &[u8]) -> Hmac<Sha256> {
+        let mut mac = signing_key.clone();
+        mac.update(path.as_bytes());
+        mac.update(b"\0");
+        mac.update(value);
+        mac
+    }
+
+    /// The value of a signed entry, if its signature is valid
+    fn verify(signing_key: &Hmac<Sha256>, path: &str, mut bytes: Bytes) -> Option<Bytes> {
+        if bytes.len() < SIGNATURE_LEN {
+            return None;
+        }
+        let value = bytes.split_off(SIGNATURE_LEN);
+        Self::signature(signing_key, path, &value)
+            .verify_slice(&bytes)
+            .ok()?;
+        Some(value)
+    }
+
+    /// A result stored under `key`, if there is one
+    pub async fn get(&self, table: &str, key: &[&str]) -> Option<Bytes> {
+        let path = Self::path(table, key);
+        let url = format!("{}/{path}", self.url);
+        let mut bytes = self.request.send(&url, None).await?;
+        if let Some(signing_key) = &self.signing_key {
+            let Some(value) = Self::verify(signing_key, &path, bytes) else {
+                tracing::warn!(
+                    "Ignoring `{table}` entry in remote cache without a valid signature"
+                );
+                return None;
+            };
+            bytes = value;
+        }
+        tracing::trace!("Found result in remote cache");
+        self.fetched.insert(url);
+        Some(bytes)
+    }
+
+    /// Store a result under `key` in the background, unless it came from the remote cache
+    pub fn put(&self, table: &str, key: &[&str], value: Bytes) {
+        if !self.write {
+            return;
+        }
+        let path = Self::path(table, key);
+        let url = format!("{}/{path}", self.url);
+        if self.fetched.remove(&url).is_some() {
+            return;
+        }
+        let value = match &self.signing_key {
+            Some(signing_key) => {
+                let signature = Self::signature(signing_key, &path, &value).finalize();
+                let mut signed = Vec::with_capacity(SIGNATURE_LEN + value.len());
+                signed.extend_from_slice(&signature.into_bytes());
+                signed.extend_from_slice(&value);
+                Bytes::from(signed)
+            }
+            None => value,
+        };
+        let request = self.request.clone();
+        let mut uploads = self.uploads.lock().unwrap();
+        uploads.spawn(async move {
+            request.send(&url, Some(value)).await;
+        });
+    }
+
+    /// Wait for uploads started by `put`
+    pub async fn finish(&self) {
+        let uploads = std::mem::take(&mut *self.uploads.lock().unwrap());
+        uploads.join_all().await;
+    }
+}
+
+/// What each `curl` invocation needs, shared with uploads running in the background
+#[derive(Clone)]
+struct Request {
+    /// Options passed to `curl` on `stdin`, so credentials don't show up in the process list
+    curl_config: Arc<str>,
+    /// Set once a request fails, so the rest of the run doesn't wait on or warn about each file
+    unreachable: Arc<AtomicBool>,
+}
+
+impl Request {
+    /// `GET` the URL, or `PUT` the body to it. Returns `None` if there's nothing at the URL or the
+    /// remote cache can't be reached.
+    async fn send(&self, url: &str, body: Option<Bytes>) -> Option<Bytes> {
+        if self.unreachable.load(Ordering::Relaxed) {
+            return None;
+        }
+        match self.curl(url, body).await {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                if !self.unreachable.swap(true, Ordering::Relaxed) {
+                    tracing::warn!("Not using remote cache for the rest of this run: {error}");
+                }
+                None
+            }
+        }
+    }
+
+    async fn curl(&self, url: &str, body: Option<Bytes>) -> eyre::Result<Option<Bytes>> {
+        let cx = cx();
+
+        let curl = cx.cache.which("curl").await?;
+
+        let mut command = Command::new(curl);
+        command.args(["--silent", "--show-error", "--location", "--config", "-"]);
+        command.args(["--write-out", "\n%{http_code}"]);
+
+        // Kept until `curl` exits, since it reads the body from the file
+        let body_file = if let Some(body) = &body {
+            let mut file = tempfile::NamedTempFile::new()?;
+            file.write_all(body)?;
+            command.arg("--upload-file").arg(file.path());
+            Some(file)
+        } else {
+            None
+        };
+
+        command.arg(url);
+
+        let mut child = command
+            .kill_on_drop(true)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let mut stdin = child.stdin.take().unwrap();
+        stdin.write_all(self.curl_config.as_bytes()).await?;
+        stdin.flush().await?;
+        drop(stdin);
+
+        let output = exec::timeout("curl", child.wait_with_output()).await?;
+
+        drop(body_file);
+
+        if !output.status.success() {
+            eyre::bail!(
+                "`curl` failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim_end(),
+            );
+        }
+
+        // The status code is on the last line, after the body
+        let stdout = Bytes::from(output.stdout);
+        let split = stdout.iter().rposition(|byte| *byte == b'\n').unwrap_or(0);
+        let status = String::from_utf8_lossy(&stdout[split..]).trim().to_string();
+
+        match status.as_str() {
+            "200" | "201" | "204" => Ok(Some(stdout.slice(..split))),
+            "404" => Ok(None),
+            _ => eyre::bail!("`{url}` responded with HTTP status {status}"),
+        }
+    }
+}
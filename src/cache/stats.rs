@@ -0,0 +1,58 @@
+use crate::{
+    cli::cache::StatsArgs,
+    context::cx,
+    utils::{kilobytes, table},
+};
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use tracing_indicatif::indicatif_println;
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &StatsArgs) -> eyre::Result<()> {
+    let cx = cx();
+
+    indicatif_println!(
+        "Cache in `{}` is {}",
+        cx.cache.dir(),
+        kilobytes(cx.cache.size().await?),
+    );
+
+    indicatif_println!();
+    indicatif_println!("{}", table(&cx.cache.row_counts().await?));
+
+    let lookups = cx.cache.lookups().await?;
+    if !lookups.is_empty() {
+        indicatif_println!();
+        let width = lookups
+            .iter()
+            .map(|(tool, _, _)| tool.len())
+            .max()
+            .unwrap_or(0);
+        for (tool, hits, misses) in lookups {
+            indicatif_println!(
+                "{tool:<width$}  {percent:>3}% of {lookups} lookups hit",
+                percent = hits * 100 / (hits + misses).max(1),
+                lookups = (hits + misses).to_formatted_string(&Locale::en),
+            );
+        }
+    }
+
+    let (count, total) = cx.cache.duration_totals().await?;
+
+    indicatif_println!();
+    indicatif_println!(
+        "Recorded {count} tool {runs} taking {:.2}s in total",
+        total.as_secs_f64(),
+        count = count.to_formatted_string(&Locale::en),
+        runs = if count == 1 { "run" } else { "runs" },
+    );
+
+    if let Some(limit) = args.slowest {
+        indicatif_println!();
+        for (tool, path, duration) in cx.cache.slowest(limit).await? {
+            indicatif_println!("{:>8.2}s  {tool:<10} {path}", duration.as_secs_f64());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use crate::{
+    cli::cache::VerifyArgs,
+    config::{Language, Pipeline},
+    context::cx,
+    events::message,
+    format, git,
+    io::read_file,
+};
+use bytes::Bytes;
+use camino::Utf8Path;
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing_indicatif::indicatif_println;
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &VerifyArgs) -> eyre::Result<()> {
+    // Not cryptographic, just enough to check different files on each run
+    let seed = u64::from(SystemTime::now().duration_since(UNIX_EPOCH)?.subsec_nanos());
+
+    let sample = args.sample;
+
+    let haskell = tokio::spawn(async move { verify(Language::Haskell, sample, seed).await });
+
+    let nix = tokio::spawn(async move { verify(Language::Nix, sample, seed).await });
+
+    let dhall = tokio::spawn(async move { verify(Language::Dhall, sample, seed).await });
+
+    // Shell scripts are only formatted when `shfmt` is installed
+    let shell = if cx().cache.which("shfmt").await.is_ok() {
+        Some(tokio::spawn(async move {
+            verify(Language::Shell, sample, seed).await
+        }))
+    } else {
+        None
+    };
+
+    let mut diverged_count = haskell.await?? + nix.await?? + dhall.await??;
+
+    if let Some(shell) = shell {
+        diverged_count += shell.await??;
+    }
+
+    if diverged_count > 0 {
+        eyre::bail!(
+            "{diverged_count} cached {files} diverged from current formatter output",
+            diverged_count = diverged_count.to_formatted_string(&Locale::en),
+            files = if diverged_count == 1 { "file" } else { "files" },
+        );
+    }
+
+    Ok(())
+}
+
+#[tracing::instrument(skip(seed))]
+async fn verify(language: Language, sample: usize, seed: u64) -> eyre::Result<usize> {
+    let paths = match language {
+        Language::Haskell => git::all_haskell_files(Pipeline::Format).await?,
+        Language::Nix => git::all_nix_files(Pipeline::Format).await?,
+        Language::Dhall => git::all_dhall_files(Pipeline::Format).await?,
+        Language::Shell => git::all_shell_files(Pipeline::Format).await?,
+    };
+
+    let mut handles = Vec::new();
+
+    for path in paths {
+        handles.push(tokio::spawn(async move {
+            let (bytes, hash) = read_file(&path).await?;
+            let is_cached = is_formatted(language, hash).await?;
+            eyre::Ok(is_cached.then_some((path, bytes, hash)))
+        }));
+    }
+
+    let mut cached = Vec::new();
+
+    for handle in handles {
+        if let Some(entry) = handle.await?? {
+            cached.push(entry);
+        }
+    }
+
+    let cached_count = cached.len();
+
+    cached.sort_by_key(|(_, _, hash)| hash ^ seed);
+    cached.truncate(sample);
+
+    let mut handles = Vec::new();
+
+    for (path, bytes, _) in cached {
+        handles.push(tokio::spawn(async move {
+            verify_file(language, &path, bytes).await
+        }));
+    }
+
+    let checked_count = handles.len();
+    let mut diverged_count = 0;
+
+    for handle in handles {
+        if handle.await?? {
+            diverged_count += 1;
+        }
+    }
+
+    message!(
+        "Verified {checked_count} of {cached_count} cached {language} {files} ({diverged_count} diverged)",
+        checked_count = checked_count.to_formatted_string(&Locale::en),
+        cached_count = cached_count.to_formatted_string(&Locale::en),
+        files = if cached_count == 1 { "file" } else { "files" },
+        diverged_count = diverged_count.to_formatted_string(&Locale::en),
+    );
+
+    Ok(diverged_count)
+}
+
+/// Whether the formatter disagrees with the cache about this file
+#[tracing::instrument(skip(bytes), fields(indicatif.pb_show))]
+async fn verify_file(language: Language, path: &Utf8Path, bytes: Bytes) -> eyre::Result<bool> {
+    let (output_bytes, formatter) = match language {
+        Language::Haskell => (
+            format::formatted_haskell(Some(path), bytes.clone()).await?,
+            "fourmolu",
+        ),
+        Language::Nix => (
+            format::formatted_nix(Some(path), bytes.clone()).await?,
+            cx().cache.config().await?.format.nix_formatter.binary(),
+        ),
+        Language::Dhall => (format::formatted_dhall(bytes.clone()).await?, "dhall"),
+        Language::Shell => (format::formatted_shell(bytes.clone()).await?, "shfmt"),
+    };
+
+    if output_bytes == bytes {
+        return Ok(false);
+    }
+
+    indicatif_println!("{path}: cached as formatted, but `{formatter}` would change it");
+
+    Ok(true)
+}
+
+async fn is_formatted(language: Language, hash: u64) -> eyre::Result<bool> {
+    let cx = cx();
+    match language {
+        Language::Haskell => cx.cache.is_haskell_formatted(hash).await,
+        Language::Nix => cx.cache.is_nix_formatted(hash).await,
+        Language::Dhall => cx.cache.is_dhall_formatted(hash).await,
+        Language::Shell => cx.cache.is_shell_formatted(hash).await,
+    }
+}
@@ -0,0 +1,142 @@
+use crate::{
+    cli::{
+        self,
+        ci::{Args, Check},
+        format::Format,
+        lint,
+        query::{self, CyclesArgs},
+    },
+    events::message,
+    exit::{self, Findings},
+    format,
+    lint::run as run_lint,
+    query::run as run_query,
+};
+use color_eyre::eyre;
+use std::fmt::Write as _;
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &Args) -> eyre::Result<()> {
+    let enabled = |check| !args.skip.contains(&check);
+
+    // Each check only runs its own tools, so they can all run at once. They're boxed since their
+    // futures are large.
+    let (format_result, lint_result, cycles_result) = tokio::join!(
+        async {
+            if enabled(Check::Format) {
+                Some(Box::pin(format::run(&format_args())).await)
+            } else {
+                None
+            }
+        },
+        async {
+            if enabled(Check::Lint) {
+                Some(Box::pin(run_lint(&lint_args(args))).await)
+            } else {
+                None
+            }
+        },
+        async {
+            if enabled(Check::Cycles) {
+                Some(Box::pin(cycles(args)).await)
+            } else {
+                None
+            }
+        },
+    );
+
+    let results: Vec<(&str, eyre::Result<()>)> = [
+        ("Format", format_result),
+        ("Lint", lint_result),
+        ("Cycles", cycles_result),
+    ]
+    .into_iter()
+    .filter_map(|(check, result)| Some((check, result?)))
+    .collect();
+
+    let width = results
+        .iter()
+        .map(|(check, _)| check.len())
+        .max()
+        .unwrap_or(0);
+
+    let mut report = String::new();
+    let mut findings = 0;
+    let mut failures = 0;
+
+    // Writing to a `String` can't fail
+    for (check, result) in &results {
+        let outcome = match exit::code(result) {
+            exit::CLEAN => String::from("passed"),
+            exit::FINDINGS => {
+                findings += 1;
+                result.as_ref().unwrap_err().to_string()
+            }
+            _ => {
+                failures += 1;
+                format!("failed: {:?}", result.as_ref().unwrap_err())
+            }
+        };
+        let _ = writeln!(report, "{check:<width$}  {outcome}");
+    }
+
+    message!("{}", report.trim_end());
+
+    let total = results.len();
+
+    if failures > 0 {
+        eyre::bail!(
+            "{failures} of {total} {checks} failed to run",
+            checks = if total == 1 { "check" } else { "checks" },
+        );
+    }
+
+    if findings > 0 {
+        return Err(Findings(format!(
+            "{findings} of {total} {checks} found problems",
+            checks = if total == 1 { "check" } else { "checks" },
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+fn format_args() -> cli::format::Args {
+    cli::format::Args {
+        command: None,
+        packages: Vec::new(),
+        stdin: false,
+        stdin_filepath: None,
+        check: true,
+        diff: false,
+        format: Format::Human,
+        watch: false,
+    }
+}
+
+fn lint_args(args: &Args) -> lint::Args {
+    lint::Args {
+        command: None,
+        packages: Vec::new(),
+        output: None,
+        fail_on: args.fail_on,
+        format: lint::Format::Human,
+        changed_lines_only: args.changed_lines_only,
+        watch: false,
+    }
+}
+
+/// Index modules, and then look for cycles between them
+async fn cycles(args: &Args) -> eyre::Result<()> {
+    run_query(&query::Args {
+        command: query::Command::Index,
+    })
+    .await?;
+    run_query(&query::Args {
+        command: query::Command::Cycles(CyclesArgs {
+            allow_list: args.allow_cycles.clone(),
+        }),
+    })
+    .await
+}
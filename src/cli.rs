@@ -1,11 +1,26 @@
+pub mod cache;
+pub mod ci;
+pub mod config;
+pub mod daemon;
+pub mod exec;
+pub mod fix;
 pub mod format;
+pub mod generate;
+pub mod hook;
 pub mod lint;
+pub mod lsp;
 pub mod query;
+pub mod report;
+pub mod undo;
 
+use camino::Utf8PathBuf;
 use clap::ArgAction;
 
 #[derive(clap::Parser)]
-#[command(disable_help_subcommand = true)]
+#[command(
+    disable_help_subcommand = true,
+    after_help = "Exits with 0 when everything is clean, 1 when there are findings, or 2 when `be` or a tool it runs fails."
+)]
 pub struct Args {
     /// Increase verbosity of output
     #[arg(short, long, action = ArgAction::Count, group = "verbosity")]
@@ -15,18 +30,98 @@ pub struct Args {
     #[arg(short = 'V', long = "VERBOSE", action = ArgAction::Count, group = "verbosity")]
     pub verbose_expanded: u8,
 
+    /// Keep cached data in this directory instead of the XDG cache directory
+    #[arg(long, global = true, env = "BE_CACHE_DIR", value_name = "PATH")]
+    pub cache_dir: Option<Utf8PathBuf>,
+
+    /// Don't read or write cached results
+    #[arg(long, global = true)]
+    pub no_cache: bool,
+
+    /// Find changed files by comparing against this ref (default: `base` in `be.toml`, or else the
+    /// remote's default branch)
+    #[arg(long, global = true, env = "BE_BASE", value_name = "REF")]
+    pub base: Option<String>,
+
+    /// Kill tools that run for longer than this many seconds (default: `timeout` for the tool in
+    /// `be.toml`, or else 120)
+    #[arg(long, global = true, env = "BE_TIMEOUT", value_name = "SECONDS")]
+    pub timeout: Option<u64>,
+
+    /// Format and lint what's staged instead of the working tree, and re-stage formatted files, for
+    /// pre-commit hooks
+    #[arg(long, global = true)]
+    pub staged: bool,
+
+    /// Report time spent in each tool, cache hits, bytes read and written, and how many tools ran at
+    /// once, at the end of the run
+    #[arg(long, global = true)]
+    pub stats: bool,
+
+    /// Print progress, per-file outcomes, and summaries on `stderr` in this format
+    #[arg(
+        long,
+        global = true,
+        env = "BE_MESSAGE_FORMAT",
+        value_enum,
+        default_value_t = MessageFormat::Human
+    )]
+    pub message_format: MessageFormat,
+
     #[command(subcommand)]
     pub command: Command,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+pub enum MessageFormat {
+    /// Progress bars and sentences, for reading
+    Human,
+    /// One JSON event per line, for dashboards and wrapper scripts
+    Jsonl,
+}
+
 #[derive(clap::Subcommand)]
 pub enum Command {
+    /// Inspect the cache
+    Cache(cache::Args),
+
+    /// Check formatting, run every linter, and look for import cycles, for CI
+    Ci(ci::Args),
+
+    /// Manage `be` configuration
+    Config(config::Args),
+
+    /// Keep the cache, config, and query index in memory, and run `be format`, `be lint`, and `be
+    /// query` for editors without starting up each time
+    Daemon(daemon::Args),
+
+    /// Run a command in a sandbox
+    Exec(exec::Args),
+
+    /// Apply fixes suggested by linters
+    Fix(fix::Args),
+
     /// Format code
     Format(format::Args),
 
+    /// Generate files from other files, like `.cabal` files from `package.yaml`
+    Generate(generate::Args),
+
+    /// Manage git hooks which run `be`
+    Hook(hook::Args),
+
     /// Lint code
     Lint(lint::Args),
 
+    /// Format and lint for editors, as a language server
+    Lsp(lsp::Args),
+
     /// Query Haskell code
     Query(query::Args),
+
+    /// Report on code health over time
+    Report(report::Args),
+
+    /// Restore files overwritten by a previous run
+    Undo(undo::Args),
 }
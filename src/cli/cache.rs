@@ -0,0 +1,77 @@
+use camino::Utf8PathBuf;
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(clap::Subcommand)]
+pub enum Command {
+    /// Show cache statistics
+    Stats(StatsArgs),
+
+    /// Delete cached results
+    Clear(ClearArgs),
+
+    /// Delete old cached results, so the cache doesn't grow without bounds
+    Gc(GcArgs),
+
+    /// Save this repo's cached results to a file, like for CI to restore in a later run
+    Export(ExportArgs),
+
+    /// Add cached results from a file made by `be cache export`
+    Import(ImportArgs),
+
+    /// Re-run formatters on files the cache says are formatted, and report any divergence
+    Verify(VerifyArgs),
+}
+
+#[derive(clap::Args)]
+pub struct VerifyArgs {
+    /// Maximum number of cached files to re-check per formatter
+    #[arg(long, default_value_t = 20)]
+    pub sample: usize,
+}
+
+#[derive(clap::Args)]
+pub struct StatsArgs {
+    /// Show the slowest files recorded in the cache
+    #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "10")]
+    pub slowest: Option<usize>,
+}
+
+#[derive(clap::Args)]
+pub struct ClearArgs {
+    /// Only delete results from this tool, like `fourmolu`, `hlint`, or `nixfmt`
+    #[arg(long)]
+    pub tool: Option<String>,
+
+    /// Only delete results from the repo containing this path, like `.`
+    #[arg(long, value_name = "PATH")]
+    pub repo: Option<Utf8PathBuf>,
+}
+
+#[derive(clap::Args)]
+#[group(required = true, multiple = true)]
+pub struct GcArgs {
+    /// Delete results cached more than this many days ago
+    #[arg(long, value_name = "DAYS")]
+    pub max_age: Option<u64>,
+
+    /// Delete the oldest results until the cache is no bigger than this many megabytes
+    #[arg(long, value_name = "MB")]
+    pub max_size: Option<u64>,
+}
+
+#[derive(clap::Args)]
+pub struct ExportArgs {
+    /// File to write, replacing it if it exists
+    pub path: Utf8PathBuf,
+}
+
+#[derive(clap::Args)]
+pub struct ImportArgs {
+    /// File written by `be cache export`
+    pub path: Utf8PathBuf,
+}
@@ -0,0 +1,34 @@
+use crate::lint::HlintSeverity;
+use camino::Utf8PathBuf;
+
+#[derive(clap::Args)]
+#[command(
+    after_help = "Exits with 0 when every check passes, 1 when any check has findings, or 2 when any check fails to run, even if others have findings."
+)]
+pub struct Args {
+    /// Don't run this check
+    #[arg(long, value_enum, value_name = "CHECK")]
+    pub skip: Vec<Check>,
+
+    /// Fail the lint check if there are findings at least this severe
+    #[arg(long, value_name = "SEVERITY", default_value = "warning")]
+    pub fail_on: HlintSeverity,
+
+    /// Only fail the lint check on findings on changed lines, like `be lint --changed-lines-only`
+    #[arg(long)]
+    pub changed_lines_only: bool,
+
+    /// File with known import cycles to ignore, like `be query cycles --allow-list`
+    #[arg(long, value_name = "PATH")]
+    pub allow_cycles: Option<Utf8PathBuf>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+pub enum Check {
+    /// Whether changed files are formatted, like `be format --check`
+    Format,
+    /// Findings in changed files, like `be lint`
+    Lint,
+    /// Import cycles between modules, like `be query cycles`
+    Cycles,
+}
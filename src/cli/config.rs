@@ -0,0 +1,22 @@
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(clap::Subcommand)]
+pub enum Command {
+    /// Write a starter `be.toml` based on the current repo
+    Init(InitArgs),
+}
+
+#[derive(clap::Args)]
+pub struct InitArgs {
+    /// Overwrite an existing `be.toml`
+    #[arg(long)]
+    pub force: bool,
+
+    /// Print the generated config to `stdout` instead of writing it
+    #[arg(long)]
+    pub stdout: bool,
+}
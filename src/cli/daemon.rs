@@ -0,0 +1,5 @@
+#[derive(clap::Args)]
+#[command(
+    after_help = "While it's running, `be format --stdin`, `be lint haskell --stdin`, and `be query` in the same repo are forwarded to it over a Unix socket, and run by `be` directly when it isn't. It stops when `be.toml` or a tool's config changes, since it only reads them once."
+)]
+pub struct Args {}
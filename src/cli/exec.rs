@@ -0,0 +1,17 @@
+#[derive(clap::Args)]
+pub struct Args {
+    /// Sandbox profile: `fourmolu`, `nixfmt`, `alejandra`, `nixpkgs-fmt`, `dhall`, `hlint`, `ghc`,
+    /// `weeder`, `stan`, `refactor`, `typos`, `hpack`, `shfmt`, `shellcheck`, `statix`, `deadnix`,
+    /// `cabal-gild`, `cabal-fmt`, or a path to a profile file. `GIT_ROOT` is the repo, and
+    /// `TEMP_DIR` is an empty directory the command can write to.
+    #[arg(long)]
+    pub profile: String,
+
+    /// Pass an environment variable through to the command
+    #[arg(long = "env", value_name = "NAME")]
+    pub envs: Vec<String>,
+
+    /// Command to run
+    #[arg(last = true, required = true)]
+    pub command: Vec<String>,
+}
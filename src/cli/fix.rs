@@ -0,0 +1,53 @@
+use camino::Utf8PathBuf;
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(clap::Subcommand)]
+pub enum Command {
+    /// Apply `hlint`'s suggested fixes with `refactor` (from `apply-refact`)
+    Haskell(HaskellArgs),
+
+    /// Regenerate `.cabal` files which are out of date with their `package.yaml` files, like `be
+    /// generate hpack`
+    Hpack(HpackArgs),
+
+    /// Group and sort Haskell imports, following `[format.import-order]`
+    ImportOrder(ImportOrderArgs),
+}
+
+#[derive(clap::Args)]
+pub struct HaskellArgs {
+    /// Only fix specific files or directories
+    #[arg(group = "input")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only fix modules in a specific package
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+}
+
+#[derive(clap::Args)]
+pub struct HpackArgs {
+    /// Only regenerate for specific `package.yaml` files or directories
+    #[arg(group = "input")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only regenerate for specific packages
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+}
+
+#[derive(clap::Args)]
+pub struct ImportOrderArgs {
+    /// Only reorder imports in specific files or directories
+    #[arg(group = "input")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only reorder imports in modules in a specific package
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+}
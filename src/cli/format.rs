@@ -1,9 +1,40 @@
 use camino::Utf8PathBuf;
 
 #[derive(clap::Args)]
+#[expect(clippy::struct_excessive_bools)]
 pub struct Args {
     #[command(subcommand)]
     pub command: Option<Command>,
+
+    /// Only format files in a specific package
+    #[arg(long = "package", value_name = "NAME")]
+    pub packages: Vec<String>,
+
+    /// Format code piped to `stdin`, with the formatter for `--stdin-filepath`
+    #[arg(long, requires = "stdin_filepath", conflicts_with = "packages")]
+    pub stdin: bool,
+
+    /// Path of the code piped to `stdin`, whose extension picks the formatter, like editors pass
+    /// to `prettier`
+    #[arg(long, value_name = "PATH", requires = "stdin")]
+    pub stdin_filepath: Option<Utf8PathBuf>,
+
+    /// Don't write files, and exit with code 1 if any aren't formatted
+    #[arg(long)]
+    pub check: bool,
+
+    /// Print a diff of what would change instead of writing files, and exit with code 1 if any
+    /// aren't formatted
+    #[arg(long)]
+    pub diff: bool,
+
+    /// How to print unformatted files (`github` implies `--check`)
+    #[arg(long, value_enum, default_value_t = Format::Human)]
+    pub format: Format,
+
+    /// Format again whenever files change, until Ctrl-C
+    #[arg(long, global = true, conflicts_with = "stdin")]
+    pub watch: bool,
 }
 
 #[derive(clap::Subcommand)]
@@ -13,26 +44,196 @@ pub enum Command {
 
     /// Format Nix code
     Nix(NixArgs),
+
+    /// Format Dhall code
+    Dhall(DhallArgs),
+
+    /// Format shell scripts with `shfmt`
+    Shell(ShellArgs),
+
+    /// Format `.cabal` files with `cabal-gild`, or else `cabal-fmt`
+    Cabal(CabalArgs),
+
+    /// Format Haskell code blocks in Markdown files and haddocks
+    CodeBlocks(CodeBlocksArgs),
 }
 
 #[derive(clap::Args)]
 pub struct HaskellArgs {
-    /// Only format specific paths
+    /// Only format specific files or directories
     #[arg(group = "input")]
     pub paths: Vec<Utf8PathBuf>,
 
+    /// Only format files in a specific package
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+
     /// Format code piped to `stdin`
     #[arg(long, group = "input")]
     pub stdin: bool,
+
+    /// Only format code piped to `stdin` from this line, counting from 1, so editors can format a
+    /// selection
+    #[arg(long, value_name = "N", requires = "stdin")]
+    pub start_line: Option<usize>,
+
+    /// Only format code piped to `stdin` up to and including this line
+    #[arg(long, value_name = "N", requires = "stdin")]
+    pub end_line: Option<usize>,
+
+    /// Don't write files, and exit with code 1 if any aren't formatted
+    #[arg(long)]
+    pub check: bool,
+
+    /// Print a diff of what would change instead of writing files, and exit with code 1 if any
+    /// aren't formatted
+    #[arg(long)]
+    pub diff: bool,
+
+    /// How to print unformatted files (`github` implies `--check`)
+    #[arg(long, value_enum, default_value_t = Format::Human)]
+    pub format: Format,
 }
 
 #[derive(clap::Args)]
 pub struct NixArgs {
-    /// Only format specific paths
+    /// Only format specific files or directories
     #[arg(group = "input")]
     pub paths: Vec<Utf8PathBuf>,
 
+    /// Only format files in a specific package
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+
+    /// Format code piped to `stdin`
+    #[arg(long, group = "input")]
+    pub stdin: bool,
+
+    /// Don't write files, and exit with code 1 if any aren't formatted
+    #[arg(long)]
+    pub check: bool,
+
+    /// Print a diff of what would change instead of writing files, and exit with code 1 if any
+    /// aren't formatted
+    #[arg(long)]
+    pub diff: bool,
+
+    /// How to print unformatted files (`github` implies `--check`)
+    #[arg(long, value_enum, default_value_t = Format::Human)]
+    pub format: Format,
+}
+
+#[derive(clap::Args)]
+pub struct DhallArgs {
+    /// Only format specific files or directories
+    #[arg(group = "input")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only format files in a specific package
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+
     /// Format code piped to `stdin`
     #[arg(long, group = "input")]
     pub stdin: bool,
+
+    /// Don't write files, and exit with code 1 if any aren't formatted
+    #[arg(long)]
+    pub check: bool,
+
+    /// Print a diff of what would change instead of writing files, and exit with code 1 if any
+    /// aren't formatted
+    #[arg(long)]
+    pub diff: bool,
+
+    /// How to print unformatted files (`github` implies `--check`)
+    #[arg(long, value_enum, default_value_t = Format::Human)]
+    pub format: Format,
+}
+
+#[derive(clap::Args)]
+pub struct ShellArgs {
+    /// Only format specific files or directories
+    #[arg(group = "input")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only format scripts in a specific package
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+
+    /// Format code piped to `stdin`
+    #[arg(long, group = "input")]
+    pub stdin: bool,
+
+    /// Don't write files, and exit with code 1 if any aren't formatted
+    #[arg(long)]
+    pub check: bool,
+
+    /// Print a diff of what would change instead of writing files, and exit with code 1 if any
+    /// aren't formatted
+    #[arg(long)]
+    pub diff: bool,
+
+    /// How to print unformatted files (`github` implies `--check`)
+    #[arg(long, value_enum, default_value_t = Format::Human)]
+    pub format: Format,
+}
+
+#[derive(clap::Args)]
+pub struct CabalArgs {
+    /// Only format specific files or directories
+    #[arg(group = "input")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only format files in a specific package
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+
+    /// Format code piped to `stdin`
+    #[arg(long, group = "input")]
+    pub stdin: bool,
+
+    /// Don't write files, and exit with code 1 if any aren't formatted
+    #[arg(long)]
+    pub check: bool,
+
+    /// Print a diff of what would change instead of writing files, and exit with code 1 if any
+    /// aren't formatted
+    #[arg(long)]
+    pub diff: bool,
+
+    /// How to print unformatted files (`github` implies `--check`)
+    #[arg(long, value_enum, default_value_t = Format::Human)]
+    pub format: Format,
+}
+
+#[derive(clap::Args)]
+pub struct CodeBlocksArgs {
+    /// Only format specific files or directories
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only format files in a specific package
+    #[arg(long = "package", value_name = "NAME")]
+    pub packages: Vec<String>,
+
+    /// Don't write files, and exit with code 1 if any aren't formatted
+    #[arg(long)]
+    pub check: bool,
+
+    /// Print a diff of what would change instead of writing files, and exit with code 1 if any
+    /// aren't formatted
+    #[arg(long)]
+    pub diff: bool,
+
+    /// How to print unformatted files (`github` implies `--check`)
+    #[arg(long, value_enum, default_value_t = Format::Human)]
+    pub format: Format,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+pub enum Format {
+    /// Paths or diffs, for reading
+    Human,
+    /// GitHub Actions workflow commands, so unformatted files show up inline on pull requests
+    Github,
 }
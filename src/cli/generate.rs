@@ -0,0 +1,28 @@
+use camino::Utf8PathBuf;
+
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(clap::Subcommand)]
+pub enum Command {
+    /// Generate `.cabal` files from `package.yaml` files with `hpack`
+    Hpack(HpackArgs),
+}
+
+#[derive(clap::Args)]
+pub struct HpackArgs {
+    /// Only generate for specific `package.yaml` files or directories
+    #[arg(group = "input")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only generate for specific packages
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+
+    /// Don't write anything, just fail if a `.cabal` file is out of date with its `package.yaml`
+    #[arg(long)]
+    pub check: bool,
+}
@@ -0,0 +1,26 @@
+#[derive(clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(clap::Subcommand)]
+pub enum Command {
+    /// Install a pre-commit hook which checks formatting and lints staged files, or print config
+    /// for the repo's hook manager if it has one
+    Install(InstallArgs),
+
+    /// Remove hooks installed by `be hook install`
+    Uninstall,
+}
+
+#[derive(clap::Args)]
+pub struct InstallArgs {
+    /// Also install a pre-push hook which checks every changed file
+    #[arg(long)]
+    pub pre_push: bool,
+
+    /// Overwrite existing hooks which weren't installed by `be`
+    #[arg(long)]
+    pub force: bool,
+}
@@ -1,24 +1,251 @@
+use crate::lint::HlintSeverity;
 use camino::Utf8PathBuf;
 
 #[derive(clap::Args)]
 pub struct Args {
     #[command(subcommand)]
     pub command: Option<Command>,
+
+    /// Only lint modules in a specific package
+    #[arg(long = "package", value_name = "NAME")]
+    pub packages: Vec<String>,
+
+    /// Also write findings to a file, replacing it once linting finishes
+    #[arg(long, global = true, value_name = "PATH")]
+    pub output: Option<Utf8PathBuf>,
+
+    /// Exit with an error if there are findings at least this severe (`ignore` fails on any
+    /// finding)
+    #[arg(
+        long,
+        global = true,
+        value_name = "SEVERITY",
+        default_value = "warning"
+    )]
+    pub fail_on: HlintSeverity,
+
+    /// How to print findings, and write them with `--output`
+    #[arg(long, global = true, value_enum, default_value_t = Format::Human)]
+    pub format: Format,
+
+    /// Only report findings on lines changed since the base (or staged, with `--staged`), so
+    /// existing findings don't fail the run but new code is held to the standard
+    #[arg(long, global = true)]
+    pub changed_lines_only: bool,
+
+    /// Lint again whenever files change, until Ctrl-C
+    #[arg(long, global = true)]
+    pub watch: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+pub enum Format {
+    /// Findings one after another, for reading
+    Human,
+    /// A SARIF 2.1.0 log, for GitHub code scanning and other tools
+    Sarif,
+    /// GitHub Actions workflow commands, so findings show up inline on pull requests
+    Github,
+    /// A JSON array of findings, for other tools to read
+    Json,
+    /// One JSON finding per line, for other tools to read as they go
+    JsonLines,
 }
 
 #[derive(clap::Subcommand)]
 pub enum Command {
     /// Lint Haskell code
     Haskell(HaskellArgs),
+
+    /// Check haddock coverage of exported declarations
+    Haddock(HaddockArgs),
+
+    /// Check spelling in source files and docs with `typos`
+    Typos(TyposArgs),
+
+    /// Find blocks of Haskell code which are copies of each other
+    Duplicates(DuplicatesArgs),
+
+    /// Check `.cabal` and `package.yaml` files for packaging problems, like missing bounds
+    Cabal(CabalArgs),
+
+    /// Lint shell scripts with `shellcheck`
+    Shell(ShellArgs),
+
+    /// Lint Nix code with `statix` and `deadnix`
+    Nix(NixArgs),
+
+    /// Check Haskell modules for GHC warnings with `ghc -fno-code`
+    Ghc(GhcArgs),
+
+    /// Find unused code with `weeder`, from the `.hie` files GHC wrote
+    Weeder(WeederArgs),
+
+    /// Analyze Haskell modules with `stan`, from the `.hie` files GHC wrote
+    Stan(StanArgs),
+
+    /// Check that `.cabal` files are up to date with their `package.yaml` files
+    Hpack(HpackArgs),
+
+    /// Check Haskell imports against the rules in `[[lint.imports.rules]]`
+    Imports(ImportsArgs),
+
+    /// Find unused and duplicate Haskell imports, using the `be query index` index for imports
+    /// without an import list
+    HaskellImports(HaskellImportsArgs),
 }
 
 #[derive(clap::Args)]
 pub struct HaskellArgs {
-    /// Only lint specific paths
+    /// Only lint specific files or directories
     #[arg(group = "input")]
     pub paths: Vec<Utf8PathBuf>,
 
+    /// Only lint modules in a specific package
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+
     /// Lint code piped to `stdin`
     #[arg(long, group = "input")]
     pub stdin: bool,
+
+    /// Path to show for code piped to `stdin`, which also picks path-specific settings
+    #[arg(long, value_name = "PATH", requires = "stdin")]
+    pub stdin_filepath: Option<Utf8PathBuf>,
+
+    /// Apply `hlint`'s suggested fixes first, like `be fix haskell`
+    #[arg(long, conflicts_with = "stdin")]
+    pub fix: bool,
+
+    /// Don't report findings recorded in this file, so only new findings fail the run, like
+    /// `.hlint-baseline.json`
+    #[arg(long, value_name = "PATH")]
+    pub baseline: Option<Utf8PathBuf>,
+
+    /// Record current findings in the `--baseline` file. Every file is linted unless paths or
+    /// packages are given, and findings in files which weren't linted are kept.
+    #[arg(long, requires = "baseline", conflicts_with = "stdin")]
+    pub update_baseline: bool,
+}
+
+#[derive(clap::Args)]
+pub struct HaddockArgs {
+    /// Only check specific files or directories (default: `lint.haddock.packages` in `be.toml`)
+    #[arg(group = "input")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only check modules in a specific package
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+}
+
+#[derive(clap::Args)]
+pub struct TyposArgs {
+    /// Only check specific files or directories
+    pub paths: Vec<Utf8PathBuf>,
+}
+
+#[derive(clap::Args)]
+pub struct DuplicatesArgs {
+    /// Only compare specific files or directories
+    #[arg(group = "input")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only compare modules in a specific package
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+}
+
+#[derive(clap::Args)]
+pub struct CabalArgs {
+    /// Only check specific files or directories
+    pub paths: Vec<Utf8PathBuf>,
+}
+
+#[derive(clap::Args)]
+pub struct ShellArgs {
+    /// Only lint specific files or directories
+    #[arg(group = "input")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only lint scripts in a specific package
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+}
+
+#[derive(clap::Args)]
+pub struct NixArgs {
+    /// Only lint specific files or directories
+    #[arg(group = "input")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only lint files in a specific package
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+}
+
+#[derive(clap::Args)]
+pub struct GhcArgs {
+    /// Only check specific files or directories
+    #[arg(group = "input")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only check modules in a specific package
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+}
+
+#[derive(clap::Args)]
+pub struct WeederArgs {
+    /// Only report unused code in specific files or directories (default: the whole repo, since
+    /// removing a use in one module can leave code unused in another)
+    pub paths: Vec<Utf8PathBuf>,
+}
+
+#[derive(clap::Args)]
+pub struct StanArgs {
+    /// Only analyze specific files or directories
+    #[arg(group = "input")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only analyze modules in a specific package
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+}
+
+#[derive(clap::Args)]
+pub struct HpackArgs {
+    /// Only check specific `package.yaml` files or directories
+    #[arg(group = "input")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only check specific packages
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+}
+
+#[derive(clap::Args)]
+pub struct ImportsArgs {
+    /// Only check specific files or directories
+    #[arg(group = "input")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only check specific packages
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+}
+
+#[derive(clap::Args)]
+pub struct HaskellImportsArgs {
+    /// Only check specific files or directories
+    #[arg(group = "input")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only check modules in a specific package
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+
+    /// Remove unused and duplicate imports instead of reporting them
+    #[arg(long)]
+    pub fix: bool,
 }
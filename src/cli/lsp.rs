@@ -0,0 +1,5 @@
+#[derive(clap::Args)]
+#[command(
+    after_help = "Editors start it and talk to it on `stdin` and `stdout`. It formats documents like `be format --stdin`, and publishes `hlint` findings for Haskell documents when they're opened or saved."
+)]
+pub struct Args {}
@@ -1,3 +1,5 @@
+use camino::Utf8PathBuf;
+
 #[derive(clap::Args)]
 pub struct Args {
     #[command(subcommand)]
@@ -9,18 +11,256 @@ pub enum Command {
     /// Index modules to build dependency graph
     Index,
 
-    /// Module imports
+    /// Module imports, with the local or external package each one comes from
     Imports(QueryArgs),
+
+    /// Names a module exports, from its export list or else its top-level declarations
+    Exports(QueryArgs),
+
+    /// `{-# LANGUAGE #-}` pragmas in each module, and how many modules use each extension
+    Extensions(ExtensionsArgs),
+
+    /// Instances whose class and types are all defined in other modules, which are probably
+    /// orphans
+    Orphans(OrphansArgs),
+
+    /// Template Haskell splices and quasiquotes in each module, which slow down builds
+    Th(UsageArgs),
+
+    /// CPP conditionals in each module, which make code hard to parse and cache
+    Cpp(UsageArgs),
+
+    /// Print what a tree-sitter query captures, to search code by its syntax
+    Run(RunArgs),
+
+    /// Top-level functions, types, and classes with a name, without loading them in HLS
+    Def(DefArgs),
+
+    /// `import`s, `fetch*` calls, and flake inputs in Nix files, and what each one refers to
+    NixInputs(NixInputsArgs),
+
+    /// The package and component a module belongs to, from its source directories
+    Package(PackageArgs),
+
+    /// Indexed modules which import a module
+    Importers(ImportersArgs),
+
+    /// Print the indexed module graph
+    Graph(GraphArgs),
+
+    /// Find import cycles in the indexed module graph
+    Cycles(CyclesArgs),
+
+    /// Indexed modules which no library exposes and no executable, test suite, or benchmark
+    /// imports, even indirectly, so they can be deleted
+    Deadmodules(DeadModulesArgs),
+
+    /// Test suites with changed modules, or modules which import changed modules, by the indexed
+    /// module graph
+    AffectedTests(AffectedTestsArgs),
+
+    /// Search top-level declaration names and haddocks
+    Search(SearchArgs),
+
+    /// Browse the indexed module graph in a web browser
+    Serve(ServeArgs),
 }
 
 #[derive(clap::Args)]
+#[command(group(clap::ArgGroup::new("input").required(true)))]
 pub struct QueryArgs {
     /// Module names or paths
     // TODO: Make a type for Haskell module names
-    #[arg(group = "input", required = true)]
+    #[arg(group = "input")]
     pub modules: Vec<String>,
 
+    /// Query every module in a specific package
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+
     /// Query code piped to `stdin`
     #[arg(long, group = "input")]
     pub stdin: bool,
 }
+
+#[derive(clap::Args)]
+pub struct ExtensionsArgs {
+    /// Only check specific files or directories (default: every Haskell file)
+    #[arg(group = "input")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only check modules in a specific package
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+
+    /// Only report extensions which aren't default extensions already, to find ones which could be
+    #[arg(long)]
+    pub not_in_defaults: bool,
+}
+
+#[derive(clap::Args)]
+pub struct OrphansArgs {
+    /// Only check specific files or directories (default: every Haskell file)
+    #[arg(group = "input")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only check modules in a specific package
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+}
+
+#[derive(clap::Args)]
+pub struct UsageArgs {
+    /// Only check specific files or directories (default: every Haskell file)
+    #[arg(group = "input")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only check modules in a specific package
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+}
+
+#[derive(clap::Args)]
+#[command(group(clap::ArgGroup::new("query_input").required(true)))]
+pub struct RunArgs {
+    /// A tree-sitter query, like `(import module: (_) @module)`
+    #[arg(long, value_name = "QUERY", group = "query_input")]
+    pub query: Option<String>,
+
+    /// File with a tree-sitter query, like `foo.scm`
+    #[arg(long, value_name = "PATH", group = "query_input")]
+    pub query_file: Option<Utf8PathBuf>,
+
+    /// Only search specific files or directories (default: changed Haskell files)
+    #[arg(group = "input")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only search modules in a specific package
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+
+    /// Print one JSON object per capture, with all of its text
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(clap::Args)]
+pub struct DefArgs {
+    /// Name to find, or a glob pattern like `*Config`
+    pub name: String,
+
+    /// Only search specific files or directories (default: every Haskell file)
+    #[arg(group = "input")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only search modules in a specific package
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+
+    /// Only search changed Haskell files
+    #[arg(long, group = "input")]
+    pub changed: bool,
+}
+
+#[derive(clap::Args)]
+pub struct NixInputsArgs {
+    /// Only check specific files or directories (default: changed Nix files)
+    #[arg(group = "input")]
+    pub paths: Vec<Utf8PathBuf>,
+
+    /// Only check files in a specific package
+    #[arg(long = "package", value_name = "NAME", group = "input")]
+    pub packages: Vec<String>,
+}
+
+#[derive(clap::Args)]
+pub struct PackageArgs {
+    /// Module names or paths
+    #[arg(required = true)]
+    pub modules: Vec<String>,
+}
+
+#[derive(clap::Args)]
+pub struct ImportersArgs {
+    /// Module names or paths
+    #[arg(required = true)]
+    pub modules: Vec<String>,
+
+    /// Include modules which import them indirectly
+    #[arg(long)]
+    pub transitive: bool,
+}
+
+#[derive(clap::Args)]
+pub struct GraphArgs {
+    #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+    pub format: GraphFormat,
+
+    /// Only include these modules and the modules they import, directly or indirectly
+    #[arg(long = "root", value_name = "MODULE")]
+    pub roots: Vec<String>,
+
+    /// Only include modules with a path starting with this, relative to the repo root
+    #[arg(long, value_name = "PATH")]
+    pub prefix: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+pub enum GraphFormat {
+    /// Graphviz, for `dot -Tsvg`
+    Dot,
+    /// Modules and imports as JSON, for other tools
+    Json,
+    /// A Mermaid flowchart, which GitHub renders in Markdown
+    Mermaid,
+}
+
+#[derive(clap::Args)]
+pub struct CyclesArgs {
+    /// File with known cycles to ignore, one per line as module names separated by spaces, in the
+    /// same format cycles are printed
+    #[arg(long, value_name = "PATH")]
+    pub allow_list: Option<Utf8PathBuf>,
+}
+
+#[derive(clap::Args)]
+pub struct DeadModulesArgs {
+    /// Modules to count as used besides the ones components start from, like ones only loaded in a
+    /// REPL
+    #[arg(long = "root", value_name = "MODULE")]
+    pub roots: Vec<String>,
+}
+
+#[derive(clap::Args)]
+pub struct AffectedTestsArgs {
+    #[arg(long, value_enum, default_value_t = AffectedTestsFormat::Plain)]
+    pub format: AffectedTestsFormat,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+pub enum AffectedTestsFormat {
+    /// `package:test-suite`, one per line
+    Plain,
+    /// A JSON array of test suites, for other tools
+    Json,
+    /// `package:test:test-suite` targets, for `cabal test`, which tests everything without any
+    Cabal,
+}
+
+#[derive(clap::Args)]
+pub struct SearchArgs {
+    /// Words to search for, matching the start of words in names, module names, and haddocks
+    #[arg(required = true)]
+    pub terms: Vec<String>,
+
+    /// Maximum number of results
+    #[arg(long, default_value_t = 20)]
+    pub limit: usize,
+}
+
+#[derive(clap::Args)]
+pub struct ServeArgs {
+    /// Port to listen on, on localhost
+    #[arg(long, default_value_t = 8080)]
+    pub port: u16,
+}
@@ -0,0 +1,18 @@
+#[derive(clap::Args)]
+#[command(group(clap::ArgGroup::new("report").required(true)))]
+pub struct Args {
+    /// Show whether lint findings are going up or down over recent runs of `be lint` on the whole
+    /// repo
+    #[arg(long, group = "report")]
+    pub trend: bool,
+
+    /// Number of recent runs to include in the trend
+    #[arg(long, default_value_t = 20)]
+    pub limit: usize,
+
+    /// Summarize formatting and lint changes in a commit range, like `main..HEAD`, or
+    /// `main...HEAD` to start from where `HEAD` diverged from `main`. Both ends are checked with
+    /// the current tool configs, not the ones at those commits.
+    #[arg(long, value_name = "A..B", group = "report")]
+    pub range: Option<String>,
+}
@@ -0,0 +1,14 @@
+#[derive(clap::Args)]
+pub struct Args {
+    /// Run to undo (default: the most recent run which hasn't been undone)
+    #[arg(value_name = "RUN_ID", conflicts_with = "list")]
+    pub run: Option<i64>,
+
+    /// Show recent runs which wrote files, instead of undoing one
+    #[arg(long)]
+    pub list: bool,
+
+    /// Restore files even if they changed after the run
+    #[arg(long)]
+    pub force: bool,
+}
@@ -0,0 +1,957 @@
+use crate::{
+    cli::config::{Args, Command, InitArgs},
+    context::cx,
+    events::message,
+    exec::{self, exec},
+    io::{write_file, write_stdout},
+    line_ending::LineEnding,
+    lint::{HlintSeverity, StanSeverity},
+    packages,
+    queue::Schedule,
+};
+use bytes::Bytes;
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre;
+use derive_more::Display;
+use globset::{Glob, GlobMatcher};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Write as _,
+    num::NonZeroUsize,
+    str,
+    thread::available_parallelism,
+};
+use tokio::fs;
+use which::{which_global, which_in_global};
+
+pub const CONFIG_FILE_NAME: &str = "be.toml";
+
+const TOOLS: &[&str] = &[
+    "fourmolu",
+    "nixfmt",
+    "dhall",
+    "hlint",
+    "ghc",
+    "weeder",
+    "stan",
+    "refactor",
+    "typos",
+    "hpack",
+    "shfmt",
+    "shellcheck",
+    "statix",
+    "deadnix",
+    "cabal-gild",
+    "cabal-fmt",
+];
+
+#[derive(Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Ref that changed files are compared against (default: the remote's default branch)
+    pub base: Option<String>,
+    /// Order in which files are handed to tools
+    pub schedule: Schedule,
+    /// Maximum number of concurrent processes across all tools (default: number of CPUs)
+    pub jobs: Option<NonZeroUsize>,
+    /// Don't start more `fourmolu` or `hlint` processes while less than this much memory is
+    /// available, in MiB (default: 1024, or 0 to never wait)
+    pub memory_reserve_mib: Option<u64>,
+    pub files: FilesConfig,
+    pub tool_configs: ToolConfigPaths,
+    pub format: FormatConfig,
+    pub remote_cache: RemoteCacheConfig,
+    pub haskell: HaskellConfig,
+    pub lint: LintConfig,
+    pub tools: HashMap<String, ToolConfig>,
+    pub file_types: Vec<FileTypeConfig>,
+    #[serde(skip)]
+    file_type_matchers: Vec<GlobMatcher>,
+}
+
+/// Which files `be` looks at, by globs matched against paths relative to the git root
+#[derive(Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct FilesConfig {
+    /// Only look at files matching one of these (default: every file)
+    pub include: Vec<String>,
+    /// Never look at files matching one of these, even if they're included
+    pub exclude: Vec<String>,
+    #[serde(skip)]
+    include_matchers: Vec<GlobMatcher>,
+    #[serde(skip)]
+    exclude_matchers: Vec<GlobMatcher>,
+}
+
+impl FilesConfig {
+    fn compile(&mut self) -> eyre::Result<()> {
+        for glob in &self.include {
+            self.include_matchers
+                .push(Glob::new(glob)?.compile_matcher());
+        }
+        for glob in &self.exclude {
+            self.exclude_matchers
+                .push(Glob::new(glob)?.compile_matcher());
+        }
+        Ok(())
+    }
+
+    /// Whether a path relative to the git root is included and not excluded
+    pub fn is_included(&self, path: &Utf8Path) -> bool {
+        (self.include_matchers.is_empty()
+            || self
+                .include_matchers
+                .iter()
+                .any(|matcher| matcher.is_match(path)))
+            && !self
+                .exclude_matchers
+                .iter()
+                .any(|matcher| matcher.is_match(path))
+    }
+}
+
+/// Where tools' config files are, relative to the git root
+#[derive(Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ToolConfigPaths {
+    /// `fourmolu` config (default: `fourmolu.yaml`)
+    pub fourmolu: Option<Utf8PathBuf>,
+    /// `hlint` configs, or directories of them (default: `.hlint.yaml` and `hlint-rules/`)
+    pub hlint: Option<Vec<Utf8PathBuf>>,
+    /// hpack file listing the `default-extensions` passed to `fourmolu` (default:
+    /// `hpack-common/default-extensions.yaml`)
+    pub default_extensions: Option<Utf8PathBuf>,
+    /// `typos` config (default: the first of `typos.toml`, `_typos.toml`, or `.typos.toml`)
+    pub typos: Option<Utf8PathBuf>,
+    /// `weeder` config (default: `weeder.toml`)
+    pub weeder: Option<Utf8PathBuf>,
+    /// `stan` config (default: `.stan.toml`)
+    pub stan: Option<Utf8PathBuf>,
+}
+
+impl ToolConfigPaths {
+    pub fn fourmolu(&self) -> &Utf8Path {
+        self.fourmolu
+            .as_deref()
+            .unwrap_or(Utf8Path::new("fourmolu.yaml"))
+    }
+
+    pub fn hlint(&self) -> Vec<&Utf8Path> {
+        match &self.hlint {
+            Some(paths) => paths.iter().map(Utf8PathBuf::as_path).collect(),
+            None => vec![Utf8Path::new(".hlint.yaml"), Utf8Path::new("hlint-rules")],
+        }
+    }
+
+    pub fn default_extensions(&self) -> &Utf8Path {
+        self.default_extensions
+            .as_deref()
+            .unwrap_or(Utf8Path::new("hpack-common/default-extensions.yaml"))
+    }
+
+    pub fn weeder(&self) -> &Utf8Path {
+        self.weeder
+            .as_deref()
+            .unwrap_or(Utf8Path::new("weeder.toml"))
+    }
+
+    pub fn stan(&self) -> &Utf8Path {
+        self.stan.as_deref().unwrap_or(Utf8Path::new(".stan.toml"))
+    }
+
+    /// Every place a config file may be, relative to the git root
+    pub fn paths(&self) -> Vec<&Utf8Path> {
+        let mut paths = vec![self.fourmolu()];
+        paths.extend(self.hlint());
+        paths.push(self.default_extensions());
+        paths.extend(self.typos());
+        paths.push(self.weeder());
+        paths.push(self.stan());
+        paths
+    }
+
+    /// Candidates for the `typos` config, where the first one that exists is used
+    pub fn typos(&self) -> Vec<&Utf8Path> {
+        match &self.typos {
+            Some(path) => vec![path.as_path()],
+            None => vec![
+                Utf8Path::new("typos.toml"),
+                Utf8Path::new("_typos.toml"),
+                Utf8Path::new(".typos.toml"),
+            ],
+        }
+    }
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct FormatConfig {
+    /// Line endings enforced in formatted files (default: keep each file's own)
+    pub line_endings: Option<LineEnding>,
+    /// Formatter used for Nix files
+    pub nix_formatter: NixFormatter,
+    /// Whether `be format` also formats Haskell code blocks in Markdown files and haddocks
+    pub code_blocks: bool,
+    pub import_order: ImportOrderConfig,
+}
+
+/// How `be fix import-order` groups and sorts imports
+#[derive(Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ImportOrderConfig {
+    /// Groups of imports, in order and separated by blank lines: "external" for modules from
+    /// dependencies, "local" for modules in the repo's packages, or a glob like `Mercury.*`, which
+    /// takes precedence (default: `["external", "local"]`)
+    pub groups: Option<Vec<String>>,
+    /// Whether qualified imports go after unqualified ones in each group
+    pub qualified_last: bool,
+    #[serde(skip)]
+    group_matchers: Vec<Option<GlobMatcher>>,
+}
+
+/// Which group of imports a module goes in
+pub enum ImportGroup {
+    External,
+    Local,
+    Matching(GlobMatcher),
+}
+
+impl ImportOrderConfig {
+    fn compile(&mut self) -> eyre::Result<()> {
+        for group in self.groups.iter().flatten() {
+            let matcher = match group.as_str() {
+                "external" | "local" => None,
+                glob => Some(Glob::new(glob)?.compile_matcher()),
+            };
+            self.group_matchers.push(matcher);
+        }
+        Ok(())
+    }
+
+    pub fn groups(&self) -> Vec<ImportGroup> {
+        let Some(groups) = &self.groups else {
+            return vec![ImportGroup::External, ImportGroup::Local];
+        };
+        groups
+            .iter()
+            .zip(&self.group_matchers)
+            .map(|(group, matcher)| match (group.as_str(), matcher) {
+                (_, Some(matcher)) => ImportGroup::Matching(matcher.clone()),
+                ("local", None) => ImportGroup::Local,
+                _ => ImportGroup::External,
+            })
+            .collect()
+    }
+}
+
+/// A cache shared with CI and teammates, checked when a result isn't in the local cache
+#[derive(Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct RemoteCacheConfig {
+    /// Base URL results are stored under, like `https://cache.example.com/be` (default: no remote
+    /// cache)
+    pub url: Option<String>,
+    /// Sign requests for this S3 region with `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`
+    pub s3_region: Option<String>,
+    /// Environment variable holding a bearer token (default: `BE_REMOTE_CACHE_TOKEN`)
+    pub token_env: Option<String>,
+    /// Environment variable holding a secret key entries are signed with when they're uploaded, and
+    /// checked against when they're downloaded (default: `BE_REMOTE_CACHE_SIGNING_KEY`)
+    pub signing_key_env: Option<String>,
+    /// Whether to upload new results, rather than only downloading them (default: true with a
+    /// signing key, and false without one)
+    pub write: Option<bool>,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Display, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NixFormatter {
+    #[default]
+    #[display("nixfmt")]
+    Nixfmt,
+    #[display("alejandra")]
+    Alejandra,
+    #[display("nixpkgs-fmt")]
+    NixpkgsFmt,
+}
+
+impl NixFormatter {
+    /// Name of the formatter's binary, which is also its name under `[tools]`
+    pub fn binary(self) -> &'static str {
+        match self {
+            Self::Nixfmt => "nixfmt",
+            Self::Alejandra => "alejandra",
+            Self::NixpkgsFmt => "nixpkgs-fmt",
+        }
+    }
+
+    pub fn profile(self) -> &'static str {
+        match self {
+            Self::Nixfmt => exec::NIXFMT_PROFILE,
+            Self::Alejandra => exec::ALEJANDRA_PROFILE,
+            Self::NixpkgsFmt => exec::NIXPKGS_FMT_PROFILE,
+        }
+    }
+
+    /// Arguments to format `stdin` to `stdout`
+    pub fn args(self, path: &Utf8Path) -> Vec<String> {
+        match self {
+            Self::Nixfmt => vec![format!("--filename={path}"), String::from("-")],
+            Self::Alejandra => vec![String::from("--quiet"), String::from("-")],
+            Self::NixpkgsFmt => vec![],
+        }
+    }
+}
+
+impl FormatConfig {
+    pub fn line_ending(&self, input: &[u8]) -> LineEnding {
+        self.line_endings
+            .unwrap_or_else(|| LineEnding::detect(input))
+    }
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct HaskellConfig {
+    /// Directories searched for changed Haskell files, relative to the git root (default: source
+    /// directories of cabal/hpack packages)
+    pub roots: Option<Vec<String>>,
+    /// Where GHC writes `.hie` files, relative to the git root, for `be lint weeder` and `be lint
+    /// stan` (default: `.hie`)
+    pub hie_directory: Option<Utf8PathBuf>,
+}
+
+impl HaskellConfig {
+    pub fn hie_directory(&self) -> &Utf8Path {
+        self.hie_directory
+            .as_deref()
+            .unwrap_or(Utf8Path::new(".hie"))
+    }
+}
+
+/// Settings for `be`'s own lints
+#[derive(Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct LintConfig {
+    pub missing_signatures: BuiltinLintConfig,
+    pub explicit_exports: BuiltinLintConfig,
+    pub module_size: BuiltinLintConfig,
+    pub module_size_limits: ModuleSizeLimits,
+    pub module_name: BuiltinLintConfig,
+    pub haddock: HaddockConfig,
+    pub duplicates: DuplicatesConfig,
+    pub ghc: GhcLintConfig,
+    pub stan: StanLintConfig,
+    pub imports: ImportsConfig,
+}
+
+/// Limits checked by the `module-size` lint (default: no limit)
+#[derive(Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ModuleSizeLimits {
+    pub lines: Option<usize>,
+    pub declarations: Option<usize>,
+    pub imports: Option<usize>,
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct DuplicatesConfig {
+    /// Smallest block of code reported, in syntax tree nodes (default: 40)
+    pub min_nodes: Option<usize>,
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct GhcLintConfig {
+    /// Warning flags passed to `ghc` by `be lint ghc`, like `-Werror=incomplete-patterns` (default:
+    /// `-Wall`)
+    pub flags: Option<Vec<String>>,
+    /// GHC package environment file with the packages modules import, relative to the git root
+    /// (default: what `cabal exec` gives GHC, once the project has been built)
+    pub package_env: Option<Utf8PathBuf>,
+}
+
+impl GhcLintConfig {
+    pub fn flags(&self) -> Vec<&str> {
+        match &self.flags {
+            Some(flags) => flags.iter().map(String::as_str).collect(),
+            None => vec!["-Wall"],
+        }
+    }
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct StanLintConfig {
+    /// Least severe observations `be lint stan` reports (default: `style`, which is all of them)
+    pub min_severity: Option<StanSeverity>,
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ImportsConfig {
+    /// Modules some modules may not import, checked by `be lint imports`
+    pub rules: Vec<ImportRule>,
+}
+
+/// Like "modules under `Mercury.Api.*` may not import `Mercury.Db.*`", where module names are
+/// matched like globs, so `Foo.*` matches modules under `Foo` but not `Foo` itself
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ImportRule {
+    /// Modules the rule applies to
+    pub modules: Vec<String>,
+    /// Modules they may not import
+    pub banned: Vec<String>,
+    /// Imports which are allowed anyway, even though they're banned
+    #[serde(default)]
+    pub allowed: Vec<String>,
+    /// Modules the rule doesn't apply to, even though it would otherwise
+    #[serde(default)]
+    pub exceptions: Vec<String>,
+    /// Why, shown with each finding
+    pub reason: Option<String>,
+    /// Severity of findings (default: error)
+    pub severity: Option<HlintSeverity>,
+    #[serde(skip)]
+    matchers: [Vec<GlobMatcher>; 4],
+}
+
+impl ImportRule {
+    fn compile(&mut self) -> eyre::Result<()> {
+        let globs = [&self.modules, &self.banned, &self.allowed, &self.exceptions];
+        for (matchers, globs) in self.matchers.iter_mut().zip(globs) {
+            for glob in globs {
+                matchers.push(Glob::new(glob)?.compile_matcher());
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the rule forbids a module from importing another
+    pub fn forbids(&self, module: &str, import: &str) -> bool {
+        let [modules, banned, allowed, exceptions] = &self.matchers;
+        let matches =
+            |matchers: &[GlobMatcher], name| matchers.iter().any(|matcher| matcher.is_match(name));
+        matches(modules, module)
+            && !matches(exceptions, module)
+            && matches(banned, import)
+            && !matches(allowed, import)
+    }
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct HaddockConfig {
+    /// Packages whose exports should be documented
+    pub packages: Vec<String>,
+    /// Fail when a module's percentage of documented exports is below this
+    pub threshold: Option<f64>,
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct BuiltinLintConfig {
+    /// Severity of findings (default: depends on the lint)
+    pub severity: Option<HlintSeverity>,
+    /// Different severities for some paths, where the last matching glob wins
+    pub paths: Vec<PathSeverityConfig>,
+    /// Modules which are never reported
+    pub exceptions: Vec<String>,
+    #[serde(skip)]
+    path_matchers: Vec<GlobMatcher>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct PathSeverityConfig {
+    /// Glob matched against paths relative to the git root (e.g. `test/**`)
+    pub glob: String,
+    pub severity: HlintSeverity,
+}
+
+impl BuiltinLintConfig {
+    fn compile(&mut self) -> eyre::Result<()> {
+        for path in &self.paths {
+            let matcher = Glob::new(&path.glob)?.compile_matcher();
+            self.path_matchers.push(matcher);
+        }
+        Ok(())
+    }
+
+    /// Severity for findings in a path relative to the git root
+    pub fn severity(&self, path: &Utf8Path, default: HlintSeverity) -> HlintSeverity {
+        self.paths
+            .iter()
+            .zip(&self.path_matchers)
+            .rev()
+            .find(|(_, matcher)| matcher.is_match(path))
+            .map_or(self.severity.unwrap_or(default), |(path, _)| path.severity)
+    }
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ToolConfig {
+    /// Whether `be format` and `be lint` run this tool (default: true). Disabled formatters still
+    /// run when asked for directly, like `be format haskell`.
+    pub enabled: Option<bool>,
+
+    /// Use this binary instead of looking it up on `$PATH`
+    pub path: Option<Utf8PathBuf>,
+
+    /// Maximum number of concurrent processes (default: number of CPUs)
+    pub jobs: Option<NonZeroUsize>,
+
+    /// Format or lint up to this many files per process, to amortize startup (only `fourmolu` and
+    /// `hlint`)
+    pub batch_size: Option<NonZeroUsize>,
+
+    /// Whether to reuse results from previous runs (default: true)
+    pub cache: Option<bool>,
+
+    /// Whether to run this tool in a sandbox, with `sandbox-exec` on macOS or `bwrap` on Linux
+    /// (default: true)
+    pub sandbox: Option<bool>,
+
+    /// Seconds a process can run before it's killed (default: 120)
+    pub timeout: Option<u64>,
+}
+
+/// Extra files to run through an existing language's pipelines
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct FileTypeConfig {
+    /// Glob matched against paths relative to the git root (e.g. `*.hsc`)
+    pub glob: String,
+    pub language: Language,
+    /// Only use these pipelines for matching files (default: all of them)
+    #[serde(default)]
+    pub pipelines: Vec<Pipeline>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Display, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Language {
+    Haskell,
+    Nix,
+    Dhall,
+    Shell,
+}
+
+impl Language {
+    fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Self::Haskell => &["hs"],
+            Self::Nix => &["nix"],
+            Self::Dhall => &["dhall"],
+            Self::Shell => &["sh", "bash"],
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Pipeline {
+    Format,
+    Lint,
+}
+
+impl Config {
+    #[tracing::instrument]
+    pub async fn load(git_root: &Utf8Path) -> eyre::Result<Self> {
+        let path = git_root.join(CONFIG_FILE_NAME);
+        let string = match fs::read_to_string(&path).await {
+            Ok(string) => string,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                tracing::debug!("No `{CONFIG_FILE_NAME}` found, using defaults");
+                return Ok(Self::default());
+            }
+            Err(error) => return Err(error.into()),
+        };
+        let mut config: Self = toml::from_str(&string)
+            .map_err(|error| eyre::eyre!("Failed to parse `{path}`:\n{error}"))?;
+        for file_type in &config.file_types {
+            let matcher = Glob::new(&file_type.glob)?.compile_matcher();
+            config.file_type_matchers.push(matcher);
+        }
+        config.files.compile()?;
+        config.format.import_order.compile()?;
+        config.lint.missing_signatures.compile()?;
+        config.lint.explicit_exports.compile()?;
+        config.lint.module_size.compile()?;
+        config.lint.module_name.compile()?;
+        for rule in &mut config.lint.imports.rules {
+            rule.compile()?;
+        }
+        Ok(config)
+    }
+
+    /// Whether a path relative to the git root should go through a language's pipeline
+    pub fn is_file_type(&self, language: Language, pipeline: Pipeline, path: &Utf8Path) -> bool {
+        if !self.files.is_included(path) {
+            return false;
+        }
+        if path
+            .extension()
+            .is_some_and(|extension| language.extensions().contains(&extension))
+        {
+            return true;
+        }
+        self.file_types
+            .iter()
+            .zip(&self.file_type_matchers)
+            .any(|(file_type, matcher)| {
+                file_type.language == language
+                    && (file_type.pipelines.is_empty() || file_type.pipelines.contains(&pipeline))
+                    && matcher.is_match(path)
+            })
+    }
+
+    pub fn tool(&self, name: &str) -> Option<&ToolConfig> {
+        self.tools.get(name)
+    }
+
+    /// Whether `be format` and `be lint` run a tool
+    pub fn is_tool_enabled(&self, name: &str) -> bool {
+        self.tool(name).and_then(|tool| tool.enabled) != Some(false)
+    }
+
+    /// Whether a tool runs in a sandbox
+    pub fn is_tool_sandboxed(&self, name: &str) -> bool {
+        self.tool(name).and_then(|tool| tool.sandbox) != Some(false)
+    }
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &Args) -> eyre::Result<()> {
+    match &args.command {
+        Command::Init(args) => run_config_init(args).await,
+    }
+}
+
+#[tracing::instrument(skip_all)]
+async fn run_config_init(args: &InitArgs) -> eyre::Result<()> {
+    let cx = cx();
+
+    let git_root = cx.cache.git_root().await?;
+
+    let path = git_root.join(CONFIG_FILE_NAME);
+
+    if !args.stdout && !args.force && fs::try_exists(&path).await? {
+        eyre::bail!("`{path}` already exists (pass `--force` to overwrite it)");
+    }
+
+    // Not `cx.cache.config()`, so a `be.toml` which doesn't parse can still be replaced
+    let config = match Config::load(git_root).await {
+        Ok(config) => config,
+        Err(error) => {
+            tracing::warn!("Ignoring the existing `{CONFIG_FILE_NAME}`: {error:#}");
+            Config::default()
+        }
+    };
+
+    let mut tools = Vec::with_capacity(TOOLS.len());
+    for tool in TOOLS {
+        tools.push((*tool, which_tool(git_root, &config, tool)));
+    }
+
+    let mut config_files = Vec::new();
+    for config_file in config.tool_configs.paths() {
+        let exists = fs::try_exists(git_root.join(config_file)).await?;
+        config_files.push((config_file.as_str(), exists));
+    }
+
+    let package_roots = packages::source_dirs(cx.cache.packages().await?)
+        .into_iter()
+        .map(|dir| dir.to_string())
+        .collect();
+
+    let tracked_roots = tracked_haskell_dirs(git_root).await?;
+
+    let template = template(&tools, &config_files, &package_roots, &tracked_roots)?;
+
+    if args.stdout {
+        write_stdout(Bytes::from(template)).await?;
+    } else {
+        write_file(&path, Bytes::from(template)).await?;
+        message!("Wrote {path}");
+    }
+
+    Ok(())
+}
+
+/// Where a tool is, looked up like `Cache::which` does but with `config` instead of the cache's
+fn which_tool(git_root: &Utf8Path, config: &Config, tool: &str) -> Option<Utf8PathBuf> {
+    let which_config = || {
+        let path = config.tool(tool).and_then(|tool| tool.path.as_ref());
+        let path = path.ok_or(which::Error::CannotFindBinaryPath)?;
+        eyre::Ok(git_root.join(path).into_std_path_buf())
+    };
+    let which_path = || eyre::Ok(which_global(tool)?);
+    let which_bin = || {
+        let mut iter = which_in_global(tool, Some(git_root.join(".bin")))?;
+        let path = iter.next().ok_or(which::Error::CannotFindBinaryPath)?;
+        eyre::Ok(path)
+    };
+    let path = which_config()
+        .or_else(|_| which_path())
+        .or_else(|_| which_bin())
+        .ok()?
+        .canonicalize()
+        .ok()?;
+    Utf8PathBuf::try_from(path).ok()
+}
+
+/// Top-level directories containing tracked Haskell files
+#[tracing::instrument]
+async fn tracked_haskell_dirs(git_root: &Utf8Path) -> eyre::Result<BTreeSet<String>> {
+    let git = Utf8PathBuf::try_from(which_global("git")?)?;
+    let stdout = exec(git, ["-C", git_root.as_str(), "ls-files", "--", "*.hs"]).await?;
+    let roots = str::from_utf8(&stdout)?
+        .lines()
+        .filter_map(|path| path.split_once('/'))
+        .map(|(root, _)| format!("{root}/"))
+        .collect();
+    Ok(roots)
+}
+
+fn template(
+    tools: &[(&str, Option<Utf8PathBuf>)],
+    config_files: &[(&str, bool)],
+    package_roots: &BTreeSet<String>,
+    tracked_roots: &BTreeSet<String>,
+) -> eyre::Result<String> {
+    let mut s = String::new();
+
+    writeln!(
+        s,
+        "# Configuration for `be`, generated by `be config init`."
+    )?;
+    writeln!(s, "#")?;
+    writeln!(s, "# Detected config files:")?;
+    for (config_file, exists) in config_files {
+        let status = if *exists { "found" } else { "missing" };
+        writeln!(s, "#   {config_file} ({status})")?;
+    }
+
+    s.push_str(
+        r#"
+# Find changed files by comparing against this ref (default: the remote's default branch, like
+# "origin/main")
+# base = "origin/main"
+
+# Maximum number of concurrent processes across all tools (default: number of CPUs)
+# jobs = 8
+
+# Order files are processed in: "smallest-first", "largest-first", "fastest-first",
+# "slowest-first" (by durations recorded in the cache), or "as-given"
+# schedule = "smallest-first"
+
+# Don't start more `fourmolu` or `hlint` processes while less than this much memory (in MiB) is
+# available, or 0 to never wait
+# memory-reserve-mib = 1024
+
+[files]
+# Only look at files matching one of these globs, relative to the git root (default: every file)
+# include = ["src/**", "test/**"]
+# Never look at files matching one of these globs
+# exclude = ["vendor/**", "**/Generated/**"]
+
+[tool-configs]
+# Where tools' config files are, relative to the git root
+# fourmolu = "fourmolu.yaml"
+# hlint = [".hlint.yaml", "hlint-rules/"]
+# default-extensions = "hpack-common/default-extensions.yaml"
+# typos = "typos.toml"
+# weeder = "weeder.toml"
+# stan = ".stan.toml"
+
+[format]
+# Enforce "lf" or "crlf" line endings (default: keep each file's own)
+# line-endings = "lf"
+# Format Nix files with "nixfmt", "alejandra", or "nixpkgs-fmt"
+# nix-formatter = "nixfmt"
+# Also format Haskell code blocks in Markdown files and haddocks
+# code-blocks = false
+
+[format.import-order]
+# Groups of imports made by `be fix import-order`, separated by blank lines: "external", "local",
+# or a glob of module names
+# groups = ["external", "local"]
+# Put qualified imports after unqualified ones in each group
+# qualified-last = false
+
+[remote-cache]
+# Share cached results with CI and teammates over HTTP, like "https://cache.example.com/be", or
+# an S3 bucket, like "https://my-bucket.s3.us-east-1.amazonaws.com/be"
+# url = "https://cache.example.com/be"
+# Sign requests for S3 with `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`
+# s3-region = "us-east-1"
+# Send a bearer token from this environment variable
+# token-env = "BE_REMOTE_CACHE_TOKEN"
+# Sign uploaded entries with a secret key from this environment variable, and ignore downloaded
+# ones without a valid signature
+# signing-key-env = "BE_REMOTE_CACHE_SIGNING_KEY"
+# Upload new results, which is the default with a signing key (without one, only enable this on
+# trusted machines like CI)
+# write = true
+
+[haskell]
+# Where GHC writes `.hie` files, for `be lint weeder` and `be lint stan` (build with
+# `-fwrite-ide-info -hiedir=.hie`)
+# hie-directory = ".hie"
+# Directories searched for changed Haskell files
+"#,
+    );
+
+    if package_roots.is_empty() && !tracked_roots.is_empty() {
+        writeln!(
+            s,
+            "# (no cabal/hpack packages found, so using directories with Haskell files)"
+        )?;
+        writeln!(s, "roots = {}", toml_array(tracked_roots))?;
+    } else {
+        writeln!(s, "# (default: source directories of cabal/hpack packages)")?;
+        writeln!(s, "# roots = {}", toml_array(package_roots))?;
+    }
+
+    s.push_str(
+        r#"
+[lint.missing-signatures]
+# Severity of exported top-level bindings without type signatures: "ignore", "suggestion",
+# "warning", or "error"
+# severity = "warning"
+# Override the severity for some paths (the last matching glob wins)
+# [[lint.missing-signatures.paths]]
+# glob = "test/**"
+# severity = "ignore"
+
+[lint.explicit-exports]
+# Severity of modules without an export list
+# severity = "suggestion"
+# Modules which may export everything
+# exceptions = ["Prelude"]
+
+[lint.module-size]
+# Severity of modules over the limits in `[lint.module-size-limits]`
+# severity = "warning"
+# exceptions = ["Generated.Types"]
+
+[lint.module-size-limits]
+# lines = 1000
+# declarations = 100
+# imports = 50
+
+[lint.module-name]
+# Severity of modules whose name doesn't match their path in the package's source directory
+# severity = "warning"
+
+[lint.haddock]
+# Packages checked by `be lint haddock`
+# packages = ["my-package"]
+# Fail when a module documents less than this percentage of its exports
+# threshold = 80.0
+
+[lint.duplicates]
+# Smallest block of code reported by `be lint duplicates`, in syntax tree nodes
+# min-nodes = 40
+
+[lint.ghc]
+# Warning flags passed to `ghc -fno-code` by `be lint ghc`
+# flags = ["-Wall", "-Werror=incomplete-patterns"]
+# Package environment file with the packages modules import, like one cabal writes with
+# `--write-ghc-environment-files=always`
+# package-env = ".ghc.environment.x86_64-linux-9.6.6"
+
+[lint.stan]
+# Least severe observations reported by `be lint stan`: "style", "performance", "potential-bug",
+# "warning", or "error"
+# min-severity = "style"
+
+# Modules which may not import others, checked by `be lint imports`
+# [[lint.imports.rules]]
+# modules = ["Mercury.Api.*"]
+# banned = ["Mercury.Db.*"]
+# Imports which are allowed anyway
+# allowed = ["Mercury.Db.Types"]
+# Modules the rule doesn't apply to
+# exceptions = ["Mercury.Api.Internal.Migrations"]
+# reason = "API handlers should go through `Mercury.Service`"
+# severity = "error"
+"#,
+    );
+
+    for (tool, path) in tools {
+        writeln!(s)?;
+        writeln!(s, "[tools.{tool}]")?;
+        writeln!(s, "# Whether `be format` and `be lint` run `{tool}`")?;
+        writeln!(s, "# enabled = true")?;
+        if let Some(path) = path {
+            writeln!(
+                s,
+                "# Use a specific binary instead of looking it up on `$PATH`"
+            )?;
+            writeln!(s, "# path = {}", toml_string(path.as_str()))?;
+        } else {
+            writeln!(s, "# `{tool}` was not found on `$PATH` or in `.bin/`")?;
+            writeln!(s, "# path = \"/path/to/{tool}\"")?;
+        }
+        writeln!(
+            s,
+            "# Maximum number of concurrent processes (default: number of CPUs)"
+        )?;
+        // `hlint` uses a lot more memory per process than the formatters
+        let jobs = if *tool == "hlint" {
+            4
+        } else {
+            available_parallelism().map_or(1, usize::from)
+        };
+        writeln!(s, "# jobs = {jobs}")?;
+        writeln!(s, "# Whether to reuse results from previous runs")?;
+        writeln!(s, "# cache = true")?;
+        writeln!(
+            s,
+            "# Whether to run `{tool}` in a sandbox, with `sandbox-exec` on macOS or `bwrap` on Linux"
+        )?;
+        writeln!(s, "# sandbox = true")?;
+        writeln!(s, "# Seconds a process can run before it's killed")?;
+        writeln!(s, "# timeout = 120")?;
+        if *tool == "fourmolu" {
+            writeln!(
+                s,
+                "# Format up to this many files per process, to amortize startup"
+            )?;
+            writeln!(s, "# batch-size = 32")?;
+        }
+        if *tool == "hlint" {
+            writeln!(
+                s,
+                "# Lint up to this many files per process, to amortize startup"
+            )?;
+            writeln!(s, "# batch-size = 32")?;
+        }
+    }
+
+    s.push_str(
+        r#"
+# Run extra files through an existing language's pipelines
+# [[file-types]]
+# glob = "*.hsc"
+# language = "haskell"
+# pipelines = ["lint"]
+"#,
+    );
+
+    Ok(s)
+}
+
+fn toml_string(s: &str) -> String {
+    toml::Value::from(s).to_string()
+}
+
+fn toml_array<'a>(strings: impl IntoIterator<Item = &'a String>) -> String {
+    let strings: Vec<_> = strings.into_iter().map(|s| toml_string(s)).collect();
+    format!("[{}]", strings.join(", "))
+}
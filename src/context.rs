@@ -1,13 +1,125 @@
-use crate::cache::Cache;
-use std::sync::OnceLock;
-use tokio::sync::Semaphore;
+use crate::{
+    cache::Cache, cli::MessageFormat, git, memory::MemoryThrottle, metrics::Metrics, state::State,
+    temp::TempStore,
+};
+use camino::Utf8PathBuf;
+use color_eyre::eyre;
+use dashmap::DashMap;
+use sqlx::sqlite::SqlitePool;
+use std::{
+    sync::{Arc, Mutex, OnceLock, atomic::AtomicUsize},
+    thread::available_parallelism,
+    time::Duration,
+};
+use tokio::sync::{OnceCell, Semaphore};
 
 pub struct Context {
     pub cache: Cache,
     pub file_permits: Semaphore,
     pub process_permits: Semaphore,
+    pub tool_permits: DashMap<&'static str, Arc<Semaphore>>,
+    /// Acquired after `process_permits` by memory-hungry tools
+    pub memory: MemoryThrottle,
+    pub state: OnceCell<State>,
+    /// Database written by `be query index`, opened once so `be daemon` keeps it between queries
+    pub query_sqlite: OnceCell<SqlitePool>,
+    /// Ref passed to `--base`
+    pub base_arg: Option<String>,
+    pub base: OnceCell<String>,
+    pub all_files: OnceCell<bool>,
+    /// Whether to use staged contents instead of the working tree, for `--staged`
+    pub staged: bool,
+    /// Seconds passed to `--timeout`
+    pub timeout_arg: Option<u64>,
+    /// Files handed to `queue::spawn`, for reporting progress when interrupted
+    pub files_spawned: AtomicUsize,
+    /// Files whose tasks from `queue::spawn` have finished
+    pub files_finished: AtomicUsize,
+    pub temp_store: TempStore,
+    /// Format passed to `--message-format`
+    pub message_format: MessageFormat,
+    pub metrics: Metrics,
+    /// Whether to report `metrics` at the end of the run, for `--stats`
+    pub stats: bool,
+    /// Files relative to the git root which changed since the last run of `--watch`, which are the
+    /// only changed files after the first run
+    pub watched: Mutex<Option<Vec<Utf8PathBuf>>>,
 }
 
+impl Context {
+    pub async fn state(&self) -> eyre::Result<&State> {
+        self.state.get_or_try_init(State::new).await
+    }
+
+    /// Ref that changed files are compared against: `--base`, or else `base` in `be.toml`, or else
+    /// the remote's default branch
+    pub async fn base(&self) -> eyre::Result<&str> {
+        let base = self
+            .base
+            .get_or_try_init(|| async {
+                if let Some(base) = &self.base_arg {
+                    return Ok(base.clone());
+                }
+                if let Some(base) = &self.cache.config().await?.base {
+                    return Ok(base.clone());
+                }
+                git::default_branch().await
+            })
+            .await?;
+        Ok(base)
+    }
+
+    /// Whether to consider all files instead of only changed ones, because config files which
+    /// affect every file have changed since the base
+    pub async fn all_files(&self) -> eyre::Result<bool> {
+        let all_files = self
+            .all_files
+            .get_or_try_init(|| async {
+                let paths = git::changed_config_files().await?;
+                if let Some(path) = paths.first() {
+                    tracing::info!("Considering all files, since `{path}` changed");
+                }
+                eyre::Ok(!paths.is_empty())
+            })
+            .await?;
+        Ok(*all_files)
+    }
+
+    /// Permits for running a specific tool, acquired before `file_permits` and `process_permits`
+    pub async fn tool_permits(&self, tool: &'static str) -> eyre::Result<Arc<Semaphore>> {
+        if let Some(permits) = self.tool_permits.get(tool) {
+            return Ok(Arc::clone(&permits));
+        }
+        let jobs = self.tool_jobs(tool).await?;
+        let permits = self
+            .tool_permits
+            .entry(tool)
+            .or_insert_with(|| Arc::new(Semaphore::new(jobs)));
+        Ok(Arc::clone(&permits))
+    }
+
+    /// Maximum number of concurrent processes for a tool
+    pub async fn tool_jobs(&self, tool: &str) -> eyre::Result<usize> {
+        let config = self.cache.config().await?;
+        match config.tool(tool).and_then(|tool| tool.jobs) {
+            Some(jobs) => Ok(usize::from(jobs)),
+            None => Ok(usize::from(available_parallelism()?)),
+        }
+    }
+
+    /// How long a tool's process can run: `--timeout`, or else `timeout` in the tool's config
+    pub async fn tool_timeout(&self, tool: &str) -> eyre::Result<Duration> {
+        let config = self.cache.config().await?;
+        let seconds = self
+            .timeout_arg
+            .or_else(|| config.tool(tool).and_then(|tool| tool.timeout))
+            .unwrap_or(DEFAULT_TIMEOUT_SECONDS);
+        Ok(Duration::from_secs(seconds))
+    }
+}
+
+const DEFAULT_TIMEOUT_SECONDS: u64 = 120;
+
 pub static CONTEXT: OnceLock<Context> = OnceLock::new();
 
 pub fn cx() -> &'static Context {
@@ -0,0 +1,336 @@
+use crate::{
+    cache,
+    cli::{self, Command, MessageFormat, daemon::Args, lint as cli_lint, query as cli_query},
+    config::CONFIG_FILE_NAME,
+    context::cx,
+    events::message,
+    exit::{self, Findings, Interrupted},
+    format,
+    io::{FORWARDED, Forwarded, read_stdin, write_stdout},
+    lint, query,
+};
+use bytes::Bytes;
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::Parser as _;
+use color_eyre::eyre;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::{
+    env,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    fs,
+    io::{
+        AsyncBufRead, AsyncBufReadExt as _, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _,
+        BufReader,
+    },
+    net::{UnixListener, UnixStream},
+};
+use twox_hash::XxHash3_64;
+
+/// Sent as soon as a client connects, before it reads `stdin`, so it can still run the command
+/// itself if the daemon is stopping
+const READY: &str = "ready\n";
+
+const STALE: &str = "stale\n";
+
+/// How long a client has to send its command, and to read the response, so one which stops
+/// partway doesn't block every other client
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A command forwarded by a client, followed by `stdin_length` bytes of `stdin`
+#[derive(Serialize, Deserialize)]
+struct Request {
+    args: Vec<String>,
+    cwd: Utf8PathBuf,
+    stdin_length: usize,
+}
+
+/// How the command went, followed by `stdout_length` bytes of `stdout`
+#[derive(Serialize, Deserialize)]
+struct Response {
+    exit_code: u8,
+    message: Option<String>,
+    stderr: String,
+    stdout_length: usize,
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn run(_args: &Args) -> eyre::Result<()> {
+    let cx = cx();
+
+    let git_root = cx.cache.git_root().await?;
+
+    let Some(socket_path) = socket_path(Some(cx.cache.dir()))? else {
+        eyre::bail!("`be daemon` must be run in a git repo");
+    };
+
+    if UnixStream::connect(&socket_path).await.is_ok() {
+        eyre::bail!("`be daemon` is already running for this repo");
+    }
+
+    // Everything a forwarded command would otherwise look up on startup
+    cx.cache.config().await?;
+    cx.cache.packages().await?;
+    query::run_query_index().await?;
+
+    let started_mtimes = config_mtimes(git_root).await?;
+
+    if let Some(parent) = socket_path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    // Left behind by a daemon which didn't get to clean up
+    if fs::try_exists(&socket_path).await? {
+        fs::remove_file(&socket_path).await?;
+    }
+    let listener = UnixListener::bind(&socket_path)?;
+    let _socket = Socket(socket_path.clone());
+
+    message!("Listening on `{socket_path}`");
+
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+
+        if config_mtimes(git_root).await? != started_mtimes {
+            stream.write_all(STALE.as_bytes()).await?;
+            message!("Stopping, since config files changed");
+            return Ok(());
+        }
+
+        // One at a time, since a command's `stdin` and `stdout` are swapped in for the daemon's,
+        // and commands like `be format` keep their report in a global
+        if let Err(error) = serve(stream).await {
+            tracing::warn!("Failed to run forwarded command: {error}");
+        }
+    }
+}
+
+/// Removes the socket when the daemon stops, including on Ctrl-C
+struct Socket(Utf8PathBuf);
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+async fn serve(stream: UnixStream) -> eyre::Result<()> {
+    let mut stream = BufReader::new(stream);
+    // Another `be daemon` checking whether one is running disconnects without sending anything
+    if stream.write_all(READY.as_bytes()).await.is_err() || stream.flush().await.is_err() {
+        return Ok(());
+    }
+    let read = async {
+        let Some(request): Option<Request> = read_header(&mut stream).await? else {
+            return eyre::Ok(None);
+        };
+        let stdin = read_body(&mut stream, request.stdin_length).await?;
+        eyre::Ok(Some((request, stdin)))
+    };
+    let Ok(read) = tokio::time::timeout(CLIENT_TIMEOUT, read).await else {
+        eyre::bail!("The client didn't send its command in time");
+    };
+    let Some((request, stdin)) = read? else {
+        return Ok(());
+    };
+
+    let forwarded = Arc::new(Forwarded {
+        stdin: std::sync::Mutex::new(Some(stdin)),
+        ..Forwarded::default()
+    });
+    let result = FORWARDED
+        .scope(Arc::clone(&forwarded), Box::pin(run_forwarded(&request)))
+        .await;
+
+    let stdout = std::mem::take(&mut *forwarded.stdout.lock().unwrap());
+    let stderr = std::mem::take(&mut *forwarded.stderr.lock().unwrap());
+    let response = Response {
+        exit_code: exit::code(&result),
+        message: result.err().map(|error| format!("{error:#}")),
+        stderr,
+        stdout_length: stdout.len(),
+    };
+    let write = write_frame(&mut stream, &response, &stdout);
+    let Ok(written) = tokio::time::timeout(CLIENT_TIMEOUT, write).await else {
+        eyre::bail!("The client didn't read the response in time");
+    };
+    written?;
+
+    Ok(())
+}
+
+async fn run_forwarded(request: &Request) -> eyre::Result<()> {
+    // Relative paths in the command are relative to where the client ran
+    env::set_current_dir(&request.cwd)?;
+    let args = cli::Args::try_parse_from(&request.args)?;
+    match &args.command {
+        Command::Format(args) => format::run(args).await,
+        Command::Lint(args) => lint::run(args).await,
+        Command::Query(args) => query::run(args).await,
+        _ => eyre::bail!("`be daemon` can't run this command"),
+    }
+}
+
+/// Run the command in `be daemon`, if it can be forwarded and a daemon is listening for this repo.
+/// Returns `None` if `be` should run it itself.
+pub async fn forward(args: &cli::Args) -> Option<eyre::Result<()>> {
+    if !is_forwardable(args) {
+        return None;
+    }
+    let socket_path = socket_path(args.cache_dir.as_deref()).ok()??;
+    let stream = UnixStream::connect(&socket_path).await.ok()?;
+    let mut stream = BufReader::new(stream);
+    let mut greeting = String::new();
+    stream.read_line(&mut greeting).await.ok()?;
+    if greeting != READY {
+        tracing::debug!("Not forwarding to `be daemon`, since it's stopping");
+        return None;
+    }
+    tracing::debug!("Forwarding to `be daemon` at `{socket_path}`");
+    Some(send(stream, uses_stdin(args)).await)
+}
+
+async fn send(mut stream: BufReader<UnixStream>, uses_stdin: bool) -> eyre::Result<()> {
+    let stdin = if uses_stdin {
+        read_stdin().await?.0
+    } else {
+        Bytes::new()
+    };
+    let request = Request {
+        args: env::args().collect(),
+        cwd: Utf8PathBuf::try_from(env::current_dir()?)?,
+        stdin_length: stdin.len(),
+    };
+    write_frame(&mut stream, &request, &stdin).await?;
+
+    let Some(response): Option<Response> = read_header(&mut stream).await? else {
+        eyre::bail!("`be daemon` stopped before responding");
+    };
+    let stdout = read_body(&mut stream, response.stdout_length).await?;
+
+    write_stdout(stdout).await?;
+    eprint!("{}", response.stderr);
+
+    let message = response.message.unwrap_or_default();
+    match response.exit_code {
+        exit::CLEAN => Ok(()),
+        exit::FINDINGS => Err(Findings(message).into()),
+        exit::INTERRUPTED => Err(Interrupted(message).into()),
+        _ => Err(eyre::eyre!(message)),
+    }
+}
+
+/// Commands editors run over and over: piping a file through `be format` or `be lint`, or asking
+/// `be query` about the code. Global flags which change how `be` runs aren't forwarded, since the
+/// daemon was started without them.
+fn is_forwardable(args: &cli::Args) -> bool {
+    let is_command_forwardable = match &args.command {
+        Command::Format(args) => args.command.is_none() && args.stdin && !args.watch,
+        Command::Lint(args) => {
+            !args.watch
+                && matches!(&args.command, Some(cli_lint::Command::Haskell(args)) if args.stdin)
+        }
+        Command::Query(args) => !matches!(args.command, cli_query::Command::Serve(_)),
+        _ => false,
+    };
+    is_command_forwardable
+        && !args.no_cache
+        && args.base.is_none()
+        && args.timeout.is_none()
+        && !args.staged
+        && !args.stats
+        && args.message_format == MessageFormat::Human
+}
+
+/// Whether the command reads `stdin`, which has to be sent along with it
+fn uses_stdin(args: &cli::Args) -> bool {
+    match &args.command {
+        Command::Format(_) | Command::Lint(_) => true,
+        Command::Query(args) => match &args.command {
+            cli_query::Command::Imports(args) | cli_query::Command::Exports(args) => args.stdin,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Where the daemon for the repo containing the current directory listens. Found without running
+/// `git` or opening the cache, so forwarding a command stays cheap.
+fn socket_path(cache_dir: Option<&Utf8Path>) -> eyre::Result<Option<Utf8PathBuf>> {
+    let cwd = Utf8PathBuf::try_from(env::current_dir()?)?;
+    // `.git` is a file in worktrees and submodules
+    let Some(git_root) = cwd.ancestors().find(|dir| dir.join(".git").exists()) else {
+        return Ok(None);
+    };
+    let cache_dir = match cache_dir {
+        Some(cache_dir) => cache_dir.to_path_buf(),
+        None => cache::default_dir()?,
+    };
+    let hash = XxHash3_64::oneshot(git_root.as_str().as_bytes());
+    Ok(Some(
+        cache_dir.join("daemon").join(format!("{hash:016x}.sock")),
+    ))
+}
+
+/// When files which are only read once were last modified: `be.toml`, tools' configs, `.bin/`,
+/// where tools not on `$PATH` are looked up, and packages' `package.yaml` and `.cabal` files
+async fn config_mtimes(git_root: &Utf8Path) -> eyre::Result<Vec<Option<SystemTime>>> {
+    let cx = cx();
+    let config = cx.cache.config().await?;
+    let mut paths = vec![
+        Utf8PathBuf::from(CONFIG_FILE_NAME),
+        Utf8PathBuf::from(".bin"),
+    ];
+    paths.extend(
+        config
+            .tool_configs
+            .paths()
+            .into_iter()
+            .map(Utf8Path::to_path_buf),
+    );
+    for package in cx.cache.packages().await? {
+        paths.push(package.dir.join("package.yaml"));
+        paths.push(package.dir.join(format!("{}.cabal", package.name)));
+    }
+    let mut mtimes = Vec::with_capacity(paths.len());
+    for path in paths {
+        let mtime = match fs::metadata(git_root.join(path)).await {
+            Ok(metadata) => Some(metadata.modified()?),
+            Err(_) => None,
+        };
+        mtimes.push(mtime);
+    }
+    Ok(mtimes)
+}
+
+/// A line of JSON, then the raw bytes it describes
+async fn write_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    header: &impl Serialize,
+    body: &[u8],
+) -> eyre::Result<()> {
+    let mut line = serde_json::to_vec(header)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+    writer.write_all(body).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// The line of JSON starting a frame, or `None` if the other side disconnected instead
+async fn read_header<R: AsyncBufRead + Unpin, T: DeserializeOwned>(
+    reader: &mut R,
+) -> eyre::Result<Option<T>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).await? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(&line)?))
+}
+
+async fn read_body<R: AsyncBufRead + Unpin>(reader: &mut R, length: usize) -> eyre::Result<Bytes> {
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+    Ok(Bytes::from(body))
+}
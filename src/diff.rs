@@ -0,0 +1,60 @@
+use similar::TextDiff;
+use std::{env, fmt::Write as _, io::IsTerminal as _};
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Unified diff between two versions of a file, colored when `stdout` is a terminal (unless
+/// `NO_COLOR` is set) or when `CLICOLOR_FORCE` is set
+pub fn unified(path: &str, before: &[u8], after: &[u8]) -> String {
+    let diff = plain(path, before, after);
+
+    if !use_color() {
+        return diff;
+    }
+
+    let mut colored = String::with_capacity(diff.len());
+    for line in diff.split_inclusive('\n') {
+        let (text, newline) = match line.strip_suffix('\n') {
+            Some(text) => (text, "\n"),
+            None => (line, ""),
+        };
+        let color = if line.starts_with("---") || line.starts_with("+++") {
+            BOLD
+        } else if line.starts_with("@@") {
+            CYAN
+        } else if line.starts_with('-') {
+            RED
+        } else if line.starts_with('+') {
+            GREEN
+        } else {
+            colored.push_str(line);
+            continue;
+        };
+        // Writing to a `String` can't fail
+        let _ = write!(colored, "{color}{text}{RESET}{newline}");
+    }
+    colored
+}
+
+/// Unified diff which is never colored, for findings which may be written as JSON
+pub fn plain(path: &str, before: &[u8], after: &[u8]) -> String {
+    let before = String::from_utf8_lossy(before);
+    let after = String::from_utf8_lossy(after);
+
+    TextDiff::from_lines(&before, &after)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{path}"), &format!("b/{path}"))
+        .to_string()
+}
+
+fn use_color() -> bool {
+    if env::var_os("CLICOLOR_FORCE").is_some_and(|value| value != "0") {
+        return true;
+    }
+    env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && std::io::stdout().is_terminal()
+}
@@ -0,0 +1,138 @@
+use crate::{cli::MessageFormat, context::CONTEXT, io::FORWARDED};
+use serde::Serialize;
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use tracing_indicatif::indicatif_eprintln;
+
+/// Something that happened during a run, printed as a line of JSON with `--message-format jsonl`
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event<'a> {
+    /// A file's task finished, successfully or not
+    File {
+        path: &'a str,
+        outcome: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        findings: Option<usize>,
+        duration_ms: u128,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    /// What would've been printed on `stderr`, like counts of files formatted or linted
+    Message { text: &'a str },
+    /// The run is over, and `be` is about to exit
+    Finished {
+        exit_code: u8,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+    },
+}
+
+/// What happened to a file, for `file` events
+pub trait Outcome {
+    fn outcome(&self) -> &'static str;
+
+    /// How many findings there were, for linters
+    fn findings(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// Formatters and generators return whether they changed the file
+impl Outcome for bool {
+    fn outcome(&self) -> &'static str {
+        if *self { "changed" } else { "unchanged" }
+    }
+}
+
+impl Outcome for Option<bool> {
+    fn outcome(&self) -> &'static str {
+        match self {
+            Some(changed) => changed.outcome(),
+            None => "skipped",
+        }
+    }
+}
+
+/// `be fix` returns how many hints it applied
+impl Outcome for usize {
+    fn outcome(&self) -> &'static str {
+        (*self > 0).outcome()
+    }
+}
+
+/// Set once the `finished` event is printed, since tasks still running when `be` exits can fail
+/// as the runtime shuts down
+static FINISHED: AtomicBool = AtomicBool::new(false);
+
+pub fn is_jsonl() -> bool {
+    CONTEXT
+        .get()
+        .is_some_and(|cx| cx.message_format == MessageFormat::Jsonl)
+}
+
+pub fn emit(event: &Event) {
+    if FINISHED.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Event::Finished { .. } = event {
+        FINISHED.store(true, Ordering::Relaxed);
+    }
+    match serde_json::to_string(event) {
+        Ok(line) => indicatif_eprintln!("{line}"),
+        Err(error) => tracing::warn!("Failed to serialize event: {error}"),
+    }
+}
+
+/// A file's task finished after `duration`
+pub fn file<T: Outcome>(path: &str, result: &color_eyre::Result<T>, duration: Duration) {
+    if !is_jsonl() {
+        return;
+    }
+    emit(&match result {
+        Ok(value) => Event::File {
+            path,
+            outcome: value.outcome(),
+            findings: value.findings(),
+            duration_ms: duration.as_millis(),
+            error: None,
+        },
+        Err(error) => Event::File {
+            path,
+            outcome: "failed",
+            findings: None,
+            duration_ms: duration.as_millis(),
+            error: Some(error.to_string()),
+        },
+    });
+}
+
+/// Print a message on `stderr`, or a `message` event with `--message-format jsonl`
+pub fn print_message(text: &str) {
+    if FORWARDED
+        .try_with(|forwarded| {
+            let mut stderr = forwarded.stderr.lock().unwrap();
+            stderr.push_str(text);
+            stderr.push('\n');
+        })
+        .is_ok()
+    {
+        return;
+    }
+    if is_jsonl() {
+        emit(&Event::Message { text });
+    } else {
+        indicatif_eprintln!("{text}");
+    }
+}
+
+/// Like `indicatif_eprintln!`, but respecting `--message-format`
+macro_rules! message {
+    ($($arg:tt)*) => {
+        $crate::events::print_message(&format!($($arg)*))
+    };
+}
+
+pub(crate) use message;
@@ -1,10 +1,142 @@
+use crate::{cli::exec::Args, context::cx};
 use bytes::Bytes;
-use color_eyre::eyre;
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{self, WrapErr as _};
 use std::{
-    ffi::{OsStr, OsString},
+    borrow::Cow,
+    cell::Cell,
+    env,
+    ffi::OsStr,
+    io,
     os::unix::process::ExitStatusExt as _,
+    path::Path,
+    process,
+    time::{Duration, Instant},
 };
-use tokio::process::Command;
+use tokio::{
+    fs,
+    io::AsyncWriteExt as _,
+    process::{Child, Command},
+    sync::OnceCell,
+};
+use which::which_global;
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &Args) -> eyre::Result<()> {
+    let cx = cx();
+
+    let profile = match profile(&args.profile) {
+        Some(profile) => Cow::Borrowed(profile),
+        None => Cow::Owned(
+            fs::read_to_string(&args.profile)
+                .await
+                .wrap_err_with(|| format!("Failed to read sandbox profile `{}`", args.profile))?,
+        ),
+    };
+
+    // `args.command` is non-empty because clap requires it
+    let (program, program_args) = args.command.split_first().unwrap();
+
+    // Resolve before clearing the environment, since that also clears `$PATH`
+    let program = std::fs::canonicalize(which_global(program)?)?;
+
+    // Without `bwrap`, `sandboxed` would run the command as is, which `be exec` is meant to prevent
+    if !cfg!(target_os = "macos") && bwrap().await.is_none() {
+        eyre::bail!("`be exec` needs `bwrap` installed to sandbox the command");
+    }
+
+    // What `be` passes the built-in profiles when it runs the tools itself
+    let mut param_paths = Vec::new();
+    for name in profile_params(&profile) {
+        let param = match name {
+            "GIT_ROOT" => {
+                let git_root = cx.cache.git_root().await.wrap_err_with(|| {
+                    format!("Sandbox profile `{}` needs a git repo", args.profile)
+                })?;
+                ("GIT_ROOT", git_root.to_path_buf(), false)
+            }
+            "TEMP_DIR" => ("TEMP_DIR", cx.temp_store.dir()?, true),
+            _ => eyre::bail!(
+                "Sandbox profile `{}` has parameter `{name}`, which `be exec` can't supply",
+                args.profile,
+            ),
+        };
+        if !param_paths.iter().any(|(name, _, _)| *name == param.0) {
+            param_paths.push(param);
+        }
+    }
+    let params: Vec<Param> = param_paths
+        .iter()
+        .map(|(name, path, writable)| Param {
+            name,
+            path,
+            writable: *writable,
+        })
+        .collect();
+
+    let mut command = sandboxed(None, &profile, &params, program).await?;
+
+    command.args(program_args).env_clear().kill_on_drop(true);
+
+    for name in &args.envs {
+        if let Some(value) = env::var_os(name) {
+            command.env(name, value);
+        }
+    }
+
+    let process_permit = cx.process_permits.acquire().await?;
+
+    let status = command.status().await?;
+
+    drop(process_permit);
+
+    // Exiting below skips `main`, which would otherwise remove `TEMP_DIR`
+    cx.temp_store.clear();
+
+    if let Some(exit_code) = status.code() {
+        if exit_code != 0 {
+            process::exit(exit_code);
+        }
+    } else if let Some(signal) = status.signal() {
+        process::exit(128 + signal);
+    }
+
+    Ok(())
+}
+
+/// Names of the parameters a sandbox profile reads, like `GIT_ROOT` in `(param "GIT_ROOT")`
+fn profile_params(profile: &str) -> impl Iterator<Item = &str> {
+    profile
+        .split("(param \"")
+        .skip(1)
+        .filter_map(|rest| rest.split_once('"'))
+        .map(|(name, _)| name)
+}
+
+/// Built-in sandbox profiles, by name
+pub fn profile(name: &str) -> Option<&'static str> {
+    match name {
+        "fourmolu" => Some(FOURMOLU_PROFILE),
+        "nixfmt" => Some(NIXFMT_PROFILE),
+        "alejandra" => Some(ALEJANDRA_PROFILE),
+        "nixpkgs-fmt" => Some(NIXPKGS_FMT_PROFILE),
+        "dhall" => Some(DHALL_PROFILE),
+        "hlint" => Some(HLINT_PROFILE),
+        "ghc" => Some(GHC_PROFILE),
+        "weeder" => Some(WEEDER_PROFILE),
+        "stan" => Some(STAN_PROFILE),
+        "typos" => Some(TYPOS_PROFILE),
+        "hpack" => Some(HPACK_PROFILE),
+        "refactor" => Some(REFACTOR_PROFILE),
+        "shfmt" => Some(SHFMT_PROFILE),
+        "shellcheck" => Some(SHELLCHECK_PROFILE),
+        "cabal-gild" => Some(CABAL_GILD_PROFILE),
+        "cabal-fmt" => Some(CABAL_FMT_PROFILE),
+        "statix" => Some(STATIX_PROFILE),
+        "deadnix" => Some(DEADNIX_PROFILE),
+        _ => None,
+    }
+}
 
 #[tracing::instrument(
     skip_all,
@@ -14,13 +146,15 @@ pub async fn exec(
     program: impl AsRef<OsStr>,
     args: impl IntoIterator<Item = impl AsRef<OsStr>>,
 ) -> eyre::Result<Bytes> {
+    let mut command = Command::new(program);
+    command.args(args);
+    output(command).await
+}
+
+async fn output(mut command: Command) -> eyre::Result<Bytes> {
     tracing::trace!("Spawning");
 
-    let output = Command::new(program)
-        .args(args)
-        .kill_on_drop(true)
-        .output()
-        .await?;
+    let output = command.kill_on_drop(true).output().await?;
 
     tracing::trace!("Finished");
 
@@ -40,24 +174,184 @@ pub async fn exec(
     Ok(Bytes::from(output.stdout))
 }
 
+/// Run a tool in its sandbox and return its output
 pub async fn sandbox_exec(
+    tool: &str,
     profile: &str,
     program: impl AsRef<OsStr>,
     args: impl IntoIterator<Item = impl AsRef<OsStr>>,
 ) -> eyre::Result<Bytes> {
+    let mut command = sandboxed(Some(tool), profile, &[], program).await?;
+    command.args(args);
+    timeout(tool, output(command)).await
+}
+
+tokio::task_local! {
+    /// How long processes waited on with `timeout` ran for, in a `timed` future
+    static RUN_TIME: Cell<Duration>;
+}
+
+/// Run a future, and also return how long processes it waited on with `timeout` ran for. Unlike
+/// timing the whole future, this leaves out waiting for permits. It's zero when the processes ran
+/// in another task, like a batch shared with other files.
+pub async fn timed<T>(
+    future: impl Future<Output = eyre::Result<T>>,
+) -> eyre::Result<(T, Duration)> {
+    RUN_TIME
+        .scope(Cell::new(Duration::ZERO), async {
+            let output = future.await?;
+            eyre::Ok((output, RUN_TIME.with(Cell::get)))
+        })
+        .await
+}
+
+/// Wait for a tool's process, giving up once it's run for longer than the tool's timeout. The
+/// process has to be spawned with `kill_on_drop`, so giving up also kills it.
+pub async fn timeout<T, E>(tool: &str, wait: impl Future<Output = Result<T, E>>) -> eyre::Result<T>
+where
+    eyre::Report: From<E>,
+{
+    let cx = cx();
+    let duration = cx.tool_timeout(tool).await?;
+    let _running = cx.metrics.start(tool);
+    let start = Instant::now();
+    let result = tokio::time::timeout(duration, wait).await;
+    // Outside of `timed`, there's nothing to add it to
+    let _ = RUN_TIME.try_with(|run_time| run_time.set(run_time.get() + start.elapsed()));
+    match result {
+        Ok(result) => Ok(result?),
+        Err(_) => eyre::bail!(
+            "`{tool}` was killed after running for {seconds} {unit} (set `--timeout` or `timeout` \
+             in `[tools.{tool}]` to wait longer)",
+            seconds = duration.as_secs(),
+            unit = if duration.as_secs() == 1 {
+                "second"
+            } else {
+                "seconds"
+            },
+        ),
+    }
+}
+
+/// Write to a child's `stdin` while collecting its output, so the write is covered by `timeout` too,
+/// and a tool which writes output before reading all of its input can't block on a full pipe
+pub async fn output_with_stdin(mut child: Child, bytes: &[u8]) -> io::Result<process::Output> {
+    let mut stdin = child.stdin.take().unwrap();
+    let write = async move {
+        stdin.write_all(bytes).await?;
+        stdin.flush().await
+    };
+    // Boxed, since every tool run holds both futures at once
+    let (written, output) = tokio::join!(write, Box::pin(child.wait_with_output()));
+    let output = output?;
+    // A tool can exit without reading all of its input, like when it fails, which its exit status
+    // explains better than a broken pipe does
+    match written {
+        Err(error) if error.kind() != io::ErrorKind::BrokenPipe => Err(error),
+        _ => Ok(output),
+    }
+}
+
+/// A directory passed to a sandbox profile as a parameter, which the tool can read, and write if
+/// `writable`
+pub struct Param<'a> {
+    pub name: &'static str,
+    pub path: &'a Utf8Path,
+    pub writable: bool,
+}
+
+/// A command which runs a tool in a sandbox, unless its config sets `sandbox = false`.
+///
+/// On macOS this is `sandbox-exec` with the tool's profile. On Linux it's `bwrap`, if it's
+/// installed, with the same restrictions the profiles have in common: the filesystem is read-only,
+/// home directories are hidden, and there's no network.
+pub async fn sandboxed(
+    tool: Option<&str>,
+    profile: &str,
+    params: &[Param<'_>],
+    binary: impl AsRef<OsStr>,
+) -> eyre::Result<Command> {
+    let binary = binary.as_ref();
+
+    if let Some(tool) = tool
+        && !cx().cache.config().await?.is_tool_sandboxed(tool)
+    {
+        return Ok(Command::new(binary));
+    }
+
     if cfg!(target_os = "macos") {
-        let program_args = args.into_iter().map(|arg| arg.as_ref().to_os_string());
-        let mut args = vec![
-            OsString::from("-p"),
-            OsString::from(profile),
-            OsString::from("--"),
-            program.as_ref().to_os_string(),
-        ];
-        args.extend(program_args);
-        exec("/usr/bin/sandbox-exec", args).await
-    } else {
-        exec(program, args).await
+        let mut command = Command::new("/usr/bin/sandbox-exec");
+        for param in params {
+            command.arg("-D");
+            command.arg(format!("{}={}", param.name, param.path));
+        }
+        command.arg("-p");
+        command.arg(profile);
+        command.arg("--");
+        command.arg(binary);
+        return Ok(command);
+    }
+
+    let Some(bwrap) = bwrap().await else {
+        return Ok(Command::new(binary));
+    };
+
+    let mut command = Command::new(bwrap);
+    command.args(["--ro-bind", "/", "/", "--dev", "/dev", "--proc", "/proc"]);
+    command.args(["--unshare-all", "--die-with-parent", "--new-session"]);
+    for dir in hidden_dirs() {
+        command.arg("--tmpfs");
+        command.arg(dir);
+    }
+    // The tool might be installed in a hidden directory, like `~/.cabal/bin`
+    command.arg("--ro-bind");
+    command.arg(binary);
+    command.arg(binary);
+    for param in params {
+        command.arg(if param.writable {
+            "--bind"
+        } else {
+            "--ro-bind"
+        });
+        command.arg(param.path);
+        command.arg(param.path);
+    }
+    command.arg("--");
+    command.arg(binary);
+    Ok(command)
+}
+
+/// Path to `bwrap`, looked up once
+async fn bwrap() -> Option<&'static Utf8Path> {
+    static BWRAP: OnceCell<Option<Utf8PathBuf>> = OnceCell::const_new();
+    BWRAP
+        .get_or_init(|| async {
+            // Outside of a repo, like with `be exec`, there's no `.bin/` or config to look in
+            let bwrap = match cx().cache.which("bwrap").await {
+                Ok(bwrap) => Some(bwrap),
+                Err(_) => which_global("bwrap")
+                    .ok()
+                    .and_then(|bwrap| Utf8PathBuf::try_from(bwrap).ok()),
+            };
+            if bwrap.is_none() {
+                tracing::debug!("Running tools without a sandbox, since `bwrap` isn't installed");
+            }
+            bwrap
+        })
+        .await
+        .as_deref()
+}
+
+/// Directories with personal files, which tools shouldn't read, like `/Users` in the profiles
+fn hidden_dirs() -> Vec<String> {
+    let mut dirs = vec![String::from("/home"), String::from("/root")];
+    if let Ok(home) = env::var("HOME")
+        && !dirs.iter().any(|dir| Path::new(&home).starts_with(dir))
+    {
+        dirs.push(home);
     }
+    dirs.retain(|dir| Path::new(dir).is_dir());
+    dirs
 }
 
 pub const FOURMOLU_PROFILE: &str = r#"
@@ -70,6 +364,19 @@ pub const FOURMOLU_PROFILE: &str = r#"
   (subpath "/Users"))
 "#;
 
+/// Like `FOURMOLU_PROFILE`, but also allows formatting files in place under `BATCH_DIR`
+pub const FOURMOLU_BATCH_PROFILE: &str = r#"
+(version 1)
+(deny default)
+(allow process-exec*
+  (regex #"^/nix/store/[a-z0-9]+-fourmolu-[^/]+/bin/fourmolu$"))
+(allow file-read*)
+(deny file-read*
+  (subpath "/Users"))
+(allow file-read* file-write*
+  (subpath (param "BATCH_DIR")))
+"#;
+
 pub const NIXFMT_PROFILE: &str = r#"
 (version 1)
 (deny default)
@@ -80,6 +387,36 @@ pub const NIXFMT_PROFILE: &str = r#"
   (subpath "/Users"))
 "#;
 
+pub const ALEJANDRA_PROFILE: &str = r#"
+(version 1)
+(deny default)
+(allow process-exec*
+  (regex #"^/nix/store/[a-z0-9]+-alejandra-[^/]+/bin/alejandra$"))
+(allow file-read*)
+(deny file-read*
+  (subpath "/Users"))
+"#;
+
+pub const NIXPKGS_FMT_PROFILE: &str = r#"
+(version 1)
+(deny default)
+(allow process-exec*
+  (regex #"^/nix/store/[a-z0-9]+-nixpkgs-fmt-[^/]+/bin/nixpkgs-fmt$"))
+(allow file-read*)
+(deny file-read*
+  (subpath "/Users"))
+"#;
+
+pub const DHALL_PROFILE: &str = r#"
+(version 1)
+(deny default)
+(allow process-exec*
+  (regex #"^/nix/store/[a-z0-9]+-dhall-[^/]+/bin/dhall$"))
+(allow file-read*)
+(deny file-read*
+  (subpath "/Users"))
+"#;
+
 // TODO: Lock this down further
 pub const HLINT_PROFILE: &str = r#"
 (version 1)
@@ -87,3 +424,144 @@ pub const HLINT_PROFILE: &str = r#"
 (deny file-read*
   (subpath "/Users"))
 "#;
+
+pub const TYPOS_PROFILE: &str = r#"
+(version 1)
+(deny default)
+(allow process-exec*
+  (regex #"^/nix/store/[a-z0-9]+-typos-[^/]+/bin/typos$"))
+(allow file-read*)
+(deny file-read*
+  (subpath "/Users"))
+"#;
+
+pub const SHFMT_PROFILE: &str = r#"
+(version 1)
+(deny default)
+(allow process-exec*
+  (regex #"^/nix/store/[a-z0-9]+-shfmt-[^/]+/bin/shfmt$"))
+(allow file-read*)
+(deny file-read*
+  (subpath "/Users"))
+"#;
+
+pub const SHELLCHECK_PROFILE: &str = r#"
+(version 1)
+(deny default)
+(allow process-exec*
+  (regex #"^/nix/store/[a-z0-9]+-shellcheck-[^/]+/bin/shellcheck$"))
+(allow file-read*)
+(deny file-read*
+  (subpath "/Users"))
+"#;
+
+pub const CABAL_GILD_PROFILE: &str = r#"
+(version 1)
+(deny default)
+(allow process-exec*
+  (regex #"^/nix/store/[a-z0-9]+-cabal-gild-[^/]+/bin/cabal-gild$"))
+(allow file-read*)
+(deny file-read*
+  (subpath "/Users"))
+"#;
+
+pub const CABAL_FMT_PROFILE: &str = r#"
+(version 1)
+(deny default)
+(allow process-exec*
+  (regex #"^/nix/store/[a-z0-9]+-cabal-fmt-[^/]+/bin/cabal-fmt$"))
+(allow file-read*)
+(deny file-read*
+  (subpath "/Users"))
+"#;
+
+/// Reads the copy of the file under `TEMP_DIR`, where `be` puts it
+pub const STATIX_PROFILE: &str = r#"
+(version 1)
+(deny default)
+(allow process-exec*
+  (regex #"^/nix/store/[a-z0-9]+-statix-[^/]+/bin/statix$"))
+(allow file-read*)
+(deny file-read*
+  (subpath "/Users"))
+(allow file-read*
+  (subpath (param "TEMP_DIR")))
+"#;
+
+/// Reads the copy of the file under `TEMP_DIR`, where `be` puts it
+pub const DEADNIX_PROFILE: &str = r#"
+(version 1)
+(deny default)
+(allow process-exec*
+  (regex #"^/nix/store/[a-z0-9]+-deadnix-[^/]+/bin/deadnix$"))
+(allow file-read*)
+(deny file-read*
+  (subpath "/Users"))
+(allow file-read*
+  (subpath (param "TEMP_DIR")))
+"#;
+
+/// Reads the module and refactorings under `TEMP_DIR`, where `be` copies them
+pub const REFACTOR_PROFILE: &str = r#"
+(version 1)
+(deny default)
+(allow process-exec*
+  (regex #"^/nix/store/[a-z0-9]+-apply-refact-[^/]+/bin/refactor$"))
+(allow file-read*)
+(deny file-read*
+  (subpath "/Users"))
+(allow file-read*
+  (subpath (param "TEMP_DIR")))
+"#;
+
+/// Reads the repo under `GIT_ROOT`, since `hpack` looks for modules and follows `!include`s
+pub const HPACK_PROFILE: &str = r#"
+(version 1)
+(deny default)
+(allow process-exec*
+  (regex #"^/nix/store/[a-z0-9]+-hpack-[^/]+/bin/hpack$"))
+(allow file-read*)
+(deny file-read*
+  (subpath "/Users"))
+(allow file-read*
+  (subpath (param "GIT_ROOT")))
+"#;
+
+/// Reads the repo under `GIT_ROOT` to find imported modules, and writes interface files under
+/// `TEMP_DIR`. Like `hlint`, it runs other programs, like the C preprocessor for `CPP`.
+pub const GHC_PROFILE: &str = r#"
+(version 1)
+(allow default)
+(deny file-read*
+  (subpath "/Users"))
+(allow file-read*
+  (subpath (param "GIT_ROOT")))
+"#;
+
+/// Reads `.hie` files and `weeder.toml` under `GIT_ROOT`
+pub const WEEDER_PROFILE: &str = r#"
+(version 1)
+(deny default)
+(allow process-exec*
+  (regex #"^/nix/store/[a-z0-9]+-weeder-[^/]+/bin/weeder$"))
+(allow file-read*)
+(deny file-read*
+  (subpath "/Users"))
+(allow file-read*
+  (subpath (param "GIT_ROOT")))
+"#;
+
+/// Reads the copy of the module's `.hie` file under `TEMP_DIR`, and `.stan.toml` and `.cabal`
+/// files under `GIT_ROOT`
+pub const STAN_PROFILE: &str = r#"
+(version 1)
+(deny default)
+(allow process-exec*
+  (regex #"^/nix/store/[a-z0-9]+-stan-[^/]+/bin/stan$"))
+(allow file-read*)
+(deny file-read*
+  (subpath "/Users"))
+(allow file-read*
+  (subpath (param "GIT_ROOT"))
+  (subpath (param "TEMP_DIR")))
+"#;
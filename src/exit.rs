@@ -0,0 +1,40 @@
+use derive_more::Display;
+use std::process::ExitCode;
+
+/// Everything is clean
+pub const CLEAN: u8 = 0;
+
+/// Code needs fixing: there are findings, or files that would be changed
+pub const FINDINGS: u8 = 1;
+
+/// `be` itself, or a tool it runs, failed
+pub const ERROR: u8 = 2;
+
+/// Stopped by Ctrl-C, like shells report for `SIGINT`
+pub const INTERRUPTED: u8 = 130;
+
+/// Returned as an error by commands which ran fine but found something that needs fixing, so `be`
+/// exits with `FINDINGS` instead of `ERROR`
+#[derive(Debug, Display)]
+pub struct Findings(pub String);
+
+impl std::error::Error for Findings {}
+
+/// Returned when Ctrl-C stops a run partway, describing how far it got
+#[derive(Debug, Display)]
+pub struct Interrupted(pub String);
+
+impl std::error::Error for Interrupted {}
+
+pub fn code(result: &color_eyre::Result<()>) -> u8 {
+    match result {
+        Ok(()) => CLEAN,
+        Err(error) if error.downcast_ref::<Findings>().is_some() => FINDINGS,
+        Err(error) if error.downcast_ref::<Interrupted>().is_some() => INTERRUPTED,
+        Err(_) => ERROR,
+    }
+}
+
+pub fn exit_code(result: &color_eyre::Result<()>) -> ExitCode {
+    ExitCode::from(code(result))
+}
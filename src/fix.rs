@@ -0,0 +1,79 @@
+mod import_order;
+
+use crate::{
+    cli::{
+        fix::{Args, Command, HaskellArgs, HpackArgs},
+        generate,
+    },
+    config::Pipeline,
+    events::message,
+    generate::run_generate_hpack,
+    git, lint, queue,
+};
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use tracing_indicatif::indicatif_println;
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &Args) -> eyre::Result<()> {
+    match &args.command {
+        Command::Haskell(args) => run_fix_haskell(args).await,
+        Command::Hpack(args) => run_fix_hpack(args).await,
+        Command::ImportOrder(args) => import_order::run(args).await,
+    }
+}
+
+/// The same as `be generate hpack`, so `be fix` can fix everything `be lint` reports
+#[tracing::instrument(skip_all)]
+async fn run_fix_hpack(args: &HpackArgs) -> eyre::Result<()> {
+    run_generate_hpack(&generate::HpackArgs {
+        paths: args.paths.clone(),
+        packages: args.packages.clone(),
+        check: false,
+    })
+    .await
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn run_fix_haskell(args: &HaskellArgs) -> eyre::Result<()> {
+    let paths = if args.paths.is_empty() && args.packages.is_empty() {
+        git::changed_haskell_files(Pipeline::Lint).await?
+    } else {
+        git::haskell_files(&args.paths, &args.packages, Pipeline::Lint).await?
+    };
+
+    let handles = queue::spawn(paths, |path| async move {
+        let count = lint::fix_haskell(&path).await?;
+        if count > 0 {
+            indicatif_println!(
+                "{path}: applied {count} {hints}",
+                hints = if count == 1 { "hint" } else { "hints" },
+            );
+        }
+        eyre::Ok(count)
+    })
+    .await?;
+
+    let total_count = handles.len();
+    let mut fixed_count = 0;
+    let mut hint_count = 0;
+
+    for handle in handles {
+        let count = handle.await??;
+        if count > 0 {
+            fixed_count += 1;
+            hint_count += count;
+        }
+    }
+
+    message!(
+        "Applied {hint_count} {hints} in {fixed_count} of {total_count} Haskell {files}",
+        hint_count = hint_count.to_formatted_string(&Locale::en),
+        hints = if hint_count == 1 { "hint" } else { "hints" },
+        fixed_count = fixed_count.to_formatted_string(&Locale::en),
+        total_count = total_count.to_formatted_string(&Locale::en),
+        files = if total_count == 1 { "file" } else { "files" },
+    );
+
+    Ok(())
+}
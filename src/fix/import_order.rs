@@ -0,0 +1,217 @@
+use crate::{
+    cli::fix::ImportOrderArgs,
+    config::{ImportGroup, Pipeline},
+    context::cx,
+    events::message,
+    git,
+    io::{read_file, write_file_unless_changed},
+    lint::parse,
+    query::{Origin, Origins},
+    queue,
+};
+use bytes::Bytes;
+use camino::Utf8Path;
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use std::sync::Arc;
+use tracing_indicatif::indicatif_println;
+use tree_sitter::Node;
+
+/// An import and the comments above and beside it, which move with it
+struct Entry<'a> {
+    group: usize,
+    qualified: bool,
+    module: &'a str,
+    text: String,
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &ImportOrderArgs) -> eyre::Result<()> {
+    let paths = if args.paths.is_empty() && args.packages.is_empty() {
+        git::changed_haskell_files(Pipeline::Format).await?
+    } else {
+        git::haskell_files(&args.paths, &args.packages, Pipeline::Format).await?
+    };
+
+    let origins = Arc::new(Origins::load().await?);
+
+    let handles = queue::spawn(paths, |path| {
+        let origins = Arc::clone(&origins);
+        async move { reorder(&path, &origins).await }
+    })
+    .await?;
+
+    let total_count = handles.len();
+    let mut changed_count = 0;
+    let mut skipped_count = 0;
+
+    for handle in handles {
+        match handle.await?? {
+            Some(true) => changed_count += 1,
+            Some(false) => {}
+            None => skipped_count += 1,
+        }
+    }
+
+    message!(
+        "Reordered imports in {changed_count} of {total_count} Haskell {files}",
+        changed_count = changed_count.to_formatted_string(&Locale::en),
+        total_count = total_count.to_formatted_string(&Locale::en),
+        files = if total_count == 1 { "file" } else { "files" },
+    );
+
+    if skipped_count > 0 {
+        message!(
+            "Skipped {skipped_count} {files} with CPP or other code between imports",
+            skipped_count = skipped_count.to_formatted_string(&Locale::en),
+            files = if skipped_count == 1 { "file" } else { "files" },
+        );
+    }
+
+    Ok(())
+}
+
+/// Whether the imports were reordered, or `None` if they couldn't be. Each import's text is kept as
+/// is, so formatting with `fourmolu` before or after gives the same result.
+#[tracing::instrument(skip(origins), fields(indicatif.pb_show))]
+async fn reorder(path: &Utf8Path, origins: &Origins) -> eyre::Result<Option<bool>> {
+    let config = &cx().cache.config().await?.format.import_order;
+
+    let (bytes, input_hash) = read_file(path).await?;
+    let (source_code, tree) = parse(&bytes)?;
+
+    let Some(imports) = tree.root_node().child_by_field_name("imports") else {
+        return Ok(Some(false));
+    };
+
+    let groups = config.groups();
+    let group = |module: &str| {
+        let matching = groups.iter().position(|group| match group {
+            ImportGroup::Matching(matcher) => matcher.is_match(module),
+            _ => false,
+        });
+        let is_local = matches!(origins.resolve(module), Origin::Local(_));
+        let by_origin = groups.iter().position(|group| match group {
+            ImportGroup::Local => is_local,
+            ImportGroup::External => !is_local,
+            ImportGroup::Matching(_) => false,
+        });
+        // Modules no group is for go in a group of their own, after the rest
+        matching.or(by_origin).unwrap_or(groups.len())
+    };
+
+    let mut cursor = imports.walk();
+    let nodes: Vec<Node> = imports
+        .children_by_field_name("import", &mut cursor)
+        .collect();
+    let (Some(first), Some(last)) = (nodes.first(), nodes.last()) else {
+        return Ok(Some(false));
+    };
+
+    let start = line_start(&source_code, first.start_byte());
+    let end = line_end(&source_code, last.end_byte());
+
+    let Some(mut entries) = entries(&source_code, start, &nodes, group) else {
+        return Ok(None);
+    };
+
+    let qualified_last = config.qualified_last;
+    entries.sort_by(|a, b| {
+        (a.group, qualified_last && a.qualified, a.module, &a.text).cmp(&(
+            b.group,
+            qualified_last && b.qualified,
+            b.module,
+            &b.text,
+        ))
+    });
+
+    let mut output = String::with_capacity(source_code.len());
+    output.push_str(&source_code[..start]);
+    for (i, entry) in entries.iter().enumerate() {
+        if i > 0 && entries[i - 1].group != entry.group {
+            output.push('\n');
+        }
+        output.push_str(&entry.text);
+        output.push('\n');
+    }
+    output.push_str(&source_code[end..]);
+
+    if output == source_code {
+        return Ok(Some(false));
+    }
+
+    let changed = write_file_unless_changed(path, Bytes::from(output), input_hash).await?;
+    if changed {
+        indicatif_println!("{path}: reordered imports");
+    }
+
+    Ok(Some(changed))
+}
+
+/// Each import with its comments, or `None` if something else is between them
+fn entries<'a>(
+    source_code: &'a str,
+    start: usize,
+    nodes: &[Node],
+    group: impl Fn(&str) -> usize,
+) -> Option<Vec<Entry<'a>>> {
+    let mut entries = Vec::with_capacity(nodes.len());
+    let mut position = start;
+    for node in nodes {
+        let comments = leading_comments(source_code, position, node.start_byte())?;
+        let entry_end = line_end(source_code, node.end_byte());
+        let after = source_code[node.end_byte()..entry_end].trim();
+        if !(after.is_empty() || after.starts_with("--")) {
+            return None;
+        }
+        let module = node
+            .child_by_field_name("module")
+            .and_then(|module| source_code.get(module.byte_range()))?;
+        let import = &source_code[line_start(source_code, node.start_byte())..entry_end];
+        let mut lines = comments;
+        lines.push(import.trim_end_matches('\n'));
+        let mut cursor = node.walk();
+        entries.push(Entry {
+            group: group(module),
+            qualified: node
+                .children(&mut cursor)
+                .any(|child| child.kind() == "qualified"),
+            module,
+            text: lines.join("\n"),
+        });
+        position = entry_end;
+    }
+    Some(entries)
+}
+
+/// Comment lines between the previous import and this one, which move with it, or `None` if
+/// there's anything else there, like CPP
+fn leading_comments(source_code: &str, from: usize, import_start: usize) -> Option<Vec<&str>> {
+    let import_line = line_start(source_code, import_start);
+    if !source_code[import_line..import_start].trim().is_empty() {
+        return None;
+    }
+    let mut comments = Vec::new();
+    for line in source_code[from..import_line].lines() {
+        let trimmed = line.trim();
+        // Blank lines separate groups, which are made again
+        if trimmed.is_empty() {
+            continue;
+        }
+        if !trimmed.starts_with("--") {
+            return None;
+        }
+        comments.push(line);
+    }
+    Some(comments)
+}
+
+fn line_start(source_code: &str, offset: usize) -> usize {
+    source_code[..offset].rfind('\n').map_or(0, |i| i + 1)
+}
+
+fn line_end(source_code: &str, offset: usize) -> usize {
+    source_code[offset..]
+        .find('\n')
+        .map_or(source_code.len(), |i| offset + i + 1)
+}
@@ -1,86 +1,474 @@
+mod code_blocks;
+mod pool;
+mod syntax;
+
 use crate::{
-    cli::format::{Args, Command, HaskellArgs, NixArgs},
+    cli::format::{
+        Args, CabalArgs, CodeBlocksArgs, Command, DhallArgs, Format, HaskellArgs, NixArgs,
+        ShellArgs,
+    },
+    config::{Language, Pipeline},
     context::cx,
-    exec, git,
-    io::{read_file, read_stdin, write_file, write_stdout},
-    utils::flatten,
+    diff,
+    events::message,
+    exec,
+    exit::Findings,
+    git,
+    github::Annotation,
+    io::{print_stdout, read_file, read_stdin, write_file_unless_changed, write_stdout},
+    line_ending::LineEnding,
+    packages, queue,
+    utils::table,
+    watch,
 };
 use bytes::Bytes;
 use camino::{Utf8Path, Utf8PathBuf};
 use color_eyre::eyre;
 use num_format::{Locale, ToFormattedString as _};
-use std::{os::unix::process::ExitStatusExt as _, process::Stdio};
-use tokio::{fs, io::AsyncWriteExt as _, process};
-use tracing_indicatif::indicatif_eprintln;
+use std::{os::unix::process::ExitStatusExt as _, process::Stdio, sync::Mutex};
+use tokio::fs;
+use twox_hash::XxHash3_64;
+
+/// What to do with files that aren't formatted
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mode {
+    /// Write the formatted contents
+    Write,
+    /// List the files
+    Check,
+    /// Print what formatting would change
+    Diff,
+    /// Print GitHub Actions annotations for the files
+    Github,
+}
+
+impl Mode {
+    fn new(check: bool, diff: bool, format: Format) -> Self {
+        if diff {
+            Self::Diff
+        } else if format == Format::Github {
+            Self::Github
+        } else if check {
+            Self::Check
+        } else {
+            Self::Write
+        }
+    }
+}
+
+/// Files looked at and what to print for unformatted ones, which is printed once every file is
+/// done so the output doesn't depend on which files finish first
+struct Report {
+    file_count: usize,
+    /// Paths and what to print for them
+    unformatted: Vec<(String, String)>,
+}
+
+impl Report {
+    const fn new() -> Self {
+        Self {
+            file_count: 0,
+            unformatted: Vec::new(),
+        }
+    }
+}
+
+static REPORT: Mutex<Report> = Mutex::new(Report::new());
+
+/// Empties `REPORT` when dropped, so a run which fails partway doesn't leave files in the report
+/// for the next run in `--watch` or `be daemon`
+struct ResetReport;
+
+impl Drop for ResetReport {
+    fn drop(&mut self) {
+        if let Ok(mut report) = REPORT.lock() {
+            *report = Report::new();
+        }
+    }
+}
+
+/// Count files looked at by one language's formatter
+fn report_files(count: usize) {
+    REPORT.lock().unwrap().file_count += count;
+}
+
+/// Keep what to print for a file that isn't formatted
+pub fn report_unformatted(mode: Mode, path: &str, input: &[u8], output: &[u8]) {
+    let text = match mode {
+        Mode::Write => return,
+        Mode::Check => String::from(path),
+        Mode::Diff => {
+            let diff = diff::unified(path, input, output);
+            String::from(diff.trim_end_matches('\n'))
+        }
+        Mode::Github => {
+            // Point at the first line formatting would change
+            let line = input
+                .split(|byte| *byte == b'\n')
+                .zip(output.split(|byte| *byte == b'\n'))
+                .take_while(|(input, output)| input == output)
+                .count()
+                + 1;
+            let annotation = Annotation {
+                level: "error",
+                file: path,
+                line: Some(line),
+                column: None,
+                end_line: None,
+                end_column: None,
+                title: "Unformatted file",
+                message: "This file isn't formatted, run `be format` to fix",
+            };
+            annotation.to_string()
+        }
+    };
+    REPORT
+        .lock()
+        .unwrap()
+        .unformatted
+        .push((String::from(path), text));
+}
 
 #[tracing::instrument(skip_all)]
 pub async fn run(args: &Args) -> eyre::Result<()> {
-    if let Some(Command::Haskell(args)) = &args.command {
-        run_format_haskell(args).await?;
-        return Ok(());
+    if args.watch {
+        return watch::run(|| Box::pin(run_format(args))).await;
+    }
+    run_format(args).await
+}
+
+#[tracing::instrument(skip_all)]
+async fn run_format(args: &Args) -> eyre::Result<()> {
+    let _reset_report = ResetReport;
+
+    let (mode, unformatted_count) = match &args.command {
+        Some(Command::Haskell(args)) => (
+            Mode::new(args.check, args.diff, args.format),
+            run_format_haskell(args).await?,
+        ),
+        Some(Command::Nix(args)) => (
+            Mode::new(args.check, args.diff, args.format),
+            run_format_nix(args).await?,
+        ),
+        Some(Command::Dhall(args)) => (
+            Mode::new(args.check, args.diff, args.format),
+            run_format_dhall(args).await?,
+        ),
+        Some(Command::Shell(args)) => (
+            Mode::new(args.check, args.diff, args.format),
+            run_format_shell(args).await?,
+        ),
+        Some(Command::Cabal(args)) => (
+            Mode::new(args.check, args.diff, args.format),
+            run_format_cabal(args).await?,
+        ),
+        Some(Command::CodeBlocks(args)) => (
+            Mode::new(args.check, args.diff, args.format),
+            run_format_code_blocks(args).await?,
+        ),
+        None => (
+            Mode::new(args.check, args.diff, args.format),
+            match &args.stdin_filepath {
+                Some(path) => run_format_stdin(args, path).await?,
+                None => run_format_all(args).await?,
+            },
+        ),
+    };
+
+    let Report {
+        file_count,
+        mut unformatted,
+    } = std::mem::replace(&mut *REPORT.lock().unwrap(), Report::new());
+
+    unformatted.sort();
+
+    for (_, text) in &unformatted {
+        print_stdout(text);
+    }
+
+    // Each language prints its own counts, so only total them up when there are several
+    if args.command.is_none() && args.stdin_filepath.is_none() {
+        let changed = if mode == Mode::Write {
+            "Formatted"
+        } else {
+            "Unformatted"
+        };
+        message!(
+            "{}",
+            table(&[("Files", file_count), (changed, unformatted_count)])
+        );
+    }
+
+    if mode != Mode::Write && unformatted_count > 0 {
+        return Err(Findings(format!(
+            "{unformatted_count} {files} not formatted, run `be format` to fix",
+            unformatted_count = unformatted_count.to_formatted_string(&Locale::en),
+            files = if unformatted_count == 1 {
+                "file is"
+            } else {
+                "files are"
+            },
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Format code piped to `stdin` with the formatter for the path it would have, so editors can use
+/// one command for every language
+#[tracing::instrument(skip(args))]
+async fn run_format_stdin(args: &Args, path: &Utf8Path) -> eyre::Result<usize> {
+    let cx = cx();
+
+    let config = cx.cache.config().await?;
+
+    let git_root = cx.cache.git_root().await?;
+
+    let absolute_path = Utf8PathBuf::try_from(std::path::absolute(path)?)?;
+    let path = absolute_path
+        .strip_prefix(git_root)
+        .unwrap_or(&absolute_path);
+
+    // Like `prettier`, pass excluded files through unchanged
+    if !config.files.is_included(path) {
+        tracing::debug!("Not formatting `{path}`, since it's excluded");
+        let (input_bytes, _input_hash) = read_stdin().await?;
+        write_stdout(input_bytes).await?;
+        return Ok(0);
     }
 
-    if let Some(Command::Nix(args)) = &args.command {
-        run_format_nix(args).await?;
-        return Ok(());
+    let language = [
+        Language::Haskell,
+        Language::Nix,
+        Language::Dhall,
+        Language::Shell,
+    ]
+    .into_iter()
+    .find(|language| config.is_file_type(*language, Pipeline::Format, path));
+
+    match language {
+        Some(Language::Haskell) => {
+            run_format_haskell(&HaskellArgs {
+                paths: vec![],
+                packages: vec![],
+                stdin: true,
+                start_line: None,
+                end_line: None,
+                check: args.check,
+                diff: args.diff,
+                format: args.format,
+            })
+            .await
+        }
+        Some(Language::Nix) => {
+            run_format_nix(&NixArgs {
+                paths: vec![],
+                packages: vec![],
+                stdin: true,
+                check: args.check,
+                diff: args.diff,
+                format: args.format,
+            })
+            .await
+        }
+        Some(Language::Dhall) => {
+            run_format_dhall(&DhallArgs {
+                paths: vec![],
+                packages: vec![],
+                stdin: true,
+                check: args.check,
+                diff: args.diff,
+                format: args.format,
+            })
+            .await
+        }
+        Some(Language::Shell) => {
+            run_format_shell(&ShellArgs {
+                paths: vec![],
+                packages: vec![],
+                stdin: true,
+                check: args.check,
+                diff: args.diff,
+                format: args.format,
+            })
+            .await
+        }
+        None if path.extension() == Some("cabal") => {
+            run_format_cabal(&CabalArgs {
+                paths: vec![],
+                packages: vec![],
+                stdin: true,
+                check: args.check,
+                diff: args.diff,
+                format: args.format,
+            })
+            .await
+        }
+        None => eyre::bail!("`be` doesn't know how to format `{path}`"),
     }
+}
 
-    let haskell = tokio::spawn(async {
+/// Format every language. Returns the number of files formatted, or which would be formatted when
+/// checking or diffing.
+#[tracing::instrument(skip_all)]
+async fn run_format_all(args: &Args) -> eyre::Result<usize> {
+    let config = cx().cache.config().await?;
+
+    let haskell = config.is_tool_enabled("fourmolu").then(|| {
         let args = HaskellArgs {
             paths: vec![],
+            packages: args.packages.clone(),
             stdin: false,
+            start_line: None,
+            end_line: None,
+            check: args.check,
+            diff: args.diff,
+            format: args.format,
         };
-        run_format_haskell(&args).await
+        tokio::spawn(async move { run_format_haskell(&args).await })
     });
 
-    let nix = tokio::spawn(async {
-        let args = NixArgs {
+    let nix = config
+        .is_tool_enabled(config.format.nix_formatter.binary())
+        .then(|| {
+            let args = NixArgs {
+                paths: vec![],
+                packages: args.packages.clone(),
+                stdin: false,
+                check: args.check,
+                diff: args.diff,
+                format: args.format,
+            };
+            tokio::spawn(async move { run_format_nix(&args).await })
+        });
+
+    let dhall = config.is_tool_enabled("dhall").then(|| {
+        let args = DhallArgs {
             paths: vec![],
+            packages: args.packages.clone(),
             stdin: false,
+            check: args.check,
+            diff: args.diff,
+            format: args.format,
         };
-        run_format_nix(&args).await
+        tokio::spawn(async move { run_format_dhall(&args).await })
     });
 
-    tokio::try_join!(flatten(haskell), flatten(nix))?;
+    // Formatting shell scripts is opt-in by installing `shfmt`
+    let shell = if config.is_tool_enabled("shfmt") && cx().cache.which("shfmt").await.is_ok() {
+        let args = ShellArgs {
+            paths: vec![],
+            packages: args.packages.clone(),
+            stdin: false,
+            check: args.check,
+            diff: args.diff,
+            format: args.format,
+        };
+        Some(tokio::spawn(async move { run_format_shell(&args).await }))
+    } else {
+        tracing::debug!("Not formatting shell scripts, since `shfmt` isn't installed");
+        None
+    };
 
-    Ok(())
+    // Formatting `.cabal` files is opt-in by installing `cabal-gild` or `cabal-fmt`
+    let cabal = if cx().cache.cabal_formatter().await.is_ok() {
+        let args = CabalArgs {
+            paths: vec![],
+            packages: args.packages.clone(),
+            stdin: false,
+            check: args.check,
+            diff: args.diff,
+            format: args.format,
+        };
+        Some(tokio::spawn(async move { run_format_cabal(&args).await }))
+    } else {
+        tracing::debug!(
+            "Not formatting `.cabal` files, since neither `cabal-gild` nor `cabal-fmt` is installed"
+        );
+        None
+    };
+
+    let mut count = 0;
+
+    for handle in [haskell, nix, dhall, shell, cabal].into_iter().flatten() {
+        count += handle.await??;
+    }
+
+    // After the other formatters, since this also writes Haskell files
+    if config.format.code_blocks && config.is_tool_enabled("fourmolu") {
+        count += run_format_code_blocks(&CodeBlocksArgs {
+            paths: vec![],
+            packages: args.packages.clone(),
+            check: args.check,
+            diff: args.diff,
+            format: args.format,
+        })
+        .await?;
+    }
+
+    Ok(count)
 }
 
 #[tracing::instrument(skip_all)]
-pub async fn run_format_haskell(args: &HaskellArgs) -> eyre::Result<()> {
+pub async fn run_format_haskell(args: &HaskellArgs) -> eyre::Result<usize> {
     let cx = cx();
 
     if args.stdin {
         let (input_bytes, input_hash) = read_stdin().await?;
 
-        let output_bytes = if cx.cache.is_haskell_formatted(input_hash).await? {
+        let line_ending = cx.cache.config().await?.format.line_ending(&input_bytes);
+
+        let region = Region {
+            start_line: args.start_line,
+            end_line: args.end_line,
+        };
+
+        let output_bytes = if region.start_line.is_some() || region.end_line.is_some() {
+            tracing::trace!("Formatting region");
+            formatted_haskell_region(input_bytes.clone(), region).await?
+        } else if line_ending.matches(&input_bytes)
+            && cx.cache.is_haskell_formatted(input_hash).await?
+        {
             tracing::trace!("Skipping format");
-            input_bytes
+            input_bytes.clone()
         } else {
             tracing::trace!("Formatting");
-            fourmolu(None, input_bytes).await?
+            formatted_haskell(None, input_bytes.clone()).await?
         };
 
+        let mode = Mode::new(args.check, args.diff, args.format);
+        if mode != Mode::Write {
+            let is_formatted = output_bytes == input_bytes;
+            if !is_formatted {
+                report_unformatted(mode, "<stdin>", &input_bytes, &output_bytes);
+            }
+            return Ok(usize::from(!is_formatted));
+        }
+
         write_stdout(output_bytes).await?;
 
-        return Ok(());
+        return Ok(0);
     }
 
-    let changed_files = git::changed_haskell_files().await?;
-
-    let paths = if args.paths.is_empty() {
-        changed_files
+    let paths = if args.paths.is_empty() && args.packages.is_empty() {
+        git::changed_haskell_files(Pipeline::Format).await?
     } else {
-        args.paths.clone()
+        git::haskell_files(&args.paths, &args.packages, Pipeline::Format).await?
     };
 
-    let mut handles = Vec::new();
+    let mode = Mode::new(args.check, args.diff, args.format);
 
-    for path in paths {
-        handles.push(tokio::spawn(async move { format_haskell(&path).await }));
-    }
+    let handles = queue::spawn(
+        paths,
+        |path| async move { format_haskell(&path, mode).await },
+    )
+    .await?;
 
     let total_count = handles.len();
+
+    report_files(total_count);
+
     let mut formatted_count = 0;
 
     for handle in handles {
@@ -89,55 +477,151 @@ pub async fn run_format_haskell(args: &HaskellArgs) -> eyre::Result<()> {
         }
     }
 
-    indicatif_eprintln!(
-        "Formatted {formatted_count} of {total_count} Haskell {files}",
-        formatted_count = formatted_count.to_formatted_string(&Locale::en),
-        total_count = total_count.to_formatted_string(&Locale::en),
-        files = if total_count == 1 { "file" } else { "files" },
-    );
+    if mode == Mode::Write {
+        message!(
+            "Formatted {formatted_count} of {total_count} Haskell {files}",
+            formatted_count = formatted_count.to_formatted_string(&Locale::en),
+            total_count = total_count.to_formatted_string(&Locale::en),
+            files = if total_count == 1 { "file" } else { "files" },
+        );
+    } else {
+        message!(
+            "Checked {total_count} Haskell {files} ({formatted_count} unformatted)",
+            total_count = total_count.to_formatted_string(&Locale::en),
+            files = if total_count == 1 { "file" } else { "files" },
+            formatted_count = formatted_count.to_formatted_string(&Locale::en),
+        );
+    }
 
-    Ok(())
+    Ok(formatted_count)
 }
 
 #[tracing::instrument(fields(indicatif.pb_show))]
-async fn format_haskell(path: &Utf8Path) -> eyre::Result<Option<bool>> {
+async fn format_haskell(path: &Utf8Path, mode: Mode) -> eyre::Result<Option<bool>> {
     let cx = cx();
 
     let (input_bytes, input_hash) = read_file(path).await?;
 
-    if cx.cache.is_haskell_formatted(input_hash).await? {
+    let line_ending = cx.cache.config().await?.format.line_ending(&input_bytes);
+
+    if !line_ending.matches(&input_bytes) {
+        tracing::debug!(?line_ending, "Normalizing line endings");
+    } else if cx.cache.is_haskell_formatted(input_hash).await? {
         tracing::trace!("Skipping format");
         return Ok(Some(false));
     }
 
     tracing::trace!("Formatting");
 
-    let output_bytes = fourmolu(Some(path), input_bytes.clone()).await?;
+    let (output_bytes, duration) =
+        exec::timed(Box::pin(formatted_haskell(Some(path), input_bytes.clone()))).await?;
+
+    cx.cache
+        .record_duration("fourmolu", input_hash, path, duration)
+        .await?;
+
+    let version = cx.cache.fourmolu_version().await?;
+    if syntax::is_broken(
+        Language::Haskell,
+        path,
+        "fourmolu",
+        version,
+        &input_bytes,
+        &output_bytes,
+    )
+    .await?
+    {
+        return Ok(Some(false));
+    }
 
-    cx.cache.mark_haskell_formatted(input_hash).await?;
+    cx.cache
+        .mark_haskell_formatted(XxHash3_64::oneshot(&output_bytes))
+        .await?;
 
     if input_bytes == output_bytes {
         tracing::trace!("Skipping write");
         return Ok(Some(false));
     }
 
+    if mode != Mode::Write {
+        report_unformatted(mode, path.as_str(), &input_bytes, &output_bytes);
+        return Ok(Some(true));
+    }
+
     tracing::trace!("Writing");
 
-    write_file(path, output_bytes).await?;
+    let written = write_file_unless_changed(path, output_bytes, input_hash).await?;
+
+    Ok(Some(written))
+}
+
+/// Format with `fourmolu`, keeping the line endings required by the config
+#[tracing::instrument(skip(input_bytes))]
+pub async fn formatted_haskell(path: Option<&Utf8Path>, input_bytes: Bytes) -> eyre::Result<Bytes> {
+    let cx = cx();
+    let line_ending = cx.cache.config().await?.format.line_ending(&input_bytes);
+    // Formatters may not preserve CRLF, so give them LF and restore the line endings after
+    let input_bytes = LineEnding::Lf.apply(input_bytes);
+    let input_hash = XxHash3_64::oneshot(&input_bytes);
+    let output_bytes =
+        if let Some(output_bytes) = cx.cache.haskell_formatted_output(input_hash).await? {
+            tracing::trace!("Reusing cached output");
+            output_bytes
+        } else {
+            let output_bytes = fourmolu(path, input_bytes.clone()).await?;
+            if output_bytes != input_bytes {
+                cx.cache
+                    .cache_haskell_formatted_output(input_hash, &output_bytes)
+                    .await?;
+            }
+            output_bytes
+        };
+    Ok(line_ending.apply(output_bytes))
+}
 
-    Ok(Some(true))
+/// Like `formatted_haskell`, but only formatting some lines. Not cached, since the output depends on
+/// the region as well as the contents.
+async fn formatted_haskell_region(input_bytes: Bytes, region: Region) -> eyre::Result<Bytes> {
+    let line_ending = cx().cache.config().await?.format.line_ending(&input_bytes);
+    let input_bytes = LineEnding::Lf.apply(input_bytes);
+    let output_bytes = fourmolu_region(None, input_bytes, region).await?;
+    Ok(line_ending.apply(output_bytes))
 }
 
+/// Whether `fourmolu` would leave contents unchanged, reusing cached results
 #[tracing::instrument(skip(bytes))]
-async fn fourmolu(path: Option<&Utf8Path>, bytes: Bytes) -> eyre::Result<Bytes> {
+pub async fn is_haskell_formatted(bytes: Bytes) -> eyre::Result<bool> {
     let cx = cx();
+    let line_ending = cx.cache.config().await?.format.line_ending(&bytes);
+    if line_ending.matches(&bytes)
+        && cx
+            .cache
+            .is_haskell_formatted(XxHash3_64::oneshot(&bytes))
+            .await?
+    {
+        return Ok(true);
+    }
+    let output_bytes = formatted_haskell(None, bytes.clone()).await?;
+    cx.cache
+        .mark_haskell_formatted(XxHash3_64::oneshot(&output_bytes))
+        .await?;
+    Ok(bytes == output_bytes)
+}
 
-    let fourmolu = &cx.cache.which("fourmolu").await?;
+#[tracing::instrument(skip(bytes))]
+async fn fourmolu(path: Option<&Utf8Path>, bytes: Bytes) -> eyre::Result<Bytes> {
+    let config = cx().cache.config().await?;
+    match config.tool("fourmolu").and_then(|tool| tool.batch_size) {
+        Some(batch_size) if batch_size.get() > 1 => {
+            pool::fourmolu(path, bytes, batch_size.get()).await
+        }
+        _ => fourmolu_one(path, bytes).await,
+    }
+}
 
-    let path = match path {
-        Some(path) => Utf8PathBuf::try_from(fs::canonicalize(path).await?).unwrap(),
-        None => Utf8PathBuf::from("<stdin>"),
-    };
+/// Arguments shared by every way of running `fourmolu`
+async fn fourmolu_args() -> eyre::Result<Vec<String>> {
+    let cx = cx();
 
     let (config, _) = cx.cache.fourmolu_config().await?;
 
@@ -147,8 +631,6 @@ async fn fourmolu(path: Option<&Utf8Path>, bytes: Bytes) -> eyre::Result<Bytes>
 
     args.push(format!("--config={config}"));
     args.push(String::from("--no-cabal"));
-    args.push(format!("--stdin-input-file={path}"));
-    args.push(String::from("--mode=stdout"));
     args.push(String::from("--source-type=module"));
     args.push(String::from("--unsafe"));
     args.push(String::from("--quiet"));
@@ -157,21 +639,58 @@ async fn fourmolu(path: Option<&Utf8Path>, bytes: Bytes) -> eyre::Result<Bytes>
         args.push(format!("--ghc-opt=-X{extension}"));
     }
 
+    Ok(args)
+}
+
+async fn fourmolu_one(path: Option<&Utf8Path>, bytes: Bytes) -> eyre::Result<Bytes> {
+    fourmolu_region(path, bytes, Region::default()).await
+}
+
+/// Lines for `fourmolu` to format, counting from 1, where `None` means the start or end of the file
+#[derive(Clone, Copy, Debug, Default)]
+struct Region {
+    start_line: Option<usize>,
+    end_line: Option<usize>,
+}
+
+#[tracing::instrument(skip(bytes))]
+async fn fourmolu_region(
+    path: Option<&Utf8Path>,
+    bytes: Bytes,
+    region: Region,
+) -> eyre::Result<Bytes> {
+    let cx = cx();
+
+    let fourmolu = &cx.cache.which("fourmolu").await?;
+
+    let tool_permits = cx.tool_permits("fourmolu").await?;
+
+    let path = match path {
+        Some(path) => Utf8PathBuf::try_from(fs::canonicalize(path).await?).unwrap(),
+        None => Utf8PathBuf::from("<stdin>"),
+    };
+
+    let mut args = fourmolu_args().await?;
+
+    args.push(format!("--stdin-input-file={path}"));
+    args.push(String::from("--mode=stdout"));
+
+    if let Some(start_line) = region.start_line {
+        args.push(format!("--start-line={start_line}"));
+    }
+    if let Some(end_line) = region.end_line {
+        args.push(format!("--end-line={end_line}"));
+    }
+
+    let tool_permit = tool_permits.acquire().await?;
     let file_permit = cx.file_permits.acquire().await?;
     let process_permit = cx.process_permits.acquire().await?;
+    let memory_permit = cx.memory.acquire().await?;
 
-    let mut command = if cfg!(target_os = "macos") {
-        let mut command = process::Command::new("/usr/bin/sandbox-exec");
-        command.arg("-p");
-        command.arg(exec::FOURMOLU_PROFILE);
-        command.arg("--");
-        command.arg(fourmolu);
-        command
-    } else {
-        process::Command::new(fourmolu)
-    };
+    let mut command =
+        exec::sandboxed(Some("fourmolu"), exec::FOURMOLU_PROFILE, &[], fourmolu).await?;
 
-    let mut child = command
+    let child = command
         .args(args)
         .env_clear()
         .current_dir("/var/empty")
@@ -181,18 +700,12 @@ async fn fourmolu(path: Option<&Utf8Path>, bytes: Bytes) -> eyre::Result<Bytes>
         .stderr(Stdio::piped())
         .spawn()?;
 
-    let mut stdin = child.stdin.take().unwrap();
-
-    stdin.write_all(&bytes).await?;
-
-    stdin.flush().await?;
-
-    drop(stdin);
-
-    let output = child.wait_with_output().await?;
+    let output = exec::timeout("fourmolu", exec::output_with_stdin(child, &bytes)).await?;
 
+    drop(memory_permit);
     drop(process_permit);
     drop(file_permit);
+    drop(tool_permit);
 
     if !output.status.success() {
         if let Some(exit_code) = output.status.code() {
@@ -211,40 +724,112 @@ async fn fourmolu(path: Option<&Utf8Path>, bytes: Bytes) -> eyre::Result<Bytes>
 }
 
 #[tracing::instrument(skip_all)]
-pub async fn run_format_nix(args: &NixArgs) -> eyre::Result<()> {
+pub async fn run_format_code_blocks(args: &CodeBlocksArgs) -> eyre::Result<usize> {
+    let cx = cx();
+
+    let config = cx.cache.config().await?;
+
+    let mut paths = if args.paths.is_empty() && args.packages.is_empty() {
+        git::changed_files(&[]).await?
+    } else {
+        let mut paths = args.paths.clone();
+        if !args.packages.is_empty() {
+            let git_root = cx.cache.git_root().await?;
+            let packages = cx.cache.packages().await?;
+            for dir in packages::package_dirs(packages, &args.packages)? {
+                paths.push(git_root.join(dir));
+            }
+        }
+        git::files(&paths).await?
+    };
+
+    paths.retain(|path| {
+        code_blocks::is_markdown(path)
+            || config.is_file_type(Language::Haskell, Pipeline::Format, path)
+    });
+
+    let mode = Mode::new(args.check, args.diff, args.format);
+
+    let handles = queue::spawn(paths, |path| async move {
+        code_blocks::format(&path, mode).await
+    })
+    .await?;
+
+    // Not counted in the report, since these are mostly Haskell files counted already
+    let total_count = handles.len();
+    let mut formatted_count = 0;
+
+    for handle in handles {
+        if handle.await?? {
+            formatted_count += 1;
+        }
+    }
+
+    if mode == Mode::Write {
+        message!(
+            "Formatted code blocks in {formatted_count} of {total_count} {files}",
+            formatted_count = formatted_count.to_formatted_string(&Locale::en),
+            total_count = total_count.to_formatted_string(&Locale::en),
+            files = if total_count == 1 { "file" } else { "files" },
+        );
+    } else {
+        message!(
+            "Checked code blocks in {total_count} {files} ({formatted_count} unformatted)",
+            total_count = total_count.to_formatted_string(&Locale::en),
+            files = if total_count == 1 { "file" } else { "files" },
+            formatted_count = formatted_count.to_formatted_string(&Locale::en),
+        );
+    }
+
+    Ok(formatted_count)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn run_format_nix(args: &NixArgs) -> eyre::Result<usize> {
     let cx = cx();
 
     if args.stdin {
         let (input_bytes, input_hash) = read_stdin().await?;
 
-        let output_bytes = if cx.cache.is_nix_formatted(input_hash).await? {
-            tracing::trace!("Skipping format");
-            input_bytes
-        } else {
-            tracing::trace!("Formatting");
-            nixfmt(None, input_bytes).await?
-        };
+        let line_ending = cx.cache.config().await?.format.line_ending(&input_bytes);
+
+        let output_bytes =
+            if line_ending.matches(&input_bytes) && cx.cache.is_nix_formatted(input_hash).await? {
+                tracing::trace!("Skipping format");
+                input_bytes.clone()
+            } else {
+                tracing::trace!("Formatting");
+                formatted_nix(None, input_bytes.clone()).await?
+            };
+
+        let mode = Mode::new(args.check, args.diff, args.format);
+        if mode != Mode::Write {
+            let is_formatted = output_bytes == input_bytes;
+            if !is_formatted {
+                report_unformatted(mode, "<stdin>", &input_bytes, &output_bytes);
+            }
+            return Ok(usize::from(!is_formatted));
+        }
 
         write_stdout(output_bytes).await?;
 
-        return Ok(());
+        return Ok(0);
     }
 
-    let changed_files = git::changed_nix_files().await?;
-
-    let paths = if args.paths.is_empty() {
-        changed_files
+    let paths = if args.paths.is_empty() && args.packages.is_empty() {
+        git::changed_nix_files(Pipeline::Format).await?
     } else {
-        args.paths.clone()
+        git::nix_files(&args.paths, &args.packages, Pipeline::Format).await?
     };
 
-    let mut handles = Vec::new();
+    let mode = Mode::new(args.check, args.diff, args.format);
 
-    for path in paths {
-        handles.push(tokio::spawn(async move { format_nix(&path).await }));
-    }
+    let handles = queue::spawn(paths, |path| async move { format_nix(&path, mode).await }).await?;
 
     let total_count = handles.len();
+
+    report_files(total_count);
+
     let mut formatted_count = 0;
 
     for handle in handles {
@@ -253,72 +838,156 @@ pub async fn run_format_nix(args: &NixArgs) -> eyre::Result<()> {
         }
     }
 
-    indicatif_eprintln!(
-        "Formatted {formatted_count} of {total_count} Nix {files}",
-        formatted_count = formatted_count.to_formatted_string(&Locale::en),
-        total_count = total_count.to_formatted_string(&Locale::en),
-        files = if total_count == 1 { "file" } else { "files" },
-    );
+    if mode == Mode::Write {
+        message!(
+            "Formatted {formatted_count} of {total_count} Nix {files}",
+            formatted_count = formatted_count.to_formatted_string(&Locale::en),
+            total_count = total_count.to_formatted_string(&Locale::en),
+            files = if total_count == 1 { "file" } else { "files" },
+        );
+    } else {
+        message!(
+            "Checked {total_count} Nix {files} ({formatted_count} unformatted)",
+            total_count = total_count.to_formatted_string(&Locale::en),
+            files = if total_count == 1 { "file" } else { "files" },
+            formatted_count = formatted_count.to_formatted_string(&Locale::en),
+        );
+    }
 
-    Ok(())
+    Ok(formatted_count)
 }
 
 #[tracing::instrument(fields(indicatif.pb_show))]
-async fn format_nix(path: &Utf8Path) -> eyre::Result<Option<bool>> {
+async fn format_nix(path: &Utf8Path, mode: Mode) -> eyre::Result<Option<bool>> {
     let cx = cx();
 
     let (input_bytes, input_hash) = read_file(path).await?;
 
-    if cx.cache.is_nix_formatted(input_hash).await? {
+    let line_ending = cx.cache.config().await?.format.line_ending(&input_bytes);
+
+    if !line_ending.matches(&input_bytes) {
+        tracing::debug!(?line_ending, "Normalizing line endings");
+    } else if cx.cache.is_nix_formatted(input_hash).await? {
         tracing::trace!("Skipping format");
         return Ok(Some(false));
     }
 
     tracing::trace!("Formatting");
 
-    let output_bytes = nixfmt(Some(path), input_bytes.clone()).await?;
+    let (output_bytes, duration) =
+        exec::timed(Box::pin(formatted_nix(Some(path), input_bytes.clone()))).await?;
+
+    let formatter = cx.cache.config().await?.format.nix_formatter.binary();
+
+    cx.cache
+        .record_duration(formatter, input_hash, path, duration)
+        .await?;
+
+    let version = cx.cache.nix_formatter_version().await?;
+    if syntax::is_broken(
+        Language::Nix,
+        path,
+        formatter,
+        version,
+        &input_bytes,
+        &output_bytes,
+    )
+    .await?
+    {
+        return Ok(Some(false));
+    }
 
-    cx.cache.mark_nix_formatted(input_hash).await?;
+    cx.cache
+        .mark_nix_formatted(XxHash3_64::oneshot(&output_bytes))
+        .await?;
 
     if input_bytes == output_bytes {
         tracing::trace!("Skipping write");
         return Ok(Some(false));
     }
 
+    if mode != Mode::Write {
+        report_unformatted(mode, path.as_str(), &input_bytes, &output_bytes);
+        return Ok(Some(true));
+    }
+
     tracing::trace!("Writing");
 
-    write_file(path, output_bytes).await?;
+    let written = write_file_unless_changed(path, output_bytes, input_hash).await?;
+
+    Ok(Some(written))
+}
+
+/// Format with the configured Nix formatter, keeping the line endings required by the config
+#[tracing::instrument(skip(input_bytes))]
+pub async fn formatted_nix(path: Option<&Utf8Path>, input_bytes: Bytes) -> eyre::Result<Bytes> {
+    let cx = cx();
+    let line_ending = cx.cache.config().await?.format.line_ending(&input_bytes);
+    // Formatters may not preserve CRLF, so give them LF and restore the line endings after
+    let input_bytes = LineEnding::Lf.apply(input_bytes);
+    let input_hash = XxHash3_64::oneshot(&input_bytes);
+    let output_bytes = if let Some(output_bytes) = cx.cache.nix_formatted_output(input_hash).await?
+    {
+        tracing::trace!("Reusing cached output");
+        output_bytes
+    } else {
+        let output_bytes = nix_formatter(path, input_bytes.clone()).await?;
+        if output_bytes != input_bytes {
+            cx.cache
+                .cache_nix_formatted_output(input_hash, &output_bytes)
+                .await?;
+        }
+        output_bytes
+    };
+    Ok(line_ending.apply(output_bytes))
+}
 
-    Ok(Some(true))
+/// Whether the configured Nix formatter would leave contents unchanged, reusing cached results
+#[tracing::instrument(skip(bytes))]
+pub async fn is_nix_formatted(bytes: Bytes) -> eyre::Result<bool> {
+    let cx = cx();
+    let line_ending = cx.cache.config().await?.format.line_ending(&bytes);
+    if line_ending.matches(&bytes)
+        && cx
+            .cache
+            .is_nix_formatted(XxHash3_64::oneshot(&bytes))
+            .await?
+    {
+        return Ok(true);
+    }
+    let output_bytes = formatted_nix(None, bytes.clone()).await?;
+    cx.cache
+        .mark_nix_formatted(XxHash3_64::oneshot(&output_bytes))
+        .await?;
+    Ok(bytes == output_bytes)
 }
 
 #[tracing::instrument(skip(bytes))]
-async fn nixfmt(path: Option<&Utf8Path>, bytes: Bytes) -> eyre::Result<Bytes> {
+async fn nix_formatter(path: Option<&Utf8Path>, bytes: Bytes) -> eyre::Result<Bytes> {
     let cx = cx();
 
-    let nixfmt = &cx.cache.which("nixfmt").await?;
+    let formatter = cx.cache.config().await?.format.nix_formatter;
+
+    let name = formatter.binary();
+
+    let binary = &cx.cache.which(name).await?;
+
+    let tool_permits = cx.tool_permits(name).await?;
 
     let path = match path {
         Some(path) => Utf8PathBuf::try_from(fs::canonicalize(path).await?).unwrap(),
         None => Utf8PathBuf::from("<stdin>"),
     };
 
+    let tool_permit = tool_permits.acquire().await?;
     let file_permit = cx.file_permits.acquire().await?;
     let process_permit = cx.process_permits.acquire().await?;
 
-    let mut command = if cfg!(target_os = "macos") {
-        let mut command = process::Command::new("/usr/bin/sandbox-exec");
-        command.arg("-p");
-        command.arg(exec::NIXFMT_PROFILE);
-        command.arg("--");
-        command.arg(nixfmt);
-        command
-    } else {
-        process::Command::new(nixfmt)
-    };
+    let mut command =
+        exec::sandboxed(Some(formatter.binary()), formatter.profile(), &[], binary).await?;
 
-    let mut child = command
-        .args([&format!("--filename={path}"), "-"])
+    let child = command
+        .args(formatter.args(&path))
         .env_clear()
         .current_dir("/var/empty")
         .kill_on_drop(true)
@@ -327,29 +996,612 @@ async fn nixfmt(path: Option<&Utf8Path>, bytes: Bytes) -> eyre::Result<Bytes> {
         .stderr(Stdio::piped())
         .spawn()?;
 
-    let mut stdin = child.stdin.take().unwrap();
+    let output = exec::timeout(formatter.binary(), exec::output_with_stdin(child, &bytes)).await?;
+
+    drop(process_permit);
+    drop(file_permit);
+    drop(tool_permit);
+
+    if !output.status.success() {
+        if let Some(exit_code) = output.status.code() {
+            eyre::bail!(
+                "`{name}` exited with code {exit_code}:\n{}",
+                String::from_utf8_lossy(&output.stderr),
+            );
+        } else if let Some(signal) = output.status.signal() {
+            eyre::bail!("`{name}` was terminated by signal {signal}");
+        } else {
+            eyre::bail!("`{name}` died of unknown causes");
+        }
+    }
+
+    Ok(Bytes::from(output.stdout))
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn run_format_dhall(args: &DhallArgs) -> eyre::Result<usize> {
+    let cx = cx();
+
+    if args.stdin {
+        let (input_bytes, input_hash) = read_stdin().await?;
+
+        let line_ending = cx.cache.config().await?.format.line_ending(&input_bytes);
+
+        let output_bytes = if line_ending.matches(&input_bytes)
+            && cx.cache.is_dhall_formatted(input_hash).await?
+        {
+            tracing::trace!("Skipping format");
+            input_bytes.clone()
+        } else {
+            tracing::trace!("Formatting");
+            formatted_dhall(input_bytes.clone()).await?
+        };
+
+        let mode = Mode::new(args.check, args.diff, args.format);
+        if mode != Mode::Write {
+            let is_formatted = output_bytes == input_bytes;
+            if !is_formatted {
+                report_unformatted(mode, "<stdin>", &input_bytes, &output_bytes);
+            }
+            return Ok(usize::from(!is_formatted));
+        }
+
+        write_stdout(output_bytes).await?;
+
+        return Ok(0);
+    }
+
+    let paths = if args.paths.is_empty() && args.packages.is_empty() {
+        git::changed_dhall_files(Pipeline::Format).await?
+    } else {
+        git::dhall_files(&args.paths, &args.packages, Pipeline::Format).await?
+    };
+
+    let mode = Mode::new(args.check, args.diff, args.format);
+
+    let handles =
+        queue::spawn(paths, |path| async move { format_dhall(&path, mode).await }).await?;
+
+    let total_count = handles.len();
+
+    report_files(total_count);
+
+    let mut formatted_count = 0;
 
-    stdin.write_all(&bytes).await?;
+    for handle in handles {
+        if let Some(true) = handle.await?? {
+            formatted_count += 1;
+        }
+    }
 
-    stdin.flush().await?;
+    if mode == Mode::Write {
+        message!(
+            "Formatted {formatted_count} of {total_count} Dhall {files}",
+            formatted_count = formatted_count.to_formatted_string(&Locale::en),
+            total_count = total_count.to_formatted_string(&Locale::en),
+            files = if total_count == 1 { "file" } else { "files" },
+        );
+    } else {
+        message!(
+            "Checked {total_count} Dhall {files} ({formatted_count} unformatted)",
+            total_count = total_count.to_formatted_string(&Locale::en),
+            files = if total_count == 1 { "file" } else { "files" },
+            formatted_count = formatted_count.to_formatted_string(&Locale::en),
+        );
+    }
 
-    drop(stdin);
+    Ok(formatted_count)
+}
+
+#[tracing::instrument(fields(indicatif.pb_show))]
+async fn format_dhall(path: &Utf8Path, mode: Mode) -> eyre::Result<Option<bool>> {
+    let cx = cx();
+
+    let (input_bytes, input_hash) = read_file(path).await?;
+
+    let line_ending = cx.cache.config().await?.format.line_ending(&input_bytes);
+
+    if !line_ending.matches(&input_bytes) {
+        tracing::debug!(?line_ending, "Normalizing line endings");
+    } else if cx.cache.is_dhall_formatted(input_hash).await? {
+        tracing::trace!("Skipping format");
+        return Ok(Some(false));
+    }
+
+    tracing::trace!("Formatting");
+
+    let (output_bytes, duration) = exec::timed(formatted_dhall(input_bytes.clone())).await?;
+
+    cx.cache
+        .record_duration("dhall", input_hash, path, duration)
+        .await?;
+
+    cx.cache
+        .mark_dhall_formatted(XxHash3_64::oneshot(&output_bytes))
+        .await?;
+
+    if input_bytes == output_bytes {
+        tracing::trace!("Skipping write");
+        return Ok(Some(false));
+    }
+
+    if mode != Mode::Write {
+        report_unformatted(mode, path.as_str(), &input_bytes, &output_bytes);
+        return Ok(Some(true));
+    }
+
+    tracing::trace!("Writing");
+
+    let written = write_file_unless_changed(path, output_bytes, input_hash).await?;
+
+    Ok(Some(written))
+}
+
+/// Format with `dhall format`, keeping the line endings required by the config
+#[tracing::instrument(skip(input_bytes))]
+pub async fn formatted_dhall(input_bytes: Bytes) -> eyre::Result<Bytes> {
+    let line_ending = cx().cache.config().await?.format.line_ending(&input_bytes);
+    // Formatters may not preserve CRLF, so give them LF and restore the line endings after
+    let output_bytes = dhall(LineEnding::Lf.apply(input_bytes)).await?;
+    Ok(line_ending.apply(output_bytes))
+}
+
+/// Whether `dhall format` would leave contents unchanged, reusing cached results
+#[tracing::instrument(skip(bytes))]
+pub async fn is_dhall_formatted(bytes: Bytes) -> eyre::Result<bool> {
+    let cx = cx();
+    let line_ending = cx.cache.config().await?.format.line_ending(&bytes);
+    if line_ending.matches(&bytes)
+        && cx
+            .cache
+            .is_dhall_formatted(XxHash3_64::oneshot(&bytes))
+            .await?
+    {
+        return Ok(true);
+    }
+    let output_bytes = formatted_dhall(bytes.clone()).await?;
+    cx.cache
+        .mark_dhall_formatted(XxHash3_64::oneshot(&output_bytes))
+        .await?;
+    Ok(bytes == output_bytes)
+}
+
+#[tracing::instrument(skip(bytes))]
+async fn dhall(bytes: Bytes) -> eyre::Result<Bytes> {
+    let cx = cx();
+
+    let dhall = &cx.cache.which("dhall").await?;
+
+    let tool_permits = cx.tool_permits("dhall").await?;
+
+    let tool_permit = tool_permits.acquire().await?;
+    let file_permit = cx.file_permits.acquire().await?;
+    let process_permit = cx.process_permits.acquire().await?;
+
+    let mut command = exec::sandboxed(Some("dhall"), exec::DHALL_PROFILE, &[], dhall).await?;
+
+    let child = command
+        .arg("format")
+        .env_clear()
+        .current_dir("/var/empty")
+        .kill_on_drop(true)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let output = exec::timeout("dhall", exec::output_with_stdin(child, &bytes)).await?;
+
+    drop(process_permit);
+    drop(file_permit);
+    drop(tool_permit);
+
+    if !output.status.success() {
+        if let Some(exit_code) = output.status.code() {
+            eyre::bail!(
+                "`dhall` exited with code {exit_code}:\n{}",
+                String::from_utf8_lossy(&output.stderr),
+            );
+        } else if let Some(signal) = output.status.signal() {
+            eyre::bail!("`dhall` was terminated by signal {signal}");
+        } else {
+            eyre::bail!("`dhall` died of unknown causes");
+        }
+    }
+
+    Ok(Bytes::from(output.stdout))
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn run_format_shell(args: &ShellArgs) -> eyre::Result<usize> {
+    let cx = cx();
+
+    if args.stdin {
+        let (input_bytes, input_hash) = read_stdin().await?;
+
+        let line_ending = cx.cache.config().await?.format.line_ending(&input_bytes);
+
+        let output_bytes = if line_ending.matches(&input_bytes)
+            && cx.cache.is_shell_formatted(input_hash).await?
+        {
+            tracing::trace!("Skipping format");
+            input_bytes.clone()
+        } else {
+            tracing::trace!("Formatting");
+            formatted_shell(input_bytes.clone()).await?
+        };
+
+        let mode = Mode::new(args.check, args.diff, args.format);
+        if mode != Mode::Write {
+            let is_formatted = output_bytes == input_bytes;
+            if !is_formatted {
+                report_unformatted(mode, "<stdin>", &input_bytes, &output_bytes);
+            }
+            return Ok(usize::from(!is_formatted));
+        }
+
+        write_stdout(output_bytes).await?;
+
+        return Ok(0);
+    }
+
+    let paths = if args.paths.is_empty() && args.packages.is_empty() {
+        git::changed_shell_files(Pipeline::Format).await?
+    } else {
+        git::shell_files(&args.paths, &args.packages, Pipeline::Format).await?
+    };
+
+    let mode = Mode::new(args.check, args.diff, args.format);
+
+    let handles =
+        queue::spawn(paths, |path| async move { format_shell(&path, mode).await }).await?;
+
+    let total_count = handles.len();
+
+    report_files(total_count);
+
+    let mut formatted_count = 0;
+
+    for handle in handles {
+        if let Some(true) = handle.await?? {
+            formatted_count += 1;
+        }
+    }
+
+    if mode == Mode::Write {
+        message!(
+            "Formatted {formatted_count} of {total_count} shell {files}",
+            formatted_count = formatted_count.to_formatted_string(&Locale::en),
+            total_count = total_count.to_formatted_string(&Locale::en),
+            files = if total_count == 1 { "file" } else { "files" },
+        );
+    } else {
+        message!(
+            "Checked {total_count} shell {files} ({formatted_count} unformatted)",
+            total_count = total_count.to_formatted_string(&Locale::en),
+            files = if total_count == 1 { "file" } else { "files" },
+            formatted_count = formatted_count.to_formatted_string(&Locale::en),
+        );
+    }
+
+    Ok(formatted_count)
+}
+
+#[tracing::instrument(fields(indicatif.pb_show))]
+async fn format_shell(path: &Utf8Path, mode: Mode) -> eyre::Result<Option<bool>> {
+    let cx = cx();
+
+    let (input_bytes, input_hash) = read_file(path).await?;
+
+    let line_ending = cx.cache.config().await?.format.line_ending(&input_bytes);
+
+    if !line_ending.matches(&input_bytes) {
+        tracing::debug!(?line_ending, "Normalizing line endings");
+    } else if cx.cache.is_shell_formatted(input_hash).await? {
+        tracing::trace!("Skipping format");
+        return Ok(Some(false));
+    }
+
+    tracing::trace!("Formatting");
+
+    let (output_bytes, duration) = exec::timed(formatted_shell(input_bytes.clone())).await?;
+
+    cx.cache
+        .record_duration("shfmt", input_hash, path, duration)
+        .await?;
+
+    cx.cache
+        .mark_shell_formatted(XxHash3_64::oneshot(&output_bytes))
+        .await?;
+
+    if input_bytes == output_bytes {
+        tracing::trace!("Skipping write");
+        return Ok(Some(false));
+    }
+
+    if mode != Mode::Write {
+        report_unformatted(mode, path.as_str(), &input_bytes, &output_bytes);
+        return Ok(Some(true));
+    }
+
+    tracing::trace!("Writing");
+
+    let written = write_file_unless_changed(path, output_bytes, input_hash).await?;
+
+    Ok(Some(written))
+}
+
+/// Format with `shfmt`, keeping the line endings required by the config
+#[tracing::instrument(skip(input_bytes))]
+pub async fn formatted_shell(input_bytes: Bytes) -> eyre::Result<Bytes> {
+    let line_ending = cx().cache.config().await?.format.line_ending(&input_bytes);
+    // Formatters may not preserve CRLF, so give them LF and restore the line endings after
+    let output_bytes = shfmt(LineEnding::Lf.apply(input_bytes)).await?;
+    Ok(line_ending.apply(output_bytes))
+}
+
+/// Whether `shfmt` would leave contents unchanged, reusing cached results
+#[tracing::instrument(skip(bytes))]
+pub async fn is_shell_formatted(bytes: Bytes) -> eyre::Result<bool> {
+    let cx = cx();
+    let line_ending = cx.cache.config().await?.format.line_ending(&bytes);
+    if line_ending.matches(&bytes)
+        && cx
+            .cache
+            .is_shell_formatted(XxHash3_64::oneshot(&bytes))
+            .await?
+    {
+        return Ok(true);
+    }
+    let output_bytes = formatted_shell(bytes.clone()).await?;
+    cx.cache
+        .mark_shell_formatted(XxHash3_64::oneshot(&output_bytes))
+        .await?;
+    Ok(bytes == output_bytes)
+}
+
+#[tracing::instrument(skip(bytes))]
+async fn shfmt(bytes: Bytes) -> eyre::Result<Bytes> {
+    let cx = cx();
+
+    let shfmt = &cx.cache.which("shfmt").await?;
+
+    let tool_permits = cx.tool_permits("shfmt").await?;
+
+    let tool_permit = tool_permits.acquire().await?;
+    let file_permit = cx.file_permits.acquire().await?;
+    let process_permit = cx.process_permits.acquire().await?;
+
+    let mut command = exec::sandboxed(Some("shfmt"), exec::SHFMT_PROFILE, &[], shfmt).await?;
+
+    let child = command
+        .env_clear()
+        .current_dir("/var/empty")
+        .kill_on_drop(true)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let output = exec::timeout("shfmt", exec::output_with_stdin(child, &bytes)).await?;
+
+    drop(process_permit);
+    drop(file_permit);
+    drop(tool_permit);
+
+    if !output.status.success() {
+        if let Some(exit_code) = output.status.code() {
+            eyre::bail!(
+                "`shfmt` exited with code {exit_code}:\n{}",
+                String::from_utf8_lossy(&output.stderr),
+            );
+        } else if let Some(signal) = output.status.signal() {
+            eyre::bail!("`shfmt` was terminated by signal {signal}");
+        } else {
+            eyre::bail!("`shfmt` died of unknown causes");
+        }
+    }
+
+    Ok(Bytes::from(output.stdout))
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn run_format_cabal(args: &CabalArgs) -> eyre::Result<usize> {
+    let cx = cx();
+
+    if args.stdin {
+        let (input_bytes, input_hash) = read_stdin().await?;
+
+        let line_ending = cx.cache.config().await?.format.line_ending(&input_bytes);
+
+        let output_bytes = if line_ending.matches(&input_bytes)
+            && cx.cache.is_cabal_formatted(input_hash).await?
+        {
+            tracing::trace!("Skipping format");
+            input_bytes.clone()
+        } else {
+            tracing::trace!("Formatting");
+            formatted_cabal(input_bytes.clone()).await?
+        };
+
+        let mode = Mode::new(args.check, args.diff, args.format);
+        if mode != Mode::Write {
+            let is_formatted = output_bytes == input_bytes;
+            if !is_formatted {
+                report_unformatted(mode, "<stdin>", &input_bytes, &output_bytes);
+            }
+            return Ok(usize::from(!is_formatted));
+        }
+
+        write_stdout(output_bytes).await?;
+
+        return Ok(0);
+    }
+
+    let config = cx.cache.config().await?;
+
+    let mut paths = if args.paths.is_empty() && args.packages.is_empty() {
+        git::changed_files(&[]).await?
+    } else {
+        let mut paths = args.paths.clone();
+        if !args.packages.is_empty() {
+            let git_root = cx.cache.git_root().await?;
+            let packages = cx.cache.packages().await?;
+            for dir in packages::package_dirs(packages, &args.packages)? {
+                paths.push(git_root.join(dir));
+            }
+        }
+        git::files(&paths).await?
+    };
+
+    paths.retain(|path| path.extension() == Some("cabal") && config.files.is_included(path));
+
+    // `hpack` generates these, so they'd be overwritten
+    let mut generated = Vec::new();
+    for path in &paths {
+        if fs::try_exists(path.with_file_name("package.yaml")).await? {
+            generated.push(path.clone());
+        }
+    }
+    paths.retain(|path| !generated.contains(path));
+
+    let mode = Mode::new(args.check, args.diff, args.format);
+
+    let handles =
+        queue::spawn(paths, |path| async move { format_cabal(&path, mode).await }).await?;
+
+    let total_count = handles.len();
+
+    report_files(total_count);
+
+    let mut formatted_count = 0;
+
+    for handle in handles {
+        if let Some(true) = handle.await?? {
+            formatted_count += 1;
+        }
+    }
+
+    if mode == Mode::Write {
+        message!(
+            "Formatted {formatted_count} of {total_count} Cabal {files}",
+            formatted_count = formatted_count.to_formatted_string(&Locale::en),
+            total_count = total_count.to_formatted_string(&Locale::en),
+            files = if total_count == 1 { "file" } else { "files" },
+        );
+    } else {
+        message!(
+            "Checked {total_count} Cabal {files} ({formatted_count} unformatted)",
+            total_count = total_count.to_formatted_string(&Locale::en),
+            files = if total_count == 1 { "file" } else { "files" },
+            formatted_count = formatted_count.to_formatted_string(&Locale::en),
+        );
+    }
+
+    Ok(formatted_count)
+}
+
+#[tracing::instrument(fields(indicatif.pb_show))]
+async fn format_cabal(path: &Utf8Path, mode: Mode) -> eyre::Result<Option<bool>> {
+    let cx = cx();
+
+    let (input_bytes, input_hash) = read_file(path).await?;
+
+    let line_ending = cx.cache.config().await?.format.line_ending(&input_bytes);
+
+    if !line_ending.matches(&input_bytes) {
+        tracing::debug!(?line_ending, "Normalizing line endings");
+    } else if cx.cache.is_cabal_formatted(input_hash).await? {
+        tracing::trace!("Skipping format");
+        return Ok(Some(false));
+    }
+
+    tracing::trace!("Formatting");
+
+    let (output_bytes, duration) = exec::timed(formatted_cabal(input_bytes.clone())).await?;
+
+    let formatter = cx.cache.cabal_formatter().await?;
+    cx.cache
+        .record_duration(formatter, input_hash, path, duration)
+        .await?;
+
+    cx.cache
+        .mark_cabal_formatted(XxHash3_64::oneshot(&output_bytes))
+        .await?;
+
+    if input_bytes == output_bytes {
+        tracing::trace!("Skipping write");
+        return Ok(Some(false));
+    }
+
+    if mode != Mode::Write {
+        report_unformatted(mode, path.as_str(), &input_bytes, &output_bytes);
+        return Ok(Some(true));
+    }
+
+    tracing::trace!("Writing");
+
+    let written = write_file_unless_changed(path, output_bytes, input_hash).await?;
+
+    Ok(Some(written))
+}
+
+/// Format with `cabal-gild` or `cabal-fmt`, keeping the line endings required by the config
+#[tracing::instrument(skip(input_bytes))]
+pub async fn formatted_cabal(input_bytes: Bytes) -> eyre::Result<Bytes> {
+    let line_ending = cx().cache.config().await?.format.line_ending(&input_bytes);
+    // Formatters may not preserve CRLF, so give them LF and restore the line endings after
+    let output_bytes = cabal_formatter(LineEnding::Lf.apply(input_bytes)).await?;
+    Ok(line_ending.apply(output_bytes))
+}
+
+#[tracing::instrument(skip(bytes))]
+async fn cabal_formatter(bytes: Bytes) -> eyre::Result<Bytes> {
+    let cx = cx();
+
+    let name = cx.cache.cabal_formatter().await?;
+
+    let formatter = &cx.cache.which(name).await?;
+
+    let profile = if name == "cabal-gild" {
+        exec::CABAL_GILD_PROFILE
+    } else {
+        exec::CABAL_FMT_PROFILE
+    };
+
+    let tool_permits = cx.tool_permits(name).await?;
+
+    let tool_permit = tool_permits.acquire().await?;
+    let file_permit = cx.file_permits.acquire().await?;
+    let process_permit = cx.process_permits.acquire().await?;
+
+    let mut command = exec::sandboxed(Some(name), profile, &[], formatter).await?;
+
+    let child = command
+        .env_clear()
+        .current_dir("/var/empty")
+        .kill_on_drop(true)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
 
-    let output = child.wait_with_output().await?;
+    let output = exec::timeout(name, exec::output_with_stdin(child, &bytes)).await?;
 
     drop(process_permit);
     drop(file_permit);
+    drop(tool_permit);
 
     if !output.status.success() {
         if let Some(exit_code) = output.status.code() {
             eyre::bail!(
-                "`nixfmt` exited with code {exit_code}:\n{}",
+                "`{name}` exited with code {exit_code}:\n{}",
                 String::from_utf8_lossy(&output.stderr),
             );
         } else if let Some(signal) = output.status.signal() {
-            eyre::bail!("`nixfmt` was terminated by signal {signal}");
+            eyre::bail!("`{name}` was terminated by signal {signal}");
         } else {
-            eyre::bail!("`nixfmt` died of unknown causes");
+            eyre::bail!("`{name}` died of unknown causes");
         }
     }
 
@@ -0,0 +1,268 @@
+use crate::{
+    config::Language,
+    context::cx,
+    format::{Mode, formatted_haskell, report_unformatted, syntax},
+    io::{read_file, write_file_unless_changed},
+    line_ending::LineEnding,
+};
+use bytes::Bytes;
+use camino::Utf8Path;
+use color_eyre::eyre;
+use std::str;
+use twox_hash::XxHash3_64;
+
+/// Haddock code blocks shorter than this many lines are left alone
+const MIN_HADDOCK_LINES: usize = 3;
+
+/// Lines `start..end` of a file are Haskell code, with `prefix` at the start of every line
+struct Block {
+    start: usize,
+    end: usize,
+    prefix: String,
+}
+
+/// Whether a path is a Markdown file, whose fenced Haskell code blocks can be formatted
+pub fn is_markdown(path: &Utf8Path) -> bool {
+    matches!(path.extension(), Some("md" | "markdown"))
+}
+
+/// Format the Haskell code blocks in a Markdown file, or the haddock code blocks in a Haskell
+/// file. Returns whether the file was written, or would be when checking or diffing.
+#[tracing::instrument(fields(indicatif.pb_show))]
+pub async fn format(path: &Utf8Path, mode: Mode) -> eyre::Result<bool> {
+    let (input_bytes, input_hash) = read_file(path).await?;
+
+    let Ok(input) = str::from_utf8(&input_bytes) else {
+        tracing::debug!("Skipping file which isn't UTF-8");
+        return Ok(false);
+    };
+
+    let mut lines: Vec<String> = input.lines().map(String::from).collect();
+
+    let blocks = if is_markdown(path) {
+        markdown_blocks(&lines)
+    } else {
+        haddock_blocks(&lines)
+    };
+
+    // Replace blocks from the end, so earlier line numbers stay valid
+    for block in blocks.into_iter().rev() {
+        if let Some(output) = format_block(path, &lines, &block).await? {
+            lines.splice(block.start..block.end, output);
+        }
+    }
+
+    let line_ending = LineEnding::detect(&input_bytes);
+    let newline = match line_ending {
+        LineEnding::Lf => "\n",
+        LineEnding::Crlf => "\r\n",
+    };
+
+    let mut output = lines.join(newline);
+    if input.ends_with('\n') {
+        output.push_str(newline);
+    }
+
+    if output.as_bytes() == input_bytes {
+        tracing::trace!("Skipping write");
+        return Ok(false);
+    }
+
+    if mode != Mode::Write {
+        report_unformatted(mode, path.as_str(), &input_bytes, output.as_bytes());
+        return Ok(true);
+    }
+
+    tracing::trace!("Writing");
+
+    write_file_unless_changed(path, Bytes::from(output), input_hash).await
+}
+
+/// Formatted lines of a code block, or `None` if it's already formatted or can't be formatted
+#[tracing::instrument(skip(lines, block), fields(line = block.start))]
+async fn format_block(
+    path: &Utf8Path,
+    lines: &[String],
+    block: &Block,
+) -> eyre::Result<Option<Vec<String>>> {
+    let cx = cx();
+
+    let blank_prefix = block.prefix.trim_end();
+
+    let mut code = String::new();
+    for line in &lines[block.start..block.end] {
+        let line = line
+            .strip_prefix(&block.prefix)
+            .unwrap_or_else(|| line.strip_prefix(blank_prefix).unwrap_or_default());
+        code.push_str(line);
+        code.push('\n');
+    }
+
+    // GHCi sessions aren't Haskell modules
+    if code
+        .lines()
+        .any(|line| line.starts_with(">>>") || line.starts_with("ghci>"))
+    {
+        return Ok(None);
+    }
+
+    let input_bytes = Bytes::from(code);
+
+    if cx
+        .cache
+        .is_haskell_formatted(XxHash3_64::oneshot(&input_bytes))
+        .await?
+    {
+        return Ok(None);
+    }
+
+    // Examples are often fragments which don't parse on their own, so leave them be
+    let output_bytes = match formatted_haskell(Some(path), input_bytes.clone()).await {
+        Ok(output_bytes) => output_bytes,
+        Err(error) => {
+            tracing::debug!(
+                "Leaving the code block at line {line} unformatted: {error}",
+                line = block.start
+            );
+            return Ok(None);
+        }
+    };
+
+    let version = cx.cache.fourmolu_version().await?;
+    if syntax::is_broken(
+        Language::Haskell,
+        path,
+        "fourmolu",
+        version,
+        &input_bytes,
+        &output_bytes,
+    )
+    .await?
+    {
+        return Ok(None);
+    }
+
+    cx.cache
+        .mark_haskell_formatted(XxHash3_64::oneshot(&output_bytes))
+        .await?;
+
+    if input_bytes == output_bytes {
+        return Ok(None);
+    }
+
+    let output = str::from_utf8(&output_bytes)?
+        .lines()
+        .map(|line| {
+            if line.is_empty() {
+                String::from(blank_prefix)
+            } else {
+                format!("{}{line}", block.prefix)
+            }
+        })
+        .collect();
+
+    Ok(Some(output))
+}
+
+/// Fenced code blocks tagged `haskell` or `hs`, indented like their opening fence
+fn markdown_blocks(lines: &[String]) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = &lines[i];
+        let rest = line.trim_start();
+        let indent = &line[..line.len() - rest.len()];
+        let Some(fence) = rest.chars().next().filter(|c| *c == '`' || *c == '~') else {
+            i += 1;
+            continue;
+        };
+        let fence_len = rest.chars().take_while(|c| *c == fence).count();
+        if fence_len < 3 {
+            i += 1;
+            continue;
+        }
+        let info = rest[fence_len..].trim();
+        let language = info.split_whitespace().next().unwrap_or_default();
+
+        let start = i + 1;
+        let Some(end) = (start..lines.len()).find(|j| {
+            let rest = lines[*j].trim();
+            rest.chars().take_while(|c| *c == fence).count() >= fence_len
+                && rest.chars().all(|c| c == fence)
+        }) else {
+            break;
+        };
+
+        let is_indented = lines[start..end]
+            .iter()
+            .all(|line| line.starts_with(indent) || line.trim().is_empty());
+
+        if matches!(language, "haskell" | "hs") && is_indented && start < end {
+            blocks.push(Block {
+                start,
+                end,
+                prefix: String::from(indent),
+            });
+        }
+
+        i = end + 1;
+    }
+    blocks
+}
+
+/// `@` code blocks in line comments, like:
+///
+/// ```haskell
+/// -- @
+/// -- example :: Int
+/// -- example = 1
+/// -- @
+/// ```
+fn haddock_blocks(lines: &[String]) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let opening = lines[i].trim_end();
+        let Some(prefix) = opening
+            .strip_suffix('@')
+            .filter(|prefix| is_comment_prefix(prefix))
+        else {
+            i += 1;
+            continue;
+        };
+
+        let start = i + 1;
+        let blank_prefix = prefix.trim_end();
+        let Some(end) = (start..lines.len()).find(|j| {
+            let line = &lines[*j];
+            line.trim_end() == opening || !(line.starts_with(prefix) || line == blank_prefix)
+        }) else {
+            break;
+        };
+
+        if lines[end].trim_end() != opening {
+            // Not a code block, but this line might start one
+            i = end;
+            continue;
+        }
+
+        if end - start >= MIN_HADDOCK_LINES {
+            blocks.push(Block {
+                start,
+                end,
+                prefix: String::from(prefix),
+            });
+        }
+
+        i = end + 1;
+    }
+    blocks
+}
+
+/// Whitespace, `--`, and more whitespace
+fn is_comment_prefix(prefix: &str) -> bool {
+    prefix
+        .trim_start()
+        .strip_prefix("--")
+        .is_some_and(|rest| rest.chars().all(char::is_whitespace))
+}
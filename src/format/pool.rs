@@ -0,0 +1,177 @@
+use crate::{context::cx, exec, format};
+use bytes::Bytes;
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre;
+use std::{os::unix::process::ExitStatusExt as _, process::Stdio, sync::Arc};
+use tempfile::tempdir;
+use tokio::{
+    fs,
+    sync::{Mutex, OnceCell, mpsc, oneshot},
+};
+
+/// Workers which format many files per `fourmolu` process.
+///
+/// `fourmolu` only reads one module from `stdin` before exiting, so rather than keeping processes
+/// alive between files, each worker takes every file queued since its last run (up to the batch
+/// size) and formats them all in place in a temporary directory with a single process.
+struct Pool {
+    sender: mpsc::UnboundedSender<Job>,
+}
+
+struct Job {
+    path: Option<Utf8PathBuf>,
+    bytes: Bytes,
+    reply: oneshot::Sender<eyre::Result<Bytes>>,
+}
+
+static POOL: OnceCell<Pool> = OnceCell::const_new();
+
+/// Format with a pooled `fourmolu` process
+#[tracing::instrument(skip(bytes))]
+pub async fn fourmolu(
+    path: Option<&Utf8Path>,
+    bytes: Bytes,
+    batch_size: usize,
+) -> eyre::Result<Bytes> {
+    let pool = POOL.get_or_try_init(|| Pool::new(batch_size)).await?;
+    let (reply, receiver) = oneshot::channel();
+    let job = Job {
+        path: path.map(Utf8Path::to_path_buf),
+        bytes,
+        reply,
+    };
+    if pool.sender.send(job).is_err() {
+        eyre::bail!("`fourmolu` workers have stopped");
+    }
+    receiver.await?
+}
+
+impl Pool {
+    async fn new(batch_size: usize) -> eyre::Result<Self> {
+        let jobs = cx().tool_jobs("fourmolu").await?;
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        tracing::debug!("Starting {jobs} `fourmolu` workers");
+        for _ in 0..jobs {
+            tokio::spawn(worker(Arc::clone(&receiver), batch_size));
+        }
+        Ok(Self { sender })
+    }
+}
+
+async fn worker(receiver: Arc<Mutex<mpsc::UnboundedReceiver<Job>>>, batch_size: usize) {
+    loop {
+        let mut jobs = Vec::new();
+        {
+            let mut receiver = receiver.lock().await;
+            let Some(job) = receiver.recv().await else {
+                return;
+            };
+            jobs.push(job);
+            while jobs.len() < batch_size
+                && let Ok(job) = receiver.try_recv()
+            {
+                jobs.push(job);
+            }
+        }
+
+        if jobs.len() > 1 {
+            let inputs: Vec<Bytes> = jobs.iter().map(|job| job.bytes.clone()).collect();
+            match fourmolu_batch(&inputs).await {
+                Ok(outputs) => {
+                    for (job, output) in jobs.into_iter().zip(outputs) {
+                        let _ = job.reply.send(Ok(output));
+                    }
+                    continue;
+                }
+                Err(error) => {
+                    // Retry one at a time so errors are reported for the files that caused them
+                    tracing::debug!("Retrying batch of {} files: {error}", jobs.len());
+                }
+            }
+        }
+
+        for job in jobs {
+            let output = format::fourmolu_one(job.path.as_deref(), job.bytes).await;
+            let _ = job.reply.send(output);
+        }
+    }
+}
+
+#[tracing::instrument(skip_all, fields(files = inputs.len()))]
+async fn fourmolu_batch(inputs: &[Bytes]) -> eyre::Result<Vec<Bytes>> {
+    let cx = cx();
+
+    let fourmolu = &cx.cache.which("fourmolu").await?;
+
+    let tool_permits = cx.tool_permits("fourmolu").await?;
+
+    let temp_dir = tempdir()?;
+    let batch_dir = Utf8PathBuf::try_from(fs::canonicalize(temp_dir.path()).await?)?;
+
+    let mut paths = Vec::with_capacity(inputs.len());
+    for (i, bytes) in inputs.iter().enumerate() {
+        let path = batch_dir.join(format!("{i}.hs"));
+        fs::write(&path, bytes).await?;
+        paths.push(path);
+    }
+
+    let mut args = format::fourmolu_args().await?;
+
+    args.push(String::from("--mode=inplace"));
+
+    let tool_permit = tool_permits.acquire().await?;
+    let process_permit = cx.process_permits.acquire().await?;
+    let memory_permit = cx.memory.acquire().await?;
+
+    let mut command = exec::sandboxed(
+        Some("fourmolu"),
+        exec::FOURMOLU_BATCH_PROFILE,
+        &[exec::Param {
+            name: "BATCH_DIR",
+            path: &batch_dir,
+            writable: true,
+        }],
+        fourmolu,
+    )
+    .await?;
+
+    let output = exec::timeout(
+        "fourmolu",
+        command
+            .args(args)
+            .args(&paths)
+            .env_clear()
+            .current_dir("/var/empty")
+            .kill_on_drop(true)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output(),
+    )
+    .await?;
+
+    drop(memory_permit);
+    drop(process_permit);
+    drop(tool_permit);
+
+    if !output.status.success() {
+        if let Some(exit_code) = output.status.code() {
+            eyre::bail!(
+                "`fourmolu` exited with code {exit_code}:\n{}",
+                String::from_utf8_lossy(&output.stderr),
+            );
+        } else if let Some(signal) = output.status.signal() {
+            eyre::bail!("`fourmolu` was terminated by signal {signal}");
+        } else {
+            eyre::bail!("`fourmolu` died of unknown causes");
+        }
+    }
+
+    let mut outputs = Vec::with_capacity(paths.len());
+    for path in &paths {
+        outputs.push(Bytes::from(fs::read(path).await?));
+    }
+
+    Ok(outputs)
+}
@@ -0,0 +1,126 @@
+use crate::{config::Language, context::cx, query};
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre;
+use std::{
+    fmt::Write as _,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::fs;
+use tree_sitter::{Parser, Tree};
+
+/// Number of syntax error positions kept in bug reports
+const MAX_ERRORS: usize = 10;
+
+/// Whether a formatter's output has syntax errors its input didn't, in which case it shouldn't be
+/// written. Saves the input and output in a bug report for the formatter's maintainers.
+#[tracing::instrument(skip(input, output))]
+pub async fn is_broken(
+    language: Language,
+    path: &Utf8Path,
+    formatter: &str,
+    version: &str,
+    input: &[u8],
+    output: &[u8],
+) -> eyre::Result<bool> {
+    let grammar = match language {
+        Language::Haskell => &*query::LANGUAGE,
+        Language::Nix => &*query::NIX_LANGUAGE,
+        // There are no tree-sitter grammars for Dhall or shell scripts here
+        Language::Dhall | Language::Shell => return Ok(false),
+    };
+
+    let input_errors = syntax_errors(grammar, input)?;
+    let output_errors = syntax_errors(grammar, output)?;
+
+    if output_errors.len() <= input_errors.len() {
+        return Ok(false);
+    }
+
+    let report_dir = write_bug_report(
+        path,
+        version,
+        input,
+        output,
+        input_errors.len(),
+        &output_errors,
+    )
+    .await?;
+
+    tracing::error!(
+        "Not writing `{path}`, since `{formatter}` output has syntax errors its input didn't. Saved a bug report in `{report_dir}`"
+    );
+
+    Ok(true)
+}
+
+/// Positions (1-based line and column) of `ERROR` and `MISSING` nodes
+fn syntax_errors(
+    grammar: &tree_sitter::Language,
+    bytes: &[u8],
+) -> eyre::Result<Vec<(usize, usize)>> {
+    let mut parser = Parser::new();
+    parser.set_language(grammar)?;
+    let Some(tree) = parser.parse(bytes, None) else {
+        eyre::bail!("Failed to parse formatter output");
+    };
+    Ok(error_positions(&tree))
+}
+
+fn error_positions(tree: &Tree) -> Vec<(usize, usize)> {
+    let mut positions = Vec::new();
+    let mut cursor = tree.walk();
+    'walk: loop {
+        let node = cursor.node();
+        if node.is_error() || node.is_missing() {
+            let point = node.start_position();
+            positions.push((point.row + 1, point.column + 1));
+        } else if node.has_error() && cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                break 'walk;
+            }
+        }
+    }
+    positions
+}
+
+#[tracing::instrument(skip(input, output, output_errors))]
+async fn write_bug_report(
+    path: &Utf8Path,
+    version: &str,
+    input: &[u8],
+    output: &[u8],
+    input_error_count: usize,
+    output_errors: &[(usize, usize)],
+) -> eyre::Result<Utf8PathBuf> {
+    let cx = cx();
+
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let file_name = path.file_name().unwrap_or("stdin");
+
+    let report_dir = cx
+        .cache
+        .dir()
+        .join("bug-reports")
+        .join(format!("{timestamp}-{file_name}"));
+
+    fs::create_dir_all(&report_dir).await?;
+
+    fs::write(report_dir.join(format!("input-{file_name}")), input).await?;
+    fs::write(report_dir.join(format!("output-{file_name}")), output).await?;
+
+    let mut report = String::new();
+    writeln!(report, "path: {path}")?;
+    writeln!(report, "formatter: {version}")?;
+    writeln!(report, "be: {}", env!("CARGO_PKG_VERSION"))?;
+    writeln!(report, "input syntax errors: {input_error_count}")?;
+    writeln!(report, "output syntax errors: {}", output_errors.len())?;
+    for (line, column) in output_errors.iter().take(MAX_ERRORS) {
+        writeln!(report, "  output-{file_name}:{line}:{column}")?;
+    }
+    fs::write(report_dir.join("report.txt"), report).await?;
+
+    Ok(report_dir)
+}
@@ -0,0 +1,270 @@
+use crate::{
+    cli::generate::{Args, Command, HpackArgs},
+    context::cx,
+    diff,
+    events::message,
+    exec,
+    exit::Findings,
+    git,
+    io::write_file,
+    packages, queue,
+};
+use bytes::Bytes;
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{self, ContextCompat as _};
+use num_format::{Locale, ToFormattedString as _};
+use std::{os::unix::process::ExitStatusExt as _, process::Stdio, str};
+use tokio::fs;
+use tracing_indicatif::indicatif_println;
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &Args) -> eyre::Result<()> {
+    match &args.command {
+        Command::Hpack(args) => run_generate_hpack(args).await,
+    }
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn run_generate_hpack(args: &HpackArgs) -> eyre::Result<()> {
+    let paths = package_yamls(&args.paths, &args.packages).await?;
+
+    let check = args.check;
+
+    let handles = queue::spawn(paths, |path| async move { generate(&path, check).await }).await?;
+
+    let total_count = handles.len();
+    let mut changed_count = 0;
+
+    for handle in handles {
+        if handle.await?? {
+            changed_count += 1;
+        }
+    }
+
+    if check {
+        message!(
+            "Checked {total_count} `.cabal` {files} ({changed_count} out of date)",
+            total_count = total_count.to_formatted_string(&Locale::en),
+            files = if total_count == 1 { "file" } else { "files" },
+            changed_count = changed_count.to_formatted_string(&Locale::en),
+        );
+        if changed_count > 0 {
+            return Err(Findings(format!(
+                "{changed_count} `.cabal` {files} out of date, run `be generate hpack` to fix",
+                changed_count = changed_count.to_formatted_string(&Locale::en),
+                files = if changed_count == 1 {
+                    "file is"
+                } else {
+                    "files are"
+                },
+            ))
+            .into());
+        }
+    } else {
+        message!(
+            "Generated {changed_count} of {total_count} `.cabal` {files}",
+            changed_count = changed_count.to_formatted_string(&Locale::en),
+            total_count = total_count.to_formatted_string(&Locale::en),
+            files = if total_count == 1 { "file" } else { "files" },
+        );
+    }
+
+    Ok(())
+}
+
+/// Changed `package.yaml` files, or else the ones under `paths` or in `packages`
+pub async fn package_yamls(
+    paths: &[Utf8PathBuf],
+    packages: &[String],
+) -> eyre::Result<Vec<Utf8PathBuf>> {
+    let cx = cx();
+
+    let mut paths = if paths.is_empty() && packages.is_empty() {
+        git::changed_files(&[]).await?
+    } else {
+        let mut paths = git::files(paths).await?;
+        if !packages.is_empty() {
+            let git_root = cx.cache.git_root().await?;
+            let all_packages = cx.cache.packages().await?;
+            for dir in packages::package_dirs(all_packages, packages)? {
+                paths.push(git_root.join(dir).join("package.yaml"));
+            }
+        }
+        paths
+    };
+
+    paths.retain(|path| path.file_name() == Some("package.yaml"));
+
+    Ok(paths)
+}
+
+/// Whether the `.cabal` file next to this `package.yaml` was out of date (and written, unless
+/// checking)
+#[tracing::instrument(fields(indicatif.pb_show))]
+async fn generate(path: &Utf8Path, check: bool) -> eyre::Result<bool> {
+    let Some(out_of_date) = out_of_date(path).await? else {
+        return Ok(false);
+    };
+
+    let cabal_path = &out_of_date.cabal_path;
+
+    if check {
+        if out_of_date.existing.is_some() {
+            indicatif_println!("{cabal_path}: out of date with `{path}`");
+        } else {
+            indicatif_println!("{cabal_path}: missing, but `{path}` would generate it");
+        }
+    } else {
+        write_file(cabal_path, out_of_date.output).await?;
+    }
+
+    Ok(true)
+}
+
+/// A `.cabal` file which doesn't match what `hpack` generates from the `package.yaml` next to it
+pub struct OutOfDate {
+    pub cabal_path: Utf8PathBuf,
+    /// `None` if the `.cabal` file doesn't exist yet
+    pub existing: Option<Vec<u8>>,
+    pub output: Bytes,
+}
+
+impl OutOfDate {
+    /// What regenerating would change, ignoring the header comment like the comparison does
+    pub fn diff(&self) -> String {
+        let existing = self
+            .existing
+            .as_deref()
+            .map(String::from_utf8_lossy)
+            .unwrap_or_default();
+        let output = String::from_utf8_lossy(&self.output);
+        let lines = |cabal| -> String {
+            without_header(cabal)
+                .into_iter()
+                .flat_map(|line| [line, "\n"])
+                .collect()
+        };
+        let before = lines(&existing);
+        let after = lines(&output);
+        diff::plain(
+            self.cabal_path.as_str(),
+            before.as_bytes(),
+            after.as_bytes(),
+        )
+    }
+}
+
+/// Run `hpack` on a `package.yaml`, returning `None` if its `.cabal` file is up to date
+#[tracing::instrument]
+pub async fn out_of_date(path: &Utf8Path) -> eyre::Result<Option<OutOfDate>> {
+    let output_bytes = hpack(path).await?;
+
+    let output = str::from_utf8(&output_bytes)?;
+
+    let name = output
+        .lines()
+        .find_map(|line| line.strip_prefix("name:"))
+        .map(str::trim)
+        .context("`hpack` output is missing a `name` field")?;
+
+    let cabal_path = path.with_file_name(format!("{name}.cabal"));
+
+    let existing = match fs::read(&cabal_path).await {
+        Ok(bytes) => Some(bytes),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => None,
+        Err(error) => return Err(error.into()),
+    };
+
+    let is_up_to_date = existing.as_deref().is_some_and(|existing| {
+        str::from_utf8(existing)
+            .is_ok_and(|existing| without_header(existing) == without_header(output))
+    });
+
+    if is_up_to_date {
+        return Ok(None);
+    }
+
+    Ok(Some(OutOfDate {
+        cabal_path,
+        existing,
+        output: output_bytes,
+    }))
+}
+
+/// Lines of a generated `.cabal` file, without the comment naming the `hpack` version and hash,
+/// so different versions of `hpack` don't count as changes
+fn without_header(cabal: &str) -> Vec<&str> {
+    let mut in_header = true;
+    cabal
+        .lines()
+        .filter(|line| {
+            if line.starts_with("name:") {
+                in_header = false;
+            }
+            !(in_header && line.starts_with("--"))
+        })
+        .collect()
+}
+
+#[tracing::instrument]
+async fn hpack(path: &Utf8Path) -> eyre::Result<Bytes> {
+    let cx = cx();
+
+    let hpack = &cx.cache.which("hpack").await?;
+
+    let git_root = cx.cache.git_root().await?;
+
+    // `hpack` runs outside the repo, like other tools
+    let path = Utf8PathBuf::try_from(fs::canonicalize(path).await?)?;
+
+    let tool_permits = cx.tool_permits("hpack").await?;
+
+    let tool_permit = tool_permits.acquire().await?;
+    let file_permit = cx.file_permits.acquire().await?;
+    let process_permit = cx.process_permits.acquire().await?;
+
+    let mut command = exec::sandboxed(
+        Some("hpack"),
+        exec::HPACK_PROFILE,
+        &[exec::Param {
+            name: "GIT_ROOT",
+            path: git_root,
+            writable: false,
+        }],
+        hpack,
+    )
+    .await?;
+
+    let output = exec::timeout(
+        "hpack",
+        command
+            .args(["--silent", path.as_str(), "-"])
+            .env_clear()
+            .current_dir("/var/empty")
+            .kill_on_drop(true)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output(),
+    )
+    .await?;
+
+    drop(process_permit);
+    drop(file_permit);
+    drop(tool_permit);
+
+    if !output.status.success() {
+        if let Some(exit_code) = output.status.code() {
+            eyre::bail!(
+                "`hpack` exited with code {exit_code}:\n{}",
+                String::from_utf8_lossy(&output.stderr),
+            );
+        } else if let Some(signal) = output.status.signal() {
+            eyre::bail!("`hpack` was terminated by signal {signal}");
+        } else {
+            eyre::bail!("`hpack` died of unknown causes");
+        }
+    }
+
+    Ok(Bytes::from(output.stdout))
+}
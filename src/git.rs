@@ -1,35 +1,584 @@
 use crate::{
+    config::{CONFIG_FILE_NAME, Language, Pipeline},
     context::cx,
     exec::exec,
+    packages,
     utils::flatten,
 };
-use camino::Utf8PathBuf;
+use bytes::Bytes;
+use camino::{Utf8Path, Utf8PathBuf};
 use color_eyre::eyre;
-use std::str::from_utf8;
+use std::{collections::HashMap, env, ops::RangeInclusive, str::from_utf8};
+use tokio::{fs, sync::Mutex};
 
-#[tracing::instrument]
-pub async fn changed_haskell_files() -> eyre::Result<Vec<Utf8PathBuf>> {
-    // Chosen by `fd -e hs | cut -d '/' -f 1 | sort | uniq --count`
-    let mut paths =
-        changed_files(&["src/", "test/", "local-packages/", "nix/packages/mercury/"]).await?;
-    paths.retain(|path| path.extension() == Some("hs"));
+/// Held while updating the index, since `git update-index` fails while another holds its lock
+static INDEX_LOCK: Mutex<()> = Mutex::const_new(());
+
+#[tracing::instrument(skip_all)]
+pub async fn changed_haskell_files(pipeline: Pipeline) -> eyre::Result<Vec<Utf8PathBuf>> {
+    let config = cx().cache.config().await?;
+    let mut paths = changed_files(&haskell_roots().await?).await?;
+    paths.retain(|path| config.is_file_type(Language::Haskell, pipeline, path));
+    Ok(paths)
+}
+
+/// All Haskell files under the search roots, relative to the current directory
+#[tracing::instrument(skip_all)]
+pub async fn all_haskell_files(pipeline: Pipeline) -> eyre::Result<Vec<Utf8PathBuf>> {
+    let cx = cx();
+    let config = cx.cache.config().await?;
+    let git_root = cx.cache.git_root().await?;
+    let roots: Vec<_> = haskell_roots()
+        .await?
+        .iter()
+        .map(|root| git_root.join(root))
+        .collect();
+    let mut paths = tracked_and_untracked_files(&roots).await?;
+    paths.retain(|path| config.is_file_type(Language::Haskell, pipeline, path));
     Ok(paths)
 }
 
+/// All Nix files in the repo, relative to the current directory
+#[tracing::instrument(skip_all)]
+pub async fn all_nix_files(pipeline: Pipeline) -> eyre::Result<Vec<Utf8PathBuf>> {
+    let cx = cx();
+    let config = cx.cache.config().await?;
+    let git_root = cx.cache.git_root().await?;
+    let mut paths = tracked_and_untracked_files(std::slice::from_ref(git_root)).await?;
+    paths.retain(|path| config.is_file_type(Language::Nix, pipeline, path));
+    Ok(paths)
+}
+
+/// Directories to search for Haskell files: configured roots, or else the source directories of
+/// all cabal/hpack packages, or else the whole repo
 #[tracing::instrument]
-pub async fn changed_nix_files() -> eyre::Result<Vec<Utf8PathBuf>> {
-    let mut paths = changed_files(&["."]).await?;
-    paths.retain(|path| path.extension() == Some("nix"));
+pub async fn haskell_roots() -> eyre::Result<Vec<String>> {
+    let cx = cx();
+
+    if let Some(roots) = &cx.cache.config().await?.haskell.roots {
+        return Ok(roots.clone());
+    }
+
+    let packages = cx.cache.packages().await?;
+
+    let mut roots: Vec<String> = packages::source_dirs(packages)
+        .into_iter()
+        .map(|dir| dir.to_string())
+        .collect();
+
+    if roots.is_empty() {
+        roots.push(String::from("."));
+    }
+
+    Ok(roots)
+}
+
+/// All Dhall files in the repo, relative to the current directory
+#[tracing::instrument(skip_all)]
+pub async fn all_dhall_files(pipeline: Pipeline) -> eyre::Result<Vec<Utf8PathBuf>> {
+    let cx = cx();
+    let config = cx.cache.config().await?;
+    let git_root = cx.cache.git_root().await?;
+    let mut paths = tracked_and_untracked_files(std::slice::from_ref(git_root)).await?;
+    paths.retain(|path| config.is_file_type(Language::Dhall, pipeline, path));
+    Ok(paths)
+}
+
+/// All shell scripts in the repo, relative to the current directory
+#[tracing::instrument(skip_all)]
+pub async fn all_shell_files(pipeline: Pipeline) -> eyre::Result<Vec<Utf8PathBuf>> {
+    let cx = cx();
+    let config = cx.cache.config().await?;
+    let git_root = cx.cache.git_root().await?;
+    let mut paths = tracked_and_untracked_files(std::slice::from_ref(git_root)).await?;
+    paths.retain(|path| config.is_file_type(Language::Shell, pipeline, path));
     Ok(paths)
 }
 
+#[tracing::instrument(skip_all)]
+pub async fn changed_nix_files(pipeline: Pipeline) -> eyre::Result<Vec<Utf8PathBuf>> {
+    let config = cx().cache.config().await?;
+    let mut paths = changed_files(&[String::from(".")]).await?;
+    paths.retain(|path| config.is_file_type(Language::Nix, pipeline, path));
+    Ok(paths)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn changed_dhall_files(pipeline: Pipeline) -> eyre::Result<Vec<Utf8PathBuf>> {
+    let config = cx().cache.config().await?;
+    let mut paths = changed_files(&[String::from(".")]).await?;
+    paths.retain(|path| config.is_file_type(Language::Dhall, pipeline, path));
+    Ok(paths)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn changed_shell_files(pipeline: Pipeline) -> eyre::Result<Vec<Utf8PathBuf>> {
+    let config = cx().cache.config().await?;
+    let mut paths = changed_files(&[String::from(".")]).await?;
+    paths.retain(|path| config.is_file_type(Language::Shell, pipeline, path));
+    Ok(paths)
+}
+
+/// Expand directories into the Haskell files they contain, and packages into their source
+/// directories
+#[tracing::instrument(skip_all)]
+pub async fn haskell_files(
+    paths: &[Utf8PathBuf],
+    package_names: &[String],
+    pipeline: Pipeline,
+) -> eyre::Result<Vec<Utf8PathBuf>> {
+    let cx = cx();
+    let mut package_dirs = Vec::new();
+    if !package_names.is_empty() {
+        let git_root = cx.cache.git_root().await?;
+        let packages = cx.cache.packages().await?;
+        for dir in packages::package_source_dirs(packages, package_names)? {
+            package_dirs.push(git_root.join(dir));
+        }
+    }
+    expand_dirs(paths, package_dirs, Language::Haskell, pipeline).await
+}
+
+/// Expand directories into the Nix files they contain, and packages into their directories
+#[tracing::instrument(skip_all)]
+pub async fn nix_files(
+    paths: &[Utf8PathBuf],
+    package_names: &[String],
+    pipeline: Pipeline,
+) -> eyre::Result<Vec<Utf8PathBuf>> {
+    let cx = cx();
+    let mut package_dirs = Vec::new();
+    if !package_names.is_empty() {
+        let git_root = cx.cache.git_root().await?;
+        let packages = cx.cache.packages().await?;
+        for dir in packages::package_dirs(packages, package_names)? {
+            package_dirs.push(git_root.join(dir));
+        }
+    }
+    expand_dirs(paths, package_dirs, Language::Nix, pipeline).await
+}
+
+/// Expand directories into the Dhall files they contain, and packages into their directories
+#[tracing::instrument(skip_all)]
+pub async fn dhall_files(
+    paths: &[Utf8PathBuf],
+    package_names: &[String],
+    pipeline: Pipeline,
+) -> eyre::Result<Vec<Utf8PathBuf>> {
+    let cx = cx();
+    let mut package_dirs = Vec::new();
+    if !package_names.is_empty() {
+        let git_root = cx.cache.git_root().await?;
+        let packages = cx.cache.packages().await?;
+        for dir in packages::package_dirs(packages, package_names)? {
+            package_dirs.push(git_root.join(dir));
+        }
+    }
+    expand_dirs(paths, package_dirs, Language::Dhall, pipeline).await
+}
+
+/// Expand directories into the shell scripts they contain, and packages into their directories
+#[tracing::instrument(skip_all)]
+pub async fn shell_files(
+    paths: &[Utf8PathBuf],
+    package_names: &[String],
+    pipeline: Pipeline,
+) -> eyre::Result<Vec<Utf8PathBuf>> {
+    let cx = cx();
+    let mut package_dirs = Vec::new();
+    if !package_names.is_empty() {
+        let git_root = cx.cache.git_root().await?;
+        let packages = cx.cache.packages().await?;
+        for dir in packages::package_dirs(packages, package_names)? {
+            package_dirs.push(git_root.join(dir));
+        }
+    }
+    expand_dirs(paths, package_dirs, Language::Shell, pipeline).await
+}
+
+async fn expand_dirs(
+    paths: &[Utf8PathBuf],
+    mut dirs: Vec<Utf8PathBuf>,
+    language: Language,
+    pipeline: Pipeline,
+) -> eyre::Result<Vec<Utf8PathBuf>> {
+    let config = cx().cache.config().await?;
+
+    let mut files = Vec::new();
+
+    for path in paths {
+        if fs::metadata(path)
+            .await
+            .is_ok_and(|metadata| metadata.is_dir())
+        {
+            dirs.push(path.clone());
+        } else {
+            files.push(path.clone());
+        }
+    }
+
+    if !dirs.is_empty() {
+        let mut dir_files = tracked_and_untracked_files(&dirs).await?;
+        dir_files.retain(|path| config.is_file_type(language, pipeline, path));
+        files.extend(dir_files);
+    }
+
+    Ok(files)
+}
+
+/// Files at these paths, with directories expanded to the files under them
+#[tracing::instrument]
+pub async fn files(paths: &[Utf8PathBuf]) -> eyre::Result<Vec<Utf8PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+    for path in paths {
+        if fs::metadata(path)
+            .await
+            .is_ok_and(|metadata| metadata.is_dir())
+        {
+            dirs.push(path.clone());
+        } else {
+            files.push(path.clone());
+        }
+    }
+    if !dirs.is_empty() {
+        files.extend(tracked_and_untracked_files(&dirs).await?);
+    }
+    Ok(files)
+}
+
+/// Files under the given paths that aren't ignored, relative to the current directory. Only staged
+/// files with `--staged`, since untracked files have nothing staged.
+#[tracing::instrument(skip_all)]
+pub async fn tracked_and_untracked_files(paths: &[Utf8PathBuf]) -> eyre::Result<Vec<Utf8PathBuf>> {
+    let cx = cx();
+    let git = cx.cache.which("git").await?;
+    let mut args = if cx.staged {
+        vec!["ls-files", "--cached", "--"]
+    } else {
+        vec![
+            "ls-files",
+            "--cached",
+            "--others",
+            "--exclude-standard",
+            "--",
+        ]
+    };
+    args.extend(paths.iter().map(|path| path.as_str()));
+    let stdout = exec(git, args).await?;
+    let files = from_utf8(&stdout)?.lines().map(Utf8PathBuf::from).collect();
+    Ok(files)
+}
+
+/// The root of the repo containing `path`
+#[tracing::instrument]
+pub async fn root(path: &Utf8Path) -> eyre::Result<Utf8PathBuf> {
+    let git = cx().cache.which("git").await?;
+    let stdout = exec(git, ["-C", path.as_str(), "rev-parse", "--show-toplevel"]).await?;
+    Ok(Utf8PathBuf::from(from_utf8(&stdout)?.trim_end()))
+}
+
+#[tracing::instrument]
+pub async fn head_commit() -> eyre::Result<String> {
+    let cx = cx();
+    let git = cx.cache.which("git").await?;
+    let git_root = cx.cache.git_root().await?;
+    let stdout = exec(git, ["-C", git_root.as_str(), "rev-parse", "HEAD"]).await?;
+    Ok(String::from(from_utf8(&stdout)?.trim_end()))
+}
+
+/// The commit where two revisions' histories diverged, like the start of `git diff A...B`
 #[tracing::instrument]
-pub async fn changed_files(paths: &[&'static str]) -> eyre::Result<Vec<Utf8PathBuf>> {
+pub async fn merge_base(a: &str, b: &str) -> eyre::Result<String> {
     let cx = cx();
+    let git = cx.cache.which("git").await?;
+    let git_root = cx.cache.git_root().await?;
+    let stdout = exec(git, ["-C", git_root.as_str(), "merge-base", a, b]).await?;
+    Ok(String::from(from_utf8(&stdout)?.trim_end()))
+}
 
+/// Files changed between two revisions, relative to the git root, with whether they existed
+/// before and after
+#[tracing::instrument]
+pub async fn changed_files_between(
+    from: &str,
+    to: &str,
+) -> eyre::Result<Vec<(Utf8PathBuf, bool, bool)>> {
+    let cx = cx();
     let git = cx.cache.which("git").await?;
+    let git_root = cx.cache.git_root().await?;
+    let stdout = exec(
+        git,
+        [
+            "-C",
+            git_root.as_str(),
+            "diff",
+            "--name-status",
+            "--no-renames",
+            from,
+            to,
+            "--",
+        ],
+    )
+    .await?;
+    let mut files = Vec::new();
+    for line in from_utf8(&stdout)?.lines() {
+        let Some((status, path)) = line.split_once('\t') else {
+            continue;
+        };
+        files.push((Utf8PathBuf::from(path), status != "A", status != "D"));
+    }
+    Ok(files)
+}
 
+/// Contents of a file, relative to the git root, at a revision
+#[tracing::instrument]
+pub async fn show_file(revision: &str, path: &Utf8Path) -> eyre::Result<Bytes> {
+    let cx = cx();
+    let git = cx.cache.which("git").await?;
     let git_root = cx.cache.git_root().await?;
+    exec(
+        git,
+        [
+            "-C",
+            git_root.as_str(),
+            "show",
+            &format!("{revision}:{path}"),
+        ],
+    )
+    .await
+}
+
+/// A path relative to the git root, which is how the index names files
+async fn index_path(path: &Utf8Path) -> eyre::Result<Utf8PathBuf> {
+    let git_root = cx().cache.git_root().await?;
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        Utf8PathBuf::try_from(env::current_dir()?)?.join(path)
+    };
+    Ok(path.strip_prefix(git_root)?.to_path_buf())
+}
+
+/// Mode and object ID of a file in the index, or `None` if it isn't in the index
+#[tracing::instrument]
+async fn index_entry(path: &Utf8Path) -> eyre::Result<Option<(String, String)>> {
+    let cx = cx();
+    let git = cx.cache.which("git").await?;
+    let git_root = cx.cache.git_root().await?;
+    let stdout = exec(
+        git,
+        [
+            "-C",
+            git_root.as_str(),
+            "ls-files",
+            "--stage",
+            "--",
+            path.as_str(),
+        ],
+    )
+    .await?;
+    // Like "100644 4b825dc642cb6eb9a060e54bf8d69288fbee4904 0\tsrc/Main.hs"
+    let entry = from_utf8(&stdout)?
+        .lines()
+        .next()
+        .and_then(|line| line.split_once('\t'))
+        .and_then(|(info, _)| {
+            let mut words = info.split(' ');
+            Some((String::from(words.next()?), String::from(words.next()?)))
+        });
+    Ok(entry)
+}
+
+/// Staged contents of a file, or `None` if it isn't in the index
+#[tracing::instrument]
+pub async fn staged_file(path: &Utf8Path) -> eyre::Result<Option<Bytes>> {
+    let cx = cx();
+    let index_path = index_path(path).await?;
+    let Some((_, object)) = index_entry(&index_path).await? else {
+        return Ok(None);
+    };
+    let git = cx.cache.which("git").await?;
+    let git_root = cx.cache.git_root().await?;
+    let bytes = exec(git, ["-C", git_root.as_str(), "cat-file", "blob", &object]).await?;
+    Ok(Some(bytes))
+}
+
+/// Replace the staged contents of a file, keeping its mode
+#[tracing::instrument(skip(bytes))]
+pub async fn stage_file(path: &Utf8Path, bytes: &[u8]) -> eyre::Result<()> {
+    let cx = cx();
+    let index_path = index_path(path).await?;
+    let Some((mode, _)) = index_entry(&index_path).await? else {
+        eyre::bail!("Can't stage `{path}`, since it isn't in the index");
+    };
+    let git = cx.cache.which("git").await?;
+    let git_root = cx.cache.git_root().await?;
+
+    let temp_file = tempfile::NamedTempFile::new()?;
+    fs::write(temp_file.path(), bytes).await?;
+
+    // `--path` applies the same filters as `git add`, like converting line endings
+    let stdout = exec(
+        &git,
+        [
+            "-C",
+            git_root.as_str(),
+            "hash-object",
+            "-w",
+            "--path",
+            index_path.as_str(),
+        ]
+        .into_iter()
+        .map(String::from)
+        .chain([temp_file.path().display().to_string()]),
+    )
+    .await?;
+    let object = from_utf8(&stdout)?.trim_end();
+
+    let _lock = INDEX_LOCK.lock().await;
+    exec(
+        &git,
+        [
+            "-C",
+            git_root.as_str(),
+            "update-index",
+            "--cacheinfo",
+            &format!("{mode},{object},{index_path}"),
+        ],
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// The remote's default branch, like `origin/main`
+#[tracing::instrument]
+pub async fn default_branch() -> eyre::Result<String> {
+    let cx = cx();
+    let git = cx.cache.which("git").await?;
+    let git_root = cx.cache.git_root().await?;
+
+    // Set by `git clone`, or by `git remote set-head origin --auto`
+    if let Ok(stdout) = exec(
+        &git,
+        [
+            "-C",
+            git_root.as_str(),
+            "symbolic-ref",
+            "--quiet",
+            "--short",
+            "refs/remotes/origin/HEAD",
+        ],
+    )
+    .await
+    {
+        let branch = from_utf8(&stdout)?.trim();
+        if !branch.is_empty() {
+            tracing::debug!("Using `{branch}` as the base");
+            return Ok(String::from(branch));
+        }
+    }
+
+    for branch in ["origin/main", "origin/master"] {
+        let exists = exec(
+            &git,
+            [
+                "-C",
+                git_root.as_str(),
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                branch,
+            ],
+        )
+        .await
+        .is_ok();
+        if exists {
+            tracing::debug!("Using `{branch}` as the base");
+            return Ok(String::from(branch));
+        }
+    }
+
+    eyre::bail!(
+        "Couldn't find the remote's default branch to compare against, so pass `--base` or set `base` in `be.toml`"
+    )
+}
+
+/// Files under the given paths, relative to the git root, which changed since the base, or which
+/// are staged with `--staged`. Every file under them when config files have changed, since then
+/// unchanged files might need formatting or linting too. With `--watch`, only files which changed
+/// since the last run, after the first one.
+#[tracing::instrument]
+pub async fn changed_files(paths: &[String]) -> eyre::Result<Vec<Utf8PathBuf>> {
+    let cx = cx();
+
+    let watched = cx.watched.lock().unwrap().clone();
+    if let Some(watched) = watched {
+        let git_root = cx.cache.git_root().await?;
+        let mut files = Vec::new();
+        for path in watched {
+            if (paths.is_empty() || paths.iter().any(|root| path.starts_with(root)))
+                && fs::try_exists(git_root.join(&path)).await?
+            {
+                files.push(path);
+            }
+        }
+        return Ok(files);
+    }
+
+    if cx.all_files().await? {
+        let git_root = cx.cache.git_root().await?;
+        let paths: Vec<_> = if paths.is_empty() {
+            vec![git_root.clone()]
+        } else {
+            paths.iter().map(|path| git_root.join(path)).collect()
+        };
+        return tracked_and_untracked_files(&paths).await;
+    }
+
+    changed_since_base(paths).await
+}
+
+/// Config files, relative to the git root, which changed since the base
+#[tracing::instrument]
+pub async fn changed_config_files() -> eyre::Result<Vec<Utf8PathBuf>> {
+    let config = cx().cache.config().await?;
+    let mut paths: Vec<String> = config
+        .tool_configs
+        .paths()
+        .into_iter()
+        .map(|path| path.to_string())
+        .collect();
+    paths.push(String::from(CONFIG_FILE_NAME));
+    changed_since_base(&paths).await
+}
+
+#[tracing::instrument]
+async fn changed_since_base(paths: &[String]) -> eyre::Result<Vec<Utf8PathBuf>> {
+    let cx = cx();
+
+    let git = cx.cache.which("git").await?;
+
+    let git_root = cx.cache.git_root().await?;
+
+    if cx.staged {
+        let mut args = vec![
+            "-C",
+            git_root.as_str(),
+            "diff",
+            "--cached",
+            "--diff-filter=dt",
+            "--name-only",
+            "--",
+        ];
+        args.extend(paths.iter().map(String::as_str));
+        let stdout = exec(git, args).await?;
+        let files = from_utf8(&stdout)?.lines().map(Utf8PathBuf::from).collect();
+        return Ok(files);
+    }
+
+    let base = cx.base().await?;
 
     let tracked_files_handle = {
         let git = git.clone();
@@ -40,10 +589,13 @@ pub async fn changed_files(paths: &[&'static str]) -> eyre::Result<Vec<Utf8PathB
             "--diff-filter=dt",
             "--name-only",
             "--merge-base",
-            "origin/master",
+            base,
             "--",
-        ];
-        args.extend(paths);
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+        args.extend_from_slice(paths);
         tokio::spawn(async move { exec(git, args).await })
     };
 
@@ -56,8 +608,11 @@ pub async fn changed_files(paths: &[&'static str]) -> eyre::Result<Vec<Utf8PathB
             "--others",
             "--exclude-standard",
             "--",
-        ];
-        args.extend(paths);
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+        args.extend_from_slice(paths);
         tokio::spawn(async move { exec(git, args).await })
     };
 
@@ -78,3 +633,200 @@ pub async fn changed_files(paths: &[&'static str]) -> eyre::Result<Vec<Utf8PathB
 
     Ok(files)
 }
+
+/// Lines changed since the base, or in the index with `--staged`, by path relative to the git root
+pub struct ChangedLines {
+    /// `None` for untracked files, which are new in their entirety
+    files: HashMap<Utf8PathBuf, Option<Vec<RangeInclusive<usize>>>>,
+}
+
+impl ChangedLines {
+    /// Whether any line from `start_line` to `end_line` changed, counting from 1
+    pub fn overlaps(&self, path: &Utf8Path, start_line: usize, end_line: usize) -> bool {
+        let path = path.strip_prefix("./").unwrap_or(path);
+        match self.files.get(path) {
+            None => false,
+            Some(None) => true,
+            Some(Some(ranges)) => ranges.iter().any(|range| {
+                *range.start() <= end_line.max(start_line) && start_line <= *range.end()
+            }),
+        }
+    }
+}
+
+#[tracing::instrument]
+pub async fn changed_lines() -> eyre::Result<ChangedLines> {
+    let cx = cx();
+
+    let git = cx.cache.which("git").await?;
+
+    let git_root = cx.cache.git_root().await?;
+
+    let mut args = vec![
+        "-C",
+        git_root.as_str(),
+        "diff",
+        "--unified=0",
+        "--no-color",
+        "--no-ext-diff",
+        "--no-prefix",
+    ];
+
+    if cx.staged {
+        args.push("--cached");
+    } else {
+        args.extend(["--merge-base", cx.base().await?]);
+    }
+
+    let stdout = exec(&git, args).await?;
+
+    let mut files = parse_changed_lines(from_utf8(&stdout)?)?
+        .into_iter()
+        .map(|(path, ranges)| (path, Some(ranges)))
+        .collect::<HashMap<_, _>>();
+
+    if !cx.staged {
+        let stdout = exec(
+            &git,
+            [
+                "-C",
+                git_root.as_str(),
+                "ls-files",
+                "--others",
+                "--exclude-standard",
+            ],
+        )
+        .await?;
+        files.extend(
+            from_utf8(&stdout)?
+                .lines()
+                .map(|path| (Utf8PathBuf::from(path), None)),
+        );
+    }
+
+    Ok(ChangedLines { files })
+}
+
+/// Lines added or changed in each file of `git diff --unified=0 --no-prefix`
+fn parse_changed_lines(
+    diff: &str,
+) -> eyre::Result<HashMap<Utf8PathBuf, Vec<RangeInclusive<usize>>>> {
+    let mut files = HashMap::<Utf8PathBuf, Vec<RangeInclusive<usize>>>::new();
+    let mut path = None;
+    // Added lines start with `+` too, so `+++` only names a file before the first hunk
+    let mut in_header = false;
+    for line in diff.lines() {
+        if line.starts_with("diff ") {
+            in_header = true;
+        } else if in_header && let Some(new_path) = line.strip_prefix("+++ ") {
+            path = (new_path != "/dev/null").then(|| Utf8PathBuf::from(new_path));
+        } else if let Some(hunk) = line.strip_prefix("@@ ") {
+            in_header = false;
+            let Some(path) = &path else {
+                continue;
+            };
+            // Like `@@ -12,3 +12,4 @@`, where a count of 1 is left out
+            let Some(new_lines) = hunk.split(' ').find_map(|part| part.strip_prefix('+')) else {
+                continue;
+            };
+            let (start, count) = match new_lines.split_once(',') {
+                Some((start, count)) => (start.parse::<usize>()?, count.parse::<usize>()?),
+                None => (new_lines.parse::<usize>()?, 1),
+            };
+            // Deleting lines changes the line after them, which is where findings about deleted code
+            // (like a missing signature) would be
+            let range = if count == 0 {
+                start + 1..=start + 1
+            } else {
+                start..=start + count - 1
+            };
+            files.entry(path.clone()).or_default().push(range);
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_changed_lines;
+    use camino::Utf8PathBuf;
+    use std::ops::RangeInclusive;
+
+    fn changed_lines(diff: &str, path: &str) -> Option<Vec<RangeInclusive<usize>>> {
+        parse_changed_lines(diff)
+            .unwrap()
+            .remove(&Utf8PathBuf::from(path))
+    }
+
+    #[test]
+    fn counts() {
+        let diff = "\
+diff --git src/A.hs src/A.hs
+--- src/A.hs
++++ src/A.hs
+@@ -3,2 +3,4 @@ module A
++a
++b
++c
++d
+@@ -10 +12 @@
+-e
++f
+";
+        assert_eq!(changed_lines(diff, "src/A.hs"), Some(vec![3..=6, 12..=12]));
+    }
+
+    #[test]
+    fn deletions() {
+        // Lines deleted after line 5 change line 6
+        let diff = "\
+diff --git src/A.hs src/A.hs
+--- src/A.hs
++++ src/A.hs
+@@ -6,2 +5,0 @@
+-a
+-b
+";
+        assert_eq!(changed_lines(diff, "src/A.hs"), Some(vec![6..=6]));
+    }
+
+    #[test]
+    fn deleted_and_new_files() {
+        let diff = "\
+diff --git src/Old.hs src/Old.hs
+deleted file mode 100644
+--- src/Old.hs
++++ /dev/null
+@@ -1,2 +0,0 @@
+-a
+-b
+diff --git src/New.hs src/New.hs
+new file mode 100644
+--- /dev/null
++++ src/New.hs
+@@ -0,0 +1,2 @@
++a
++b
+";
+        let files = parse_changed_lines(diff).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(changed_lines(diff, "src/New.hs"), Some(vec![1..=2]));
+    }
+
+    #[test]
+    fn added_lines_like_headers() {
+        // An added line `++ x` looks like a `+++` header, but comes after the first hunk
+        let diff = "\
+diff --git src/A.hs src/A.hs
+--- src/A.hs
++++ src/A.hs
+@@ -1,0 +2,1 @@
++++ x
+@@ -5 +6 @@
+-a
++b
+";
+        assert_eq!(changed_lines(diff, "src/A.hs"), Some(vec![2..=2, 6..=6]));
+        assert_eq!(changed_lines(diff, "x"), None);
+    }
+}
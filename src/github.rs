@@ -0,0 +1,49 @@
+use std::fmt::{self, Display};
+
+/// A GitHub Actions workflow command, which shows a message inline on the file in pull requests
+pub struct Annotation<'a> {
+    /// `error`, `warning`, or `notice`
+    pub level: &'static str,
+    pub file: &'a str,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub end_line: Option<usize>,
+    pub end_column: Option<usize>,
+    pub title: &'a str,
+    pub message: &'a str,
+}
+
+impl Display for Annotation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "::{} file={}", self.level, escape_property(self.file))?;
+        let positions = [
+            ("line", self.line),
+            ("col", self.column),
+            ("endLine", self.end_line),
+            ("endColumn", self.end_column),
+        ];
+        for (name, value) in positions {
+            if let Some(value) = value {
+                write!(f, ",{name}={value}")?;
+            }
+        }
+        write!(
+            f,
+            ",title={}::{}",
+            escape_property(self.title),
+            escape_data(self.message)
+        )
+    }
+}
+
+fn escape_data(data: &str) -> String {
+    data.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(property: &str) -> String {
+    escape_data(property)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
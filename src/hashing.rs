@@ -37,9 +37,10 @@ where
         buf: &mut ReadBuf<'_>,
     ) -> Poll<std::io::Result<()>> {
         let Self { reader, hasher } = self.get_mut();
+        let filled_len = buf.filled().len();
         match pin!(reader).poll_read(cx, buf) {
             Poll::Ready(Ok(())) => {
-                hasher.write(buf.filled());
+                hasher.write(&buf.filled()[filled_len..]);
                 Poll::Ready(Ok(()))
             }
             poll => poll,
@@ -0,0 +1,162 @@
+use crate::{
+    cli::hook::{Args, Command, InstallArgs},
+    context::cx,
+    events::message,
+    exec::exec,
+    io::write_stdout,
+};
+use bytes::Bytes;
+use camino::Utf8PathBuf;
+use color_eyre::eyre;
+use std::{fmt::Write as _, fs::Permissions, os::unix::fs::PermissionsExt as _, str};
+use tokio::fs;
+
+/// Marks hooks as `be`'s, so they can be replaced or removed without losing someone else's
+const MARKER: &str = "# Installed by `be hook install`";
+
+const PRE_COMMIT: &str = "be format --staged --check && be lint --staged";
+
+const PRE_PUSH: &str = "be format --check && be lint";
+
+/// Config files of hook managers, which would overwrite or ignore hooks installed directly
+const HOOK_MANAGERS: &[(&str, &str)] = &[
+    ("lefthook.yml", "lefthook"),
+    ("lefthook.yaml", "lefthook"),
+    (".lefthook.yml", "lefthook"),
+    (".lefthook.yaml", "lefthook"),
+    (".pre-commit-config.yaml", "pre-commit"),
+];
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &Args) -> eyre::Result<()> {
+    match &args.command {
+        Command::Install(args) => run_hook_install(args).await,
+        Command::Uninstall => run_hook_uninstall().await,
+    }
+}
+
+#[tracing::instrument(skip_all)]
+async fn run_hook_install(args: &InstallArgs) -> eyre::Result<()> {
+    let git_root = cx().cache.git_root().await?;
+
+    for (file, manager) in HOOK_MANAGERS {
+        if fs::try_exists(git_root.join(file)).await? {
+            message!(
+                "Found `{file}`, so add this to it rather than installing hooks `{manager}` would replace:"
+            );
+            let snippet = if *manager == "lefthook" {
+                lefthook_snippet(args.pre_push)
+            } else {
+                pre_commit_snippet(args.pre_push)
+            };
+            write_stdout(Bytes::from(snippet)).await?;
+            return Ok(());
+        }
+    }
+
+    let mut hooks = vec![("pre-commit", PRE_COMMIT)];
+    if args.pre_push {
+        hooks.push(("pre-push", PRE_PUSH));
+    }
+
+    let hooks_dir = hooks_dir().await?;
+
+    // Check every hook before writing any, so a conflict doesn't leave some installed
+    for (hook, _) in &hooks {
+        let path = hooks_dir.join(hook);
+        if !args.force && !is_ours_or_missing(&path).await? {
+            eyre::bail!(
+                "`{path}` already exists and wasn't installed by `be` (pass `--force` to overwrite it)"
+            );
+        }
+    }
+
+    fs::create_dir_all(&hooks_dir).await?;
+
+    for (hook, command) in &hooks {
+        let path = hooks_dir.join(hook);
+        fs::write(&path, format!("#!/bin/sh\n{MARKER}\n{command}\n")).await?;
+        fs::set_permissions(&path, Permissions::from_mode(0o755)).await?;
+        message!("Installed `{path}`");
+    }
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn run_hook_uninstall() -> eyre::Result<()> {
+    let hooks_dir = hooks_dir().await?;
+
+    let mut removed = 0;
+
+    for hook in ["pre-commit", "pre-push"] {
+        let path = hooks_dir.join(hook);
+        if fs::try_exists(&path).await? && is_ours_or_missing(&path).await? {
+            fs::remove_file(&path).await?;
+            message!("Removed `{path}`");
+            removed += 1;
+        }
+    }
+
+    if removed == 0 {
+        message!("No hooks installed by `be` to remove");
+    }
+
+    Ok(())
+}
+
+/// Where `git` looks for hooks, which `core.hooksPath` or worktrees can move
+async fn hooks_dir() -> eyre::Result<Utf8PathBuf> {
+    let cx = cx();
+    let git = cx.cache.which("git").await?;
+    let git_root = cx.cache.git_root().await?;
+    let stdout = exec(
+        git,
+        ["-C", git_root.as_str(), "rev-parse", "--git-path", "hooks"],
+    )
+    .await?;
+    // Relative to the git root, unless it's absolute
+    Ok(git_root.join(str::from_utf8(&stdout)?.trim_end()))
+}
+
+async fn is_ours_or_missing(path: &Utf8PathBuf) -> eyre::Result<bool> {
+    match fs::read_to_string(path).await {
+        Ok(contents) => Ok(contents.lines().any(|line| line == MARKER)),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(true),
+        Err(error) => Err(error.into()),
+    }
+}
+
+fn lefthook_snippet(pre_push: bool) -> String {
+    let mut hooks = vec![("pre-commit", PRE_COMMIT)];
+    if pre_push {
+        hooks.push(("pre-push", PRE_PUSH));
+    }
+    let mut snippet = String::new();
+    // Writing to a `String` can't fail
+    for (hook, command) in hooks {
+        let _ = writeln!(snippet, "{hook}:");
+        let _ = writeln!(snippet, "  commands:");
+        let _ = writeln!(snippet, "    be:");
+        let _ = writeln!(snippet, "      run: {command}");
+    }
+    snippet
+}
+
+fn pre_commit_snippet(pre_push: bool) -> String {
+    let mut hooks = vec![("be", PRE_COMMIT, "pre-commit")];
+    if pre_push {
+        hooks.push(("be-pre-push", PRE_PUSH, "pre-push"));
+    }
+    let mut snippet = String::from("repos:\n  - repo: local\n    hooks:\n");
+    // Writing to a `String` can't fail
+    for (id, command, stage) in hooks {
+        let _ = writeln!(snippet, "      - id: {id}");
+        let _ = writeln!(snippet, "        name: be");
+        let _ = writeln!(snippet, "        entry: sh -c '{command}'");
+        let _ = writeln!(snippet, "        language: system");
+        let _ = writeln!(snippet, "        pass_filenames: false");
+        let _ = writeln!(snippet, "        stages: [{stage}]");
+    }
+    snippet
+}
@@ -1,17 +1,41 @@
-use crate::{context::cx, hashing::WithHashingExt as _};
+use crate::{context::cx, git, hashing::WithHashingExt as _};
 use bytes::Bytes;
 use camino::Utf8Path;
 use color_eyre::eyre;
-use std::io::Write as _;
-use tempfile::tempdir;
+use std::{
+    fs::Permissions,
+    io::Write as _,
+    os::unix::fs::PermissionsExt as _,
+    sync::{Arc, Mutex},
+};
 use tokio::{
     fs::{self, File},
     io::{self, AsyncReadExt as _, AsyncWriteExt as _},
 };
-use tracing_indicatif::writer::get_indicatif_stdout_writer;
+use tracing_indicatif::{indicatif_println, writer::get_indicatif_stdout_writer};
+
+/// `stdin`, `stdout`, and messages of a command forwarded to `be daemon`, which stand in for the
+/// daemon's own while it runs the command
+#[derive(Default)]
+pub struct Forwarded {
+    pub stdin: Mutex<Option<Bytes>>,
+    pub stdout: Mutex<Vec<u8>>,
+    pub stderr: Mutex<String>,
+}
+
+tokio::task_local! {
+    pub static FORWARDED: Arc<Forwarded>;
+}
 
 #[tracing::instrument]
 pub async fn read_stdin() -> eyre::Result<(Bytes, u64)> {
+    if let Ok(Some(bytes)) = FORWARDED.try_with(|forwarded| forwarded.stdin.lock().unwrap().take())
+    {
+        let mut reader = (&bytes[..]).with_hashing();
+        io::copy(&mut reader, &mut io::sink()).await?;
+        let hash = reader.hash();
+        return Ok((bytes, hash));
+    }
     let mut stdin = io::stdin().with_hashing();
     let mut bytes = Vec::new();
     stdin.read_to_end(&mut bytes).await?;
@@ -21,6 +45,12 @@ pub async fn read_stdin() -> eyre::Result<(Bytes, u64)> {
 
 #[tracing::instrument(skip_all)]
 pub async fn write_stdout(bytes: Bytes) -> eyre::Result<()> {
+    if FORWARDED
+        .try_with(|forwarded| forwarded.stdout.lock().unwrap().extend_from_slice(&bytes))
+        .is_ok()
+    {
+        return Ok(());
+    }
     if let Some(mut stdout) = get_indicatif_stdout_writer() {
         tokio::task::spawn_blocking(move || stdout.write_all(&bytes)).await??;
     } else {
@@ -29,28 +59,154 @@ pub async fn write_stdout(bytes: Bytes) -> eyre::Result<()> {
     Ok(())
 }
 
+/// Print a line on `stdout`, like `indicatif_println!`, but into the response when `be daemon` is
+/// running the command
+pub fn print_stdout(text: &str) {
+    if FORWARDED
+        .try_with(|forwarded| {
+            let mut stdout = forwarded.stdout.lock().unwrap();
+            stdout.extend_from_slice(text.as_bytes());
+            stdout.push(b'\n');
+        })
+        .is_ok()
+    {
+        return;
+    }
+    indicatif_println!("{text}");
+}
+
+/// Contents of a file and their hash, or its staged contents with `--staged` if it's in the index
 #[tracing::instrument]
 pub async fn read_file(path: &Utf8Path) -> eyre::Result<(Bytes, u64)> {
+    let cx = cx();
+    if cx.staged
+        && let Some(bytes) = git::staged_file(path).await?
+    {
+        let mut reader = (&bytes[..]).with_hashing();
+        io::copy(&mut reader, &mut io::sink()).await?;
+        let hash = reader.hash();
+        cx.metrics.read(bytes.len());
+        return Ok((bytes, hash));
+    }
+    read_working_file(path).await
+}
+
+async fn read_working_file(path: &Utf8Path) -> eyre::Result<(Bytes, u64)> {
     let cx = cx();
     let _permit = cx.file_permits.acquire().await?;
     let mut file = File::open(path).await?.with_hashing();
     let mut bytes = Vec::new();
     file.read_to_end(&mut bytes).await?;
     let hash = file.hash();
+    cx.metrics.read(bytes.len());
     Ok((Bytes::from(bytes), hash))
 }
 
 #[tracing::instrument(skip(bytes))]
 pub async fn write_file(path: &Utf8Path, bytes: Bytes) -> eyre::Result<()> {
+    write_file_if(path, bytes, None).await?;
+    Ok(())
+}
+
+/// Like `write_file`, but leaves the file alone if its contents no longer hash to `input_hash`
+/// (e.g. someone saved it in their editor while it was being formatted). Returns whether the file
+/// was written.
+///
+/// With `--staged`, `input_hash` is of the staged contents, which are replaced and re-staged. The
+/// working tree is only written too if it has no unstaged changes, so they aren't lost.
+#[tracing::instrument(skip(bytes))]
+pub async fn write_file_unless_changed(
+    path: &Utf8Path,
+    bytes: Bytes,
+    input_hash: u64,
+) -> eyre::Result<bool> {
+    if !cx().staged {
+        return write_file_if(path, bytes, Some(input_hash)).await;
+    }
+    let (_, staged_hash) = read_file(path).await?;
+    if staged_hash != input_hash {
+        tracing::warn!(
+            "Not staging `{path}`, since it was staged again while `be` was working on it"
+        );
+        return Ok(false);
+    }
+    let (_, working_hash) = read_working_file(path).await?;
+    if working_hash == input_hash {
+        write_file_if(path, bytes.clone(), Some(input_hash)).await?;
+    } else {
+        tracing::info!("Only staging `{path}`, since it has unstaged changes");
+    }
+    git::stage_file(path, &bytes).await?;
+    Ok(true)
+}
+
+async fn write_file_if(
+    path: &Utf8Path,
+    bytes: Bytes,
+    input_hash: Option<u64>,
+) -> eyre::Result<bool> {
     let cx = cx();
-    let temp_dir = tempdir()?;
-    let temp_path = temp_dir.path().join(path.file_name().unwrap_or("temp"));
+    // Next to the destination, so renaming it over the destination can't cross filesystems
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_str().is_empty() => parent,
+        _ => Utf8Path::new("."),
+    };
     let permit = cx.file_permits.acquire().await?;
-    let mut temp_file = File::create(&temp_path).await?;
+    let (temp_file, temp_path) = tempfile::Builder::new()
+        .prefix(&format!(".{}.", path.file_name().unwrap_or("temp")))
+        .suffix(".be")
+        .permissions(Permissions::from_mode(0o666))
+        .tempfile_in(dir)?
+        .into_parts();
+    let mut temp_file = File::from_std(temp_file);
     temp_file.write_all(&bytes).await?;
     temp_file.flush().await?;
+    if let Ok(metadata) = fs::metadata(path).await {
+        temp_file.set_permissions(metadata.permissions()).await?;
+    }
+    temp_file.sync_all().await?;
     drop(temp_file);
     drop(permit);
-    fs::rename(temp_path, path).await?;
+    // Check as late as possible, right before replacing the file
+    let previous = if fs::try_exists(path).await? {
+        Some(read_working_file(path).await?)
+    } else {
+        None
+    };
+    if let Some(input_hash) = input_hash
+        && previous.as_ref().map(|(_, hash)| *hash) != Some(input_hash)
+    {
+        tracing::warn!("Not writing `{path}`, since it changed while `be` was working on it");
+        return Ok(false);
+    }
+    let previous = previous.map(|(bytes, _)| bytes);
+    journal_write(path, previous.as_deref(), Some(&bytes)).await?;
+    temp_path.persist(path)?;
+    cx.metrics.written(bytes.len());
+    Ok(true)
+}
+
+/// Remove a file, keeping a copy of it for `be undo`
+#[tracing::instrument]
+pub async fn remove_file(path: &Utf8Path) -> eyre::Result<()> {
+    let (bytes, _) = read_working_file(path).await?;
+    journal_write(path, Some(&bytes), None).await?;
+    fs::remove_file(path).await?;
     Ok(())
 }
+
+async fn journal_write(
+    path: &Utf8Path,
+    previous: Option<&[u8]>,
+    new: Option<&[u8]>,
+) -> eyre::Result<()> {
+    if previous == new {
+        return Ok(());
+    }
+    let cx = cx();
+    let git_root = cx.cache.git_root().await?;
+    cx.state()
+        .await?
+        .journal_write(git_root, path, previous, new)
+        .await
+}
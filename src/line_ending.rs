@@ -0,0 +1,57 @@
+use bytes::{BufMut as _, Bytes, BytesMut};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    /// Whichever line ending is more common, preferring LF
+    pub fn detect(bytes: &[u8]) -> Self {
+        let mut lf_count = 0;
+        let mut crlf_count = 0;
+        for (i, byte) in bytes.iter().enumerate() {
+            if *byte == b'\n' {
+                if i > 0 && bytes[i - 1] == b'\r' {
+                    crlf_count += 1;
+                } else {
+                    lf_count += 1;
+                }
+            }
+        }
+        if crlf_count > lf_count {
+            Self::Crlf
+        } else {
+            Self::Lf
+        }
+    }
+
+    /// Whether every line in `bytes` ends with this line ending
+    pub fn matches(self, bytes: &[u8]) -> bool {
+        match self {
+            Self::Lf => !bytes.windows(2).any(|window| window == b"\r\n"),
+            Self::Crlf => bytes
+                .iter()
+                .enumerate()
+                .all(|(i, byte)| *byte != b'\n' || (i > 0 && bytes[i - 1] == b'\r')),
+        }
+    }
+
+    pub fn apply(self, bytes: Bytes) -> Bytes {
+        if self.matches(&bytes) {
+            return bytes;
+        }
+        let mut output = BytesMut::with_capacity(bytes.len());
+        let mut iter = bytes.iter().peekable();
+        while let Some(byte) = iter.next() {
+            match byte {
+                b'\r' if iter.peek() == Some(&&b'\n') => {}
+                b'\n' if self == Self::Crlf => output.put_slice(b"\r\n"),
+                byte => output.put_u8(*byte),
+            }
+        }
+        output.freeze()
+    }
+}
@@ -1,104 +1,739 @@
+mod baseline;
+mod cabal;
+mod duplicates;
+mod exports;
+mod ghc;
+pub mod haddock;
+mod haskell_imports;
+mod hie;
+mod hpack;
+mod imports;
+mod module_name;
+mod nix;
+mod output;
+mod pool;
+mod refactor;
+mod shell;
+mod signatures;
+mod size;
+mod stan;
+mod typos;
+mod weeder;
+
+pub use output::{Diagnostic, Position, Range};
+pub use shell::ShellcheckHint;
+pub use stan::StanSeverity;
+
 use crate::{
-    cli::lint::{Args, Command, HaskellArgs},
+    cli::{
+        fix::HaskellArgs as FixHaskellArgs,
+        lint::{
+            Args, CabalArgs, Command, Format, HaddockArgs, HaskellArgs, ImportsArgs, NixArgs,
+            ShellArgs, TyposArgs,
+        },
+    },
+    config::{BuiltinLintConfig, LintConfig, Pipeline},
     context::cx,
-    exec, git,
-    io::read_file,
+    events::{Outcome, message},
+    exec,
+    exit::Findings,
+    fix,
+    git::{self, ChangedLines},
+    io::{print_stdout, read_file, read_stdin, write_file, write_file_unless_changed},
+    query, queue,
+    utils::table,
+    watch,
 };
 use bytes::Bytes;
-use camino::Utf8Path;
+use camino::{Utf8Path, Utf8PathBuf};
 use color_eyre::eyre;
 use derive_more::Display;
 use num_format::{Locale, ToFormattedString as _};
 use std::{
+    collections::BTreeMap,
     fmt::{self, Display},
     io::IsTerminal as _,
     os::unix::process::ExitStatusExt as _,
     process::Stdio,
+    str,
 };
-use tokio::{io::AsyncWriteExt as _, process};
-use tracing_indicatif::{indicatif_eprintln, indicatif_println};
+use tree_sitter::{Node, Parser, Tree};
+use twox_hash::XxHash3_64;
 
 #[tracing::instrument(skip_all)]
 pub async fn run(args: &Args) -> eyre::Result<()> {
-    if let Some(Command::Haskell(args)) = &args.command {
-        run_lint_haskell(args).await?;
+    if args.watch {
+        return watch::run(|| Box::pin(run_lint(args))).await;
+    }
+    run_lint(args).await
+}
+
+#[tracing::instrument(skip_all)]
+async fn run_lint(args: &Args) -> eyre::Result<()> {
+    let mut summary = match &args.command {
+        Some(Command::Haskell(args)) => run_lint_haskell(args).await?,
+        Some(Command::Haddock(args)) => return haddock::run(args).await,
+        Some(Command::Typos(args)) => typos::run(args).await?,
+        Some(Command::Duplicates(args)) => duplicates::run(args).await?,
+        Some(Command::Cabal(args)) => cabal::run(args).await?,
+        Some(Command::Shell(args)) => shell::run(args).await?,
+        Some(Command::Nix(args)) => nix::run(args).await?,
+        Some(Command::Ghc(args)) => ghc::run(args).await?,
+        Some(Command::Weeder(args)) => weeder::run(args).await?,
+        Some(Command::Stan(args)) => stan::run(args).await?,
+        Some(Command::Hpack(args)) => hpack::run(args).await?,
+        Some(Command::Imports(args)) => imports::run(args).await?,
+        Some(Command::HaskellImports(args)) => haskell_imports::run(args).await?,
+        None => run_lint_all(&args.packages).await?,
+    };
+
+    record_lint_run(args, &summary).await;
+
+    // After recording, so `be report` still tracks every finding
+    if args.changed_lines_only {
+        summary.retain_changed(&git::changed_lines().await?);
+    }
+
+    // Findings are printed once every file is linted, so they can be in any format
+    summary.hints.sort_by(|a, b| {
+        (&a.file, a.start_line, a.start_column).cmp(&(&b.file, b.start_line, b.start_column))
+    });
+
+    let stdout = output::render(args.format, &summary.hints, std::io::stdout().is_terminal())?;
+    if !stdout.is_empty() {
+        print_stdout(stdout.trim_end_matches('\n'));
+    }
+
+    if let Some(path) = &args.output {
+        write_report(path, args.format, &summary).await?;
+    }
+
+    // Each language prints its own counts, so only total them up when there are several
+    if args.command.is_none() {
+        message!("{}", summary.table());
+    }
+
+    if args.command.is_none() && !cx().cache.config().await?.lint.haddock.packages.is_empty() {
+        haddock::run(&HaddockArgs {
+            paths: vec![],
+            packages: vec![],
+        })
+        .await?;
+    }
+
+    if summary.hints.is_empty() {
         return Ok(());
     }
 
-    let haskell = tokio::spawn(async {
+    let message = summary.findings_message();
+
+    if summary
+        .hints
+        .iter()
+        .any(|hint| hint.severity >= args.fail_on)
+    {
+        return Err(Findings(message).into());
+    }
+
+    message!("{message}");
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn run_lint_all(packages: &[String]) -> eyre::Result<Summary> {
+    let cx = cx();
+
+    let haskell = tokio::spawn({
         let args = HaskellArgs {
             paths: vec![],
+            packages: packages.to_vec(),
             stdin: false,
+            stdin_filepath: None,
+            fix: false,
+            baseline: None,
+            update_baseline: false,
         };
-        run_lint_haskell(&args).await
+        async move { run_lint_haskell(&args).await }
     });
 
-    haskell.await??;
+    // Spell-checking is opt-in by installing `typos`
+    let typos = if cx.cache.config().await?.is_tool_enabled("typos")
+        && cx.cache.which("typos").await.is_ok()
+    {
+        let args = TyposArgs { paths: vec![] };
+        Some(tokio::spawn(async move { typos::run(&args).await }))
+    } else {
+        tracing::debug!("Not spell-checking, since `typos` isn't installed");
+        None
+    };
 
-    Ok(())
-}
+    let cabal = tokio::spawn(async move { cabal::run(&CabalArgs { paths: vec![] }).await });
 
-// TODO: Handle input on `stdin`
-#[tracing::instrument(skip_all)]
-async fn run_lint_haskell(args: &HaskellArgs) -> eyre::Result<()> {
-    let changed_files = git::changed_haskell_files().await?;
+    // Like spell-checking, linting shell scripts is opt-in by installing `shellcheck`
+    let shell = if cx.cache.config().await?.is_tool_enabled("shellcheck")
+        && cx.cache.which("shellcheck").await.is_ok()
+    {
+        let args = ShellArgs {
+            paths: vec![],
+            packages: packages.to_vec(),
+        };
+        Some(tokio::spawn(async move { shell::run(&args).await }))
+    } else {
+        tracing::debug!("Not linting shell scripts, since `shellcheck` isn't installed");
+        None
+    };
 
-    let paths = if args.paths.is_empty() {
-        changed_files
+    // Also opt-in by installing `statix` or `deadnix`
+    let nix = if nix::linters().await?.is_empty() {
+        None
     } else {
-        args.paths.clone()
+        let args = NixArgs {
+            paths: vec![],
+            packages: packages.to_vec(),
+        };
+        Some(tokio::spawn(async move { nix::run(&args).await }))
     };
 
-    let mut handles = Vec::new();
+    // Only checked when there are rules to check against
+    let imports = if cx.cache.config().await?.lint.imports.rules.is_empty() {
+        None
+    } else {
+        let args = ImportsArgs {
+            paths: vec![],
+            packages: packages.to_vec(),
+        };
+        Some(tokio::spawn(async move { imports::run(&args).await }))
+    };
+
+    let mut summary = haskell.await??;
+
+    summary.extend(cabal.await??);
+
+    if let Some(typos) = typos {
+        summary.extend(typos.await??);
+    }
+
+    if let Some(shell) = shell {
+        summary.extend(shell.await??);
+    }
+
+    if let Some(nix) = nix {
+        summary.extend(nix.await??);
+    }
+
+    if let Some(imports) = imports {
+        let mut imports = imports.await??;
+        // They're the same files `be lint haskell` counted
+        imports.file_count = 0;
+        summary.extend(imports);
+    }
+
+    Ok(summary)
+}
+
+#[tracing::instrument(skip_all)]
+async fn run_lint_haskell(args: &HaskellArgs) -> eyre::Result<Summary> {
+    let (paths, mut summary) = lint_haskell_files(args).await?;
+
+    if let Some(path) = &args.baseline {
+        if args.update_baseline {
+            baseline::update(path, &paths, &summary.hints).await?;
+        }
+        baseline::filter(path, &mut summary).await?;
+    }
+
+    Ok(summary)
+}
 
-    for path in paths {
-        handles.push(tokio::spawn(async move { lint_haskell(&path).await }));
+/// Lint the files `be lint haskell` was asked to, returning which ones they were
+async fn lint_haskell_files(args: &HaskellArgs) -> eyre::Result<(Vec<Utf8PathBuf>, Summary)> {
+    if args.stdin {
+        let path = args
+            .stdin_filepath
+            .as_deref()
+            .unwrap_or(Utf8Path::new("<stdin>"));
+        let (input_bytes, input_hash) = read_stdin().await?;
+        let (_, hints) = lint_haskell_bytes(path, input_bytes, input_hash, false).await?;
+        let mut summary = Summary::default();
+        summary.add(hints);
+        return Ok((vec![path.to_path_buf()], summary));
     }
 
-    let total_count = handles.len();
+    if args.fix {
+        fix::run_fix_haskell(&FixHaskellArgs {
+            paths: args.paths.clone(),
+            packages: args.packages.clone(),
+        })
+        .await?;
+    }
+
+    let paths = if !args.paths.is_empty() || !args.packages.is_empty() {
+        git::haskell_files(&args.paths, &args.packages, Pipeline::Lint).await?
+    } else if args.update_baseline {
+        // The baseline is for the whole repo, not just what changed
+        git::all_haskell_files(Pipeline::Lint).await?
+    } else {
+        git::changed_haskell_files(Pipeline::Lint).await?
+    };
+
+    let handles = queue::spawn(
+        paths.clone(),
+        |path| async move { lint_haskell(&path).await },
+    )
+    .await?;
+
+    let mut summary = Summary::default();
     let mut linted_count = 0;
 
     for handle in handles {
-        if let Some(true) = handle.await?? {
+        let (linted, hints) = handle.await??;
+        if linted {
             linted_count += 1;
         }
+        summary.add(hints);
     }
 
-    indicatif_eprintln!(
+    message!(
         "Linted {linted_count} of {total_count} Haskell {files}",
         linted_count = linted_count.to_formatted_string(&Locale::en),
-        total_count = total_count.to_formatted_string(&Locale::en),
-        files = if total_count == 1 { "file" } else { "files" },
+        total_count = summary.file_count.to_formatted_string(&Locale::en),
+        files = if summary.file_count == 1 {
+            "file"
+        } else {
+            "files"
+        },
     );
 
-    Ok(())
+    Ok((paths, summary))
+}
+
+/// Files looked at and their findings
+#[derive(Default)]
+struct Summary {
+    file_count: usize,
+    /// Counts by severity and hint
+    finding_counts: BTreeMap<(String, String), usize>,
+    hints: Vec<HlintHint>,
+}
+
+impl Summary {
+    /// Keep one file's findings
+    fn add(&mut self, hints: Vec<HlintHint>) {
+        self.file_count += 1;
+        for hint in &hints {
+            *self
+                .finding_counts
+                .entry((hint.severity.to_string(), hint.hint.clone()))
+                .or_default() += 1;
+        }
+        self.hints.extend(hints);
+    }
+
+    /// Files and findings by severity, in aligned columns
+    fn table(&self) -> String {
+        let count = |severity| {
+            self.hints
+                .iter()
+                .filter(|hint| hint.severity == severity)
+                .count()
+        };
+        table(&[
+            ("Files", self.file_count),
+            ("Errors", count(HlintSeverity::Error)),
+            ("Warnings", count(HlintSeverity::Warning)),
+            ("Suggestions", count(HlintSeverity::Suggestion)),
+        ])
+    }
+
+    /// Like "Found 3 findings (1 error, 2 warnings)"
+    fn findings_message(&self) -> String {
+        let mut severity_counts = BTreeMap::new();
+        for hint in &self.hints {
+            *severity_counts.entry(hint.severity).or_insert(0_usize) += 1;
+        }
+
+        let counts = severity_counts
+            .into_iter()
+            .rev()
+            .map(|(severity, count)| {
+                format!(
+                    "{count} {severity}",
+                    count = count.to_formatted_string(&Locale::en),
+                    severity = severity.name(count),
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let count = self.hints.len();
+        format!(
+            "Found {count} {findings} ({counts})",
+            count = count.to_formatted_string(&Locale::en),
+            findings = if count == 1 { "finding" } else { "findings" },
+        )
+    }
+
+    fn extend(&mut self, other: Self) {
+        self.file_count += other.file_count;
+        for (key, count) in other.finding_counts {
+            *self.finding_counts.entry(key).or_default() += count;
+        }
+        self.hints.extend(other.hints);
+    }
+
+    /// Only keep findings on lines which changed, for `--changed-lines-only`
+    fn retain_changed(&mut self, changed_lines: &ChangedLines) {
+        self.retain(|hint| {
+            changed_lines.overlaps(Utf8Path::new(&hint.file), hint.start_line, hint.end_line)
+        });
+    }
+
+    /// Only keep some findings, as if the rest were never found
+    fn retain(&mut self, mut keep: impl FnMut(&HlintHint) -> bool) {
+        let hints = std::mem::take(&mut self.hints)
+            .into_iter()
+            .filter(|hint| keep(hint))
+            .collect();
+        let mut summary = Self::default();
+        summary.add(hints);
+        summary.file_count = self.file_count;
+        *self = summary;
+    }
+}
+
+/// Write findings to a file for CI to keep, replacing it all at once
+#[tracing::instrument(skip(summary))]
+async fn write_report(path: &Utf8Path, format: Format, summary: &Summary) -> eyre::Result<()> {
+    let report = output::render(format, &summary.hints, false)?;
+    write_file(path, Bytes::from(report)).await
+}
+
+/// Keep aggregate finding counts for `be report --trend`. Only runs over the whole repo are kept,
+/// since counts from a package or from changed files aren't comparable with each other.
+#[tracing::instrument(skip_all)]
+async fn record_lint_run(args: &Args, summary: &Summary) {
+    let result = async {
+        let cx = cx();
+        let is_watch_rerun = cx.watched.lock().unwrap().is_some();
+        if args.command.is_some()
+            || !args.packages.is_empty()
+            || cx.staged
+            || is_watch_rerun
+            || !cx.all_files().await?
+        {
+            return Ok(());
+        }
+        let git_root = cx.cache.git_root().await?;
+        let commit = git::head_commit().await?;
+        let state = cx.state().await?;
+        state
+            .record_lint_run(
+                git_root,
+                &commit,
+                summary.file_count,
+                &summary.finding_counts,
+            )
+            .await
+    };
+
+    if let Err(error) = result.await {
+        tracing::warn!("Failed to record lint run: {error}");
+    }
 }
 
 #[tracing::instrument(fields(indicatif.pb_show))]
-async fn lint_haskell(path: &Utf8Path) -> eyre::Result<Option<bool>> {
+async fn lint_haskell(path: &Utf8Path) -> eyre::Result<(bool, Vec<HlintHint>)> {
+    let (input_bytes, input_hash) = read_file(path).await?;
+    lint_haskell_bytes(path, input_bytes, input_hash, true).await
+}
+
+/// Findings for code read from `path`, or shown as `path` if it came from `stdin`
+/// Findings in a Haskell file's unsaved contents, for `be lsp`
+pub async fn lint_haskell_diagnostics(
+    path: &Utf8Path,
+    input_bytes: Bytes,
+) -> eyre::Result<Vec<Diagnostic>> {
+    let input_hash = XxHash3_64::oneshot(&input_bytes);
+    let (_, hints) = lint_haskell_bytes(path, input_bytes, input_hash, false).await?;
+    Ok(output::diagnostics(&hints))
+}
+
+async fn lint_haskell_bytes(
+    path: &Utf8Path,
+    input_bytes: Bytes,
+    input_hash: u64,
+    record_duration: bool,
+) -> eyre::Result<(bool, Vec<HlintHint>)> {
     let cx = cx();
 
-    let (input_bytes, input_hash) = read_file(path).await?;
+    let mut hints = builtin_hints(path, &input_bytes, input_hash).await?;
 
-    if let Some(hints) = cx.cache.is_haskell_linted(input_hash).await? {
+    let linted = if !cx.cache.config().await?.is_tool_enabled("hlint") {
+        tracing::trace!("Not running `hlint`, since it's disabled");
+        false
+    } else if let Some(hlint_hints) = cx.cache.is_haskell_linted(input_hash).await? {
         tracing::trace!("Using cached lint results");
-        for hint in hints {
-            indicatif_println!("{hint}");
+        hints.extend(hlint_hints.into_iter().map(|hint| HlintHint {
+            tool: "hlint",
+            ..hint
+        }));
+        false
+    } else {
+        tracing::trace!("Linting");
+
+        let (hlint_hints, duration) = exec::timed(hlint(Some(path), input_bytes)).await?;
+
+        if record_duration {
+            cx.cache
+                .record_duration("hlint", input_hash, path, duration)
+                .await?;
         }
-        return Ok(Some(false));
+
+        cx.cache
+            .mark_haskell_linted(input_hash, &hlint_hints)
+            .await?;
+
+        hints.extend(hlint_hints);
+        true
+    };
+
+    // Cached findings keep the path of whichever file had the same contents first
+    for hint in &mut hints {
+        hint.file.clear();
+        hint.file.push_str(path.as_str());
     }
 
-    tracing::trace!("Linting");
+    hints.sort_by_key(|hint| (hint.start_line, hint.start_column));
+
+    Ok((linted, hints))
+}
+
+/// Apply the refactorings `hlint` suggests. Returns how many hints were applied.
+#[tracing::instrument(fields(indicatif.pb_show))]
+pub async fn fix_haskell(path: &Utf8Path) -> eyre::Result<usize> {
+    let (input_bytes, input_hash) = read_file(path).await?;
+
+    let hints = haskell_hints(path, input_bytes.clone()).await?;
+
+    let fixable: Vec<_> = hints
+        .iter()
+        .filter(|hint| refactor::is_fixable(hint))
+        .collect();
+
+    if fixable.is_empty() {
+        tracing::trace!("Nothing to fix");
+        return Ok(0);
+    }
+
+    tracing::trace!("Fixing");
+
+    let output_bytes = refactor::apply(input_bytes.clone(), &fixable).await?;
+
+    if output_bytes == input_bytes {
+        tracing::debug!("`refactor` didn't change anything");
+        return Ok(0);
+    }
+
+    tracing::trace!("Writing");
+
+    if !write_file_unless_changed(path, output_bytes, input_hash).await? {
+        return Ok(0);
+    }
+
+    Ok(fixable.len())
+}
+
+/// One of `be`'s own lints, run on the tree-sitter syntax tree
+struct Builtin {
+    name: &'static str,
+    default_severity: HlintSeverity,
+    config: fn(&LintConfig) -> &BuiltinLintConfig,
+    /// Settings which change the findings, so they're part of the cache key
+    settings: fn(&LintConfig, &BuiltinFile) -> String,
+    hints: fn(&LintConfig, &BuiltinFile, &str, &Tree) -> Vec<HlintHint>,
+}
+
+/// What `be`'s own lints know about a file besides its contents
+struct BuiltinFile {
+    /// Names the module may have, from where it is in its package's source directories
+    expected_modules: Vec<String>,
+}
+
+const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: signatures::NAME,
+        default_severity: HlintSeverity::Warning,
+        config: |config| &config.missing_signatures,
+        settings: |_, _| String::new(),
+        hints: |_, _, source_code, tree| signatures::hints(source_code, tree),
+    },
+    Builtin {
+        name: exports::NAME,
+        default_severity: HlintSeverity::Suggestion,
+        config: |config| &config.explicit_exports,
+        settings: |_, _| String::new(),
+        hints: |_, _, source_code, tree| exports::hints(source_code, tree),
+    },
+    Builtin {
+        name: size::NAME,
+        default_severity: HlintSeverity::Warning,
+        config: |config| &config.module_size,
+        settings: |config, _| size::settings(config),
+        hints: |config, _, source_code, tree| size::hints(config, source_code, tree),
+    },
+    Builtin {
+        name: module_name::NAME,
+        default_severity: HlintSeverity::Warning,
+        config: |config| &config.module_name,
+        settings: |_, file| module_name::settings(file),
+        hints: |_, file, source_code, tree| module_name::hints(file, source_code, tree),
+    },
+];
+
+/// Run `be`'s own lints, reusing cached results for the same contents
+#[tracing::instrument(skip(bytes))]
+async fn builtin_hints(path: &Utf8Path, bytes: &[u8], hash: u64) -> eyre::Result<Vec<HlintHint>> {
+    let cx = cx();
+
+    let config = &cx.cache.config().await?.lint;
+
+    let git_root = cx.cache.git_root().await?;
+    let absolute_path = Utf8PathBuf::try_from(std::path::absolute(path)?)?;
+    let relative_path = absolute_path
+        .strip_prefix(git_root)
+        .unwrap_or(&absolute_path);
 
-    let hints = hlint(Some(path), input_bytes).await?;
+    let file = BuiltinFile {
+        expected_modules: module_name::expected_modules(relative_path, cx.cache.packages().await?),
+    };
+
+    let mut tree = None;
+    let mut all_hints = Vec::new();
+
+    for builtin in BUILTINS {
+        let lint_config = (builtin.config)(config);
+        let severity = lint_config.severity(relative_path, builtin.default_severity);
+        if severity == HlintSeverity::Ignore {
+            continue;
+        }
+
+        let settings = (builtin.settings)(config, &file);
+        let cache_name = if settings.is_empty() {
+            String::from(builtin.name)
+        } else {
+            format!("{}:{settings}", builtin.name)
+        };
 
-    for hint in &hints {
-        indicatif_println!("{hint}");
+        let mut hints = if let Some(hints) = cx.cache.builtin_hints(&cache_name, hash).await? {
+            hints
+        } else {
+            if tree.is_none() {
+                tree = Some(parse(bytes)?);
+            }
+            let (source_code, tree) = tree.as_ref().unwrap();
+            let hints = (builtin.hints)(config, &file, source_code, tree);
+            cx.cache
+                .mark_builtin_hints(&cache_name, hash, &hints)
+                .await?;
+            hints
+        };
+
+        hints.retain(|hint| {
+            !hint
+                .module
+                .iter()
+                .any(|module| lint_config.exceptions.contains(module))
+        });
+
+        for hint in &mut hints {
+            hint.tool = "be";
+            hint.severity = severity;
+            hint.file.clear();
+            hint.file.push_str(path.as_str());
+        }
+
+        all_hints.extend(hints);
     }
 
-    cx.cache.mark_haskell_linted(input_hash, &hints).await?;
+    Ok(all_hints)
+}
+
+pub fn parse(bytes: &[u8]) -> eyre::Result<(String, Tree)> {
+    let source_code = String::from(str::from_utf8(bytes)?);
+    let mut parser = Parser::new();
+    parser.set_language(&query::LANGUAGE)?;
+    let tree = parser.parse(&source_code, None).unwrap();
+    Ok((source_code, tree))
+}
 
-    Ok(Some(true))
+/// The module header, which comes after any pragmas and comments at the top of the file
+pub fn header(root: Node) -> Option<Node> {
+    let mut cursor = root.walk();
+    root.named_children(&mut cursor)
+        .find(|node| node.kind() == "header")
+}
+
+/// The name in the module header, or `None` for a module without one, which is `Main`
+pub fn header_module<'a>(source_code: &'a str, root: Node) -> Option<&'a str> {
+    header(root)
+        .and_then(|header| header.child_by_field_name("module"))
+        .and_then(|module| source_code.get(module.byte_range()))
+}
+
+/// A finding from one of `be`'s own lints, in the same shape as `hlint`'s
+fn builtin_hint(source_code: &str, root: Node, node: Node, decl: &str, hint: &str) -> HlintHint {
+    let module = header_module(source_code, root).map(String::from);
+    let range = node.range();
+    let from = source_code
+        .get(node.byte_range())
+        .and_then(|text| text.lines().next())
+        .unwrap_or_default();
+    HlintHint {
+        module: module.into_iter().collect(),
+        decl: vec![String::from(decl)],
+        severity: HlintSeverity::Warning,
+        hint: String::from(hint),
+        file: String::new(),
+        start_line: range.start_point.row + 1,
+        start_column: range.start_point.column + 1,
+        end_line: range.end_point.row + 1,
+        end_column: range.end_point.column + 1,
+        from: String::from(from),
+        to: None,
+        note: Vec::new(),
+        refactorings: String::from("[]"),
+        tool: "be",
+    }
+}
+
+/// Lint with `hlint` and `be`'s own lints, reusing cached results for the same contents
+#[tracing::instrument(skip(bytes))]
+pub async fn haskell_hints(path: &Utf8Path, bytes: Bytes) -> eyre::Result<Vec<HlintHint>> {
+    let cx = cx();
+
+    let hash = XxHash3_64::oneshot(&bytes);
+
+    let mut hints = builtin_hints(path, &bytes, hash).await?;
+
+    if !cx.cache.config().await?.is_tool_enabled("hlint") {
+        return Ok(hints);
+    }
+
+    if let Some(mut hlint_hints) = cx.cache.is_haskell_linted(hash).await? {
+        for hint in &mut hlint_hints {
+            hint.tool = "hlint";
+            hint.file.clear();
+            hint.file.push_str(path.as_str());
+        }
+        hints.extend(hlint_hints);
+    } else {
+        let hlint_hints = hlint(Some(path), bytes).await?;
+        cx.cache.mark_haskell_linted(hash, &hlint_hints).await?;
+        hints.extend(hlint_hints);
+    }
+
+    Ok(hints)
 }
 
 #[derive(serde::Deserialize, serde::Serialize)]
@@ -106,8 +741,8 @@ async fn lint_haskell(path: &Utf8Path) -> eyre::Result<Option<bool>> {
 pub struct HlintHint {
     module: Vec<String>,
     decl: Vec<String>,
-    severity: HlintSeverity,
-    hint: String,
+    pub severity: HlintSeverity,
+    pub hint: String,
     file: String,
     start_line: usize,
     start_column: usize,
@@ -117,67 +752,138 @@ pub struct HlintHint {
     to: Option<String>,
     note: Vec<String>,
     refactorings: String,
+    /// What found it, which isn't cached since each cache table belongs to one tool
+    #[serde(skip)]
+    pub tool: &'static str,
+}
+
+/// Linters return whether they ran, rather than using cached results, and their findings
+impl Outcome for (bool, Vec<HlintHint>) {
+    fn outcome(&self) -> &'static str {
+        if self.0 { "linted" } else { "cached" }
+    }
+
+    fn findings(&self) -> Option<usize> {
+        Some(self.1.len())
+    }
+}
+
+impl HlintHint {
+    /// Human-readable finding, with the first line bold and underlined if `color` is set
+    fn render(&self, color: bool) -> RenderedHint<'_> {
+        RenderedHint { hint: self, color }
+    }
 }
 
 impl Display for HlintHint {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        let Self {
+        self.render(std::io::stdout().is_terminal()).fmt(f)
+    }
+}
+
+struct RenderedHint<'a> {
+    hint: &'a HlintHint,
+    color: bool,
+}
+
+impl Display for RenderedHint<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let HlintHint {
             file,
             start_line,
             start_column,
             severity,
             hint,
             from,
+            to,
+            note,
             ..
-        } = self;
+        } = self.hint;
         // TODO: Go beyond MVP formatting
         let first_line = format!("{file}:{start_line}:{start_column}: {severity}: {hint}");
-        if std::io::stdout().is_terminal() {
+        if self.color {
             // Bold and underline
             writeln!(f, "\x1b[1m\x1b[4m{first_line}\x1b[0m")?;
         } else {
             writeln!(f, "{first_line}")?;
         }
         writeln!(f, "Found:\n  {from}")?;
-        if let Some(to) = &self.to {
+        if let Some(to) = to {
             writeln!(f, "Perhaps:\n  {to}")?;
         }
-        for note in &self.note {
+        for note in note {
             writeln!(f, "Note: {note}")?;
         }
         Ok(())
     }
 }
 
-#[derive(Display, serde::Deserialize, serde::Serialize)]
-enum HlintSeverity {
+/// Ordered from least to most severe
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Debug,
+    Display,
+    clap::ValueEnum,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+pub enum HlintSeverity {
+    #[serde(alias = "ignore")]
     Ignore,
+    #[serde(alias = "suggestion")]
     Suggestion,
+    #[serde(alias = "warning")]
     Warning,
+    #[serde(alias = "error")]
     Error,
 }
 
+impl HlintSeverity {
+    /// Lowercase name, pluralized for counts other than one
+    fn name(self, count: usize) -> &'static str {
+        match (self, count == 1) {
+            (Self::Ignore, _) => "ignored",
+            (Self::Suggestion, true) => "suggestion",
+            (Self::Suggestion, false) => "suggestions",
+            (Self::Warning, true) => "warning",
+            (Self::Warning, false) => "warnings",
+            (Self::Error, true) => "error",
+            (Self::Error, false) => "errors",
+        }
+    }
+}
+
+async fn hlint(path: Option<&Utf8Path>, bytes: Bytes) -> eyre::Result<Vec<HlintHint>> {
+    let config = cx().cache.config().await?;
+    match config.tool("hlint").and_then(|tool| tool.batch_size) {
+        Some(batch_size) if batch_size.get() > 1 => {
+            pool::hlint(path, bytes, batch_size.get()).await
+        }
+        _ => hlint_one(path, bytes).await,
+    }
+}
+
 // TODO: Do an `strace`-style tracking of files it reads and processes it spawns. Might be reading
 // Haskell files or talking to Git to infer language extensions and files to look at respectively.
 #[tracing::instrument(skip(bytes))]
-async fn hlint(path: Option<&Utf8Path>, bytes: Bytes) -> eyre::Result<Vec<HlintHint>> {
+async fn hlint_one(path: Option<&Utf8Path>, bytes: Bytes) -> eyre::Result<Vec<HlintHint>> {
     let cx = cx();
 
     let hlint = &cx.cache.which("hlint").await?;
 
+    let tool_permits = cx.tool_permits("hlint").await?;
+
+    let tool_permit = tool_permits.acquire().await?;
     let file_permit = cx.file_permits.acquire().await?;
     let process_permit = cx.process_permits.acquire().await?;
+    let memory_permit = cx.memory.acquire().await?;
 
-    let mut command = if cfg!(target_os = "macos") {
-        let mut command = process::Command::new("/usr/bin/sandbox-exec");
-        command.arg("-p");
-        command.arg(exec::HLINT_PROFILE);
-        command.arg("--");
-        command.arg(hlint);
-        command
-    } else {
-        process::Command::new(hlint)
-    };
+    let mut command = exec::sandboxed(Some("hlint"), exec::HLINT_PROFILE, &[], hlint).await?;
 
     let (hlint_configs, _) = cx.cache.hlint_configs().await?;
 
@@ -191,7 +897,7 @@ async fn hlint(path: Option<&Utf8Path>, bytes: Bytes) -> eyre::Result<Vec<HlintH
         args.push(format!("--hint={config}"));
     }
 
-    let mut child = command
+    let child = command
         .args(args)
         .env_clear()
         .current_dir("/var/empty")
@@ -201,18 +907,12 @@ async fn hlint(path: Option<&Utf8Path>, bytes: Bytes) -> eyre::Result<Vec<HlintH
         .stderr(Stdio::piped())
         .spawn()?;
 
-    let mut stdin = child.stdin.take().unwrap();
-
-    stdin.write_all(&bytes).await?;
-
-    stdin.flush().await?;
-
-    drop(stdin);
-
-    let output = child.wait_with_output().await?;
+    let output = exec::timeout("hlint", exec::output_with_stdin(child, &bytes)).await?;
 
+    drop(memory_permit);
     drop(process_permit);
     drop(file_permit);
+    drop(tool_permit);
 
     if !output.status.success() {
         if let Some(exit_code) = output.status.code() {
@@ -229,6 +929,10 @@ async fn hlint(path: Option<&Utf8Path>, bytes: Bytes) -> eyre::Result<Vec<HlintH
 
     let mut hints: Vec<HlintHint> = serde_json::from_slice(&output.stdout)?;
 
+    for hint in &mut hints {
+        hint.tool = "hlint";
+    }
+
     if let Some(path) = path {
         for hint in &mut hints {
             hint.file.clear();
@@ -0,0 +1,135 @@
+use crate::{
+    events::message,
+    io::write_file,
+    lint::{HlintHint, Summary},
+};
+use bytes::Bytes;
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use tokio::fs;
+use twox_hash::XxHash3_64;
+
+/// Findings which existed when the baseline was last updated, which aren't reported
+#[derive(Default, Serialize, Deserialize)]
+struct Baseline {
+    findings: Vec<Entry>,
+}
+
+/// Identical findings in one file. They're identified by what was found rather than where, so they
+/// stay in the baseline when code around them moves.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    file: String,
+    hint: String,
+    fingerprint: String,
+    count: usize,
+}
+
+/// File, hint, and fingerprint
+type Key = (String, String, String);
+
+fn key(hint: &HlintHint) -> Key {
+    let file = hint.file.strip_prefix("./").unwrap_or(&hint.file);
+    // Reformatting code shouldn't make its findings new
+    let from = hint.from.split_whitespace().collect::<Vec<_>>().join(" ");
+    let fingerprint = format!("{:016x}", XxHash3_64::oneshot(from.as_bytes()));
+    (String::from(file), hint.hint.clone(), fingerprint)
+}
+
+async fn load(path: &Utf8Path) -> eyre::Result<Option<Baseline>> {
+    match fs::read(path).await {
+        Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Replace the baseline's findings for the linted files with their current ones
+#[tracing::instrument(skip(linted, hints))]
+pub async fn update(
+    path: &Utf8Path,
+    linted: &[Utf8PathBuf],
+    hints: &[HlintHint],
+) -> eyre::Result<()> {
+    let linted: HashSet<&str> = linted
+        .iter()
+        .map(|path| path.as_str().strip_prefix("./").unwrap_or(path.as_str()))
+        .collect();
+
+    let mut counts = BTreeMap::<Key, usize>::new();
+    for entry in load(path).await?.unwrap_or_default().findings {
+        if !linted.contains(entry.file.as_str()) {
+            *counts
+                .entry((entry.file, entry.hint, entry.fingerprint))
+                .or_default() += entry.count;
+        }
+    }
+    for hint in hints {
+        *counts.entry(key(hint)).or_default() += 1;
+    }
+
+    let baseline = Baseline {
+        findings: counts
+            .into_iter()
+            .map(|((file, hint, fingerprint), count)| Entry {
+                file,
+                hint,
+                fingerprint,
+                count,
+            })
+            .collect(),
+    };
+    let mut json = serde_json::to_string_pretty(&baseline)?;
+    json.push('\n');
+    write_file(path, Bytes::from(json)).await?;
+
+    message!(
+        "Recorded {count} {findings} in `{path}`",
+        count = hints.len().to_formatted_string(&Locale::en),
+        findings = if hints.len() == 1 {
+            "finding"
+        } else {
+            "findings"
+        },
+    );
+
+    Ok(())
+}
+
+/// Drop findings recorded in the baseline, up to as many of each as it has
+#[tracing::instrument(skip(summary))]
+pub async fn filter(path: &Utf8Path, summary: &mut Summary) -> eyre::Result<()> {
+    let Some(baseline) = load(path).await? else {
+        eyre::bail!("No baseline at `{path}`, create one with `--update-baseline`");
+    };
+
+    let mut remaining = BTreeMap::<Key, usize>::new();
+    for entry in baseline.findings {
+        *remaining
+            .entry((entry.file, entry.hint, entry.fingerprint))
+            .or_default() += entry.count;
+    }
+
+    let count = summary.hints.len();
+    summary.retain(|hint| match remaining.get_mut(&key(hint)) {
+        Some(remaining) if *remaining > 0 => {
+            *remaining -= 1;
+            false
+        }
+        _ => true,
+    });
+    let ignored = count - summary.hints.len();
+
+    if ignored > 0 {
+        message!(
+            "Ignored {ignored} {findings} in `{path}`",
+            ignored = ignored.to_formatted_string(&Locale::en),
+            findings = if ignored == 1 { "finding" } else { "findings" },
+        );
+    }
+
+    Ok(())
+}
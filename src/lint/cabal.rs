@@ -0,0 +1,783 @@
+use crate::{
+    cli::lint::CabalArgs,
+    context::cx,
+    events::message,
+    git,
+    io::read_file,
+    lint::{HlintHint, HlintSeverity, Summary},
+    queue,
+};
+use camino::Utf8Path;
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use saphyr::{LoadableYamlNode as _, Yaml};
+use std::{
+    collections::{BTreeSet, HashSet},
+    str,
+};
+use tokio::fs;
+use twox_hash::XxHash3_64;
+
+pub const NAME: &str = "cabal";
+
+/// Top-level fields of a `.cabal` file
+const PACKAGE_FIELDS: &[&str] = &[
+    "cabal-version",
+    "name",
+    "version",
+    "build-type",
+    "license",
+    "license-file",
+    "license-files",
+    "copyright",
+    "author",
+    "maintainer",
+    "stability",
+    "homepage",
+    "bug-reports",
+    "package-url",
+    "synopsis",
+    "description",
+    "category",
+    "tested-with",
+    "data-files",
+    "data-dir",
+    "extra-source-files",
+    "extra-doc-files",
+    "extra-tmp-files",
+];
+
+/// Fields of library, executable, test suite, benchmark, and common stanzas
+const BUILD_INFO_FIELDS: &[&str] = &[
+    "import",
+    "build-depends",
+    "build-tool-depends",
+    "build-tools",
+    "pkgconfig-depends",
+    "mixins",
+    "buildable",
+    "hs-source-dirs",
+    "exposed-modules",
+    "other-modules",
+    "autogen-modules",
+    "reexported-modules",
+    "signatures",
+    "virtual-modules",
+    "exposed",
+    "visibility",
+    "main-is",
+    "type",
+    "test-module",
+    "scope",
+    "default-language",
+    "other-languages",
+    "default-extensions",
+    "other-extensions",
+    "extensions",
+    "ghc-options",
+    "ghc-prof-options",
+    "ghc-shared-options",
+    "ghcjs-options",
+    "ghcjs-prof-options",
+    "ghcjs-shared-options",
+    "cpp-options",
+    "cc-options",
+    "cxx-options",
+    "ld-options",
+    "hsc2hs-options",
+    "asm-options",
+    "cmm-options",
+    "c-sources",
+    "cxx-sources",
+    "asm-sources",
+    "cmm-sources",
+    "js-sources",
+    "includes",
+    "include-dirs",
+    "install-includes",
+    "autogen-includes",
+    "extra-libraries",
+    "extra-lib-dirs",
+    "extra-bundled-libraries",
+    "extra-ghci-libraries",
+    "extra-library-flavours",
+    "extra-dynamic-library-flavours",
+    "extra-framework-dirs",
+    "frameworks",
+    "code-generators",
+    "lib-version-info",
+    "lib-version-linux",
+    "mod-def-file",
+    "options",
+];
+
+const FLAG_FIELDS: &[&str] = &["description", "default", "manual", "lowercase"];
+
+const SOURCE_REPOSITORY_FIELDS: &[&str] = &[
+    "type", "location", "module", "branch", "tag", "subdir", "commit",
+];
+
+const CUSTOM_SETUP_FIELDS: &[&str] = &["setup-depends"];
+
+/// Top-level fields of a `package.yaml` file, besides those allowed in components
+const HPACK_PACKAGE_FIELDS: &[&str] = &[
+    "spec-version",
+    "name",
+    "version",
+    "synopsis",
+    "description",
+    "category",
+    "stability",
+    "homepage",
+    "bug-reports",
+    "author",
+    "maintainer",
+    "copyright",
+    "license",
+    "license-file",
+    "tested-with",
+    "build-type",
+    "extra-source-files",
+    "extra-doc-files",
+    "data-files",
+    "data-dir",
+    "github",
+    "git",
+    "custom-setup",
+    "flags",
+    "library",
+    "internal-libraries",
+    "executable",
+    "executables",
+    "tests",
+    "benchmarks",
+    "defaults",
+    "verbatim",
+];
+
+/// Fields of `package.yaml` components, which are also allowed at the top level
+const HPACK_COMPONENT_FIELDS: &[&str] = &[
+    "source-dirs",
+    "dependencies",
+    "pkg-config-dependencies",
+    "build-tools",
+    "build-tool-depends",
+    "system-build-tools",
+    "language",
+    "default-extensions",
+    "other-extensions",
+    "ghc-options",
+    "ghc-prof-options",
+    "ghc-shared-options",
+    "ghcjs-options",
+    "cpp-options",
+    "cc-options",
+    "cxx-options",
+    "ld-options",
+    "asm-options",
+    "c-sources",
+    "cxx-sources",
+    "asm-sources",
+    "js-sources",
+    "extra-lib-dirs",
+    "extra-libraries",
+    "extra-frameworks-dirs",
+    "frameworks",
+    "include-dirs",
+    "install-includes",
+    "buildable",
+    "when",
+    "main",
+    "exposed",
+    "exposed-modules",
+    "other-modules",
+    "generated-exposed-modules",
+    "generated-other-modules",
+    "reexported-modules",
+    "signatures",
+    "visibility",
+    "mixins",
+    "verbatim",
+];
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &CabalArgs) -> eyre::Result<Summary> {
+    let mut paths = if args.paths.is_empty() {
+        git::changed_files(&[]).await?
+    } else {
+        git::files(&args.paths).await?
+    };
+
+    paths.retain(|path| is_package_file(path));
+
+    let mut generated = Vec::new();
+    for path in &paths {
+        if path.extension() == Some("cabal") {
+            let hpack = path.with_file_name("package.yaml");
+            if fs::try_exists(&hpack).await? {
+                generated.push(path.clone());
+            }
+        }
+    }
+
+    // `hpack` generates these, so its problems are reported in `package.yaml` instead
+    paths.retain(|path| !generated.contains(path));
+
+    let handles = queue::spawn(paths, |path| async move { check(&path).await }).await?;
+
+    let mut summary = Summary::default();
+    let mut checked_count = 0;
+
+    for handle in handles {
+        let (checked, hints) = handle.await??;
+        if checked {
+            checked_count += 1;
+        }
+        summary.add(hints);
+    }
+
+    message!(
+        "Checked {checked_count} of {total_count} package {files}",
+        checked_count = checked_count.to_formatted_string(&Locale::en),
+        total_count = summary.file_count.to_formatted_string(&Locale::en),
+        files = if summary.file_count == 1 {
+            "file"
+        } else {
+            "files"
+        },
+    );
+
+    Ok(summary)
+}
+
+fn is_package_file(path: &Utf8Path) -> bool {
+    path.file_name() == Some("package.yaml") || path.extension() == Some("cabal")
+}
+
+#[tracing::instrument(fields(indicatif.pb_show))]
+async fn check(path: &Utf8Path) -> eyre::Result<(bool, Vec<HlintHint>)> {
+    let cx = cx();
+
+    let (input_bytes, input_hash) = read_file(path).await?;
+
+    // Dependencies on packages in this repo don't need bounds, so they're part of the cache key
+    let local_packages: BTreeSet<&str> = cx
+        .cache
+        .packages()
+        .await?
+        .iter()
+        .map(|package| package.name.as_str())
+        .collect();
+    let packages_hash = XxHash3_64::oneshot(
+        local_packages
+            .iter()
+            .copied()
+            .collect::<Vec<_>>()
+            .join(",")
+            .as_bytes(),
+    );
+    let cache_name = format!("{NAME}:{packages_hash}");
+
+    let (checked, mut hints) =
+        if let Some(hints) = cx.cache.builtin_hints(&cache_name, input_hash).await? {
+            tracing::trace!("Using cached package check results");
+            (false, hints)
+        } else {
+            tracing::trace!("Checking package");
+            let source = str::from_utf8(&input_bytes)?;
+            let hints = if path.extension() == Some("cabal") {
+                cabal_hints(source, &local_packages)
+            } else {
+                hpack_hints(source, &local_packages)
+            };
+            cx.cache
+                .mark_builtin_hints(&cache_name, input_hash, &hints)
+                .await?;
+            (true, hints)
+        };
+
+    for hint in &mut hints {
+        hint.tool = "be";
+        hint.file.clear();
+        hint.file.push_str(path.as_str());
+    }
+
+    Ok((checked, hints))
+}
+
+fn hint(severity: HlintSeverity, hint: &str, line: usize, column: usize, from: &str) -> HlintHint {
+    HlintHint {
+        module: Vec::new(),
+        decl: Vec::new(),
+        severity,
+        hint: String::from(hint),
+        file: String::new(),
+        start_line: line,
+        start_column: column,
+        end_line: line,
+        end_column: column + from.len(),
+        from: String::from(from),
+        to: None,
+        note: Vec::new(),
+        refactorings: String::from("[]"),
+        tool: "be",
+    }
+}
+
+/// A dependency in a `build-depends` or `dependencies` list
+struct Dependency<'a> {
+    line: usize,
+    column: usize,
+    text: &'a str,
+}
+
+impl Dependency<'_> {
+    /// Package name, without any sublibrary like `foo:bar`
+    fn package(&self) -> &str {
+        let end = self
+            .text
+            .find(|c: char| c.is_whitespace() || "<>=^:".contains(c))
+            .unwrap_or(self.text.len());
+        &self.text[..end]
+    }
+
+    fn constraint(&self) -> &str {
+        let rest = &self.text[self.package().len()..];
+        let rest = rest.strip_prefix(':').map_or(rest, |rest| {
+            rest.find(|c: char| c.is_whitespace() || "<>=^".contains(c))
+                .map_or("", |end| &rest[end..])
+        });
+        rest.trim()
+    }
+}
+
+/// Comma-separated dependencies in part of a line, starting at this column (1-based)
+fn dependencies(line: usize, column: usize, text: &str) -> Vec<Dependency<'_>> {
+    let mut dependencies = Vec::new();
+    let mut offset = 0;
+    for part in text.split(',') {
+        let trimmed = part.trim();
+        if !trimmed.is_empty() {
+            let leading = part.len() - part.trim_start().len();
+            dependencies.push(Dependency {
+                line,
+                column: column + offset + leading,
+                text: trimmed,
+            });
+        }
+        offset += part.len() + 1;
+    }
+    dependencies
+}
+
+/// Report dependencies on other packages without version bounds, or without upper bounds
+fn bounds_hints(
+    dependencies: &[Dependency],
+    own_packages: &BTreeSet<&str>,
+    hints: &mut Vec<HlintHint>,
+) {
+    for dependency in dependencies {
+        if own_packages.contains(dependency.package()) {
+            continue;
+        }
+        let constraint = dependency.constraint();
+        let (severity, message) = if constraint.is_empty() || constraint == "-any" {
+            (HlintSeverity::Warning, "Missing version bounds")
+        } else if constraint.contains('<')
+            || constraint.contains("^>=")
+            || constraint.contains("==")
+        {
+            continue;
+        } else {
+            (HlintSeverity::Suggestion, "Missing upper bound")
+        };
+        hints.push(hint(
+            severity,
+            message,
+            dependency.line,
+            dependency.column,
+            dependency.text,
+        ));
+    }
+}
+
+/// Stanza in a `.cabal` file
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Stanza {
+    Component,
+    Flag,
+    SourceRepository,
+    CustomSetup,
+}
+
+impl Stanza {
+    fn parse(keyword: &str) -> Option<Self> {
+        match keyword {
+            "library" | "foreign-library" | "executable" | "test-suite" | "benchmark"
+            | "common" => Some(Self::Component),
+            "flag" => Some(Self::Flag),
+            "source-repository" => Some(Self::SourceRepository),
+            "custom-setup" => Some(Self::CustomSetup),
+            _ => None,
+        }
+    }
+
+    fn fields(self) -> &'static [&'static str] {
+        match self {
+            Self::Component => BUILD_INFO_FIELDS,
+            Self::Flag => FLAG_FIELDS,
+            Self::SourceRepository => SOURCE_REPOSITORY_FIELDS,
+            Self::CustomSetup => CUSTOM_SETUP_FIELDS,
+        }
+    }
+}
+
+/// What's been seen so far while reading a `.cabal` file
+#[derive(Default)]
+struct CabalFile<'a> {
+    /// Top-level fields, with their line numbers
+    fields: Vec<(String, usize)>,
+    /// Package name and the names of its sublibraries
+    own_packages: BTreeSet<&'a str>,
+    dependencies: Vec<Dependency<'a>>,
+    hints: Vec<HlintHint>,
+}
+
+// TODO: Follow `import`s of `common` stanzas when checking for duplicate fields
+fn cabal_hints(source: &str, local_packages: &BTreeSet<&str>) -> Vec<HlintHint> {
+    let mut file = CabalFile::default();
+
+    let mut stanza: Option<Stanza> = None;
+    let mut stanza_fields = HashSet::new();
+    let mut stanza_indent = None;
+    // Field whose value continues on more indented lines, and its indent
+    let mut field: Option<(String, usize)> = None;
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with("--") {
+            continue;
+        }
+        let indent = line.len() - trimmed.len();
+
+        if let Some((name, field_indent)) = &field
+            && indent > *field_indent
+        {
+            if is_dependency_field(name) {
+                let deps = dependencies(line_number, indent + 1, trimmed);
+                file.dependencies.extend(deps);
+            }
+            continue;
+        }
+        field = None;
+
+        if indent == 0 {
+            stanza_fields.clear();
+            stanza_indent = None;
+            if let Some((key, value)) = field_line(trimmed) {
+                stanza = None;
+                file.top_level_field(line_number, trimmed, &key, value);
+                field = Some((key, indent));
+            } else {
+                let mut words = trimmed.split_whitespace();
+                let keyword = words.next().unwrap_or("").to_ascii_lowercase();
+                stanza = Stanza::parse(&keyword);
+                if stanza.is_none() {
+                    let hint = hint(
+                        HlintSeverity::Warning,
+                        "Unknown stanza",
+                        line_number,
+                        1,
+                        trimmed,
+                    );
+                    file.hints.push(hint);
+                }
+                if let ("library", Some(name)) = (keyword.as_str(), words.next()) {
+                    file.own_packages.insert(name);
+                }
+            }
+            continue;
+        }
+
+        let Some(stanza) = stanza else {
+            continue;
+        };
+
+        // Fields inside conditionals can repeat fields outside them
+        let is_unconditional = *stanza_indent.get_or_insert(indent) == indent;
+
+        let Some((key, value)) = field_line(trimmed) else {
+            // Conditionals like `if flag(dev)`, whose fields are indented further
+            continue;
+        };
+
+        if !stanza.fields().contains(&key.as_str()) && !key.starts_with("x-") {
+            let hint = hint(
+                HlintSeverity::Warning,
+                "Unknown field",
+                line_number,
+                indent + 1,
+                trimmed,
+            );
+            file.hints.push(hint);
+        }
+
+        if is_unconditional && !stanza_fields.insert(key.clone()) {
+            let hint = hint(
+                HlintSeverity::Warning,
+                "Duplicate field",
+                line_number,
+                indent + 1,
+                trimmed,
+            );
+            file.hints.push(hint);
+        }
+
+        if is_dependency_field(&key) {
+            let column = line.len() - value.len() + 1;
+            file.dependencies
+                .extend(dependencies(line_number, column, value));
+        }
+
+        field = Some((key, indent));
+    }
+
+    file.finish(source, local_packages)
+}
+
+impl<'a> CabalFile<'a> {
+    fn top_level_field(&mut self, line_number: usize, line: &'a str, key: &str, value: &'a str) {
+        if !PACKAGE_FIELDS.contains(&key) && !key.starts_with("x-") {
+            let hint = hint(
+                HlintSeverity::Warning,
+                "Unknown field",
+                line_number,
+                1,
+                line,
+            );
+            self.hints.push(hint);
+        }
+        if self.fields.iter().any(|(field, _)| field == key) {
+            let hint = hint(
+                HlintSeverity::Warning,
+                "Duplicate field",
+                line_number,
+                1,
+                line,
+            );
+            self.hints.push(hint);
+        }
+        if key == "name" {
+            self.own_packages.insert(value.trim());
+        }
+        self.fields.push((String::from(key), line_number));
+    }
+
+    fn finish(mut self, source: &str, local_packages: &BTreeSet<&str>) -> Vec<HlintHint> {
+        let first_line = source.lines().next().unwrap_or("");
+
+        for field in ["cabal-version", "name", "version"] {
+            if !self.fields.iter().any(|(key, _)| key == field) {
+                let mut hint = hint(HlintSeverity::Error, "Missing field", 1, 1, first_line);
+                hint.note.push(format!("Packages need a `{field}` field"));
+                self.hints.push(hint);
+            }
+        }
+
+        let cabal_version = self
+            .fields
+            .iter()
+            .enumerate()
+            .find(|(_, (key, _))| key == "cabal-version");
+        if let Some((index, (_, line_number))) = cabal_version
+            && index > 0
+        {
+            let line = source.lines().nth(line_number - 1).unwrap_or("");
+            let mut hint = hint(
+                HlintSeverity::Warning,
+                "Misplaced field",
+                *line_number,
+                1,
+                line,
+            );
+            hint.note
+                .push(String::from("`cabal-version` should be the first field"));
+            self.hints.push(hint);
+        }
+
+        let own_packages: BTreeSet<&str> =
+            self.own_packages.union(local_packages).copied().collect();
+        bounds_hints(&self.dependencies, &own_packages, &mut self.hints);
+
+        self.hints
+            .sort_by_key(|hint| (hint.start_line, hint.start_column));
+        self.hints
+    }
+}
+
+fn is_dependency_field(key: &str) -> bool {
+    key == "build-depends" || key == "setup-depends"
+}
+
+/// Lowercased field name and its value
+fn field_line(line: &str) -> Option<(String, &str)> {
+    let (key, value) = line.split_once(':')?;
+    let key = key.trim_end();
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((key.to_ascii_lowercase(), value))
+}
+
+fn hpack_hints(source: &str, local_packages: &BTreeSet<&str>) -> Vec<HlintHint> {
+    let first_line = source.lines().next().unwrap_or("");
+
+    let yaml = match Yaml::load_from_str(source) {
+        Ok(documents) => documents.into_iter().next(),
+        Err(error) => {
+            let mut hint = hint(HlintSeverity::Error, "Invalid YAML", 1, 1, first_line);
+            hint.note.push(error.to_string());
+            return vec![hint];
+        }
+    };
+
+    let Some(mapping) = yaml.as_ref().and_then(Yaml::as_mapping) else {
+        let mut hint = hint(HlintSeverity::Error, "Invalid package", 1, 1, first_line);
+        hint.note
+            .push(String::from("`package.yaml` should be a mapping of fields"));
+        return vec![hint];
+    };
+
+    let mut hints = Vec::new();
+
+    let mut own_packages = local_packages.clone();
+    if let Some(name) = mapping
+        .iter()
+        .find(|(key, _)| key.as_str() == Some("name"))
+        .and_then(|(_, name)| name.as_str())
+    {
+        own_packages.insert(name);
+    }
+    if let Some(Some(internal)) = mapping
+        .iter()
+        .find(|(key, _)| key.as_str() == Some("internal-libraries"))
+        .map(|(_, internal)| internal.as_mapping())
+    {
+        own_packages.extend(internal.keys().filter_map(Yaml::as_str));
+    }
+
+    let mut dependencies = Vec::new();
+
+    for (key, value) in mapping {
+        let Some(key) = key.as_str() else {
+            continue;
+        };
+        let (line, column) = locate(source, 0, &format!("{key}:"));
+        if !is_hpack_extra(key)
+            && !HPACK_PACKAGE_FIELDS.contains(&key)
+            && !HPACK_COMPONENT_FIELDS.contains(&key)
+        {
+            hints.push(hint(
+                HlintSeverity::Warning,
+                "Unknown field",
+                line,
+                column,
+                key,
+            ));
+        }
+        match key {
+            "dependencies" => dependencies.extend(hpack_dependencies(source, 0, value)),
+            "library" | "executable" => {
+                component_hints(source, line, value, &mut dependencies, &mut hints);
+            }
+            "internal-libraries" | "executables" | "tests" | "benchmarks" => {
+                for (name, component) in value.as_mapping().into_iter().flatten() {
+                    let line = name
+                        .as_str()
+                        .map_or(line, |name| locate(source, line, &format!("{name}:")).0);
+                    component_hints(source, line, component, &mut dependencies, &mut hints);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    bounds_hints(&dependencies, &own_packages, &mut hints);
+
+    hints.sort_by_key(|hint| (hint.start_line, hint.start_column));
+    hints
+}
+
+/// Fields `hpack` ignores, like `_common: &common` for YAML anchors
+fn is_hpack_extra(key: &str) -> bool {
+    key.starts_with('_') || key.starts_with("x-") || key == "<<"
+}
+
+fn component_hints<'a>(
+    source: &'a str,
+    line: usize,
+    component: &'a Yaml,
+    dependencies: &mut Vec<Dependency<'a>>,
+    hints: &mut Vec<HlintHint>,
+) {
+    for (key, value) in component.as_mapping().into_iter().flatten() {
+        let Some(key) = key.as_str() else {
+            continue;
+        };
+        let (key_line, column) = locate(source, line, &format!("{key}:"));
+        if !is_hpack_extra(key) && !HPACK_COMPONENT_FIELDS.contains(&key) {
+            hints.push(hint(
+                HlintSeverity::Warning,
+                "Unknown field",
+                key_line,
+                column,
+                key,
+            ));
+        }
+        if key == "dependencies" {
+            dependencies.extend(hpack_dependencies(source, key_line, value));
+        }
+    }
+}
+
+/// Dependencies given as a list, a comma-separated string, or a mapping to constraints
+fn hpack_dependencies<'a>(source: &'a str, line: usize, yaml: &'a Yaml) -> Vec<Dependency<'a>> {
+    let mut dependencies = Vec::new();
+    if let Some(text) = yaml.as_str() {
+        let (line, column) = locate(source, line, text);
+        dependencies.extend(self::dependencies(line, column, text));
+    } else if let Some(sequence) = yaml.as_sequence() {
+        for text in sequence.iter().filter_map(Yaml::as_str) {
+            let (line, column) = locate(source, line, text);
+            dependencies.push(Dependency { line, column, text });
+        }
+    } else if let Some(mapping) = yaml.as_mapping() {
+        // Constraints in mappings aren't checked, since they may be nested
+        for name in mapping.keys().filter_map(Yaml::as_str) {
+            let (line, column) = locate(source, line, name);
+            let is_bounded = mapping
+                .iter()
+                .find(|(key, _)| key.as_str() == Some(name))
+                .is_some_and(|(_, constraint)| !constraint.is_null());
+            if !is_bounded {
+                dependencies.push(Dependency {
+                    line,
+                    column,
+                    text: name,
+                });
+            }
+        }
+    }
+    dependencies
+}
+
+/// First line and column (1-based) of some text at or after this line, or the line itself
+fn locate(source: &str, after: usize, text: &str) -> (usize, usize) {
+    source
+        .lines()
+        .enumerate()
+        .skip(after.saturating_sub(1))
+        .find_map(|(index, line)| line.find(text).map(|column| (index + 1, column + 1)))
+        .unwrap_or((after.max(1), 1))
+}
@@ -0,0 +1,210 @@
+use crate::{
+    cli::lint::DuplicatesArgs,
+    config::Pipeline,
+    context::cx,
+    events::message,
+    git,
+    io::read_file,
+    lint::{HlintHint, HlintSeverity, Summary, builtin_hint, parse},
+};
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hasher as _,
+    ops::Range,
+};
+use tree_sitter::{Node, Tree};
+use twox_hash::XxHash3_64;
+
+/// Smallest subtree reported, in named nodes
+const DEFAULT_MIN_NODES: usize = 40;
+
+struct Module {
+    path: String,
+    source_code: String,
+    tree: Tree,
+}
+
+/// A subtree whose normalized shape hashed to some fingerprint
+struct Occurrence<'a> {
+    module: usize,
+    node: Node<'a>,
+    size: usize,
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &DuplicatesArgs) -> eyre::Result<Summary> {
+    let config = &cx().cache.config().await?.lint.duplicates;
+
+    let min_nodes = config.min_nodes.unwrap_or(DEFAULT_MIN_NODES);
+
+    let paths = if args.paths.is_empty() && args.packages.is_empty() {
+        git::changed_haskell_files(Pipeline::Lint).await?
+    } else {
+        git::haskell_files(&args.paths, &args.packages, Pipeline::Lint).await?
+    };
+
+    let mut handles = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        handles.push(tokio::spawn(async move {
+            let (bytes, _) = read_file(&path).await?;
+            let (source_code, tree) = parse(&bytes)?;
+            eyre::Ok(Module {
+                path: path.to_string(),
+                source_code,
+                tree,
+            })
+        }));
+    }
+
+    let mut modules = Vec::with_capacity(handles.len());
+    for handle in handles {
+        modules.push(handle.await??);
+    }
+
+    modules.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut hints = duplicate_hints(&modules, min_nodes);
+
+    let mut summary = Summary::default();
+    let mut duplicate_count = 0;
+
+    for module_hints in &mut hints {
+        module_hints.sort_by_key(|hint| (hint.start_line, hint.start_column));
+        duplicate_count += module_hints.len();
+        summary.add(std::mem::take(module_hints));
+    }
+
+    message!(
+        "Found {duplicate_count} duplicated code {blocks} in {total_count} Haskell {files}",
+        duplicate_count = duplicate_count.to_formatted_string(&Locale::en),
+        blocks = if duplicate_count == 1 {
+            "block"
+        } else {
+            "blocks"
+        },
+        total_count = summary.file_count.to_formatted_string(&Locale::en),
+        files = if summary.file_count == 1 {
+            "file"
+        } else {
+            "files"
+        },
+    );
+
+    Ok(summary)
+}
+
+/// Hints for each module, pairing every duplicated block with the locations of its copies
+fn duplicate_hints(modules: &[Module], min_nodes: usize) -> Vec<Vec<HlintHint>> {
+    let mut fingerprints: HashMap<u64, Vec<Occurrence>> = HashMap::new();
+
+    for (index, module) in modules.iter().enumerate() {
+        let Some(declarations) = module.tree.root_node().child_by_field_name("declarations") else {
+            continue;
+        };
+        let mut cursor = declarations.walk();
+        for declaration in declarations.named_children(&mut cursor) {
+            fingerprint(index, declaration, min_nodes, &mut fingerprints);
+        }
+    }
+
+    // Report the largest duplicates first, and skip the smaller ones they contain
+    let mut groups: Vec<Vec<Occurrence>> = fingerprints
+        .into_values()
+        .filter(|occurrences| occurrences.len() > 1)
+        .collect();
+    groups.sort_by_key(|occurrences| std::cmp::Reverse(occurrences[0].size));
+
+    let mut reported: HashSet<(usize, Range<usize>)> = HashSet::new();
+    let mut hints: Vec<Vec<HlintHint>> = modules.iter().map(|_| Vec::new()).collect();
+
+    for group in groups {
+        let group: Vec<&Occurrence> = group
+            .iter()
+            .filter(|occurrence| {
+                !reported.iter().any(|(module, range)| {
+                    *module == occurrence.module
+                        && range.start <= occurrence.node.start_byte()
+                        && occurrence.node.end_byte() <= range.end
+                })
+            })
+            .collect();
+        if group.len() < 2 {
+            continue;
+        }
+        for occurrence in &group {
+            reported.insert((occurrence.module, occurrence.node.byte_range()));
+            let module = &modules[occurrence.module];
+            let root = module.tree.root_node();
+            let mut hint = builtin_hint(
+                &module.source_code,
+                root,
+                occurrence.node,
+                declaration_name(&module.source_code, occurrence.node),
+                "Duplicate code",
+            );
+            hint.severity = HlintSeverity::Suggestion;
+            hint.file.push_str(&module.path);
+            for other in &group {
+                if std::ptr::eq(*other, *occurrence) {
+                    continue;
+                }
+                let start = other.node.start_position();
+                hint.note.push(format!(
+                    "Also at {}:{}:{}",
+                    modules[other.module].path,
+                    start.row + 1,
+                    start.column + 1,
+                ));
+            }
+            hints[occurrence.module].push(hint);
+        }
+    }
+
+    hints
+}
+
+/// Hash a subtree's shape, ignoring names and literals, recording subtrees of at least `min_nodes`
+/// named nodes. Returns the hash and size.
+fn fingerprint<'a>(
+    module: usize,
+    node: Node<'a>,
+    min_nodes: usize,
+    fingerprints: &mut HashMap<u64, Vec<Occurrence<'a>>>,
+) -> (u64, usize) {
+    let mut hasher = XxHash3_64::default();
+    hasher.write_u16(node.kind_id());
+    let mut size = 1;
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        if child.is_extra() {
+            continue;
+        }
+        let (child_hash, child_size) = fingerprint(module, child, min_nodes, fingerprints);
+        hasher.write_u64(child_hash);
+        size += child_size;
+    }
+    let hash = hasher.finish();
+    if size >= min_nodes {
+        fingerprints
+            .entry(hash)
+            .or_default()
+            .push(Occurrence { module, node, size });
+    }
+    (hash, size)
+}
+
+/// Name of the top-level declaration containing a node
+fn declaration_name<'a>(source_code: &'a str, mut node: Node) -> &'a str {
+    while let Some(parent) = node.parent() {
+        if parent.kind() == "declarations" {
+            break;
+        }
+        node = parent;
+    }
+    node.child_by_field_name("name")
+        .and_then(|name| source_code.get(name.byte_range()))
+        .unwrap_or_default()
+}
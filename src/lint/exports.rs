@@ -0,0 +1,38 @@
+use crate::lint::{self, HlintHint, builtin_hint};
+use tree_sitter::Tree;
+
+pub const NAME: &str = "explicit-exports";
+
+/// Modules which export everything because they have no export list
+pub fn hints(source_code: &str, tree: &Tree) -> Vec<HlintHint> {
+    let root = tree.root_node();
+
+    // A module without a header only exports `main`
+    let Some(header) = lint::header(root) else {
+        return Vec::new();
+    };
+
+    if header.child_by_field_name("exports").is_some() {
+        return Vec::new();
+    }
+
+    let Some(module) = header
+        .child_by_field_name("module")
+        .and_then(|module| source_code.get(module.byte_range()))
+    else {
+        return Vec::new();
+    };
+
+    // Nothing can import `Main`, so exporting everything from it is harmless
+    if module == "Main" {
+        return Vec::new();
+    }
+
+    vec![builtin_hint(
+        source_code,
+        root,
+        header,
+        module,
+        "Missing export list",
+    )]
+}
@@ -0,0 +1,471 @@
+use crate::{
+    cli::lint::GhcArgs,
+    config::Pipeline,
+    context::cx,
+    events::message,
+    exec, git,
+    io::read_file,
+    lint::{HlintHint, HlintSeverity, Summary},
+    queue,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use std::{
+    fmt::Write as _, hash::Hasher as _, os::unix::process::ExitStatusExt as _, process::Stdio,
+};
+use tempfile::tempdir;
+use tokio::{fs, process::Command, sync::OnceCell};
+use twox_hash::XxHash3_64;
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &GhcArgs) -> eyre::Result<Summary> {
+    let paths = if args.paths.is_empty() && args.packages.is_empty() {
+        git::changed_haskell_files(Pipeline::Lint).await?
+    } else {
+        git::haskell_files(&args.paths, &args.packages, Pipeline::Lint).await?
+    };
+
+    let handles = queue::spawn(paths, |path| async move { check(&path).await }).await?;
+
+    let mut summary = Summary::default();
+    let mut checked_count = 0;
+
+    for handle in handles {
+        let (checked, hints) = handle.await??;
+        if checked {
+            checked_count += 1;
+        }
+        summary.add(hints);
+    }
+
+    message!(
+        "Checked {checked_count} of {total_count} Haskell {files} with GHC",
+        checked_count = checked_count.to_formatted_string(&Locale::en),
+        total_count = summary.file_count.to_formatted_string(&Locale::en),
+        files = if summary.file_count == 1 {
+            "file"
+        } else {
+            "files"
+        },
+    );
+
+    Ok(summary)
+}
+
+#[tracing::instrument(fields(indicatif.pb_show))]
+async fn check(path: &Utf8Path) -> eyre::Result<(bool, Vec<HlintHint>)> {
+    let cx = cx();
+
+    let (input_bytes, input_hash) = read_file(path).await?;
+
+    let flags = cx.cache.config().await?.lint.ghc.flags();
+    let mut hasher = XxHash3_64::default();
+    for flag in &flags {
+        hasher.write(flag.as_bytes());
+        hasher.write(b"\0");
+    }
+    // Packages it can import change what's found, like an import which used to fail
+    if let Some(package_env) = package_env().await? {
+        hasher.write(package_env.contents.as_bytes());
+    }
+    let flags_hash = hasher.finish();
+
+    let (checked, mut hints) =
+        if let Some(hints) = cx.cache.is_ghc_linted(flags_hash, input_hash).await? {
+            tracing::trace!("Using cached `ghc` results");
+            (false, hints)
+        } else {
+            tracing::trace!("Checking");
+
+            let (hints, duration) = exec::timed(ghc(path, &input_bytes, &flags)).await?;
+
+            cx.cache
+                .record_duration("ghc", input_hash, path, duration)
+                .await?;
+
+            cx.cache
+                .mark_ghc_linted(flags_hash, input_hash, &hints)
+                .await?;
+
+            (true, hints)
+        };
+
+    for hint in &mut hints {
+        hint.tool = "ghc";
+        hint.file.clear();
+        hint.file.push_str(path.as_str());
+    }
+
+    hints.sort_by_key(|hint| (hint.start_line, hint.start_column));
+
+    Ok((checked, hints))
+}
+
+/// A diagnostic from `ghc -fdiagnostics-as-json`
+#[derive(serde::Deserialize)]
+struct GhcDiagnostic {
+    span: Option<GhcSpan>,
+    severity: String,
+    code: Option<u32>,
+    message: Vec<String>,
+    #[serde(default)]
+    reason: Option<GhcReason>,
+}
+
+#[derive(serde::Deserialize)]
+struct GhcSpan {
+    file: Utf8PathBuf,
+    start: GhcPosition,
+    end: GhcPosition,
+}
+
+#[derive(serde::Deserialize)]
+struct GhcPosition {
+    line: usize,
+    column: usize,
+}
+
+/// Which flag turned on a warning, which older versions of GHC give on its own
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum GhcReason {
+    Flags { flags: Vec<String> },
+    Flag(String),
+}
+
+impl GhcDiagnostic {
+    /// Like `-Wunused-imports`, or else the error code, like `GHC-88464`
+    fn name(&self) -> String {
+        let flag = match &self.reason {
+            Some(GhcReason::Flags { flags }) => flags.first(),
+            Some(GhcReason::Flag(flag)) => Some(flag),
+            None => None,
+        };
+        match (flag, self.code) {
+            (Some(flag), _) => format!("-W{flag}"),
+            (None, Some(code)) => format!("GHC-{code:05}"),
+            (None, None) => self.severity.clone(),
+        }
+    }
+
+    fn to_hlint_hint(&self, span: &GhcSpan, source_code: &str) -> HlintHint {
+        let severity = if self.severity == "Error" {
+            HlintSeverity::Error
+        } else {
+            HlintSeverity::Warning
+        };
+        let from = source_code
+            .lines()
+            .nth(span.start.line.saturating_sub(1))
+            .unwrap_or_default()
+            .trim();
+        HlintHint {
+            module: Vec::new(),
+            decl: Vec::new(),
+            severity,
+            hint: self.name(),
+            file: String::new(),
+            start_line: span.start.line,
+            start_column: span.start.column,
+            end_line: span.end.line,
+            end_column: span.end.column,
+            from: String::from(from),
+            to: None,
+            note: vec![self.message.join("\n")],
+            refactorings: String::from("[]"),
+            tool: "ghc",
+        }
+    }
+}
+
+/// Source directories of the package a module is in, where the modules it imports may be
+async fn source_dirs(relative_path: &Utf8Path) -> eyre::Result<Vec<Utf8PathBuf>> {
+    let mut source_dirs = Vec::new();
+    for package in cx().cache.packages().await? {
+        let components = &package.components;
+        if !components.iter().any(|component| {
+            component
+                .source_dirs
+                .iter()
+                .any(|dir| relative_path.starts_with(dir))
+        }) {
+            continue;
+        }
+        for component in components {
+            for dir in &component.source_dirs {
+                if !source_dirs.contains(dir) {
+                    source_dirs.push(dir.clone());
+                }
+            }
+        }
+    }
+
+    Ok(source_dirs)
+}
+
+/// A GHC package environment, which `ghc` can't find on its own with its environment cleared
+struct PackageEnv {
+    contents: String,
+    /// Directories with package databases outside the repo, like the cabal store, which the sandbox
+    /// has to let `ghc` read. Packages' libraries are next to their database.
+    package_dirs: Vec<Utf8PathBuf>,
+}
+
+/// The package environment from `lint.ghc.package-env`, or else from `cabal exec` once the project
+/// has been built, looked up once. Without one, `ghc` only sees its global package database.
+async fn package_env() -> eyre::Result<Option<&'static PackageEnv>> {
+    static PACKAGE_ENV: OnceCell<Option<PackageEnv>> = OnceCell::const_new();
+    let package_env = PACKAGE_ENV
+        .get_or_try_init(|| Box::pin(load_package_env()))
+        .await?;
+    Ok(package_env.as_ref())
+}
+
+async fn load_package_env() -> eyre::Result<Option<PackageEnv>> {
+    let cx = cx();
+    let git_root = cx.cache.git_root().await?;
+    let contents = if let Some(path) = &cx.cache.config().await?.lint.ghc.package_env {
+        fs::read_to_string(git_root.join(path)).await?
+    } else if fs::try_exists(git_root.join("dist-newstyle/cache/plan.json")).await? {
+        match cabal_package_env().await {
+            Ok(contents) => contents,
+            Err(error) => {
+                tracing::warn!(
+                    "Not using the project's packages, since `cabal exec` failed (set \
+                     `lint.ghc.package-env` in `be.toml` instead): {error}"
+                );
+                return Ok(None);
+            }
+        }
+    } else {
+        tracing::debug!("Not using a package environment, since the project isn't built");
+        return Ok(None);
+    };
+    let mut package_dirs: Vec<Utf8PathBuf> = Vec::new();
+    for line in contents.lines() {
+        let Some(package_db) = line.strip_prefix("package-db ").map(Utf8Path::new) else {
+            continue;
+        };
+        let dir = package_db.parent().unwrap_or(package_db);
+        if !dir.starts_with(git_root) && !package_dirs.iter().any(|other| other == dir) {
+            package_dirs.push(dir.to_path_buf());
+        }
+    }
+    Ok(Some(PackageEnv {
+        contents,
+        package_dirs,
+    }))
+}
+
+/// The package environment `cabal exec` gives GHC, which has every package in the build plan
+async fn cabal_package_env() -> eyre::Result<String> {
+    let cx = cx();
+    let cabal = cx.cache.which("cabal").await?;
+    let mut command = Command::new(cabal);
+    command
+        .args([
+            "exec",
+            "--verbose=0",
+            "--",
+            "sh",
+            "-c",
+            "cat \"$GHC_ENVIRONMENT\"",
+        ])
+        .current_dir(cx.cache.git_root().await?)
+        .kill_on_drop(true)
+        .stdin(Stdio::null());
+    let output = exec::timeout("cabal", command.output()).await?;
+    if !output.status.success() {
+        eyre::bail!("{}", String::from_utf8_lossy(&output.stderr).trim_end());
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Typecheck a module without generating code, looking for the modules it imports in its package's
+/// source directories and the rest in the package environment
+#[tracing::instrument(skip(bytes))]
+async fn ghc(path: &Utf8Path, bytes: &[u8], flags: &[&str]) -> eyre::Result<Vec<HlintHint>> {
+    let cx = cx();
+
+    let ghc = &cx.cache.which("ghc").await?;
+
+    let git_root = cx.cache.git_root().await?;
+    let absolute_path = Utf8PathBuf::try_from(std::path::absolute(path)?)?;
+    let relative_path = absolute_path
+        .strip_prefix(git_root)
+        .unwrap_or(&absolute_path);
+
+    let (extensions, _) = cx.cache.fourmolu_extensions().await?;
+
+    let source_dirs = source_dirs(relative_path).await?;
+
+    let tool_permits = cx.tool_permits("ghc").await?;
+
+    // Where interface files go, so nothing is written to the repo
+    let temp_dir = tempdir()?;
+    let temp_dir_path = Utf8PathBuf::try_from(fs::canonicalize(temp_dir.path()).await?)?;
+
+    // With `--staged`, the staged contents are checked, rather than what's on disk
+    let module_path = if cx.staged {
+        let module_path = temp_dir_path.join("staged").join(relative_path);
+        fs::create_dir_all(module_path.parent().unwrap()).await?;
+        fs::write(&module_path, bytes).await?;
+        module_path
+    } else {
+        relative_path.to_path_buf()
+    };
+
+    let mut args = vec![
+        String::from("-fno-code"),
+        String::from("-fdiagnostics-as-json"),
+        format!("-outputdir={temp_dir_path}"),
+    ];
+    let package_env = package_env().await?;
+    if let Some(package_env) = package_env {
+        let package_env_path = temp_dir_path.join("package-env");
+        fs::write(&package_env_path, &package_env.contents).await?;
+        args.push(format!("-package-env={package_env_path}"));
+    }
+    for dir in &source_dirs {
+        args.push(format!("-i{dir}"));
+    }
+    for extension in extensions {
+        args.push(format!("-X{extension}"));
+    }
+    args.extend(flags.iter().map(|flag| String::from(*flag)));
+    args.push(module_path.to_string());
+
+    let tool_permit = tool_permits.acquire().await?;
+    let file_permit = cx.file_permits.acquire().await?;
+    let process_permit = cx.process_permits.acquire().await?;
+    let memory_permit = cx.memory.acquire().await?;
+
+    let mut command = sandboxed(ghc, git_root, &temp_dir_path, package_env).await?;
+
+    command.args(args).env_clear();
+    // Some installations find the C preprocessor and package databases through `$PATH`
+    if let Some(path) = std::env::var_os("PATH") {
+        command.env("PATH", path);
+    }
+
+    let output = exec::timeout(
+        "ghc",
+        command
+            .current_dir(git_root)
+            .kill_on_drop(true)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output(),
+    )
+    .await?;
+
+    drop(memory_permit);
+    drop(process_permit);
+    drop(file_permit);
+    drop(tool_permit);
+
+    let source_code = String::from_utf8_lossy(bytes);
+
+    let (hints, other_errors) = parse(&output.stderr, &module_path, &source_code)?;
+
+    // `ghc` exits with code 1 when there are errors, which are findings if they're in this module
+    let has_errors = hints
+        .iter()
+        .any(|hint| hint.severity == HlintSeverity::Error);
+    if !output.status.success() && !has_errors {
+        let details = if other_errors.is_empty() {
+            String::from_utf8_lossy(&output.stderr).into_owned()
+        } else {
+            other_errors
+        };
+        if let Some(exit_code) = output.status.code() {
+            eyre::bail!("`ghc` exited with code {exit_code}:\n{details}");
+        } else if let Some(signal) = output.status.signal() {
+            eyre::bail!("`ghc` was terminated by signal {signal}");
+        } else {
+            eyre::bail!("`ghc` died of unknown causes");
+        }
+    }
+
+    Ok(hints)
+}
+
+/// `ghc` in a sandbox which can read the repo and the package environment's package databases, and
+/// write to the temporary directory
+async fn sandboxed(
+    ghc: &Utf8Path,
+    git_root: &Utf8Path,
+    temp_dir_path: &Utf8Path,
+    package_env: Option<&PackageEnv>,
+) -> eyre::Result<Command> {
+    let mut params = vec![
+        exec::Param {
+            name: "GIT_ROOT",
+            path: git_root,
+            writable: false,
+        },
+        exec::Param {
+            name: "TEMP_DIR",
+            path: temp_dir_path,
+            writable: true,
+        },
+    ];
+    // Writing to a `String` can't fail
+    let mut profile = String::from(exec::GHC_PROFILE);
+    for dir in package_env
+        .iter()
+        .flat_map(|package_env| &package_env.package_dirs)
+    {
+        let _ = writeln!(profile, "(allow file-read*\n  (subpath {dir:?}))");
+        params.push(exec::Param {
+            name: "PACKAGE_DB",
+            path: dir,
+            writable: false,
+        });
+    }
+
+    exec::sandboxed(Some("ghc"), &profile, &params, ghc).await
+}
+
+/// Findings in the module, and errors elsewhere which stopped `ghc` from checking it
+fn parse(
+    stderr: &[u8],
+    relative_path: &Utf8Path,
+    source_code: &str,
+) -> eyre::Result<(Vec<HlintHint>, String)> {
+    let mut hints = Vec::new();
+    // Errors in modules it imports, which stop it from checking this one. Writing to a `String`
+    // can't fail.
+    let mut other_errors = String::new();
+
+    // One JSON object per line, among any other output
+    for line in stderr.split(|byte| *byte == b'\n') {
+        if !line.starts_with(b"{") {
+            continue;
+        }
+        let diagnostic: GhcDiagnostic = serde_json::from_slice(line)?;
+        match &diagnostic.span {
+            Some(span) if span.file == relative_path => {
+                hints.push(diagnostic.to_hlint_hint(span, source_code));
+            }
+            Some(span) if diagnostic.severity == "Error" => {
+                let _ = writeln!(
+                    other_errors,
+                    "{}:{}:{}: {}",
+                    span.file,
+                    span.start.line,
+                    span.start.column,
+                    diagnostic.message.join("\n"),
+                );
+            }
+            Some(_) => {}
+            None => {
+                let _ = writeln!(other_errors, "{}", diagnostic.message.join("\n"));
+            }
+        }
+    }
+
+    Ok((hints, other_errors))
+}
@@ -0,0 +1,208 @@
+use crate::{
+    cli::lint::HaddockArgs,
+    config::Pipeline,
+    context::cx,
+    events::message,
+    exit::Findings,
+    git,
+    io::{print_stdout, read_file},
+    lint::{self, parse, signatures},
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use std::collections::HashSet;
+use tree_sitter::{Node, Tree};
+
+/// Haddock coverage of a module's exported declarations
+struct Coverage {
+    path: Utf8PathBuf,
+    module: String,
+    documented: usize,
+    undocumented: Vec<String>,
+}
+
+impl Coverage {
+    fn total(&self) -> usize {
+        self.documented + self.undocumented.len()
+    }
+
+    #[expect(clippy::cast_precision_loss)]
+    fn percent(&self) -> f64 {
+        if self.total() == 0 {
+            100.0
+        } else {
+            100.0 * self.documented as f64 / self.total() as f64
+        }
+    }
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &HaddockArgs) -> eyre::Result<()> {
+    let config = &cx().cache.config().await?.lint.haddock;
+
+    let paths = if args.paths.is_empty() && args.packages.is_empty() {
+        if config.packages.is_empty() {
+            eyre::bail!(
+                "No packages to check haddock coverage of (set `lint.haddock.packages` in `be.toml` or pass `--package`)"
+            );
+        }
+        git::haskell_files(&[], &config.packages, Pipeline::Lint).await?
+    } else {
+        git::haskell_files(&args.paths, &args.packages, Pipeline::Lint).await?
+    };
+
+    let mut handles = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        handles.push(tokio::spawn(async move {
+            let (bytes, _) = read_file(&path).await?;
+            let (source_code, tree) = parse(&bytes)?;
+            eyre::Ok(coverage(&path, &source_code, &tree))
+        }));
+    }
+
+    let mut coverages = Vec::with_capacity(handles.len());
+    for handle in handles {
+        coverages.push(handle.await??);
+    }
+
+    coverages.sort_by(|a, b| a.module.cmp(&b.module));
+
+    let mut below_threshold = 0;
+
+    for coverage in &coverages {
+        print_stdout(&format!(
+            "{:>5.1}%  {} ({} of {} exports documented, in `{}`)",
+            coverage.percent(),
+            coverage.module,
+            coverage.documented,
+            coverage.total(),
+            coverage.path,
+        ));
+        if !coverage.undocumented.is_empty() {
+            print_stdout(&format!(
+                "        Undocumented: {}",
+                coverage.undocumented.join(", ")
+            ));
+        }
+        if config
+            .threshold
+            .is_some_and(|threshold| coverage.percent() < threshold)
+        {
+            below_threshold += 1;
+        }
+    }
+
+    let documented: usize = coverages.iter().map(|coverage| coverage.documented).sum();
+    let total: usize = coverages.iter().map(Coverage::total).sum();
+
+    message!(
+        "Documented {documented} of {total} exported {declarations}",
+        documented = documented.to_formatted_string(&Locale::en),
+        total = total.to_formatted_string(&Locale::en),
+        declarations = if total == 1 {
+            "declaration"
+        } else {
+            "declarations"
+        },
+    );
+
+    if let Some(threshold) = config.threshold
+        && below_threshold > 0
+    {
+        return Err(Findings(format!(
+            "{below_threshold} {modules} below the haddock coverage threshold of {threshold}%",
+            modules = if below_threshold == 1 {
+                "module is"
+            } else {
+                "modules are"
+            },
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+fn coverage(path: &Utf8Path, source_code: &str, tree: &Tree) -> Coverage {
+    let root = tree.root_node();
+
+    let module = lint::header_module(source_code, root).unwrap_or("Main");
+
+    let exports = signatures::exports(source_code, root);
+
+    let mut seen = HashSet::new();
+    let mut documented = 0;
+    let mut undocumented = Vec::new();
+
+    if let Some(declarations) = root.child_by_field_name("declarations") {
+        let mut cursor = declarations.walk();
+        for declaration in declarations.named_children(&mut cursor) {
+            for name in declaration_names(source_code, declaration) {
+                if exports
+                    .as_ref()
+                    .is_some_and(|exports| !exports.contains(name))
+                {
+                    continue;
+                }
+                // Haddocks go on the first declaration of a name, like its type signature
+                if !seen.insert(name) {
+                    continue;
+                }
+                if has_haddock(declaration) {
+                    documented += 1;
+                } else {
+                    undocumented.push(String::from(name));
+                }
+            }
+        }
+    }
+
+    Coverage {
+        path: normalize(path),
+        module: String::from(module),
+        documented,
+        undocumented,
+    }
+}
+
+/// Names a top-level declaration introduces, except for data constructors and class methods
+pub fn declaration_names<'a>(source_code: &'a str, declaration: Node) -> Vec<&'a str> {
+    match declaration.kind() {
+        "signature" => signatures::signature_names(source_code, declaration),
+        "function" | "bind" => declaration
+            .child_by_field_name("name")
+            .and_then(|name| signatures::binding_name(source_code, name))
+            .into_iter()
+            .collect(),
+        "data_type" | "newtype" | "type_synomym" | "class" | "type_family" | "data_family" => {
+            declaration
+                .child_by_field_name("name")
+                .and_then(|name| source_code.get(name.byte_range()))
+                .into_iter()
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Whether a `-- |` or `{- |` comment comes right before a declaration
+fn has_haddock(declaration: Node) -> bool {
+    haddock(declaration).is_some()
+}
+
+/// The `-- |` or `{- |` comment right before a declaration
+pub fn haddock(declaration: Node) -> Option<Node> {
+    // The comment before the first declaration isn't inside `declarations`
+    let previous = declaration.prev_sibling().or_else(|| {
+        declaration
+            .parent()
+            .and_then(|parent| parent.prev_sibling())
+    });
+    previous.filter(|previous| previous.kind() == "haddock")
+}
+
+fn normalize(path: &Utf8Path) -> Utf8PathBuf {
+    path.strip_prefix("./").unwrap_or(path).to_path_buf()
+}
@@ -0,0 +1,592 @@
+use crate::{
+    cli::lint::HaskellImportsArgs,
+    config::Pipeline,
+    context::cx,
+    events::message,
+    git,
+    io::{print_stdout, read_file, write_file_unless_changed},
+    lint::{self, HlintHint, Summary, builtin_hint, haddock, parse, signatures},
+    query, queue,
+};
+use bytes::Bytes;
+use camino::Utf8Path;
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    sync::Arc,
+};
+use tree_sitter::{Node, Tree};
+
+/// An `import` declaration, as written
+struct Import<'a> {
+    node: Node<'a>,
+    module: &'a str,
+    package: Option<&'a str>,
+    qualified: bool,
+    alias: Option<&'a str>,
+    hiding: bool,
+    /// Names each item in the import list brings into scope, or `None` for an item like
+    /// `Maybe (..)` whose constructors aren't known. `None` without an import list.
+    items: Option<Vec<Option<Vec<&'a str>>>>,
+    /// The import list without whitespace, to compare imports
+    list_text: String,
+}
+
+impl Import<'_> {
+    /// What qualified names from the module are qualified with
+    fn qualifier(&self) -> &str {
+        self.alias.unwrap_or(self.module)
+    }
+
+    /// Whether this import brings everything the other one does into scope
+    fn covers(&self, other: &Self) -> bool {
+        self.module == other.module
+            && self.package == other.package
+            && self.qualifier() == other.qualifier()
+            && self.items.is_none()
+            && (!self.qualified || other.qualified)
+    }
+
+    fn is_same(&self, other: &Self) -> bool {
+        self.module == other.module
+            && self.package == other.package
+            && self.qualified == other.qualified
+            && self.alias == other.alias
+            && self.hiding == other.hiding
+            && self.list_text == other.list_text
+    }
+}
+
+/// Names used outside of imports
+#[derive(Default)]
+struct Usages<'a> {
+    unqualified: HashSet<&'a str>,
+    qualified: HashSet<(&'a str, &'a str)>,
+    qualifiers: HashSet<&'a str>,
+    /// Modules and aliases exported with `module M`, which use everything imported from them
+    reexported: HashSet<&'a str>,
+}
+
+impl Usages<'_> {
+    fn is_used(&self, import: &Import, name: &str) -> bool {
+        (!import.qualified && self.unqualified.contains(name))
+            || self.qualified.contains(&(import.qualifier(), name))
+    }
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &HaskellImportsArgs) -> eyre::Result<Summary> {
+    let paths = if args.paths.is_empty() && args.packages.is_empty() {
+        git::changed_haskell_files(Pipeline::Lint).await?
+    } else {
+        git::haskell_files(&args.paths, &args.packages, Pipeline::Lint).await?
+    };
+
+    let indexed = query::indexed_modules().await?;
+    if indexed.is_empty() {
+        message!("Nothing is indexed, so run `be query index` to check imports without a list too");
+    }
+    let indexed = Arc::new(indexed);
+
+    let fix = args.fix;
+
+    let handles = queue::spawn(paths, |path| {
+        let indexed = Arc::clone(&indexed);
+        async move { check(&path, &indexed, fix).await }
+    })
+    .await?;
+
+    let mut summary = Summary::default();
+
+    for handle in handles {
+        let (_, hints) = handle.await??;
+        summary.add(hints);
+    }
+
+    message!(
+        "Checked imports in {count} Haskell {files}",
+        count = summary.file_count.to_formatted_string(&Locale::en),
+        files = if summary.file_count == 1 {
+            "file"
+        } else {
+            "files"
+        },
+    );
+
+    Ok(summary)
+}
+
+/// Unused and duplicate imports, which are removed instead when fixing. It's never cached, since
+/// whether an import is used can depend on other modules.
+#[tracing::instrument(skip(indexed), fields(indicatif.pb_show))]
+async fn check(
+    path: &Utf8Path,
+    indexed: &HashMap<String, Vec<String>>,
+    fix: bool,
+) -> eyre::Result<(bool, Vec<HlintHint>)> {
+    let (bytes, input_hash) = read_file(path).await?;
+    let (source_code, tree) = parse(&bytes)?;
+
+    let imports = imports(&source_code, &tree);
+
+    // Everything modules imported without a list export, when it's all known. An import of a module
+    // which isn't in the repo, or which isn't known exactly, is assumed to be used.
+    let git_root = cx().cache.git_root().await?;
+    let mut declarations = HashMap::new();
+    for import in &imports {
+        if (import.items.is_some() && !import.hiding) || import.package.is_some() {
+            continue;
+        }
+        let Some([module_path]) = indexed.get(import.module).map(Vec::as_slice) else {
+            continue;
+        };
+        let Ok((module_bytes, _)) = read_file(&git_root.join(module_path)).await else {
+            continue;
+        };
+        let Ok((module_source_code, module_tree)) = parse(&module_bytes) else {
+            continue;
+        };
+        if lint::header_module(&module_source_code, module_tree.root_node()) != Some(import.module)
+        {
+            tracing::debug!("`{}` moved since it was indexed", import.module);
+            continue;
+        }
+        if let Some(names) = exported_names(&module_source_code, &module_tree) {
+            declarations.insert(import.module, names);
+        } else {
+            tracing::debug!("Not everything `{}` exports is known", import.module);
+        }
+    }
+
+    let findings = findings(&source_code, &tree, &imports, &declarations);
+
+    if !fix || findings.is_empty() {
+        let hints = findings.into_iter().map(|(_, hint)| hint);
+        let mut hints: Vec<_> = hints
+            .map(|mut hint| {
+                hint.file.push_str(path.as_str());
+                hint
+            })
+            .collect();
+        hints.sort_by_key(|hint| (hint.start_line, hint.start_column));
+        return Ok((true, hints));
+    }
+
+    let count = findings.len();
+    let ranges: Vec<_> = findings
+        .iter()
+        .map(|(node, _)| line_range(&source_code, node.byte_range()))
+        .collect();
+    let output = remove(&source_code, ranges);
+
+    if write_file_unless_changed(path, Bytes::from(output), input_hash).await? {
+        print_stdout(&format!(
+            "{path}: removed {count} {imports}",
+            imports = if count == 1 { "import" } else { "imports" },
+        ));
+    }
+
+    Ok((true, Vec::new()))
+}
+
+fn imports<'a>(source_code: &'a str, tree: &'a Tree) -> Vec<Import<'a>> {
+    let mut imports = Vec::new();
+    let Some(node) = tree.root_node().child_by_field_name("imports") else {
+        return imports;
+    };
+    let text = |node: Node| source_code.get(node.byte_range());
+    let mut cursor = node.walk();
+    for node in node.children_by_field_name("import", &mut cursor) {
+        let Some(module) = node.child_by_field_name("module").and_then(text) else {
+            continue;
+        };
+        let has_token = |kind| {
+            let mut cursor = node.walk();
+            node.children(&mut cursor).any(|child| child.kind() == kind)
+        };
+        let list = node.child_by_field_name("names");
+        let items = list.map(|list| {
+            let mut cursor = list.walk();
+            list.children_by_field_name("name", &mut cursor)
+                .map(|item| item_names(source_code, item))
+                .collect()
+        });
+        imports.push(Import {
+            node,
+            module,
+            package: node.child_by_field_name("package").and_then(text),
+            qualified: has_token("qualified"),
+            alias: node.child_by_field_name("alias").and_then(text),
+            hiding: has_token("hiding"),
+            items,
+            list_text: list
+                .and_then(text)
+                .unwrap_or_default()
+                .split_whitespace()
+                .collect(),
+        });
+    }
+    imports
+}
+
+/// Names an import list item brings into scope, like `Maybe (Just)` bringing `Maybe` and `Just`
+fn item_names<'a>(source_code: &'a str, item: Node) -> Option<Vec<&'a str>> {
+    let name = item
+        .child_by_field_name("variable")
+        .or_else(|| item.child_by_field_name("type"))
+        .or_else(|| item.child_by_field_name("operator"))
+        .and_then(|name| source_code.get(name.byte_range()))?;
+    let mut names = vec![unparenthesize(name)];
+    if let Some(children) = item.child_by_field_name("children") {
+        let mut cursor = children.walk();
+        for element in children.children_by_field_name("element", &mut cursor) {
+            if element.kind() == "all_names" {
+                return None;
+            }
+            names.extend(source_code.get(element.byte_range()).map(unparenthesize));
+        }
+    }
+    Some(names)
+}
+
+fn unparenthesize(name: &str) -> &str {
+    name.trim_start_matches('(').trim_end_matches(')').trim()
+}
+
+/// Every name used in the export list and declarations
+fn usages<'a>(source_code: &'a str, tree: &'a Tree) -> Usages<'a> {
+    let mut usages = Usages::default();
+    let root = tree.root_node();
+    let mut nodes: Vec<Node> = Vec::new();
+    if let Some(exports) =
+        lint::header(root).and_then(|header| header.child_by_field_name("exports"))
+    {
+        nodes.push(exports);
+    }
+    nodes.extend(root.child_by_field_name("declarations"));
+    let text = |node: Node| source_code.get(node.byte_range()).unwrap_or_default();
+    while let Some(node) = nodes.pop() {
+        match node.kind() {
+            "qualified" => {
+                let (Some(module), Some(id)) = (
+                    node.child_by_field_name("module"),
+                    node.child_by_field_name("id"),
+                ) else {
+                    continue;
+                };
+                let module = text(module).trim_end_matches('.');
+                usages.qualifiers.insert(module);
+                usages.qualified.insert((module, unparenthesize(text(id))));
+            }
+            "module_export" => {
+                if let Some(module) = node.child_by_field_name("module") {
+                    usages.reexported.insert(text(module));
+                }
+            }
+            "variable"
+            | "name"
+            | "constructor"
+            | "operator"
+            | "constructor_operator"
+            | "field_name" => {
+                usages.unqualified.insert(text(node));
+            }
+            _ => {
+                let mut cursor = node.walk();
+                nodes.extend(node.children(&mut cursor));
+            }
+        }
+    }
+    usages
+}
+
+/// Names a module brings into scope where it's imported without a list, or `None` when they aren't
+/// all known. That's the case with a `module M` re-export or a Template Haskell splice, and with
+/// orphan instances, which an import can be for without using any names.
+fn exported_names(source_code: &str, tree: &Tree) -> Option<HashSet<String>> {
+    let root = tree.root_node();
+
+    if query::has_orphans(source_code, tree) {
+        return None;
+    }
+
+    // Top-level names, with the constructors, fields, and methods that come with them
+    let mut declared: HashMap<&str, Vec<&str>> = HashMap::new();
+    if let Some(declarations) = root.child_by_field_name("declarations") {
+        let mut cursor = declarations.walk();
+        for declaration in declarations.named_children(&mut cursor) {
+            match declaration.kind() {
+                "top_splice" => return None,
+                "pattern_synonym" => {
+                    let mut cursor = declaration.walk();
+                    for child in declaration.named_children(&mut cursor) {
+                        let synonym = child.child_by_field_name("synonym")?;
+                        declared.entry(text(source_code, synonym)).or_default();
+                    }
+                }
+                "data_instance" | "instance" => {
+                    for (family, constructors) in data_instances(source_code, declaration) {
+                        declared.entry(family).or_default().extend(constructors);
+                    }
+                }
+                "foreign_import" => {
+                    let signature = declaration.child_by_field_name("signature")?;
+                    for name in signatures::signature_names(source_code, signature) {
+                        declared.entry(name).or_default();
+                    }
+                }
+                _ => {}
+            }
+            for name in haddock::declaration_names(source_code, declaration) {
+                declared
+                    .entry(name)
+                    .or_default()
+                    .extend(subordinate_names(source_code, declaration));
+            }
+        }
+    }
+
+    let all = || {
+        declared
+            .iter()
+            .flat_map(|(name, subordinates)| std::iter::once(name).chain(subordinates))
+            .map(|name| String::from(*name))
+    };
+
+    let Some(exports) = lint::header(root).and_then(|header| header.child_by_field_name("exports"))
+    else {
+        return Some(all().collect());
+    };
+
+    let module = lint::header_module(source_code, root);
+    let mut names = HashSet::new();
+    let mut cursor = exports.walk();
+    for export in exports.named_children(&mut cursor) {
+        match export.kind() {
+            "module_export" => {
+                let reexported = export
+                    .child_by_field_name("module")
+                    .map(|module| text(source_code, module));
+                if reexported.is_none() || reexported != module {
+                    return None;
+                }
+                names.extend(all());
+            }
+            "export" => {
+                let Some(name) = export
+                    .child_by_field_name("variable")
+                    .or_else(|| export.child_by_field_name("type"))
+                    .or_else(|| export.child_by_field_name("operator"))
+                else {
+                    continue;
+                };
+                // A qualified export is imported by its unqualified name
+                let name = name.child_by_field_name("id").unwrap_or(name);
+                let name = unparenthesize(text(source_code, name));
+                names.insert(String::from(name));
+                let Some(children) = export.child_by_field_name("children") else {
+                    continue;
+                };
+                let mut cursor = children.walk();
+                for element in children.children_by_field_name("element", &mut cursor) {
+                    if element.kind() == "all_names" {
+                        // The constructors of a re-exported type aren't known
+                        let subordinates = declared.get(name)?;
+                        names.extend(subordinates.iter().map(|name| String::from(*name)));
+                    } else {
+                        names.insert(String::from(unparenthesize(text(source_code, element))));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Some(names)
+}
+
+/// Data constructors and record fields of a type, or methods and associated types of a class
+fn subordinate_names<'a>(source_code: &'a str, declaration: Node) -> Vec<&'a str> {
+    let mut names = Vec::new();
+    match declaration.kind() {
+        "data_type" | "newtype" => {
+            let mut nodes: Vec<Node> = Vec::new();
+            nodes.extend(declaration.child_by_field_name("constructors"));
+            nodes.extend(declaration.child_by_field_name("constructor"));
+            while let Some(node) = nodes.pop() {
+                match node.kind() {
+                    "constructor" | "constructor_operator" | "field_name" => {
+                        names.push(unparenthesize(text(source_code, node)));
+                    }
+                    _ => {
+                        let mut cursor = node.walk();
+                        nodes.extend(node.named_children(&mut cursor));
+                    }
+                }
+            }
+        }
+        "class" => {
+            if let Some(declarations) = declaration.child_by_field_name("declarations") {
+                let mut cursor = declarations.walk();
+                for declaration in declarations.named_children(&mut cursor) {
+                    names.extend(haddock::declaration_names(source_code, declaration));
+                }
+            }
+        }
+        _ => {}
+    }
+    names
+}
+
+/// Data and newtype instances, at the top level or in a class instance, with their constructors
+fn data_instances<'a>(source_code: &'a str, declaration: Node) -> Vec<(&'a str, Vec<&'a str>)> {
+    let mut instances: Vec<Node> = Vec::new();
+    if declaration.kind() == "data_instance" {
+        instances.push(declaration);
+    } else if let Some(declarations) = declaration.child_by_field_name("declarations") {
+        let mut cursor = declarations.walk();
+        instances.extend(
+            declarations
+                .named_children(&mut cursor)
+                .filter(|declaration| declaration.kind() == "data_instance"),
+        );
+    }
+    instances
+        .into_iter()
+        .filter_map(|instance| instance.named_child(0))
+        .filter_map(|instance| {
+            let family = instance.child_by_field_name("name")?;
+            Some((
+                text(source_code, family),
+                subordinate_names(source_code, instance),
+            ))
+        })
+        .collect()
+}
+
+fn text<'a>(source_code: &'a str, node: Node) -> &'a str {
+    source_code.get(node.byte_range()).unwrap_or_default()
+}
+
+/// Imports to remove, with why
+fn findings<'a>(
+    source_code: &str,
+    tree: &'a Tree,
+    imports: &[Import<'a>],
+    declarations: &HashMap<&str, HashSet<String>>,
+) -> Vec<(Node<'a>, HlintHint)> {
+    let root = tree.root_node();
+    let usages = usages(source_code, tree);
+    let mut findings = Vec::new();
+
+    for (i, import) in imports.iter().enumerate() {
+        let line = |import: &Import| import.node.start_position().row + 1;
+        let duplicate = imports.iter().enumerate().find(|(j, other)| {
+            *j != i
+                && ((other.covers(import) && !import.covers(other))
+                    || (*j < i && (other.covers(import) || other.is_same(import))))
+        });
+        let hint = if let Some((_, other)) = duplicate {
+            let mut hint = builtin_hint(
+                source_code,
+                root,
+                import.node,
+                import.module,
+                "Duplicate import",
+            );
+            hint.note.push(format!(
+                "The import on line {} already brings all of this into scope",
+                line(other),
+            ));
+            hint
+        } else if !is_used(import, &usages, declarations) {
+            let mut hint = builtin_hint(
+                source_code,
+                root,
+                import.node,
+                import.module,
+                "Unused import",
+            );
+            if import.items.is_none() {
+                hint.note.push(format!(
+                    "If it's only imported for its instances, use `import {} ()`",
+                    import.module
+                ));
+            }
+            hint
+        } else {
+            continue;
+        };
+        findings.push((import.node, hint));
+    }
+
+    findings
+}
+
+/// Whether anything an import brings into scope is used, assuming it is when that's not known
+fn is_used(
+    import: &Import,
+    usages: &Usages,
+    declarations: &HashMap<&str, HashSet<String>>,
+) -> bool {
+    if usages.reexported.contains(import.qualifier()) || usages.reexported.contains(import.module) {
+        return true;
+    }
+
+    if let Some(items) = &import.items
+        && !import.hiding
+    {
+        // An empty import list is for instances
+        return items.is_empty()
+            || items.iter().any(|names| {
+                names
+                    .as_ref()
+                    .is_none_or(|names| names.iter().any(|name| usages.is_used(import, name)))
+            });
+    }
+
+    if usages.qualifiers.contains(import.qualifier()) {
+        return true;
+    }
+
+    if import.qualified {
+        return false;
+    }
+
+    let Some(declarations) = declarations.get(import.module) else {
+        return true;
+    };
+    declarations.iter().any(|name| usages.is_used(import, name))
+}
+
+/// The lines a node is on, when nothing but whitespace and comments is on them with it
+fn line_range(source_code: &str, range: Range<usize>) -> Range<usize> {
+    let line_start = source_code[..range.start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source_code[range.end..]
+        .find('\n')
+        .map_or(source_code.len(), |i| range.end + i + 1);
+    let before = &source_code[line_start..range.start];
+    let after = source_code[range.end..line_end].trim();
+    if before.trim().is_empty() && (after.is_empty() || after.starts_with("--")) {
+        line_start..line_end
+    } else {
+        range
+    }
+}
+
+fn remove(source_code: &str, mut ranges: Vec<Range<usize>>) -> String {
+    ranges.sort_by_key(|range| range.start);
+    let mut output = String::with_capacity(source_code.len());
+    let mut position = 0;
+    for range in ranges {
+        if range.start < position {
+            continue;
+        }
+        output.push_str(&source_code[position..range.start]);
+        position = range.end;
+    }
+    output.push_str(&source_code[position..]);
+    output
+}
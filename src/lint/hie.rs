@@ -0,0 +1,116 @@
+use crate::context::cx;
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre;
+use std::collections::HashMap;
+use tokio::fs;
+
+/// The `.hie` files GHC wrote, which `weeder` and `stan` read instead of source code
+pub struct HieFiles {
+    /// Where they are, relative to the git root
+    pub dir: Utf8PathBuf,
+    /// Relative to `dir`, sorted so a combined hash is stable
+    pub paths: Vec<Utf8PathBuf>,
+    /// Indices into `paths` by every suffix of each path, since there may be a directory per
+    /// package
+    by_suffix: HashMap<Utf8PathBuf, usize>,
+    git_root: Utf8PathBuf,
+    /// Source directories of every package, relative to the git root
+    source_dirs: Vec<Utf8PathBuf>,
+}
+
+impl HieFiles {
+    /// Every `.hie` file under `[haskell] hie-directory`, failing if there are none
+    pub async fn find() -> eyre::Result<Self> {
+        let cx = cx();
+
+        let config = cx.cache.config().await?;
+        let git_root = cx.cache.git_root().await?;
+        let dir = config.haskell.hie_directory();
+
+        let paths = files(&git_root.join(dir)).await?;
+        if paths.is_empty() {
+            eyre::bail!(
+                "No `.hie` files in `{dir}`, so build with `-fwrite-ide-info -hiedir={dir}` first"
+            );
+        }
+
+        let mut by_suffix = HashMap::new();
+        for (i, path) in paths.iter().enumerate() {
+            let components: Vec<_> = path.components().collect();
+            for start in 0..components.len() {
+                by_suffix.insert(components[start..].iter().collect(), i);
+            }
+        }
+
+        let mut source_dirs = Vec::new();
+        for package in cx.cache.packages().await? {
+            for component in &package.components {
+                source_dirs.extend(component.source_dirs.iter().cloned());
+            }
+        }
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            paths,
+            by_suffix,
+            git_root: git_root.clone(),
+            source_dirs,
+        })
+    }
+
+    /// The `.hie` file for a Haskell file, relative to `dir`. Returns `None` if it's missing, or if
+    /// the file isn't in a package's source directory, so its module name isn't known.
+    pub fn for_source(&self, path: &Utf8Path) -> eyre::Result<Option<&Utf8Path>> {
+        let absolute_path = Utf8PathBuf::try_from(std::path::absolute(path)?)?;
+        let relative_path = absolute_path
+            .strip_prefix(&self.git_root)
+            .unwrap_or(&absolute_path);
+        let Some(module) = self
+            .source_dirs
+            .iter()
+            .find_map(|dir| relative_path.strip_prefix(dir).ok())
+        else {
+            return Ok(None);
+        };
+        Ok(self
+            .by_suffix
+            .get(&module.with_extension("hie"))
+            .map(|i| self.paths[*i].as_path()))
+    }
+
+    /// Whether a Haskell file is in a package's source directory but has no `.hie` file
+    pub fn is_missing(&self, path: &Utf8Path) -> eyre::Result<bool> {
+        let absolute_path = Utf8PathBuf::try_from(std::path::absolute(path)?)?;
+        let relative_path = absolute_path
+            .strip_prefix(&self.git_root)
+            .unwrap_or(&absolute_path);
+        let in_package = self
+            .source_dirs
+            .iter()
+            .any(|dir| relative_path.starts_with(dir));
+        Ok(in_package && self.for_source(path)?.is_none())
+    }
+}
+
+/// Every `.hie` file under the directory, relative to it
+async fn files(dir: &Utf8Path) -> eyre::Result<Vec<Utf8PathBuf>> {
+    let mut paths = Vec::new();
+    if !fs::try_exists(dir).await? {
+        return Ok(paths);
+    }
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(subdir) = dirs.pop() {
+        let mut entries = fs::read_dir(&subdir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = Utf8PathBuf::try_from(entry.path())?;
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                dirs.push(path);
+            } else if file_type.is_file() && path.extension() == Some("hie") {
+                paths.push(path.strip_prefix(dir)?.to_path_buf());
+            }
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
@@ -0,0 +1,82 @@
+use crate::{
+    cli::lint::HpackArgs,
+    events::message,
+    generate,
+    lint::{HlintHint, HlintSeverity, Summary},
+    queue,
+};
+use camino::Utf8Path;
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &HpackArgs) -> eyre::Result<Summary> {
+    let paths = generate::package_yamls(&args.paths, &args.packages).await?;
+
+    let handles = queue::spawn(paths, |path| async move { check(&path).await }).await?;
+
+    let mut summary = Summary::default();
+
+    for handle in handles {
+        let (_, hints) = handle.await??;
+        summary.add(hints);
+    }
+
+    message!(
+        "Checked {count} `.cabal` {files} against `package.yaml`",
+        count = summary.file_count.to_formatted_string(&Locale::en),
+        files = if summary.file_count == 1 {
+            "file"
+        } else {
+            "files"
+        },
+    );
+
+    Ok(summary)
+}
+
+/// The same comparison as `be generate hpack --check`, reported as a finding with the diff. It's
+/// never cached, since `hpack` is fast and a `.cabal` file can change without its `package.yaml`.
+#[tracing::instrument(fields(indicatif.pb_show))]
+async fn check(path: &Utf8Path) -> eyre::Result<(bool, Vec<HlintHint>)> {
+    let Some(out_of_date) = generate::out_of_date(path).await? else {
+        return Ok((true, Vec::new()));
+    };
+
+    let (hint, from) = match &out_of_date.existing {
+        Some(existing) => (
+            "Out of date with `package.yaml`",
+            String::from_utf8_lossy(existing)
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+        ),
+        None => (
+            "Missing, but `package.yaml` would generate it",
+            String::new(),
+        ),
+    };
+
+    let hint = HlintHint {
+        module: Vec::new(),
+        decl: Vec::new(),
+        severity: HlintSeverity::Warning,
+        hint: String::from(hint),
+        file: out_of_date.cabal_path.to_string(),
+        start_line: 1,
+        start_column: 1,
+        end_line: 1,
+        end_column: from.chars().count() + 1,
+        from,
+        to: None,
+        note: vec![
+            String::from("Run `be fix hpack` to regenerate it"),
+            out_of_date.diff(),
+        ],
+        refactorings: String::from("[]"),
+        tool: "hpack",
+    };
+
+    Ok((true, vec![hint]))
+}
@@ -0,0 +1,94 @@
+use crate::{
+    cli::lint::ImportsArgs,
+    config::Pipeline,
+    context::cx,
+    events::message,
+    git,
+    io::read_file,
+    lint::{self, HlintHint, HlintSeverity, Summary, parse},
+    query, queue,
+};
+use camino::Utf8Path;
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &ImportsArgs) -> eyre::Result<Summary> {
+    if cx().cache.config().await?.lint.imports.rules.is_empty() {
+        eyre::bail!("No import rules, so add some to `[[lint.imports.rules]]` first");
+    }
+
+    let paths = if args.paths.is_empty() && args.packages.is_empty() {
+        git::changed_haskell_files(Pipeline::Lint).await?
+    } else {
+        git::haskell_files(&args.paths, &args.packages, Pipeline::Lint).await?
+    };
+
+    let handles = queue::spawn(paths, |path| async move { check(&path).await }).await?;
+
+    let mut summary = Summary::default();
+
+    for handle in handles {
+        let (_, hints) = handle.await??;
+        summary.add(hints);
+    }
+
+    message!(
+        "Checked imports in {count} Haskell {files}",
+        count = summary.file_count.to_formatted_string(&Locale::en),
+        files = if summary.file_count == 1 {
+            "file"
+        } else {
+            "files"
+        },
+    );
+
+    Ok(summary)
+}
+
+/// Imports any rule forbids. It's never cached, since parsing is faster than a cache lookup.
+#[tracing::instrument(fields(indicatif.pb_show))]
+async fn check(path: &Utf8Path) -> eyre::Result<(bool, Vec<HlintHint>)> {
+    let rules = &cx().cache.config().await?.lint.imports.rules;
+
+    let (bytes, _) = read_file(path).await?;
+    let (source_code, tree) = parse(&bytes)?;
+
+    // A module without a header is `Main`
+    let module = lint::header_module(&source_code, tree.root_node()).unwrap_or("Main");
+
+    let mut hints = Vec::new();
+
+    for import in query::query_imports(&source_code, &tree)? {
+        let Some(rule) = rules.iter().find(|rule| rule.forbids(module, import.text)) else {
+            continue;
+        };
+        let from = source_code
+            .lines()
+            .nth(import.line)
+            .unwrap_or_default()
+            .trim();
+        hints.push(HlintHint {
+            module: vec![String::from(module)],
+            decl: vec![String::from(import.text)],
+            severity: rule.severity.unwrap_or(HlintSeverity::Error),
+            hint: String::from("Banned import"),
+            file: path.to_string(),
+            start_line: import.line + 1,
+            start_column: import.column + 1,
+            end_line: import.line + 1,
+            end_column: import.column + import.text.chars().count() + 1,
+            from: String::from(from),
+            to: None,
+            note: vec![
+                rule.reason
+                    .clone()
+                    .unwrap_or_else(|| format!("`{module}` may not import `{}`", import.text)),
+            ],
+            refactorings: String::from("[]"),
+            tool: "be",
+        });
+    }
+
+    Ok((true, hints))
+}
@@ -0,0 +1,82 @@
+use crate::{
+    lint::{self, BuiltinFile, HlintHint, builtin_hint},
+    packages::{self, Package},
+};
+use camino::Utf8Path;
+use tree_sitter::Tree;
+
+pub const NAME: &str = "module-name";
+
+/// The names the path allows are part of the cache key, since the same contents can be in
+/// different places
+pub fn settings(file: &BuiltinFile) -> String {
+    file.expected_modules.join(",")
+}
+
+/// Modules whose name doesn't match their path, which GHC only reports once something imports them
+pub fn hints(file: &BuiltinFile, source_code: &str, tree: &Tree) -> Vec<HlintHint> {
+    let Some(expected) = file.expected_modules.first() else {
+        return Vec::new();
+    };
+
+    let root = tree.root_node();
+
+    // A module without a header is `Main`
+    let Some(module) = lint::header(root).and_then(|header| header.child_by_field_name("module"))
+    else {
+        return Vec::new();
+    };
+
+    let Some(name) = source_code.get(module.byte_range()) else {
+        return Vec::new();
+    };
+
+    // An executable's `main-is` module can be at any path
+    if name == "Main" || file.expected_modules.iter().any(|module| module == name) {
+        return Vec::new();
+    }
+
+    let mut hint = builtin_hint(
+        source_code,
+        root,
+        module,
+        name,
+        "Module name doesn't match its path",
+    );
+    hint.to = Some(expected.clone());
+    hint.note.push(format!(
+        "Modules at this path are named {}",
+        file.expected_modules
+            .iter()
+            .map(|module| format!("`{module}`"))
+            .collect::<Vec<_>>()
+            .join(" or "),
+    ));
+    vec![hint]
+}
+
+/// Module names for a path relative to the git root, in every source directory it's in, most
+/// specific first. Empty if it isn't in one, or if some directory isn't a valid module name.
+pub fn expected_modules(relative_path: &Utf8Path, packages: &[Package]) -> Vec<String> {
+    let mut candidates = Vec::new();
+    for package in packages {
+        for component in &package.components {
+            for dir in &component.source_dirs {
+                let module_path = if dir == "." {
+                    relative_path
+                } else if let Ok(module_path) = relative_path.strip_prefix(dir) {
+                    module_path
+                } else {
+                    continue;
+                };
+                if let Some(module) = packages::module_name(module_path)
+                    && !candidates.iter().any(|(_, name)| *name == module)
+                {
+                    candidates.push((dir.as_str().len(), module));
+                }
+            }
+        }
+    }
+    candidates.sort_by_key(|(dir_len, _)| std::cmp::Reverse(*dir_len));
+    candidates.into_iter().map(|(_, module)| module).collect()
+}
@@ -0,0 +1,372 @@
+use crate::{
+    cli::lint::NixArgs,
+    config::Pipeline,
+    context::cx,
+    events::message,
+    exec, git,
+    io::read_file,
+    lint::{HlintHint, HlintSeverity, Summary},
+    queue,
+};
+use bytes::Bytes;
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use std::{os::unix::process::ExitStatusExt as _, process::Stdio};
+use tempfile::tempdir;
+use tokio::fs;
+
+/// Nix linters, each of which is run if it's installed and enabled
+const LINTERS: &[&str] = &["statix", "deadnix"];
+
+/// Linters which are installed and enabled
+#[tracing::instrument]
+pub async fn linters() -> eyre::Result<Vec<&'static str>> {
+    let cx = cx();
+    let config = cx.cache.config().await?;
+    let mut linters = Vec::new();
+    for linter in LINTERS {
+        if config.is_tool_enabled(linter) && cx.cache.which(linter).await.is_ok() {
+            linters.push(*linter);
+        } else {
+            tracing::debug!("Not running `{linter}`, since it isn't installed or is disabled");
+        }
+    }
+    Ok(linters)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &NixArgs) -> eyre::Result<Summary> {
+    let linters = linters().await?;
+
+    if linters.is_empty() {
+        eyre::bail!("Install `statix` or `deadnix` to lint Nix files");
+    }
+
+    let paths = if args.paths.is_empty() && args.packages.is_empty() {
+        git::changed_nix_files(Pipeline::Lint).await?
+    } else {
+        git::nix_files(&args.paths, &args.packages, Pipeline::Lint).await?
+    };
+
+    let handles = queue::spawn(paths, |path| {
+        let linters = linters.clone();
+        async move { check(&path, &linters).await }
+    })
+    .await?;
+
+    let mut summary = Summary::default();
+    let mut checked_count = 0;
+
+    for handle in handles {
+        let (checked, hints) = handle.await??;
+        if checked {
+            checked_count += 1;
+        }
+        summary.add(hints);
+    }
+
+    message!(
+        "Linted {checked_count} of {total_count} Nix {files}",
+        checked_count = checked_count.to_formatted_string(&Locale::en),
+        total_count = summary.file_count.to_formatted_string(&Locale::en),
+        files = if summary.file_count == 1 {
+            "file"
+        } else {
+            "files"
+        },
+    );
+
+    Ok(summary)
+}
+
+/// Findings from every linter, sorted by position
+#[tracing::instrument(fields(indicatif.pb_show))]
+async fn check(path: &Utf8Path, linters: &[&'static str]) -> eyre::Result<(bool, Vec<HlintHint>)> {
+    let cx = cx();
+
+    let (input_bytes, input_hash) = read_file(path).await?;
+
+    let mut checked = false;
+    let mut hints = Vec::new();
+
+    for linter in linters {
+        if let Some(linter_hints) = cx.cache.is_nix_linted(linter, input_hash).await? {
+            tracing::trace!("Using cached `{linter}` results");
+            hints.extend(linter_hints.into_iter().map(|hint| HlintHint {
+                tool: linter,
+                ..hint
+            }));
+            continue;
+        }
+
+        tracing::trace!("Linting with `{linter}`");
+
+        let (linter_hints, duration) = exec::timed(lint(linter, input_bytes.clone())).await?;
+
+        cx.cache
+            .record_duration(linter, input_hash, path, duration)
+            .await?;
+
+        cx.cache
+            .mark_nix_linted(linter, input_hash, &linter_hints)
+            .await?;
+
+        hints.extend(linter_hints);
+        checked = true;
+    }
+
+    for hint in &mut hints {
+        hint.file.clear();
+        hint.file.push_str(path.as_str());
+    }
+
+    hints.sort_by_key(|hint| (hint.start_line, hint.start_column));
+
+    Ok((checked, hints))
+}
+
+#[derive(serde::Deserialize)]
+struct StatixFile {
+    report: Vec<StatixReport>,
+}
+
+#[derive(serde::Deserialize)]
+struct StatixReport {
+    note: String,
+    severity: String,
+    diagnostics: Vec<StatixDiagnostic>,
+}
+
+#[derive(serde::Deserialize)]
+struct StatixDiagnostic {
+    at: StatixSpan,
+    message: String,
+    suggestion: Option<StatixSuggestion>,
+}
+
+#[derive(serde::Deserialize)]
+struct StatixSuggestion {
+    fix: String,
+}
+
+#[derive(serde::Deserialize)]
+struct StatixSpan {
+    from: StatixPosition,
+    to: StatixPosition,
+}
+
+#[derive(serde::Deserialize)]
+struct StatixPosition {
+    line: usize,
+    column: usize,
+}
+
+#[derive(serde::Deserialize)]
+struct DeadnixFile {
+    results: Vec<DeadnixResult>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeadnixResult {
+    line: usize,
+    column: usize,
+    end_column: usize,
+    message: String,
+}
+
+/// Run a linter on a copy of the file, since neither reads code from `stdin`
+#[tracing::instrument(skip(bytes))]
+async fn lint(linter: &'static str, bytes: Bytes) -> eyre::Result<Vec<HlintHint>> {
+    let cx = cx();
+
+    let binary = &cx.cache.which(linter).await?;
+
+    let tool_permits = cx.tool_permits(linter).await?;
+
+    let temp_dir = tempdir()?;
+    let temp_dir_path = Utf8PathBuf::try_from(fs::canonicalize(temp_dir.path()).await?)?;
+
+    let file_path = temp_dir_path.join("default.nix");
+    fs::write(&file_path, &bytes).await?;
+
+    let (profile, args) = if linter == "statix" {
+        (
+            exec::STATIX_PROFILE,
+            vec![
+                String::from("check"),
+                String::from("--format=json"),
+                file_path.to_string(),
+            ],
+        )
+    } else {
+        (
+            exec::DEADNIX_PROFILE,
+            vec![String::from("--output-format=json"), file_path.to_string()],
+        )
+    };
+
+    let tool_permit = tool_permits.acquire().await?;
+    let file_permit = cx.file_permits.acquire().await?;
+    let process_permit = cx.process_permits.acquire().await?;
+
+    let mut command = exec::sandboxed(
+        Some(linter),
+        profile,
+        &[exec::Param {
+            name: "TEMP_DIR",
+            path: &temp_dir_path,
+            writable: false,
+        }],
+        binary,
+    )
+    .await?;
+
+    let output = exec::timeout(
+        linter,
+        command
+            .args(args)
+            .env_clear()
+            .current_dir("/var/empty")
+            .kill_on_drop(true)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output(),
+    )
+    .await?;
+
+    drop(process_permit);
+    drop(file_permit);
+    drop(tool_permit);
+
+    // Both exit with code 1 when they find problems
+    if !output.status.success() && output.status.code() != Some(1) {
+        if let Some(exit_code) = output.status.code() {
+            eyre::bail!(
+                "`{linter}` exited with code {exit_code}:\n{}",
+                String::from_utf8_lossy(&output.stderr),
+            );
+        } else if let Some(signal) = output.status.signal() {
+            eyre::bail!("`{linter}` was terminated by signal {signal}");
+        } else {
+            eyre::bail!("`{linter}` died of unknown causes");
+        }
+    }
+
+    let source_code = String::from_utf8_lossy(&bytes);
+
+    let mut hints = Vec::new();
+
+    // One JSON object per file
+    for line in output.stdout.split(|byte| *byte == b'\n') {
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        if linter == "statix" {
+            let file: StatixFile = serde_json::from_slice(line)?;
+            hints.extend(statix_hints(&source_code, file));
+        } else {
+            let file: DeadnixFile = serde_json::from_slice(line)?;
+            hints.extend(deadnix_hints(&source_code, file));
+        }
+    }
+
+    Ok(hints)
+}
+
+fn statix_hints(source_code: &str, file: StatixFile) -> Vec<HlintHint> {
+    let mut hints = Vec::new();
+    for report in file.report {
+        let severity = match report.severity.as_str() {
+            "Error" => HlintSeverity::Error,
+            "Warn" => HlintSeverity::Warning,
+            _ => HlintSeverity::Suggestion,
+        };
+        for diagnostic in report.diagnostics {
+            let StatixSpan { from, to } = diagnostic.at;
+            let note = if diagnostic.message == report.note {
+                Vec::new()
+            } else {
+                vec![diagnostic.message]
+            };
+            hints.push(HlintHint {
+                module: Vec::new(),
+                decl: Vec::new(),
+                severity,
+                hint: report.note.clone(),
+                file: String::new(),
+                start_line: from.line,
+                start_column: from.column,
+                end_line: to.line,
+                end_column: to.column,
+                from: span(source_code, &from, &to),
+                to: diagnostic.suggestion.map(|suggestion| suggestion.fix),
+                note,
+                refactorings: String::from("[]"),
+                tool: "statix",
+            });
+        }
+    }
+    hints
+}
+
+fn deadnix_hints(source_code: &str, file: DeadnixFile) -> Vec<HlintHint> {
+    file.results
+        .into_iter()
+        .map(|result| {
+            // Like "Unused lambda argument: x"
+            let (hint, name) = result
+                .message
+                .split_once(": ")
+                .unwrap_or((&result.message, ""));
+            let from = if name.is_empty() {
+                span(
+                    source_code,
+                    &StatixPosition {
+                        line: result.line,
+                        column: result.column,
+                    },
+                    &StatixPosition {
+                        line: result.line,
+                        column: result.end_column,
+                    },
+                )
+            } else {
+                String::from(name)
+            };
+            HlintHint {
+                module: Vec::new(),
+                decl: Vec::new(),
+                severity: HlintSeverity::Warning,
+                hint: String::from(hint),
+                file: String::new(),
+                start_line: result.line,
+                start_column: result.column,
+                end_line: result.line,
+                end_column: result.end_column,
+                from,
+                to: None,
+                note: Vec::new(),
+                refactorings: String::from("[]"),
+                tool: "deadnix",
+            }
+        })
+        .collect()
+}
+
+/// Source code between two 1-based positions, or the first line of it if it spans several
+fn span(source_code: &str, from: &StatixPosition, to: &StatixPosition) -> String {
+    let line = source_code
+        .lines()
+        .nth(from.line.saturating_sub(1))
+        .unwrap_or_default();
+    let start = from.column.saturating_sub(1).min(line.len());
+    let end = if to.line == from.line {
+        to.column.saturating_sub(1).clamp(start, line.len())
+    } else {
+        line.len()
+    };
+    line.get(start..end).unwrap_or(line).trim().to_string()
+}
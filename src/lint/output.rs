@@ -0,0 +1,280 @@
+use crate::{
+    cli::lint::Format,
+    github::Annotation,
+    lint::{HlintHint, HlintSeverity},
+};
+use color_eyre::eyre;
+use serde::Serialize;
+use std::{collections::BTreeSet, fmt::Write as _};
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Findings from every file, in the requested format
+pub fn render(format: Format, hints: &[HlintHint], color: bool) -> eyre::Result<String> {
+    match format {
+        Format::Human => {
+            let mut output = String::new();
+            for hint in hints {
+                writeln!(output, "{}", hint.render(color))?;
+            }
+            Ok(output)
+        }
+        Format::Github => {
+            let mut output = String::new();
+            for hint in hints {
+                writeln!(output, "{}", annotation(hint, &message(hint)))?;
+            }
+            Ok(output)
+        }
+        Format::Json => {
+            let findings: Vec<Finding> = hints.iter().map(Finding::from).collect();
+            let mut output = serde_json::to_string_pretty(&findings)?;
+            output.push('\n');
+            Ok(output)
+        }
+        Format::JsonLines => {
+            let mut output = String::new();
+            for hint in hints {
+                writeln!(output, "{}", serde_json::to_string(&Finding::from(hint))?)?;
+            }
+            Ok(output)
+        }
+        Format::Sarif => {
+            let mut output = serde_json::to_string_pretty(&sarif(hints))?;
+            output.push('\n');
+            Ok(output)
+        }
+    }
+}
+
+/// A finding in `hlint --json`'s shape, plus what found it
+#[derive(Serialize)]
+struct Finding<'a> {
+    tool: &'static str,
+    #[serde(flatten)]
+    hint: &'a HlintHint,
+}
+
+impl<'a> From<&'a HlintHint> for Finding<'a> {
+    fn from(hint: &'a HlintHint) -> Self {
+        Finding {
+            tool: hint.tool,
+            hint,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Sarif<'a> {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: [Run<'a>; 1],
+}
+
+#[derive(Serialize)]
+struct Run<'a> {
+    tool: Tool<'a>,
+    results: Vec<SarifResult<'a>>,
+}
+
+#[derive(Serialize)]
+struct Tool<'a> {
+    driver: Driver<'a>,
+}
+
+#[derive(Serialize)]
+struct Driver<'a> {
+    name: &'static str,
+    version: &'static str,
+    rules: Vec<Rule<'a>>,
+}
+
+#[derive(Serialize)]
+struct Rule<'a> {
+    id: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult<'a> {
+    rule_id: &'a str,
+    level: &'static str,
+    message: Message,
+    locations: [Location<'a>; 1],
+}
+
+#[derive(Serialize)]
+struct Message {
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Location<'a> {
+    physical_location: PhysicalLocation<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PhysicalLocation<'a> {
+    artifact_location: ArtifactLocation<'a>,
+    region: Region,
+}
+
+#[derive(Serialize)]
+struct ArtifactLocation<'a> {
+    uri: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Region {
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+/// A SARIF 2.1.0 log, which GitHub code scanning accepts
+fn sarif(hints: &[HlintHint]) -> Sarif<'_> {
+    let rules = hints
+        .iter()
+        .map(|hint| hint.hint.as_str())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|id| Rule { id })
+        .collect();
+
+    let results = hints
+        .iter()
+        .map(|hint| SarifResult {
+            rule_id: &hint.hint,
+            level: level(hint.severity),
+            message: Message {
+                text: message(hint),
+            },
+            locations: [Location {
+                physical_location: PhysicalLocation {
+                    artifact_location: ArtifactLocation { uri: &hint.file },
+                    region: Region {
+                        // SARIF positions start at 1, but some findings are for a whole file
+                        start_line: hint.start_line.max(1),
+                        start_column: hint.start_column.max(1),
+                        end_line: hint.end_line.max(hint.start_line).max(1),
+                        end_column: hint.end_column.max(1),
+                    },
+                },
+            }],
+        })
+        .collect();
+
+    Sarif {
+        schema: SARIF_SCHEMA,
+        version: "2.1.0",
+        runs: [Run {
+            tool: Tool {
+                driver: Driver {
+                    name: "be",
+                    version: env!("CARGO_PKG_VERSION"),
+                    rules,
+                },
+            },
+            results,
+        }],
+    }
+}
+
+/// A finding as an LSP diagnostic, for `be lsp`
+#[derive(Serialize)]
+pub struct Diagnostic {
+    range: Range,
+    severity: u8,
+    code: String,
+    source: &'static str,
+    message: String,
+}
+
+#[derive(Serialize)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Clone, Copy, Serialize)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+pub fn diagnostics(hints: &[HlintHint]) -> Vec<Diagnostic> {
+    hints
+        .iter()
+        .map(|hint| {
+            // LSP positions start at 0, and findings for a whole file are put at its start
+            let start = Position {
+                line: hint.start_line.saturating_sub(1),
+                character: hint.start_column.saturating_sub(1),
+            };
+            let end = if hint.end_line < hint.start_line {
+                start
+            } else {
+                Position {
+                    line: hint.end_line.saturating_sub(1),
+                    character: hint.end_column.saturating_sub(1),
+                }
+            };
+            Diagnostic {
+                range: Range { start, end },
+                severity: match hint.severity {
+                    HlintSeverity::Error => 1,
+                    HlintSeverity::Warning => 2,
+                    HlintSeverity::Suggestion => 3,
+                    HlintSeverity::Ignore => 4,
+                },
+                code: hint.hint.clone(),
+                source: hint.tool,
+                message: message(hint),
+            }
+        })
+        .collect()
+}
+
+fn annotation<'a>(hint: &'a HlintHint, message: &'a str) -> Annotation<'a> {
+    Annotation {
+        level: match hint.severity {
+            HlintSeverity::Error => "error",
+            HlintSeverity::Warning => "warning",
+            HlintSeverity::Ignore | HlintSeverity::Suggestion => "notice",
+        },
+        file: &hint.file,
+        line: Some(hint.start_line.max(1)),
+        column: Some(hint.start_column.max(1)),
+        end_line: Some(hint.end_line.max(hint.start_line).max(1)),
+        end_column: Some(hint.end_column.max(1)),
+        title: &hint.hint,
+        message,
+    }
+}
+
+fn level(severity: HlintSeverity) -> &'static str {
+    match severity {
+        HlintSeverity::Ignore => "none",
+        HlintSeverity::Suggestion => "note",
+        HlintSeverity::Warning => "warning",
+        HlintSeverity::Error => "error",
+    }
+}
+
+/// The hint, and what was found and could replace it
+fn message(hint: &HlintHint) -> String {
+    let mut message = format!("{}\nFound:\n  {}", hint.hint, hint.from);
+    // Writing to a `String` can't fail
+    if let Some(to) = &hint.to {
+        let _ = write!(message, "\nPerhaps:\n  {to}");
+    }
+    for note in &hint.note {
+        let _ = write!(message, "\nNote: {note}");
+    }
+    message
+}
@@ -0,0 +1,198 @@
+use crate::{
+    context::cx,
+    exec,
+    lint::{self, HlintHint},
+};
+use bytes::Bytes;
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre;
+use std::{os::unix::process::ExitStatusExt as _, process::Stdio, sync::Arc};
+use tempfile::tempdir;
+use tokio::{
+    fs,
+    sync::{Mutex, OnceCell, mpsc, oneshot},
+};
+
+/// Workers which lint many files per `hlint` process.
+///
+/// Like `fourmolu`'s pool, each worker takes every file queued since its last run (up to the batch
+/// size), and lints them all from a temporary directory with a single process. Findings are split
+/// back up by file, so each file's are cached on their own.
+struct Pool {
+    sender: mpsc::UnboundedSender<Job>,
+}
+
+struct Job {
+    path: Option<Utf8PathBuf>,
+    bytes: Bytes,
+    reply: oneshot::Sender<eyre::Result<Vec<HlintHint>>>,
+}
+
+static POOL: OnceCell<Pool> = OnceCell::const_new();
+
+/// Lint with a pooled `hlint` process
+#[tracing::instrument(skip(bytes))]
+pub async fn hlint(
+    path: Option<&Utf8Path>,
+    bytes: Bytes,
+    batch_size: usize,
+) -> eyre::Result<Vec<HlintHint>> {
+    let pool = POOL.get_or_try_init(|| Pool::new(batch_size)).await?;
+    let (reply, receiver) = oneshot::channel();
+    let job = Job {
+        path: path.map(Utf8Path::to_path_buf),
+        bytes,
+        reply,
+    };
+    if pool.sender.send(job).is_err() {
+        eyre::bail!("`hlint` workers have stopped");
+    }
+    receiver.await?
+}
+
+impl Pool {
+    async fn new(batch_size: usize) -> eyre::Result<Self> {
+        let jobs = cx().tool_jobs("hlint").await?;
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        tracing::debug!("Starting {jobs} `hlint` workers");
+        for _ in 0..jobs {
+            tokio::spawn(worker(Arc::clone(&receiver), batch_size));
+        }
+        Ok(Self { sender })
+    }
+}
+
+async fn worker(receiver: Arc<Mutex<mpsc::UnboundedReceiver<Job>>>, batch_size: usize) {
+    loop {
+        let mut jobs = Vec::new();
+        {
+            let mut receiver = receiver.lock().await;
+            let Some(job) = receiver.recv().await else {
+                return;
+            };
+            jobs.push(job);
+            while jobs.len() < batch_size
+                && let Ok(job) = receiver.try_recv()
+            {
+                jobs.push(job);
+            }
+        }
+
+        if jobs.len() > 1 {
+            let inputs: Vec<Bytes> = jobs.iter().map(|job| job.bytes.clone()).collect();
+            match hlint_batch(&inputs).await {
+                Ok(outputs) => {
+                    for (job, mut hints) in jobs.into_iter().zip(outputs) {
+                        if let Some(path) = &job.path {
+                            for hint in &mut hints {
+                                hint.file.clear();
+                                hint.file.push_str(path.as_str());
+                            }
+                        }
+                        let _ = job.reply.send(Ok(hints));
+                    }
+                    continue;
+                }
+                Err(error) => {
+                    // Retry one at a time so errors are reported for the files that caused them
+                    tracing::debug!("Retrying batch of {} files: {error}", jobs.len());
+                }
+            }
+        }
+
+        for job in jobs {
+            let output = lint::hlint_one(job.path.as_deref(), job.bytes).await;
+            let _ = job.reply.send(output);
+        }
+    }
+}
+
+/// Findings for each input, in the same order
+#[tracing::instrument(skip_all, fields(files = inputs.len()))]
+async fn hlint_batch(inputs: &[Bytes]) -> eyre::Result<Vec<Vec<HlintHint>>> {
+    let cx = cx();
+
+    let hlint = &cx.cache.which("hlint").await?;
+
+    let tool_permits = cx.tool_permits("hlint").await?;
+
+    let temp_dir = tempdir()?;
+    let batch_dir = Utf8PathBuf::try_from(fs::canonicalize(temp_dir.path()).await?)?;
+
+    let mut paths = Vec::with_capacity(inputs.len());
+    for (i, bytes) in inputs.iter().enumerate() {
+        let path = batch_dir.join(format!("{i}.hs"));
+        fs::write(&path, bytes).await?;
+        paths.push(path);
+    }
+
+    let (hlint_configs, _) = cx.cache.hlint_configs().await?;
+
+    let mut args = vec![String::from("--json"), String::from("--no-exit-code")];
+
+    for config in hlint_configs {
+        args.push(format!("--hint={config}"));
+    }
+
+    let tool_permit = tool_permits.acquire().await?;
+    let process_permit = cx.process_permits.acquire().await?;
+    let memory_permit = cx.memory.acquire().await?;
+
+    let mut command = exec::sandboxed(
+        Some("hlint"),
+        exec::HLINT_PROFILE,
+        &[exec::Param {
+            name: "BATCH_DIR",
+            path: &batch_dir,
+            writable: false,
+        }],
+        hlint,
+    )
+    .await?;
+
+    let output = exec::timeout(
+        "hlint",
+        command
+            .args(args)
+            .args(&paths)
+            .env_clear()
+            .current_dir("/var/empty")
+            .kill_on_drop(true)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output(),
+    )
+    .await?;
+
+    drop(memory_permit);
+    drop(process_permit);
+    drop(tool_permit);
+
+    if !output.status.success() {
+        if let Some(exit_code) = output.status.code() {
+            eyre::bail!(
+                "`hlint` exited with code {exit_code}:\n{}",
+                String::from_utf8_lossy(&output.stderr),
+            );
+        } else if let Some(signal) = output.status.signal() {
+            eyre::bail!("`hlint` was terminated by signal {signal}");
+        } else {
+            eyre::bail!("`hlint` died of unknown causes");
+        }
+    }
+
+    let hints: Vec<HlintHint> = serde_json::from_slice(&output.stdout)?;
+
+    let mut outputs: Vec<Vec<HlintHint>> = paths.iter().map(|_| Vec::new()).collect();
+    for mut hint in hints {
+        let Some(i) = paths.iter().position(|path| path.as_str() == hint.file) else {
+            eyre::bail!("`hlint` reported a finding in unknown file `{}`", hint.file);
+        };
+        hint.tool = "hlint";
+        outputs[i].push(hint);
+    }
+
+    Ok(outputs)
+}
@@ -0,0 +1,126 @@
+use crate::{context::cx, exec, lint::HlintHint};
+use bytes::Bytes;
+use camino::Utf8PathBuf;
+use color_eyre::eyre;
+use std::{fmt::Write as _, os::unix::process::ExitStatusExt as _, process::Stdio};
+use tempfile::tempdir;
+use tokio::fs;
+
+/// Whether `refactor` can apply a hint automatically
+pub fn is_fixable(hint: &HlintHint) -> bool {
+    !matches!(hint.refactorings.trim(), "" | "[]")
+}
+
+/// Contents of a module with the hints' refactorings applied by `refactor` (from `apply-refact`)
+#[tracing::instrument(skip_all)]
+pub async fn apply(bytes: Bytes, hints: &[&HlintHint]) -> eyre::Result<Bytes> {
+    let cx = cx();
+
+    let refactor = &cx.cache.which("refactor").await?;
+
+    let tool_permits = cx.tool_permits("refactor").await?;
+
+    // `refactor` reads both the module and its refactorings from files
+    let temp_dir = tempdir()?;
+    let temp_dir_path = Utf8PathBuf::try_from(fs::canonicalize(temp_dir.path()).await?)?;
+
+    let module_path = temp_dir_path.join("Module.hs");
+    fs::write(&module_path, &bytes).await?;
+
+    let refact_path = temp_dir_path.join("refactorings");
+    fs::write(&refact_path, refact_file(hints)?).await?;
+
+    let (extensions, _) = cx.cache.fourmolu_extensions().await?;
+
+    let mut args = vec![
+        module_path.to_string(),
+        format!("--refact-file={refact_path}"),
+    ];
+
+    for extension in extensions {
+        args.push(format!("-X{extension}"));
+    }
+
+    let tool_permit = tool_permits.acquire().await?;
+    let file_permit = cx.file_permits.acquire().await?;
+    let process_permit = cx.process_permits.acquire().await?;
+
+    let mut command = exec::sandboxed(
+        Some("refactor"),
+        exec::REFACTOR_PROFILE,
+        &[exec::Param {
+            name: "TEMP_DIR",
+            path: &temp_dir_path,
+            writable: false,
+        }],
+        refactor,
+    )
+    .await?;
+
+    let output = exec::timeout(
+        "refactor",
+        command
+            .args(args)
+            .env_clear()
+            .current_dir("/var/empty")
+            .kill_on_drop(true)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output(),
+    )
+    .await?;
+
+    drop(process_permit);
+    drop(file_permit);
+    drop(tool_permit);
+
+    if !output.status.success() {
+        if let Some(exit_code) = output.status.code() {
+            eyre::bail!(
+                "`refactor` exited with code {exit_code}:\n{}",
+                String::from_utf8_lossy(&output.stderr),
+            );
+        } else if let Some(signal) = output.status.signal() {
+            eyre::bail!("`refactor` was terminated by signal {signal}");
+        } else {
+            eyre::bail!("`refactor` died of unknown causes");
+        }
+    }
+
+    Ok(Bytes::from(output.stdout))
+}
+
+/// Refactorings in the format `refactor` reads from `--refact-file`, which is what `hlint
+/// --serialise` prints: a Haskell list of pairs of hint names and their refactorings
+fn refact_file(hints: &[&HlintHint]) -> eyre::Result<String> {
+    let mut file = String::from("[");
+    for (i, hint) in hints.iter().enumerate() {
+        if i > 0 {
+            file.push(',');
+        }
+        write!(
+            file,
+            "({},{})",
+            haskell_string(&hint.hint),
+            hint.refactorings
+        )?;
+    }
+    file.push(']');
+    Ok(file)
+}
+
+/// A Haskell string literal
+fn haskell_string(s: &str) -> String {
+    let mut literal = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
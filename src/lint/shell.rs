@@ -0,0 +1,192 @@
+use crate::{
+    cli::lint::ShellArgs,
+    config::Pipeline,
+    context::cx,
+    events::message,
+    exec, git,
+    io::read_file,
+    lint::{HlintHint, HlintSeverity, Summary},
+    queue,
+};
+use bytes::Bytes;
+use camino::Utf8Path;
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use std::{os::unix::process::ExitStatusExt as _, process::Stdio};
+
+/// A comment from `shellcheck --format=json`, as stored in the `shellcheck` table
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShellcheckHint {
+    line: usize,
+    end_line: usize,
+    column: usize,
+    end_column: usize,
+    /// `error`, `warning`, `info`, or `style`
+    level: String,
+    code: u32,
+    message: String,
+}
+
+impl ShellcheckHint {
+    fn to_hlint_hint(&self, path: &Utf8Path, source_code: &str) -> HlintHint {
+        let severity = match self.level.as_str() {
+            "error" => HlintSeverity::Error,
+            "warning" => HlintSeverity::Warning,
+            _ => HlintSeverity::Suggestion,
+        };
+        let from = source_code
+            .lines()
+            .nth(self.line.saturating_sub(1))
+            .unwrap_or_default()
+            .trim();
+        HlintHint {
+            module: Vec::new(),
+            decl: Vec::new(),
+            severity,
+            hint: format!("SC{}", self.code),
+            file: path.to_string(),
+            start_line: self.line,
+            start_column: self.column,
+            end_line: self.end_line,
+            end_column: self.end_column,
+            from: String::from(from),
+            to: None,
+            note: vec![self.message.clone()],
+            refactorings: String::from("[]"),
+            tool: "shellcheck",
+        }
+    }
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &ShellArgs) -> eyre::Result<Summary> {
+    let paths = if args.paths.is_empty() && args.packages.is_empty() {
+        git::changed_shell_files(Pipeline::Lint).await?
+    } else {
+        git::shell_files(&args.paths, &args.packages, Pipeline::Lint).await?
+    };
+
+    let handles = queue::spawn(paths, |path| async move { check(&path).await }).await?;
+
+    let mut summary = Summary::default();
+    let mut checked_count = 0;
+
+    for handle in handles {
+        let (checked, hints) = handle.await??;
+        if checked {
+            checked_count += 1;
+        }
+        summary.add(hints);
+    }
+
+    message!(
+        "Linted {checked_count} of {total_count} shell {files}",
+        checked_count = checked_count.to_formatted_string(&Locale::en),
+        total_count = summary.file_count.to_formatted_string(&Locale::en),
+        files = if summary.file_count == 1 {
+            "file"
+        } else {
+            "files"
+        },
+    );
+
+    Ok(summary)
+}
+
+#[tracing::instrument(fields(indicatif.pb_show))]
+async fn check(path: &Utf8Path) -> eyre::Result<(bool, Vec<HlintHint>)> {
+    let cx = cx();
+
+    let (input_bytes, input_hash) = read_file(path).await?;
+
+    let source_code = String::from_utf8_lossy(&input_bytes).into_owned();
+
+    let (checked, hints) = if let Some(hints) = cx.cache.is_shell_linted(input_hash).await? {
+        tracing::trace!("Using cached lint results");
+        (false, hints)
+    } else {
+        tracing::trace!("Linting");
+
+        let (hints, duration) = exec::timed(shellcheck(input_bytes)).await?;
+
+        cx.cache
+            .record_duration("shellcheck", input_hash, path, duration)
+            .await?;
+
+        cx.cache.mark_shell_linted(input_hash, &hints).await?;
+
+        (true, hints)
+    };
+
+    let hints = hints
+        .iter()
+        .map(|hint| hint.to_hlint_hint(path, &source_code))
+        .collect();
+
+    Ok((checked, hints))
+}
+
+#[tracing::instrument(skip(bytes))]
+async fn shellcheck(bytes: Bytes) -> eyre::Result<Vec<ShellcheckHint>> {
+    let cx = cx();
+
+    let shellcheck = &cx.cache.which("shellcheck").await?;
+
+    let tool_permits = cx.tool_permits("shellcheck").await?;
+
+    let tool_permit = tool_permits.acquire().await?;
+    let file_permit = cx.file_permits.acquire().await?;
+    let process_permit = cx.process_permits.acquire().await?;
+
+    let mut command = exec::sandboxed(
+        Some("shellcheck"),
+        exec::SHELLCHECK_PROFILE,
+        &[],
+        shellcheck,
+    )
+    .await?;
+
+    let mut args = vec!["--format=json"];
+
+    // Scripts meant to be sourced often don't have a shebang, so assume Bash like `shfmt` does
+    if !bytes.starts_with(b"#!") {
+        args.push("--shell=bash");
+    }
+
+    args.push("-");
+
+    let child = command
+        .args(args)
+        .env_clear()
+        .current_dir("/var/empty")
+        .kill_on_drop(true)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let output = exec::timeout("shellcheck", exec::output_with_stdin(child, &bytes)).await?;
+
+    drop(process_permit);
+    drop(file_permit);
+    drop(tool_permit);
+
+    // `shellcheck` exits with code 1 when it finds problems
+    if !output.status.success() && output.status.code() != Some(1) {
+        if let Some(exit_code) = output.status.code() {
+            eyre::bail!(
+                "`shellcheck` exited with code {exit_code}:\n{}",
+                String::from_utf8_lossy(&output.stderr),
+            );
+        } else if let Some(signal) = output.status.signal() {
+            eyre::bail!("`shellcheck` was terminated by signal {signal}");
+        } else {
+            eyre::bail!("`shellcheck` died of unknown causes");
+        }
+    }
+
+    let hints = serde_json::from_slice(&output.stdout)?;
+
+    Ok(hints)
+}
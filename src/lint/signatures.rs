@@ -0,0 +1,125 @@
+use crate::lint::{self, HlintHint, builtin_hint};
+use std::collections::HashSet;
+use tree_sitter::{Node, Tree};
+
+pub const NAME: &str = "missing-signatures";
+
+/// Exported top-level bindings without a type signature
+pub fn hints(source_code: &str, tree: &Tree) -> Vec<HlintHint> {
+    let root = tree.root_node();
+
+    let exports = exports(source_code, root);
+
+    let mut signatures = HashSet::new();
+    let mut bindings = Vec::new();
+
+    if let Some(declarations) = root.child_by_field_name("declarations") {
+        let mut cursor = declarations.walk();
+        for declaration in declarations.named_children(&mut cursor) {
+            match declaration.kind() {
+                "signature" => signatures.extend(signature_names(source_code, declaration)),
+                "function" | "bind" => {
+                    if let Some(name) = declaration
+                        .child_by_field_name("name")
+                        .and_then(|name| binding_name(source_code, name))
+                    {
+                        bindings.push((name, declaration));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut hints = Vec::new();
+
+    for (name, declaration) in bindings {
+        if !seen.insert(name) || signatures.contains(name) {
+            continue;
+        }
+        if exports
+            .as_ref()
+            .is_some_and(|exports| !exports.contains(name))
+        {
+            continue;
+        }
+        hints.push(builtin_hint(
+            source_code,
+            root,
+            declaration,
+            name,
+            "Missing type signature",
+        ));
+    }
+
+    hints
+}
+
+/// Names of values and types in the export list, or `None` if everything is exported
+pub fn exports<'a>(source_code: &'a str, root: Node) -> Option<HashSet<&'a str>> {
+    let Some(header) = lint::header(root) else {
+        // A module without a header only exports `main`
+        return Some(HashSet::from(["main"]));
+    };
+    let exports = header.child_by_field_name("exports")?;
+    let module = header
+        .child_by_field_name("module")
+        .and_then(|module| source_code.get(module.byte_range()));
+    let mut names = HashSet::new();
+    let mut cursor = exports.walk();
+    for export in exports.named_children(&mut cursor) {
+        match export.kind() {
+            "export" => {
+                let name = export
+                    .child_by_field_name("variable")
+                    .filter(|variable| variable.kind() == "variable")
+                    .or_else(|| export.child_by_field_name("operator"));
+                if let Some(name) = name.and_then(|name| binding_name(source_code, name)) {
+                    names.insert(name);
+                }
+                if let Some(name) = export
+                    .child_by_field_name("type")
+                    .filter(|name| name.kind() == "name")
+                    .and_then(|name| source_code.get(name.byte_range()))
+                {
+                    names.insert(name);
+                }
+            }
+            "module_export" => {
+                let exported_module = export
+                    .child_by_field_name("module")
+                    .and_then(|module| source_code.get(module.byte_range()));
+                if exported_module.is_some() && exported_module == module {
+                    return None;
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(names)
+}
+
+pub fn signature_names<'a>(source_code: &'a str, signature: Node) -> Vec<&'a str> {
+    if let Some(name) = signature.child_by_field_name("name") {
+        return binding_name(source_code, name).into_iter().collect();
+    }
+    let Some(names) = signature.child_by_field_name("names") else {
+        return Vec::new();
+    };
+    let mut cursor = names.walk();
+    names
+        .named_children(&mut cursor)
+        .filter_map(|name| binding_name(source_code, name))
+        .collect()
+}
+
+/// Name of a variable or operator, without parentheses
+pub fn binding_name<'a>(source_code: &'a str, node: Node) -> Option<&'a str> {
+    let text = source_code.get(node.byte_range())?;
+    match node.kind() {
+        "variable" => Some(text),
+        "prefix_id" => Some(text.trim_start_matches('(').trim_end_matches(')').trim()),
+        _ => None,
+    }
+}
@@ -0,0 +1,92 @@
+use crate::{
+    config::{LintConfig, ModuleSizeLimits},
+    lint::{self, HlintHint, builtin_hint},
+    query,
+};
+use num_format::{Locale, ToFormattedString as _};
+use std::collections::HashSet;
+use tree_sitter::{Node, Tree};
+
+pub const NAME: &str = "module-size";
+
+/// Limits are part of the cache key, since they change which modules are reported
+pub fn settings(config: &LintConfig) -> String {
+    let ModuleSizeLimits {
+        lines,
+        declarations,
+        imports,
+    } = &config.module_size_limits;
+    format!("{lines:?}:{declarations:?}:{imports:?}")
+}
+
+/// Modules with more lines, top-level declarations, or imports than configured
+pub fn hints(config: &LintConfig, source_code: &str, tree: &Tree) -> Vec<HlintHint> {
+    let limits = &config.module_size_limits;
+
+    let root = tree.root_node();
+
+    let module = lint::header_module(source_code, root).unwrap_or("Main");
+
+    let header = lint::header(root).unwrap_or(root);
+
+    let mut hints = Vec::new();
+
+    let mut check = |node: Node, count: usize, limit: Option<usize>, what: &str| {
+        let Some(limit) = limit.filter(|limit| count > *limit) else {
+            return;
+        };
+        let mut hint = builtin_hint(
+            source_code,
+            root,
+            node,
+            module,
+            &format!("Module has too many {what}"),
+        );
+        hint.note.push(format!(
+            "{} {what} (limit: {})",
+            count.to_formatted_string(&Locale::en),
+            limit.to_formatted_string(&Locale::en),
+        ));
+        hints.push(hint);
+    };
+
+    check(header, source_code.lines().count(), limits.lines, "lines");
+
+    if let Some(declarations) = root.child_by_field_name("declarations") {
+        check(
+            declarations,
+            declaration_count(source_code, declarations),
+            limits.declarations,
+            "declarations",
+        );
+    }
+
+    if let Some(imports) = root.child_by_field_name("imports") {
+        let count = query::query_imports(source_code, tree).map_or(0, |items| items.len());
+        check(imports, count, limits.imports, "imports");
+    }
+
+    hints
+}
+
+/// Top-level declarations, counting a function once however many equations and signatures it has
+fn declaration_count(source_code: &str, declarations: Node) -> usize {
+    let mut names = HashSet::new();
+    let mut count = 0;
+    let mut cursor = declarations.walk();
+    for declaration in declarations.named_children(&mut cursor) {
+        match declaration.kind() {
+            "signature" | "comment" | "haddock" | "pragma" => {}
+            "function" | "bind" => {
+                let name = declaration
+                    .child_by_field_name("name")
+                    .and_then(|name| source_code.get(name.byte_range()));
+                if name.is_none_or(|name| names.insert(name)) {
+                    count += 1;
+                }
+            }
+            _ => count += 1,
+        }
+    }
+    count
+}
@@ -0,0 +1,361 @@
+use crate::{
+    cli::lint::StanArgs,
+    config::Pipeline,
+    context::cx,
+    events::message,
+    exec, git,
+    io::read_file,
+    lint::{HlintHint, HlintSeverity, Summary, hie::HieFiles},
+    queue,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre;
+use derive_more::Display;
+use num_format::{Locale, ToFormattedString as _};
+use std::{hash::Hasher as _, os::unix::process::ExitStatusExt as _, process::Stdio, sync::Arc};
+use tempfile::tempdir;
+use tokio::fs;
+use twox_hash::XxHash3_64;
+
+/// How bad `stan` says an observation is, ordered from least to most severe
+#[derive(
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Debug,
+    Display,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+pub enum StanSeverity {
+    #[default]
+    #[serde(alias = "style")]
+    Style,
+    #[serde(alias = "performance")]
+    Performance,
+    #[serde(alias = "potential-bug")]
+    PotentialBug,
+    #[serde(alias = "warning")]
+    Warning,
+    #[serde(alias = "error")]
+    Error,
+}
+
+impl StanSeverity {
+    fn to_hlint_severity(self) -> HlintSeverity {
+        match self {
+            Self::Style | Self::Performance => HlintSeverity::Suggestion,
+            Self::PotentialBug | Self::Warning => HlintSeverity::Warning,
+            Self::Error => HlintSeverity::Error,
+        }
+    }
+}
+
+/// What `be lint stan` needs for every module
+struct Settings {
+    hie_files: HieFiles,
+    config_path: Option<Utf8PathBuf>,
+    min_severity: StanSeverity,
+    /// `.stan.toml` and `min-severity`, which both change which observations are reported
+    config_hash: u64,
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &StanArgs) -> eyre::Result<Summary> {
+    let cx = cx();
+
+    let config = cx.cache.config().await?;
+    let git_root = cx.cache.git_root().await?;
+
+    let paths = if args.paths.is_empty() && args.packages.is_empty() {
+        git::changed_haskell_files(Pipeline::Lint).await?
+    } else {
+        git::haskell_files(&args.paths, &args.packages, Pipeline::Lint).await?
+    };
+
+    let hie_files = HieFiles::find().await?;
+
+    let config_path = git_root.join(config.tool_configs.stan());
+    let config_path = fs::try_exists(&config_path).await?.then_some(config_path);
+    let min_severity = config.lint.stan.min_severity.unwrap_or_default();
+    let mut hasher = XxHash3_64::default();
+    if let Some(path) = &config_path {
+        hasher.write(&read_file(path).await?.1.to_le_bytes());
+    }
+    hasher.write(min_severity.to_string().as_bytes());
+    let config_hash = hasher.finish();
+
+    let mut missing = Vec::new();
+    let mut checked_paths = Vec::with_capacity(paths.len());
+    for path in paths {
+        if hie_files.for_source(&path)?.is_some() {
+            checked_paths.push(path);
+        } else {
+            missing.push(path);
+        }
+    }
+    if !missing.is_empty() {
+        message!(
+            "Not analyzing {count} {modules} without a `.hie` file in `{dir}`",
+            count = missing.len().to_formatted_string(&Locale::en),
+            modules = if missing.len() == 1 {
+                "module"
+            } else {
+                "modules"
+            },
+            dir = hie_files.dir,
+        );
+    }
+
+    let settings = Arc::new(Settings {
+        hie_files,
+        config_path,
+        min_severity,
+        config_hash,
+    });
+
+    let handles = queue::spawn(checked_paths, |path| {
+        let settings = Arc::clone(&settings);
+        async move { check(&path, &settings).await }
+    })
+    .await?;
+
+    let mut summary = Summary::default();
+    let mut checked_count = 0;
+
+    for handle in handles {
+        let (checked, hints) = handle.await??;
+        if checked {
+            checked_count += 1;
+        }
+        summary.add(hints);
+    }
+
+    message!(
+        "Analyzed {checked_count} of {total_count} Haskell {files} with stan",
+        checked_count = checked_count.to_formatted_string(&Locale::en),
+        total_count = summary.file_count.to_formatted_string(&Locale::en),
+        files = if summary.file_count == 1 {
+            "file"
+        } else {
+            "files"
+        },
+    );
+
+    Ok(summary)
+}
+
+#[tracing::instrument(skip(settings), fields(indicatif.pb_show))]
+async fn check(path: &Utf8Path, settings: &Settings) -> eyre::Result<(bool, Vec<HlintHint>)> {
+    let cx = cx();
+
+    let (input_bytes, input_hash) = read_file(path).await?;
+
+    let (checked, mut hints) = if let Some(hints) = cx
+        .cache
+        .is_stan_checked(settings.config_hash, input_hash)
+        .await?
+    {
+        tracing::trace!("Using cached `stan` results");
+        (false, hints)
+    } else {
+        tracing::trace!("Analyzing");
+
+        // Only found modules are checked
+        let hie_path = settings.hie_files.for_source(path)?.unwrap();
+        let source_code = String::from_utf8_lossy(&input_bytes);
+        let (hints, duration) = exec::timed(stan(hie_path, settings, &source_code)).await?;
+
+        cx.cache
+            .record_duration("stan", input_hash, path, duration)
+            .await?;
+
+        cx.cache
+            .mark_stan_checked(settings.config_hash, input_hash, &hints)
+            .await?;
+
+        (true, hints)
+    };
+
+    for hint in &mut hints {
+        hint.tool = "stan";
+        hint.file.clear();
+        hint.file.push_str(path.as_str());
+    }
+
+    hints.sort_by_key(|hint| (hint.start_line, hint.start_column));
+
+    Ok((checked, hints))
+}
+
+/// `stan --json-output`, trimmed to what's reported
+#[derive(serde::Deserialize)]
+struct StanReport {
+    observations: Vec<StanObservation>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StanObservation {
+    inspection_id: String,
+    src_span: StanSpan,
+    severity: StanSeverity,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    solution: Vec<String>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StanSpan {
+    start_line: usize,
+    start_col: usize,
+    end_line: usize,
+    end_col: usize,
+}
+
+impl StanObservation {
+    fn to_hlint_hint(&self, source_code: &str) -> HlintHint {
+        let from = source_code
+            .lines()
+            .nth(self.src_span.start_line.saturating_sub(1))
+            .unwrap_or_default()
+            .trim();
+        let hint = match &self.name {
+            Some(name) => format!("{}: {name}", self.inspection_id),
+            None => self.inspection_id.clone(),
+        };
+        HlintHint {
+            module: Vec::new(),
+            decl: Vec::new(),
+            severity: self.severity.to_hlint_severity(),
+            hint,
+            file: String::new(),
+            start_line: self.src_span.start_line,
+            start_column: self.src_span.start_col,
+            end_line: self.src_span.end_line,
+            end_column: self.src_span.end_col,
+            from: String::from(from),
+            to: None,
+            note: self
+                .description
+                .iter()
+                .chain(&self.solution)
+                .cloned()
+                .collect(),
+            refactorings: String::from("[]"),
+            tool: "stan",
+        }
+    }
+}
+
+/// Run `stan` on a copy of just this module's `.hie` file, so it doesn't analyze every module
+#[tracing::instrument(skip(settings, source_code))]
+async fn stan(
+    hie_path: &Utf8Path,
+    settings: &Settings,
+    source_code: &str,
+) -> eyre::Result<Vec<HlintHint>> {
+    let cx = cx();
+
+    let stan = &cx.cache.which("stan").await?;
+
+    let git_root = cx.cache.git_root().await?;
+
+    let tool_permits = cx.tool_permits("stan").await?;
+
+    let temp_dir = tempdir()?;
+    let temp_dir_path = Utf8PathBuf::try_from(fs::canonicalize(temp_dir.path()).await?)?;
+    let temp_hie_path = temp_dir_path.join(hie_path);
+    if let Some(parent) = temp_hie_path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::copy(
+        git_root.join(&settings.hie_files.dir).join(hie_path),
+        &temp_hie_path,
+    )
+    .await?;
+
+    let mut args = vec![
+        format!("--hiedir={temp_dir_path}"),
+        String::from("--json-output"),
+    ];
+    if let Some(config_path) = &settings.config_path {
+        args.push(format!("--config-file={config_path}"));
+    } else {
+        // Otherwise it would look for configs in the home directory
+        args.push(String::from("--no-default"));
+    }
+
+    let tool_permit = tool_permits.acquire().await?;
+    let file_permit = cx.file_permits.acquire().await?;
+    let process_permit = cx.process_permits.acquire().await?;
+
+    let mut command = exec::sandboxed(
+        Some("stan"),
+        exec::STAN_PROFILE,
+        &[
+            exec::Param {
+                name: "GIT_ROOT",
+                path: git_root,
+                writable: false,
+            },
+            exec::Param {
+                name: "TEMP_DIR",
+                path: &temp_dir_path,
+                writable: false,
+            },
+        ],
+        stan,
+    )
+    .await?;
+
+    let output = exec::timeout(
+        "stan",
+        command
+            .args(args)
+            .env_clear()
+            .current_dir(git_root)
+            .kill_on_drop(true)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output(),
+    )
+    .await?;
+
+    drop(process_permit);
+    drop(file_permit);
+    drop(tool_permit);
+
+    if !output.status.success() {
+        if let Some(exit_code) = output.status.code() {
+            eyre::bail!(
+                "`stan` exited with code {exit_code}:\n{}",
+                String::from_utf8_lossy(&output.stderr),
+            );
+        } else if let Some(signal) = output.status.signal() {
+            eyre::bail!("`stan` was terminated by signal {signal}");
+        } else {
+            eyre::bail!("`stan` died of unknown causes");
+        }
+    }
+
+    let report: StanReport = serde_json::from_slice(&output.stdout)?;
+
+    let hints = report
+        .observations
+        .iter()
+        .filter(|observation| observation.severity >= settings.min_severity)
+        .map(|observation| observation.to_hlint_hint(source_code))
+        .collect();
+
+    Ok(hints)
+}
@@ -0,0 +1,203 @@
+use crate::{
+    cli::lint::TyposArgs,
+    config::{Config, Language, Pipeline},
+    context::cx,
+    events::message,
+    exec, git,
+    io::read_file,
+    lint::{HlintHint, HlintSeverity, Summary},
+    queue,
+};
+use bytes::Bytes;
+use camino::Utf8Path;
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use std::{os::unix::process::ExitStatusExt as _, process::Stdio};
+
+/// Docs checked alongside Haskell and Nix files
+const DOCS_EXTENSIONS: &[&str] = &["md", "markdown", "rst", "txt", "cabal"];
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &TyposArgs) -> eyre::Result<Summary> {
+    let config = cx().cache.config().await?;
+
+    let mut paths = if args.paths.is_empty() {
+        git::changed_files(&[]).await?
+    } else {
+        git::files(&args.paths).await?
+    };
+
+    paths.retain(|path| is_checked(config, path));
+
+    let handles = queue::spawn(paths, |path| async move { check(&path).await }).await?;
+
+    let mut summary = Summary::default();
+    let mut checked_count = 0;
+
+    for handle in handles {
+        let (checked, hints) = handle.await??;
+        if checked {
+            checked_count += 1;
+        }
+        summary.add(hints);
+    }
+
+    message!(
+        "Spell-checked {checked_count} of {total_count} {files}",
+        checked_count = checked_count.to_formatted_string(&Locale::en),
+        total_count = summary.file_count.to_formatted_string(&Locale::en),
+        files = if summary.file_count == 1 {
+            "file"
+        } else {
+            "files"
+        },
+    );
+
+    Ok(summary)
+}
+
+/// Whether `typos` should check a file: Haskell, Nix, and Dhall sources, plus docs
+fn is_checked(config: &Config, path: &Utf8Path) -> bool {
+    config.is_file_type(Language::Haskell, Pipeline::Lint, path)
+        || config.is_file_type(Language::Nix, Pipeline::Lint, path)
+        || config.is_file_type(Language::Dhall, Pipeline::Lint, path)
+        || path
+            .extension()
+            .is_some_and(|extension| DOCS_EXTENSIONS.contains(&extension))
+}
+
+#[tracing::instrument(fields(indicatif.pb_show))]
+async fn check(path: &Utf8Path) -> eyre::Result<(bool, Vec<HlintHint>)> {
+    let cx = cx();
+
+    let (input_bytes, input_hash) = read_file(path).await?;
+
+    let (checked, mut hints) = if let Some(hints) = cx.cache.is_typos_checked(input_hash).await? {
+        tracing::trace!("Using cached spell-check results");
+        (false, hints)
+    } else {
+        tracing::trace!("Spell-checking");
+
+        let (hints, duration) = exec::timed(typos(input_bytes)).await?;
+
+        cx.cache
+            .record_duration("typos", input_hash, path, duration)
+            .await?;
+
+        cx.cache.mark_typos_checked(input_hash, &hints).await?;
+
+        (true, hints)
+    };
+
+    for hint in &mut hints {
+        hint.tool = "typos";
+        hint.file.clear();
+        hint.file.push_str(path.as_str());
+    }
+
+    Ok((checked, hints))
+}
+
+#[derive(serde::Deserialize)]
+struct TyposMessage {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    line_num: usize,
+    #[serde(default)]
+    byte_offset: usize,
+    #[serde(default)]
+    typo: String,
+    #[serde(default)]
+    corrections: Vec<String>,
+}
+
+#[tracing::instrument(skip(bytes))]
+async fn typos(bytes: Bytes) -> eyre::Result<Vec<HlintHint>> {
+    let cx = cx();
+
+    let typos = &cx.cache.which("typos").await?;
+
+    let tool_permits = cx.tool_permits("typos").await?;
+
+    let tool_permit = tool_permits.acquire().await?;
+    let file_permit = cx.file_permits.acquire().await?;
+    let process_permit = cx.process_permits.acquire().await?;
+
+    let mut command = exec::sandboxed(Some("typos"), exec::TYPOS_PROFILE, &[], typos).await?;
+
+    let (typos_config, _) = cx.cache.typos_config().await?;
+
+    let mut args = vec![String::from("--format=json")];
+
+    if let Some(config) = typos_config {
+        args.push(format!("--config={config}"));
+    }
+
+    args.push(String::from("-"));
+
+    let child = command
+        .args(args)
+        .env_clear()
+        .current_dir("/var/empty")
+        .kill_on_drop(true)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let output = exec::timeout("typos", exec::output_with_stdin(child, &bytes)).await?;
+
+    drop(process_permit);
+    drop(file_permit);
+    drop(tool_permit);
+
+    // `typos` exits with code 2 when it finds typos
+    if !output.status.success() && output.status.code() != Some(2) {
+        if let Some(exit_code) = output.status.code() {
+            eyre::bail!(
+                "`typos` exited with code {exit_code}:\n{}",
+                String::from_utf8_lossy(&output.stderr),
+            );
+        } else if let Some(signal) = output.status.signal() {
+            eyre::bail!("`typos` was terminated by signal {signal}");
+        } else {
+            eyre::bail!("`typos` died of unknown causes");
+        }
+    }
+
+    let mut hints = Vec::new();
+
+    for line in output.stdout.split(|byte| *byte == b'\n') {
+        if line.is_empty() {
+            continue;
+        }
+        let typo: TyposMessage = serde_json::from_slice(line)?;
+        if typo.kind != "typo" {
+            continue;
+        }
+        let to = if typo.corrections.is_empty() {
+            None
+        } else {
+            Some(typo.corrections.join(" or "))
+        };
+        hints.push(HlintHint {
+            module: Vec::new(),
+            decl: Vec::new(),
+            severity: HlintSeverity::Warning,
+            hint: String::from("Misspelling"),
+            file: String::new(),
+            start_line: typo.line_num,
+            start_column: typo.byte_offset + 1,
+            end_line: typo.line_num,
+            end_column: typo.byte_offset + typo.typo.len() + 1,
+            from: typo.typo,
+            to,
+            note: Vec::new(),
+            refactorings: String::from("[]"),
+            tool: "typos",
+        });
+    }
+
+    Ok(hints)
+}
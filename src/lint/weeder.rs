@@ -0,0 +1,284 @@
+use crate::{
+    cli::lint::WeederArgs,
+    config::Pipeline,
+    context::cx,
+    events::message,
+    exec, git,
+    io::read_file,
+    lint::{HlintHint, HlintSeverity, Summary, hie::HieFiles},
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use std::{hash::Hasher as _, os::unix::process::ExitStatusExt as _, process::Stdio, str};
+use tokio::fs;
+use twox_hash::XxHash3_64;
+
+/// What `weeder` exits with when it finds unused code
+const WEEDS_FOUND: i32 = 228;
+
+/// Most modules named when some are missing `.hie` files
+const MAX_MISSING_SHOWN: usize = 5;
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &WeederArgs) -> eyre::Result<Summary> {
+    let cx = cx();
+
+    let config = cx.cache.config().await?;
+    let git_root = cx.cache.git_root().await?;
+
+    let hie_files = HieFiles::find().await?;
+    let hie_directory = &hie_files.dir;
+
+    report_missing(&hie_files).await?;
+
+    // Any change to a module changes what every other module uses, so results are for the whole
+    // program
+    let mut hasher = XxHash3_64::default();
+    for path in &hie_files.paths {
+        let (_, hash) = read_file(&git_root.join(hie_directory).join(path)).await?;
+        hasher.write(path.as_str().as_bytes());
+        hasher.write(&hash.to_le_bytes());
+    }
+    let hie_hash = hasher.finish();
+
+    let config_path = git_root.join(config.tool_configs.weeder());
+    let config_path = fs::try_exists(&config_path).await?.then_some(config_path);
+    let config_hash = match &config_path {
+        Some(path) => read_file(path).await?.1,
+        None => 0,
+    };
+
+    let (checked, mut hints) =
+        if let Some(hints) = cx.cache.is_weeder_checked(config_hash, hie_hash).await? {
+            tracing::trace!("Using cached `weeder` results");
+            (false, hints)
+        } else {
+            tracing::trace!("Weeding");
+
+            let weeded = weeder(hie_directory, config_path.as_deref());
+            let (hints, duration) = exec::timed(weeded).await?;
+
+            cx.cache
+                .record_duration("weeder", hie_hash, hie_directory, duration)
+                .await?;
+
+            cx.cache
+                .mark_weeder_checked(config_hash, hie_hash, &hints)
+                .await?;
+
+            (true, hints)
+        };
+
+    for hint in &mut hints {
+        hint.tool = "weeder";
+    }
+
+    if !args.paths.is_empty() {
+        let mut prefixes = Vec::with_capacity(args.paths.len());
+        for path in &args.paths {
+            let absolute_path = Utf8PathBuf::try_from(std::path::absolute(path)?)?;
+            let relative_path = absolute_path
+                .strip_prefix(git_root)
+                .unwrap_or(&absolute_path)
+                .to_path_buf();
+            prefixes.push(relative_path);
+        }
+        hints.retain(|hint| {
+            prefixes
+                .iter()
+                .any(|prefix| Utf8Path::new(&hint.file).starts_with(prefix))
+        });
+    }
+
+    let mut summary = Summary::default();
+    summary.add(hints);
+    summary.file_count = hie_files.paths.len();
+
+    message!(
+        "{verb} {count} `.hie` {files}",
+        verb = if checked {
+            "Weeded"
+        } else {
+            "Reused results for"
+        },
+        count = hie_files.paths.len().to_formatted_string(&Locale::en),
+        files = if hie_files.paths.len() == 1 {
+            "file"
+        } else {
+            "files"
+        },
+    );
+
+    Ok(summary)
+}
+
+/// Say which modules don't have a `.hie` file, since `weeder` can't see what they use and may
+/// report code as unused when only they use it
+async fn report_missing(hie_files: &HieFiles) -> eyre::Result<()> {
+    let mut missing = Vec::new();
+    for path in git::all_haskell_files(Pipeline::Lint).await? {
+        if hie_files.is_missing(&path)? {
+            missing.push(path);
+        }
+    }
+
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let mut shown: Vec<_> = missing
+        .iter()
+        .take(MAX_MISSING_SHOWN)
+        .map(|path| format!("`{path}`"))
+        .collect();
+    if missing.len() > MAX_MISSING_SHOWN {
+        shown.push(String::from("…"));
+    }
+    message!(
+        "Missing `.hie` files for {count} {modules}, so code only they use may be reported as \
+         unused: {shown}",
+        count = missing.len().to_formatted_string(&Locale::en),
+        modules = if missing.len() == 1 {
+            "module"
+        } else {
+            "modules"
+        },
+        shown = shown.join(", "),
+    );
+
+    Ok(())
+}
+
+#[tracing::instrument]
+async fn weeder(
+    hie_directory: &Utf8Path,
+    config_path: Option<&Utf8Path>,
+) -> eyre::Result<Vec<HlintHint>> {
+    let cx = cx();
+
+    let weeder = &cx.cache.which("weeder").await?;
+
+    let git_root = cx.cache.git_root().await?;
+
+    let tool_permits = cx.tool_permits("weeder").await?;
+
+    let mut args = vec![format!("--hie-directory={hie_directory}")];
+    if let Some(config_path) = config_path {
+        args.push(format!("--config={config_path}"));
+    }
+
+    let tool_permit = tool_permits.acquire().await?;
+    let process_permit = cx.process_permits.acquire().await?;
+
+    let mut command = exec::sandboxed(
+        Some("weeder"),
+        exec::WEEDER_PROFILE,
+        &[exec::Param {
+            name: "GIT_ROOT",
+            path: git_root,
+            writable: false,
+        }],
+        weeder,
+    )
+    .await?;
+
+    let output = exec::timeout(
+        "weeder",
+        command
+            .args(args)
+            .env_clear()
+            .current_dir(git_root)
+            .kill_on_drop(true)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output(),
+    )
+    .await?;
+
+    drop(process_permit);
+    drop(tool_permit);
+
+    if !output.status.success() && output.status.code() != Some(WEEDS_FOUND) {
+        if let Some(exit_code) = output.status.code() {
+            eyre::bail!(
+                "`weeder` exited with code {exit_code}:\n{}",
+                String::from_utf8_lossy(&output.stderr),
+            );
+        } else if let Some(signal) = output.status.signal() {
+            eyre::bail!("`weeder` was terminated by signal {signal}");
+        } else {
+            eyre::bail!("`weeder` died of unknown causes");
+        }
+    }
+
+    let mut hints = Vec::new();
+    for line in str::from_utf8(&output.stdout)?.lines() {
+        if let Some(hint) = parse_line(line) {
+            hints.push(hint);
+        }
+    }
+
+    for hint in &mut hints {
+        hint.file = resolve(&hint.file).await?.to_string();
+    }
+
+    Ok(hints)
+}
+
+/// A line like `src/Foo.hs:12:1: bar`, or `None` for lines which don't report unused code
+fn parse_line(line: &str) -> Option<HlintHint> {
+    let mut parts = line.splitn(4, ':');
+    let file = parts.next()?;
+    let line: usize = parts.next()?.parse().ok()?;
+    let column: usize = parts.next()?.parse().ok()?;
+    let name = parts.next()?.trim();
+    if name.is_empty() {
+        return None;
+    }
+    // Qualified with the module it's defined in, as in `Foo.bar`
+    let (module, decl) = match name.rsplit_once('.') {
+        Some((module, decl)) if !decl.is_empty() => (Some(module), decl),
+        _ => (None, name),
+    };
+    Some(HlintHint {
+        module: module.map(String::from).into_iter().collect(),
+        decl: vec![String::from(decl)],
+        severity: HlintSeverity::Warning,
+        hint: String::from("Unused declaration"),
+        file: String::from(file),
+        start_line: line,
+        start_column: column,
+        end_line: line,
+        end_column: column + decl.chars().count(),
+        from: String::from(decl),
+        to: None,
+        note: vec![format!(
+            "`{decl}` isn't used by any root in the weeder config"
+        )],
+        refactorings: String::from("[]"),
+        tool: "weeder",
+    })
+}
+
+/// A path relative to the git root for a source path recorded in a `.hie` file, which is relative
+/// to wherever GHC ran: the git root, or the package's directory when built by `cabal`
+async fn resolve(path: &str) -> eyre::Result<Utf8PathBuf> {
+    let cx = cx();
+    let git_root = cx.cache.git_root().await?;
+    let path = Utf8Path::new(path);
+    if path.is_absolute() {
+        return Ok(path.strip_prefix(git_root).unwrap_or(path).to_path_buf());
+    }
+    if fs::try_exists(git_root.join(path)).await? {
+        return Ok(path.to_path_buf());
+    }
+    for package in cx.cache.packages().await? {
+        let candidate = package.dir.join(path);
+        if fs::try_exists(git_root.join(&candidate)).await? {
+            return Ok(candidate);
+        }
+    }
+    Ok(path.to_path_buf())
+}
@@ -0,0 +1,445 @@
+use crate::{
+    cli::{
+        format::{Args as FormatArgs, Format},
+        lsp::Args,
+    },
+    config::{Language, Pipeline},
+    context::cx,
+    format,
+    io::{FORWARDED, Forwarded},
+    lint::{self, Diagnostic, Position, Range},
+};
+use bytes::Bytes;
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use std::{collections::HashMap, str, sync::Arc};
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt as _, AsyncReadExt as _, AsyncWriteExt as _, BufReader, Stdout,
+};
+
+/// JSON-RPC error code for requests the server doesn't handle
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// JSON-RPC error code for requests which failed
+const INTERNAL_ERROR: i64 = -32603;
+
+/// A request or notification from the editor
+#[derive(Deserialize)]
+struct Message {
+    /// Only requests have one, and expect a response with the same one
+    id: Option<Value>,
+    method: Option<String>,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize)]
+struct Response<'a> {
+    jsonrpc: &'static str,
+    id: &'a Value,
+    result: Value,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse<'a> {
+    jsonrpc: &'static str,
+    id: &'a Value,
+    error: ResponseError,
+}
+
+#[derive(Serialize)]
+struct ResponseError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct Notification<T> {
+    jsonrpc: &'static str,
+    method: &'static str,
+    params: T,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InitializeResult {
+    capabilities: ServerCapabilities,
+    server_info: ServerInfo,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerCapabilities {
+    text_document_sync: TextDocumentSync,
+    document_formatting_provider: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TextDocumentSync {
+    open_close: bool,
+    /// 1 is for sending the whole document on each change
+    change: u8,
+    save: bool,
+}
+
+#[derive(Serialize)]
+struct ServerInfo {
+    name: &'static str,
+    version: &'static str,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DidOpenParams {
+    text_document: TextDocumentItem,
+}
+
+#[derive(Deserialize)]
+struct TextDocumentItem {
+    uri: String,
+    text: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DidChangeParams {
+    text_document: TextDocumentIdentifier,
+    content_changes: Vec<ContentChange>,
+}
+
+#[derive(Deserialize)]
+struct ContentChange {
+    text: String,
+}
+
+/// Params of `didSave`, `didClose`, and `formatting`, which only need the document
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DocumentParams {
+    text_document: TextDocumentIdentifier,
+}
+
+#[derive(Deserialize)]
+struct TextDocumentIdentifier {
+    uri: String,
+}
+
+#[derive(Serialize)]
+struct PublishDiagnosticsParams<'a> {
+    uri: &'a str,
+    diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TextEdit {
+    range: Range,
+    new_text: String,
+}
+
+/// Serve the Language Server Protocol on `stdin` and `stdout` until the editor says to exit
+#[tracing::instrument(skip_all)]
+pub async fn run(_args: &Args) -> eyre::Result<()> {
+    let mut stdin = BufReader::new(tokio::io::stdin());
+    let mut stdout = tokio::io::stdout();
+
+    // Contents of open documents by URI, which may not be saved yet
+    let mut documents = HashMap::<String, String>::new();
+
+    while let Some(message) = read_message(&mut stdin).await? {
+        let Some(method) = message.method.as_deref() else {
+            // A response, but nothing is ever requested from the editor
+            continue;
+        };
+        match method {
+            "initialize" => {
+                let result = InitializeResult {
+                    capabilities: ServerCapabilities {
+                        text_document_sync: TextDocumentSync {
+                            open_close: true,
+                            change: 1,
+                            save: true,
+                        },
+                        document_formatting_provider: true,
+                    },
+                    server_info: ServerInfo {
+                        name: "be",
+                        version: env!("CARGO_PKG_VERSION"),
+                    },
+                };
+                respond(&mut stdout, &message, Ok(serde_json::to_value(result)?)).await?;
+            }
+            "shutdown" => {
+                respond(&mut stdout, &message, Ok(Value::Null)).await?;
+            }
+            "exit" => {
+                return Ok(());
+            }
+            "textDocument/didOpen" => {
+                let params: DidOpenParams = params(&message)?;
+                let TextDocumentItem { uri, text } = params.text_document;
+                publish_diagnostics(&mut stdout, &uri, &text).await?;
+                documents.insert(uri, text);
+            }
+            "textDocument/didChange" => {
+                let params: DidChangeParams = params(&message)?;
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    documents.insert(params.text_document.uri, change.text);
+                }
+            }
+            "textDocument/didSave" => {
+                let params: DocumentParams = params(&message)?;
+                let uri = params.text_document.uri;
+                if let Some(text) = documents.get(&uri) {
+                    publish_diagnostics(&mut stdout, &uri, text).await?;
+                }
+            }
+            "textDocument/didClose" => {
+                let params: DocumentParams = params(&message)?;
+                let uri = params.text_document.uri;
+                documents.remove(&uri);
+                notify(
+                    &mut stdout,
+                    "textDocument/publishDiagnostics",
+                    PublishDiagnosticsParams {
+                        uri: &uri,
+                        diagnostics: Vec::new(),
+                    },
+                )
+                .await?;
+            }
+            "textDocument/formatting" => {
+                let params: DocumentParams = params(&message)?;
+                let uri = params.text_document.uri;
+                let result = match documents.get(&uri) {
+                    Some(text) => format_document(&uri, text).await,
+                    None => Err(eyre::eyre!("`{uri}` isn't open")),
+                };
+                respond(&mut stdout, &message, result).await?;
+            }
+            _ => {
+                if let Some(id) = &message.id {
+                    let error = ErrorResponse {
+                        jsonrpc: "2.0",
+                        id,
+                        error: ResponseError {
+                            code: METHOD_NOT_FOUND,
+                            message: format!("`be lsp` doesn't handle `{method}`"),
+                        },
+                    };
+                    write_message(&mut stdout, &error).await?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Edits replacing the whole document with its formatted contents, or none if it's formatted
+async fn format_document(uri: &str, text: &str) -> eyre::Result<Value> {
+    let path = document_path(uri).await?;
+
+    // `be format --stdin`, with the document standing in for `stdin`
+    let forwarded = Arc::new(Forwarded {
+        stdin: std::sync::Mutex::new(Some(Bytes::from(text.to_owned()))),
+        ..Forwarded::default()
+    });
+    FORWARDED
+        .scope(
+            Arc::clone(&forwarded),
+            Box::pin(format::run(&FormatArgs {
+                command: None,
+                packages: vec![],
+                stdin: true,
+                stdin_filepath: Some(path),
+                check: false,
+                diff: false,
+                format: Format::Human,
+                watch: false,
+            })),
+        )
+        .await?;
+    let formatted = String::from_utf8(std::mem::take(&mut *forwarded.stdout.lock().unwrap()))?;
+
+    if formatted == text {
+        return Ok(serde_json::to_value(Vec::<TextEdit>::new())?);
+    }
+    let edit = TextEdit {
+        range: Range {
+            start: Position {
+                line: 0,
+                character: 0,
+            },
+            // Past the last line, so the edit covers everything
+            end: Position {
+                line: text.split('\n').count(),
+                character: 0,
+            },
+        },
+        new_text: formatted,
+    };
+    Ok(serde_json::to_value([edit])?)
+}
+
+/// Publish findings for a Haskell document, or log why it couldn't be linted
+async fn publish_diagnostics(stdout: &mut Stdout, uri: &str, text: &str) -> eyre::Result<()> {
+    let diagnostics = match lint_document(uri, text).await {
+        Ok(Some(diagnostics)) => diagnostics,
+        Ok(None) => return Ok(()),
+        Err(error) => {
+            tracing::warn!("Failed to lint `{uri}`: {error}");
+            return Ok(());
+        }
+    };
+    notify(
+        stdout,
+        "textDocument/publishDiagnostics",
+        PublishDiagnosticsParams { uri, diagnostics },
+    )
+    .await
+}
+
+/// Findings for the document, or `None` if it isn't a Haskell file `be lint` would lint
+async fn lint_document(uri: &str, text: &str) -> eyre::Result<Option<Vec<Diagnostic>>> {
+    let cx = cx();
+    let config = cx.cache.config().await?;
+    let path = document_path(uri).await?;
+    if !config.is_file_type(Language::Haskell, Pipeline::Lint, &path) {
+        return Ok(None);
+    }
+    // Messages and output go into the response, so nothing is printed over the protocol
+    let diagnostics = FORWARDED
+        .scope(
+            Arc::new(Forwarded::default()),
+            lint::lint_haskell_diagnostics(&path, Bytes::from(text.to_owned())),
+        )
+        .await?;
+    Ok(Some(diagnostics))
+}
+
+/// A document's path relative to the git root, like paths in `be.toml`
+async fn document_path(uri: &str) -> eyre::Result<Utf8PathBuf> {
+    let Some(absolute_path) = uri_path(uri) else {
+        eyre::bail!("`{uri}` isn't a file");
+    };
+    let git_root = cx().cache.git_root().await?;
+    Ok(absolute_path
+        .strip_prefix(git_root)
+        .map_or_else(|_| absolute_path.clone(), Utf8Path::to_path_buf))
+}
+
+/// Path of a `file://` URI, with `%XX` escapes decoded
+fn uri_path(uri: &str) -> Option<Utf8PathBuf> {
+    let mut rest = uri.strip_prefix("file://")?.as_bytes();
+    let mut bytes = Vec::with_capacity(rest.len());
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%'
+            && let Some(hex) = tail.get(..2)
+            && let Ok(hex) = str::from_utf8(hex)
+            && let Ok(decoded) = u8::from_str_radix(hex, 16)
+        {
+            bytes.push(decoded);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok().map(Utf8PathBuf::from)
+}
+
+fn params<T: DeserializeOwned>(message: &Message) -> eyre::Result<T> {
+    Ok(serde_json::from_value(message.params.clone())?)
+}
+
+async fn respond(
+    stdout: &mut Stdout,
+    message: &Message,
+    result: eyre::Result<Value>,
+) -> eyre::Result<()> {
+    let id = message.id.as_ref().unwrap_or(&Value::Null);
+    match result {
+        Ok(result) => {
+            write_message(
+                stdout,
+                &Response {
+                    jsonrpc: "2.0",
+                    id,
+                    result,
+                },
+            )
+            .await
+        }
+        Err(error) => {
+            write_message(
+                stdout,
+                &ErrorResponse {
+                    jsonrpc: "2.0",
+                    id,
+                    error: ResponseError {
+                        code: INTERNAL_ERROR,
+                        message: format!("{error:#}"),
+                    },
+                },
+            )
+            .await
+        }
+    }
+}
+
+async fn notify(
+    stdout: &mut Stdout,
+    method: &'static str,
+    params: impl Serialize,
+) -> eyre::Result<()> {
+    write_message(
+        stdout,
+        &Notification {
+            jsonrpc: "2.0",
+            method,
+            params,
+        },
+    )
+    .await
+}
+
+/// A message's JSON, after a `Content-Length` header
+async fn write_message(stdout: &mut Stdout, message: &impl Serialize) -> eyre::Result<()> {
+    let body = serde_json::to_vec(message)?;
+    stdout
+        .write_all(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes())
+        .await?;
+    stdout.write_all(&body).await?;
+    stdout.flush().await?;
+    Ok(())
+}
+
+/// The next message, or `None` once the editor closes `stdin`
+async fn read_message<R: AsyncBufRead + Unpin>(reader: &mut R) -> eyre::Result<Option<Message>> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            content_length = Some(value.trim().parse::<usize>()?);
+        }
+    }
+    let Some(content_length) = content_length else {
+        eyre::bail!("Message is missing a `Content-Length` header");
+    };
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
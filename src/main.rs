@@ -1,24 +1,59 @@
 mod cache;
+mod ci;
 mod cli;
+mod config;
 mod context;
+mod daemon;
+mod diff;
+mod events;
 mod exec;
+mod exit;
+mod fix;
 mod format;
+mod generate;
 mod git;
+mod github;
 mod hashing;
+mod hook;
 mod io;
+mod line_ending;
 mod lint;
+mod lsp;
+mod memory;
+mod metrics;
+mod packages;
 mod query;
+mod queue;
+mod report;
+mod state;
+mod temp;
+mod undo;
 mod utils;
+mod watch;
 
 use crate::{
     cache::Cache,
-    cli::{Args, Command},
+    cli::{Args, Command, MessageFormat},
     context::{CONTEXT, Context},
+    exit::{Findings, Interrupted},
+    memory::MemoryThrottle,
+    metrics::Metrics,
+    temp::TempStore,
 };
 use clap::Parser as _;
 use color_eyre::eyre;
-use std::{env, thread::available_parallelism};
-use tokio::sync::Semaphore;
+use dashmap::DashMap;
+use num_format::{Locale, ToFormattedString as _};
+use std::{
+    env,
+    process::ExitCode,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread::available_parallelism,
+};
+use tokio::sync::{OnceCell, Semaphore};
 use tracing::{Event, Subscriber};
 use tracing_error::ErrorLayer;
 use tracing_indicatif::{
@@ -37,33 +72,124 @@ use tracing_subscriber::{
     util::SubscriberInitExt as _,
 };
 
-// TODO: Currently I'm excluding files from consideration simply if they are on `master`, because
-// they should've been formatted/linted/whatever successfully there. But I should not apply this
-// shortcut if any dependent config files have changed from `master`! For example, if I change how
-// formatting works, or add a new lint, I want _all_ files to be considered, because the rules have
-// changed since `master`.
-
 #[tokio::main]
-async fn main() -> eyre::Result<()> {
+async fn main() -> ExitCode {
+    // Returning drops every task, which kills the tools they spawned and removes their temp files
+    let result = tokio::select! {
+        result = run() => result,
+        _ = tokio::signal::ctrl_c() => Err(Interrupted(interrupted_message()).into()),
+    };
+    if let Some(cx) = CONTEXT.get() {
+        // Results are still worth sharing when there were findings, but not after Ctrl-C
+        if !result
+            .as_ref()
+            .is_err_and(|error| error.is::<Interrupted>())
+        {
+            cx.cache.finish_uploads().await;
+        }
+        // Counted for the whole run, so lookups don't each write to the cache
+        if let Err(error) = cx.cache.record_lookups(&cx.metrics.cache_lookups()).await {
+            tracing::warn!("Failed to record cache lookups: {error}");
+        }
+        cx.temp_store.clear();
+        if cx.stats {
+            events::print_message(&cx.metrics.report());
+        }
+    }
+    if events::is_jsonl() {
+        events::emit(&events::Event::Finished {
+            exit_code: exit::code(&result),
+            message: result.as_ref().err().map(ToString::to_string),
+        });
+    } else if let Err(error) = &result {
+        if let Some(findings) = error.downcast_ref::<Findings>() {
+            eprintln!("{findings}");
+        } else if let Some(interrupted) = error.downcast_ref::<Interrupted>() {
+            eprintln!("{interrupted}");
+        } else {
+            eprintln!("Error: {error:?}");
+        }
+    }
+    exit::exit_code(&result)
+}
+
+/// How many files were done before Ctrl-C
+fn interrupted_message() -> String {
+    let Some(cx) = CONTEXT.get() else {
+        return String::from("Interrupted");
+    };
+    let spawned = cx.files_spawned.load(Ordering::Relaxed);
+    let finished = cx.files_finished.load(Ordering::Relaxed);
+    if spawned == 0 {
+        return String::from("Interrupted");
+    }
+    format!(
+        "Interrupted after finishing {finished} of {spawned} {files}",
+        finished = finished.to_formatted_string(&Locale::en),
+        spawned = spawned.to_formatted_string(&Locale::en),
+        files = if spawned == 1 { "file" } else { "files" },
+    )
+}
+
+async fn run() -> eyre::Result<()> {
     let args = Args::parse();
 
     color_eyre::install()?;
     init_tracing(&args)?;
 
-    let cache = Cache::new().await?;
+    if let Some(result) = daemon::forward(&args).await {
+        return result;
+    }
+
+    let cache = Cache::new(args.cache_dir.as_deref(), args.no_cache).await?;
     let file_permits = Semaphore::new(100);
-    let process_permits = Semaphore::new(usize::from(available_parallelism()?));
+    // Outside of a repo there's no config, but commands like `be exec` still work
+    let jobs = match cache.config().await {
+        Ok(config) => config.jobs,
+        Err(_) => None,
+    };
+    let process_permits = Semaphore::new(usize::from(match jobs {
+        Some(jobs) => jobs,
+        None => available_parallelism()?,
+    }));
 
     CONTEXT.get_or_init(move || Context {
         cache,
         file_permits,
         process_permits,
+        tool_permits: DashMap::new(),
+        memory: MemoryThrottle::new(),
+        state: OnceCell::new(),
+        query_sqlite: OnceCell::new(),
+        base_arg: args.base.clone(),
+        base: OnceCell::new(),
+        staged: args.staged,
+        timeout_arg: args.timeout,
+        all_files: OnceCell::new(),
+        files_spawned: AtomicUsize::new(0),
+        files_finished: AtomicUsize::new(0),
+        temp_store: TempStore::new(),
+        message_format: args.message_format,
+        metrics: Metrics::new(),
+        stats: args.stats,
+        watched: Mutex::new(None),
     });
 
     match &args.command {
+        Command::Cache(args) => cache::run(args).await,
+        Command::Ci(args) => ci::run(args).await,
+        Command::Config(args) => config::run(args).await,
+        Command::Daemon(args) => daemon::run(args).await,
+        Command::Exec(args) => exec::run(args).await,
+        Command::Fix(args) => fix::run(args).await,
         Command::Format(args) => format::run(args).await,
+        Command::Generate(args) => generate::run(args).await,
+        Command::Hook(args) => hook::run(args).await,
         Command::Lint(args) => lint::run(args).await,
+        Command::Lsp(args) => lsp::run(args).await,
         Command::Query(args) => query::run(args).await,
+        Command::Report(args) => report::run(args).await,
+        Command::Undo(args) => undo::run(args).await,
     }
 }
 
@@ -100,7 +226,11 @@ fn init_tracing(args: &Args) -> eyre::Result<()> {
 
     tracing_subscriber::registry()
         .with(fmt_layer)
-        .with(indicatif_layer.with_filter(IndicatifFilter::new(false)))
+        // Progress bars would interleave with events
+        .with(
+            (args.message_format == MessageFormat::Human)
+                .then(|| indicatif_layer.with_filter(IndicatifFilter::new(false))),
+        )
         .with(ErrorLayer::default())
         .init();
 
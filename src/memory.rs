@@ -0,0 +1,91 @@
+use crate::context::cx;
+use color_eyre::eyre;
+use std::{
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+use sysinfo::System;
+
+/// Memory kept free when `memory-reserve-mib` isn't set
+const DEFAULT_RESERVE_MIB: u64 = 1024;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Holds back new tool processes while available memory is low, so heavy runs slow down instead of
+/// getting killed by the OOM killer
+pub struct MemoryThrottle {
+    system: Mutex<System>,
+    running: AtomicUsize,
+}
+
+/// A running process counted by `MemoryThrottle`, until dropped
+pub struct MemoryPermit<'a> {
+    throttle: &'a MemoryThrottle,
+}
+
+impl Drop for MemoryPermit<'_> {
+    fn drop(&mut self) {
+        self.throttle.running.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl MemoryThrottle {
+    pub fn new() -> Self {
+        Self {
+            system: Mutex::new(System::new()),
+            running: AtomicUsize::new(0),
+        }
+    }
+
+    /// Wait until enough memory is available to start another process. One process may always
+    /// run, so a run keeps making progress even when memory stays low.
+    #[tracing::instrument(skip(self))]
+    pub async fn acquire(&self) -> eyre::Result<MemoryPermit<'_>> {
+        let reserve_mib = cx()
+            .cache
+            .config()
+            .await?
+            .memory_reserve_mib
+            .unwrap_or(DEFAULT_RESERVE_MIB);
+
+        let reserve = reserve_mib * 1024 * 1024;
+
+        let mut waiting = false;
+
+        loop {
+            if reserve == 0 || self.running.load(Ordering::SeqCst) == 0 {
+                break;
+            }
+
+            let (total, available) = {
+                let mut system = self.system.lock().unwrap();
+                system.refresh_memory();
+                (system.total_memory(), system.available_memory())
+            };
+
+            // `sysinfo` reports zero when it can't read memory usage on this platform
+            if total == 0 || available >= reserve {
+                break;
+            }
+
+            if !waiting {
+                tracing::debug!(
+                    available_mib = available / 1024 / 1024,
+                    reserve_mib,
+                    running = self.running.load(Ordering::SeqCst),
+                    "Waiting for available memory",
+                );
+                waiting = true;
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+
+        self.running.fetch_add(1, Ordering::SeqCst);
+
+        Ok(MemoryPermit { throttle: self })
+    }
+}
@@ -0,0 +1,146 @@
+use crate::utils::kilobytes;
+use num_format::{Locale, ToFormattedString as _};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+/// Counts for `--stats`, collected over the whole run
+#[derive(Default)]
+pub struct Metrics {
+    tools: Mutex<BTreeMap<String, ToolMetrics>>,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    running: AtomicUsize,
+    peak_running: AtomicUsize,
+}
+
+#[derive(Default)]
+struct ToolMetrics {
+    runs: usize,
+    cache_hits: usize,
+    cache_misses: usize,
+    time: Duration,
+}
+
+/// A running tool process, recorded in `Metrics` once dropped
+pub struct RunningTool<'a> {
+    metrics: &'a Metrics,
+    tool: String,
+    start: Instant,
+}
+
+impl Drop for RunningTool<'_> {
+    fn drop(&mut self) {
+        self.metrics.running.fetch_sub(1, Ordering::Relaxed);
+        let mut tools = self.metrics.tools.lock().unwrap();
+        let tool = tools.entry(std::mem::take(&mut self.tool)).or_default();
+        tool.runs += 1;
+        tool.time += self.start.elapsed();
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a tool process until the returned guard is dropped
+    pub fn start(&self, tool: &str) -> RunningTool<'_> {
+        let running = self.running.fetch_add(1, Ordering::Relaxed) + 1;
+        self.peak_running.fetch_max(running, Ordering::Relaxed);
+        RunningTool {
+            metrics: self,
+            tool: String::from(tool),
+            start: Instant::now(),
+        }
+    }
+
+    /// Count a cache hit or miss, for the hit ratios in `--stats` and, once `be` exits,
+    /// `be cache stats`
+    pub fn cache_lookup(&self, tool: &str, hit: bool) {
+        let mut tools = self.tools.lock().unwrap();
+        let tool = tools.entry(String::from(tool)).or_default();
+        if hit {
+            tool.cache_hits += 1;
+        } else {
+            tool.cache_misses += 1;
+        }
+    }
+
+    /// Cache hits and misses for each tool which looked anything up
+    pub fn cache_lookups(&self) -> Vec<(String, usize, usize)> {
+        let tools = self.tools.lock().unwrap();
+        tools
+            .iter()
+            .filter(|(_, metrics)| metrics.cache_hits + metrics.cache_misses > 0)
+            .map(|(tool, metrics)| (tool.clone(), metrics.cache_hits, metrics.cache_misses))
+            .collect()
+    }
+
+    pub fn read(&self, bytes: usize) {
+        self.bytes_read.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn written(&self, bytes: usize) {
+        self.bytes_written
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Runs, cache hits, and time spent for each tool, then totals for the whole run
+    pub fn report(&self) -> String {
+        let tools = self.tools.lock().unwrap();
+
+        let rows: Vec<[String; 4]> = tools
+            .iter()
+            .map(|(tool, metrics)| {
+                [
+                    tool.clone(),
+                    metrics.runs.to_formatted_string(&Locale::en),
+                    metrics.cache_hits.to_formatted_string(&Locale::en),
+                    format!("{:.2}s", metrics.time.as_secs_f64()),
+                ]
+            })
+            .collect();
+
+        let header = [
+            String::from("Tool"),
+            String::from("Runs"),
+            String::from("Cache hits"),
+            String::from("Time"),
+        ];
+
+        let mut widths = [0; 4];
+        for row in std::iter::once(&header).chain(&rows) {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+
+        let mut report = String::new();
+        // Writing to a `String` can't fail
+        for [tool, runs, cache_hits, time] in std::iter::once(&header).chain(&rows) {
+            let _ = writeln!(
+                report,
+                "{tool:<0$}  {runs:>1$}  {cache_hits:>2$}  {time:>3$}",
+                widths[0], widths[1], widths[2], widths[3],
+            );
+        }
+
+        let peak_running = self.peak_running.load(Ordering::Relaxed);
+        let _ = write!(
+            report,
+            "Read {}, wrote {}, and ran up to {peak_running} {tools} at once",
+            kilobytes(self.bytes_read.load(Ordering::Relaxed)),
+            kilobytes(self.bytes_written.load(Ordering::Relaxed)),
+            tools = if peak_running == 1 { "tool" } else { "tools" },
+        );
+
+        report
+    }
+}
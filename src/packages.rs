@@ -0,0 +1,432 @@
+use crate::{exec::exec, io::read_file};
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{self, ContextCompat as _};
+use derive_more::Display;
+use saphyr::{LoadableYamlNode as _, Yaml};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    str,
+};
+use which::which_global;
+
+pub struct Package {
+    pub name: String,
+    /// Package directory, relative to the git root
+    pub dir: Utf8PathBuf,
+    pub components: Vec<Component>,
+}
+
+pub struct Component {
+    pub kind: ComponentKind,
+    pub name: String,
+    /// Source directories, relative to the git root
+    pub source_dirs: Vec<Utf8PathBuf>,
+    /// Modules a library exposes, or `None` if `hpack` exposes every module in its source
+    /// directories
+    pub exposed_modules: Option<Vec<String>>,
+    /// `main` or `main-is`, a path relative to a source directory or a module name
+    pub main: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Display)]
+pub enum ComponentKind {
+    #[display("lib")]
+    Library,
+    #[display("exe")]
+    Executable,
+    #[display("test")]
+    TestSuite,
+    #[display("bench")]
+    Benchmark,
+}
+
+/// All source directories of all components, relative to the git root
+pub fn source_dirs(packages: &[Package]) -> BTreeSet<&Utf8Path> {
+    packages.iter().flat_map(source_dirs_of).collect()
+}
+
+/// Source directories of the named packages, relative to the git root
+pub fn package_source_dirs<'a>(
+    packages: &'a [Package],
+    names: &[String],
+) -> eyre::Result<BTreeSet<&'a Utf8Path>> {
+    let mut source_dirs = BTreeSet::new();
+    for name in names {
+        source_dirs.extend(source_dirs_of(find(packages, name)?));
+    }
+    Ok(source_dirs)
+}
+
+/// Directories of the named packages, relative to the git root
+pub fn package_dirs<'a>(
+    packages: &'a [Package],
+    names: &[String],
+) -> eyre::Result<BTreeSet<&'a Utf8Path>> {
+    let mut dirs = BTreeSet::new();
+    for name in names {
+        dirs.insert(find(packages, name)?.dir.as_path());
+    }
+    Ok(dirs)
+}
+
+/// Components with a module at a path relative to the git root in one of their source directories,
+/// most specific directory first, since a test suite can share a library's directory
+pub fn components_containing<'a>(
+    packages: &'a [Package],
+    relative_path: &Utf8Path,
+) -> Vec<(&'a Package, &'a Component)> {
+    let mut found = Vec::new();
+    for package in packages {
+        for component in &package.components {
+            let longest = component
+                .source_dirs
+                .iter()
+                .filter_map(|dir| {
+                    let (dir_len, module_path) = if dir == "." {
+                        (0, relative_path)
+                    } else {
+                        (dir.as_str().len(), relative_path.strip_prefix(dir).ok()?)
+                    };
+                    module_name(module_path).map(|_| dir_len)
+                })
+                .max();
+            if let Some(dir_len) = longest {
+                found.push((dir_len, package, component));
+            }
+        }
+    }
+    found.sort_by_key(|(dir_len, _, _)| std::cmp::Reverse(*dir_len));
+    found
+        .into_iter()
+        .map(|(_, package, component)| (package, component))
+        .collect()
+}
+
+/// Like `Foo.Bar` for `Foo/Bar.hs`
+pub fn module_name(module_path: &Utf8Path) -> Option<String> {
+    let stem = module_path.file_stem()?;
+    let mut segments: Vec<&str> = module_path
+        .parent()?
+        .components()
+        .map(|component| component.as_str())
+        .collect();
+    segments.push(stem);
+    let is_valid = |segment: &str| {
+        let mut chars = segment.chars();
+        chars.next().is_some_and(|char| char.is_ascii_uppercase())
+            && chars.all(|char| char.is_alphanumeric() || char == '_' || char == '\'')
+    };
+    segments
+        .iter()
+        .all(|segment| is_valid(segment))
+        .then(|| segments.join("."))
+}
+
+fn find<'a>(packages: &'a [Package], name: &str) -> eyre::Result<&'a Package> {
+    let Some(package) = packages.iter().find(|package| package.name == name) else {
+        let known: Vec<_> = packages
+            .iter()
+            .map(|package| package.name.as_str())
+            .collect();
+        eyre::bail!(
+            "Unknown package `{name}` (known packages: {})",
+            known.join(", ")
+        );
+    };
+    Ok(package)
+}
+
+fn source_dirs_of(package: &Package) -> impl Iterator<Item = &Utf8Path> {
+    package
+        .components
+        .iter()
+        .flat_map(|component| &component.source_dirs)
+        .map(|dir| dir.as_path())
+}
+
+/// Find packages from tracked `package.yaml` files, or `*.cabal` files when there is no
+/// `package.yaml` next to them.
+#[tracing::instrument]
+pub async fn discover(git_root: &Utf8Path) -> eyre::Result<Vec<Package>> {
+    // Like `Cache::git_root`, so `be config init` still works when `be.toml` doesn't parse
+    let git = Utf8PathBuf::try_from(which_global("git")?)?;
+
+    let stdout = exec(
+        git,
+        [
+            "-C",
+            git_root.as_str(),
+            "ls-files",
+            "--",
+            ":(glob)**/package.yaml",
+            ":(glob)**/*.cabal",
+        ],
+    )
+    .await?;
+
+    let mut package_files = BTreeMap::<Utf8PathBuf, Utf8PathBuf>::new();
+
+    for path in str::from_utf8(&stdout)?.lines().map(Utf8PathBuf::from) {
+        let dir = path.parent().map(Utf8Path::to_path_buf).unwrap_or_default();
+        let is_hpack = path.file_name() == Some("package.yaml");
+        package_files
+            .entry(dir)
+            .and_modify(|existing| {
+                if is_hpack {
+                    existing.clone_from(&path);
+                }
+            })
+            .or_insert(path);
+    }
+
+    let mut packages = Vec::with_capacity(package_files.len());
+
+    for (dir, path) in package_files {
+        let (bytes, _) = read_file(&git_root.join(&path)).await?;
+        let source = str::from_utf8(&bytes)?;
+        let package = if path.file_name() == Some("package.yaml") {
+            parse_hpack(&dir, source)
+        } else {
+            parse_cabal(&dir, source)
+        };
+        match package {
+            Ok(package) => {
+                for component in &package.components {
+                    tracing::debug!(
+                        "Found component `{}:{}:{}` in `{}`",
+                        package.name,
+                        component.kind,
+                        component.name,
+                        package.dir,
+                    );
+                }
+                packages.push(package);
+            }
+            Err(error) => tracing::warn!("Failed to parse `{path}`: {error}"),
+        }
+    }
+
+    Ok(packages)
+}
+
+fn parse_hpack(dir: &Utf8Path, source: &str) -> eyre::Result<Package> {
+    let yaml = Yaml::load_from_str(source)?;
+    let yaml = yaml.first().context("Missing first YAML document")?;
+
+    let name = yaml
+        .as_mapping_get("name")
+        .and_then(|name| name.as_str())
+        .map_or_else(|| String::from(dir.file_name().unwrap_or("")), String::from);
+
+    let common_source_dirs = hpack_source_dirs(yaml);
+
+    let mut components = Vec::new();
+
+    let mut push = |kind, name: &str, yaml: &Yaml| {
+        let mut source_dirs = common_source_dirs.clone();
+        source_dirs.extend(hpack_source_dirs(yaml));
+        let mut component = component(dir, kind, name, &source_dirs);
+        component.exposed_modules = yaml.as_mapping_get("exposed-modules").map(hpack_strings);
+        component.main = yaml
+            .as_mapping_get("main")
+            .and_then(|main| main.as_str())
+            .map(String::from);
+        components.push(component);
+    };
+
+    if let Some(library) = yaml.as_mapping_get("library") {
+        push(ComponentKind::Library, &name, library);
+    }
+
+    for (key, kind) in [
+        ("internal-libraries", ComponentKind::Library),
+        ("executables", ComponentKind::Executable),
+        ("tests", ComponentKind::TestSuite),
+        ("benchmarks", ComponentKind::Benchmark),
+    ] {
+        let Some(mapping) = yaml.as_mapping_get(key).and_then(|yaml| yaml.as_mapping()) else {
+            continue;
+        };
+        for (component_name, component_yaml) in mapping {
+            if let Some(component_name) = component_name.as_str() {
+                push(kind, component_name, component_yaml);
+            }
+        }
+    }
+
+    Ok(Package {
+        name,
+        dir: dir.to_path_buf(),
+        components,
+    })
+}
+
+fn hpack_source_dirs(yaml: &Yaml) -> Vec<String> {
+    yaml.as_mapping_get("source-dirs")
+        .map(hpack_strings)
+        .unwrap_or_default()
+}
+
+/// A list of strings, or a single string for a list of one
+fn hpack_strings(yaml: &Yaml) -> Vec<String> {
+    if yaml.is_sequence() {
+        yaml.as_sequence()
+            .into_iter()
+            .flatten()
+            .filter_map(|yaml| yaml.as_str())
+            .map(String::from)
+            .collect()
+    } else {
+        yaml.as_str().map(String::from).into_iter().collect()
+    }
+}
+
+/// A field whose values can continue on more indented lines
+#[derive(Clone, Copy)]
+enum CabalField {
+    SourceDirs,
+    ExposedModules,
+}
+
+// TODO: Handle `common` stanzas and conditionals
+fn parse_cabal(dir: &Utf8Path, source: &str) -> eyre::Result<Package> {
+    let mut name = None;
+    let mut components = Vec::new();
+    let mut current: Option<(Component, Vec<String>)> = None;
+    let mut continued: Option<(usize, CabalField)> = None;
+
+    for line in source.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with("--") {
+            continue;
+        }
+        let indent = line.len() - trimmed.len();
+
+        if indent == 0 {
+            continued = None;
+            if let Some((stanza, source_dirs)) = current.take() {
+                components.push(cabal_component(dir, stanza, &source_dirs));
+            }
+            if let Some(value) = cabal_field(trimmed, "name") {
+                name = Some(String::from(value));
+                continue;
+            }
+            let mut words = trimmed.split_whitespace();
+            let stanza = words.next().unwrap_or("").to_ascii_lowercase();
+            let stanza_name = words.next();
+            let kind = match stanza.as_str() {
+                "library" => ComponentKind::Library,
+                "executable" => ComponentKind::Executable,
+                "test-suite" => ComponentKind::TestSuite,
+                "benchmark" => ComponentKind::Benchmark,
+                _ => continue,
+            };
+            let component_name = match (kind, stanza_name) {
+                (_, Some(stanza_name)) => String::from(stanza_name),
+                (ComponentKind::Library, None) => name.clone().unwrap_or_default(),
+                (_, None) => continue,
+            };
+            let mut stanza = component(dir, kind, &component_name, &[]);
+            if kind == ComponentKind::Library {
+                stanza.exposed_modules = Some(Vec::new());
+            }
+            current = Some((stanza, Vec::new()));
+            continue;
+        }
+
+        let Some((stanza, source_dirs)) = &mut current else {
+            continue;
+        };
+
+        let (field, values) = if let Some(value) = cabal_field(trimmed, "hs-source-dirs") {
+            continued = Some((indent, CabalField::SourceDirs));
+            (CabalField::SourceDirs, value)
+        } else if let Some(value) = cabal_field(trimmed, "exposed-modules") {
+            continued = Some((indent, CabalField::ExposedModules));
+            (CabalField::ExposedModules, value)
+        } else if let Some((field_indent, field)) = continued
+            && indent > field_indent
+        {
+            (field, trimmed)
+        } else {
+            continued = None;
+            if let Some(value) = cabal_field(trimmed, "main-is") {
+                stanza.main = Some(String::from(value));
+            }
+            continue;
+        };
+
+        match field {
+            CabalField::SourceDirs => source_dirs.extend(cabal_values(values)),
+            CabalField::ExposedModules => {
+                if let Some(exposed_modules) = &mut stanza.exposed_modules {
+                    exposed_modules.extend(cabal_values(values));
+                }
+            }
+        }
+    }
+
+    if let Some((stanza, source_dirs)) = current.take() {
+        components.push(cabal_component(dir, stanza, &source_dirs));
+    }
+
+    let name = name.context("Missing `name` field")?;
+
+    Ok(Package {
+        name,
+        dir: dir.to_path_buf(),
+        components,
+    })
+}
+
+/// A component from a stanza, with its source directories once they're all known
+fn cabal_component(dir: &Utf8Path, stanza: Component, source_dirs: &[String]) -> Component {
+    Component {
+        source_dirs: component(dir, stanza.kind, &stanza.name, source_dirs).source_dirs,
+        ..stanza
+    }
+}
+
+fn cabal_field<'a>(line: &'a str, field: &str) -> Option<&'a str> {
+    let (key, value) = line.split_once(':')?;
+    key.trim().eq_ignore_ascii_case(field).then(|| value.trim())
+}
+
+fn cabal_values(value: &str) -> impl Iterator<Item = String> {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .map(|value| value.trim_matches('"'))
+        .filter(|value| !value.is_empty())
+        .map(String::from)
+}
+
+fn component(dir: &Utf8Path, kind: ComponentKind, name: &str, source_dirs: &[String]) -> Component {
+    let source_dirs = if source_dirs.is_empty() {
+        vec![normalize(dir)]
+    } else {
+        source_dirs
+            .iter()
+            .map(|source_dir| normalize(&dir.join(source_dir)))
+            .collect()
+    };
+    Component {
+        kind,
+        name: String::from(name),
+        source_dirs,
+        exposed_modules: None,
+        main: None,
+    }
+}
+
+/// Remove `.` components, using `.` for the git root itself
+fn normalize(path: &Utf8Path) -> Utf8PathBuf {
+    let path: Utf8PathBuf = path
+        .components()
+        .filter(|component| *component != Utf8Component::CurDir)
+        .collect();
+    if path.as_str().is_empty() {
+        Utf8PathBuf::from(".")
+    } else {
+        path
+    }
+}
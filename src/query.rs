@@ -1,38 +1,81 @@
+mod affected;
+mod def;
+mod extensions;
+mod graph;
+mod metaprogramming;
+mod modules;
+mod nix;
+mod origins;
+mod orphans;
+mod package;
+mod run;
+mod serve;
+mod symbols;
+
 use crate::{
     cli::query::{Args, Command, QueryArgs},
-    io::{read_file, read_stdin},
+    config::Pipeline,
+    context::cx,
+    git,
+    io::{print_stdout, read_file, read_stdin},
+    lint::{self, haddock},
 };
 use camino::{Utf8Path, Utf8PathBuf};
 use color_eyre::eyre;
-use etcetera::app_strategy::{AppStrategy as _, AppStrategyArgs, Xdg};
 use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqliteSynchronous};
+use std::collections::{HashMap, HashSet};
 use std::str::{self, FromStr as _};
-use std::sync::LazyLock;
-use tokio::fs;
-use tracing_indicatif::indicatif_println;
+use std::sync::{Arc, LazyLock};
 use tree_sitter::{Language, Node, Parser, QueryCursor, StreamingIterator as _, Tree};
 
+pub use origins::{Origin, Origins};
+pub use orphans::has_orphans;
+
 #[tracing::instrument(skip_all)]
 pub async fn run(args: &Args) -> eyre::Result<()> {
     match &args.command {
         Command::Index => run_query_index().await,
         Command::Imports(args) => run_query_imports(args).await,
+        Command::Exports(args) => run_query_exports(args).await,
+        Command::Extensions(args) => extensions::extensions(args).await,
+        Command::Orphans(args) => orphans::orphans(args).await,
+        Command::Th(args) => metaprogramming::th(args).await,
+        Command::Cpp(args) => metaprogramming::cpp(args).await,
+        Command::Run(args) => run::run(args).await,
+        Command::Def(args) => def::def(args).await,
+        Command::NixInputs(args) => nix::run_query_nix_inputs(args).await,
+        Command::Package(args) => package::package(args).await,
+        Command::Importers(args) => graph::importers(query_sqlite().await?, args).await,
+        Command::Graph(args) => graph::graph(query_sqlite().await?, args).await,
+        Command::Cycles(args) => graph::cycles(query_sqlite().await?, args).await,
+        Command::Deadmodules(args) => graph::dead_modules(query_sqlite().await?, args).await,
+        Command::AffectedTests(args) => affected::affected_tests(query_sqlite().await?, args).await,
+        Command::Search(args) => symbols::search(query_sqlite().await?, args).await,
+        Command::Serve(args) => serve::serve(query_sqlite().await?, args).await,
     }
 }
 
-async fn run_query_index() -> eyre::Result<()> {
-    let xdg = Xdg::new(AppStrategyArgs {
-        top_level_domain: String::from("com"),
-        author: String::from("Evan Relf"),
-        app_name: String::from("Be"),
-    })?;
+pub async fn run_query_index() -> eyre::Result<()> {
+    let sqlite = query_sqlite().await?;
+
+    modules::index(sqlite).await?;
+
+    symbols::index(sqlite).await?;
 
-    let xdg_cache_dir = xdg.cache_dir();
+    Ok(())
+}
+
+/// Paths of modules in the repo by module name, as of the last `be query index`
+pub async fn indexed_modules() -> eyre::Result<HashMap<String, Vec<String>>> {
+    modules::paths_by_name(query_sqlite().await?).await
+}
 
-    fs::create_dir_all(&xdg_cache_dir).await?;
+async fn query_sqlite() -> eyre::Result<&'static SqlitePool> {
+    cx().query_sqlite.get_or_try_init(query_sqlite_open).await
+}
 
-    let sqlite_path = xdg.in_cache_dir("query.sqlite");
-    let sqlite_path = sqlite_path.to_str().unwrap();
+async fn query_sqlite_open() -> eyre::Result<SqlitePool> {
+    let sqlite_path = cx().cache.dir().join("query.sqlite");
 
     let sqlite_url = format!("sqlite://{sqlite_path}");
 
@@ -44,28 +87,67 @@ async fn run_query_index() -> eyre::Result<()> {
 
     let sqlite = SqlitePool::connect_with(sqlite_opts).await?;
 
-    sqlite_reset(&sqlite).await?;
+    // The newest table, which older versions of the database don't have
+    let has_module_imports: bool = sqlx::query_scalar(
+        "select exists(select 1 from sqlite_master where type = 'table' and name = 'module_imports')",
+    )
+    .fetch_one(&sqlite)
+    .await?;
 
-    Ok(())
+    if !has_module_imports {
+        sqlite_reset(&sqlite).await?;
+    }
+
+    Ok(sqlite)
 }
 
 async fn sqlite_reset(sqlite: &SqlitePool) -> eyre::Result<()> {
     sqlx::raw_sql(
         "
+        drop table if exists module_edges;
+
+        drop table if exists module_imports;
+
         drop table if exists module_vertices;
 
-        drop table if exists module_edges;
+        drop table if exists symbols;
 
         create table module_vertices (
-            path text primary key,
-            name text not null
+            repo text not null,
+            path text not null,
+            name text not null,
+            source_hash text not null,
+            primary key (repo, path)
+        ) strict;
+
+        create table module_imports (
+            repo text not null,
+            path text not null,
+            name text not null,
+            foreign key (repo, path) references module_vertices,
+            unique (repo, path, name)
         ) strict;
 
         create table module_edges (
-            source text not null references module_vertices,
-            target text not null references module_vertices,
-            unique (source, target)
+            repo text not null,
+            source text not null,
+            target text not null,
+            foreign key (repo, source) references module_vertices,
+            foreign key (repo, target) references module_vertices,
+            unique (repo, source, target)
         ) strict;
+
+        create virtual table symbols using fts5 (
+            name,
+            words,
+            module,
+            doc,
+            kind unindexed,
+            path unindexed,
+            start_line unindexed,
+            start_column unindexed,
+            repo unindexed
+        );
         ",
     )
     .execute(sqlite)
@@ -76,58 +158,113 @@ async fn sqlite_reset(sqlite: &SqlitePool) -> eyre::Result<()> {
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
-static LANGUAGE: LazyLock<Language> = LazyLock::new(|| tree_sitter_haskell::LANGUAGE.into());
+pub static LANGUAGE: LazyLock<Language> = LazyLock::new(|| tree_sitter_haskell::LANGUAGE.into());
+
+pub static NIX_LANGUAGE: LazyLock<Language> = LazyLock::new(|| tree_sitter_nix::LANGUAGE.into());
 
 #[tracing::instrument(skip_all)]
 pub async fn run_query_imports(args: &QueryArgs) -> eyre::Result<()> {
+    let origins = Arc::new(Origins::load().await?);
+
+    let process = move |path: Option<&Utf8Path>, bytes: &[u8]| {
+        let source_code = str::from_utf8(bytes)?;
+        let mut parser = Parser::new();
+        parser.set_language(&LANGUAGE)?;
+        let tree = parser.parse(source_code, None).unwrap();
+        let path = match path {
+            Some(path) => path.as_str(),
+            None => "<stdin>",
+        };
+        let lines = query_imports(source_code, &tree)?
+            .into_iter()
+            .map(|Item { line, column, text }| {
+                let origin = origins.resolve(text);
+                format!("{path}:{line}:{column}:{text}:{origin}")
+            })
+            .collect();
+        eyre::Ok(lines)
+    };
+
+    for_each_module(args, process).await
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn run_query_exports(args: &QueryArgs) -> eyre::Result<()> {
     let process = |path: Option<&Utf8Path>, bytes: &[u8]| {
         let source_code = str::from_utf8(bytes)?;
         let mut parser = Parser::new();
         parser.set_language(&LANGUAGE)?;
         let tree = parser.parse(source_code, None).unwrap();
-        let items = query_imports(source_code, &tree)?;
-        for Item { line, column, text } in items {
-            let path = match path {
-                Some(path) => path.as_str(),
-                None => "<stdin>",
-            };
-            indicatif_println!("{path}:{line}:{column}:{text}");
-        }
-        eyre::Ok(())
+        let path = match path {
+            Some(path) => path.as_str(),
+            None => "<stdin>",
+        };
+        let lines = query_exports(source_code, &tree)?
+            .into_iter()
+            .map(|Item { line, column, text }| format!("{path}:{line}:{column}:{text}"))
+            .collect();
+        eyre::Ok(lines)
     };
 
+    for_each_module(args, process).await
+}
+
+/// Run `process` on code from `stdin`, or on every module given as arguments, and print the
+/// lines it returns. They're printed once every module is done, sorted by path, since printing
+/// from other tasks wouldn't reach a client of `be daemon`.
+async fn for_each_module<F>(args: &QueryArgs, process: F) -> eyre::Result<()>
+where
+    F: Fn(Option<&Utf8Path>, &[u8]) -> eyre::Result<Vec<String>> + Clone + Send + 'static,
+{
     if args.stdin {
         let (input_bytes, _input_hash) = read_stdin().await?;
-        process(None, &input_bytes)?;
+        for line in process(None, &input_bytes)? {
+            print_stdout(&line);
+        }
         return Ok(());
     }
 
+    let mut paths: Vec<Utf8PathBuf> = args.modules.iter().map(Utf8PathBuf::from).collect();
+
+    if !args.packages.is_empty() {
+        // Query the same modules that would be linted
+        paths.extend(git::haskell_files(&[], &args.packages, Pipeline::Lint).await?);
+    }
+
     let mut handles = Vec::new();
 
-    for module in &args.modules {
-        let path = Utf8PathBuf::from(module);
+    for path in paths {
+        let process = process.clone();
         handles.push(tokio::spawn(async move {
             // TODO: Detect if module name, convert to path
             let (input_bytes, _input_hash) = read_file(&path).await?;
-            process(Some(&path), &input_bytes)?;
-            eyre::Ok(())
+            let lines = process(Some(&path), &input_bytes)?;
+            eyre::Ok((path, lines))
         }));
     }
 
+    let mut modules: Vec<(Utf8PathBuf, Vec<String>)> = Vec::with_capacity(handles.len());
     for handle in handles {
-        handle.await??;
+        modules.push(handle.await??);
+    }
+    modules.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for (_, lines) in &modules {
+        for line in lines {
+            print_stdout(line);
+        }
     }
 
     Ok(())
 }
 
-struct Item<'a> {
-    line: usize,
-    column: usize,
-    text: &'a str,
+pub struct Item<'a> {
+    pub line: usize,
+    pub column: usize,
+    pub text: &'a str,
 }
 
-fn query_imports<'a>(source_code: &'a str, tree: &'a Tree) -> eyre::Result<Vec<Item<'a>>> {
+pub fn query_imports<'a>(source_code: &'a str, tree: &'a Tree) -> eyre::Result<Vec<Item<'a>>> {
     query(
         source_code,
         tree,
@@ -135,23 +272,101 @@ fn query_imports<'a>(source_code: &'a str, tree: &'a Tree) -> eyre::Result<Vec<I
     )
 }
 
+/// Names in the module header's export list, or every top-level declaration if it doesn't have one
+pub fn query_exports<'a>(source_code: &'a str, tree: &'a Tree) -> eyre::Result<Vec<Item<'a>>> {
+    let has_export_list = lint::header(tree.root_node())
+        .and_then(|header| header.child_by_field_name("exports"))
+        .is_some();
+
+    if has_export_list {
+        return query(
+            source_code,
+            tree,
+            "
+            (haskell
+              (header
+                exports: (exports
+                  [
+                    (export variable: (_) @export)
+                    (export type: (_) @export)
+                    (export operator: (_) @export)
+                    (module_export) @export
+                  ])))
+            ",
+        );
+    }
+
+    let mut items = Vec::new();
+
+    let Some(declarations) = tree.root_node().child_by_field_name("declarations") else {
+        return Ok(items);
+    };
+
+    let mut seen = HashSet::new();
+    let mut cursor = declarations.walk();
+    for declaration in declarations.named_children(&mut cursor) {
+        for text in haddock::declaration_names(source_code, declaration) {
+            // A type signature and its binding export the same name
+            if !seen.insert(text) {
+                continue;
+            }
+            let position = declaration.start_position();
+            items.push(Item {
+                line: position.row,
+                column: position.column,
+                text,
+            });
+        }
+    }
+
+    Ok(items)
+}
+
 fn query<'a>(source_code: &'a str, tree: &'a Tree, query: &str) -> eyre::Result<Vec<Item<'a>>> {
-    let root_node = tree.root_node();
     let query = tree_sitter::Query::new(&LANGUAGE, query)?;
+    let items = captures(source_code, tree, &query)
+        .into_iter()
+        .map(|capture| Item {
+            line: capture.line,
+            column: capture.column,
+            text: capture.text,
+        })
+        .collect();
+    Ok(items)
+}
+
+pub struct Capture<'a, 'q> {
+    pub line: usize,
+    pub column: usize,
+    /// Like `import` for `@import`
+    pub name: &'q str,
+    pub text: &'a str,
+}
+
+/// Every node a query captures, in the order they match
+pub fn captures<'a, 'q>(
+    source_code: &'a str,
+    tree: &'a Tree,
+    query: &'q tree_sitter::Query,
+) -> Vec<Capture<'a, 'q>> {
+    let root_node = tree.root_node();
+    let capture_names = query.capture_names();
     let mut query_cursor = QueryCursor::new();
-    let mut query_matches = query_cursor.matches(&query, root_node, source_code.as_bytes());
-    let mut items = Vec::with_capacity(query_matches.size_hint().0);
+    let mut query_matches = query_cursor.matches(query, root_node, source_code.as_bytes());
+    let mut captures = Vec::with_capacity(query_matches.size_hint().0);
     while let Some(query_match) = query_matches.next() {
         for match_capture in query_match.captures {
             let node = match_capture.node;
             let range = node.range();
-            let line = range.start_point.row;
-            let column = range.start_point.column;
-            let text = node_text(source_code, &node).unwrap();
-            items.push(Item { line, column, text });
+            captures.push(Capture {
+                line: range.start_point.row,
+                column: range.start_point.column,
+                name: capture_names[match_capture.index as usize],
+                text: node_text(source_code, &node).unwrap(),
+            });
         }
     }
-    Ok(items)
+    captures
 }
 
 fn node_text<'a>(source_code: &'a str, node: &Node) -> Option<&'a str> {
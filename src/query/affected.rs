@@ -0,0 +1,125 @@
+use crate::{
+    cli::query::{AffectedTestsArgs, AffectedTestsFormat},
+    context::cx,
+    events::message,
+    git,
+    io::print_stdout,
+    packages::{self, ComponentKind, Package},
+    query::graph::ModuleGraph,
+};
+use camino::Utf8Path;
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use serde::Serialize;
+use sqlx::sqlite::SqlitePool;
+use std::collections::BTreeSet;
+
+#[derive(Serialize)]
+struct TestSuite<'a> {
+    package: &'a str,
+    name: &'a str,
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn affected_tests(sqlite: &SqlitePool, args: &AffectedTestsArgs) -> eyre::Result<()> {
+    let cx = cx();
+    let packages = cx.cache.packages().await?;
+    let graph = ModuleGraph::load(sqlite).await?;
+
+    let all_test_suites = test_suites(packages, |_| true);
+
+    let changed = if cx.all_files().await? {
+        message!("Config files changed, so every test suite is affected");
+        None
+    } else {
+        Some(git::changed_files(&[]).await?)
+    };
+
+    let affected = match &changed {
+        None => all_test_suites.clone(),
+        Some(changed) => {
+            let changed_modules: BTreeSet<&str> = changed
+                .iter()
+                .filter_map(|path| graph.names.get_key_value(path.as_str()))
+                .map(|(path, _)| path.as_str())
+                .collect();
+
+            let mut paths: BTreeSet<&str> = changed.iter().map(|path| path.as_str()).collect();
+            paths.extend(graph.importers(&changed_modules, true));
+
+            let mut affected = BTreeSet::new();
+            for path in paths {
+                for (package, component) in
+                    packages::components_containing(packages, Utf8Path::new(path))
+                {
+                    if component.kind == ComponentKind::TestSuite {
+                        affected.insert((package.name.as_str(), component.name.as_str()));
+                    }
+                }
+            }
+            // A package's own files can change how every component builds
+            affected.extend(test_suites(packages, |package| {
+                changed.iter().any(|path| is_package_file(package, path))
+            }));
+            affected
+        }
+    };
+
+    match args.format {
+        AffectedTestsFormat::Plain => {
+            for (package, name) in &affected {
+                print_stdout(&format!("{package}:{name}"));
+            }
+        }
+        AffectedTestsFormat::Json => {
+            let test_suites: Vec<TestSuite> = affected
+                .iter()
+                .map(|&(package, name)| TestSuite { package, name })
+                .collect();
+            print_stdout(&serde_json::to_string_pretty(&test_suites)?);
+        }
+        AffectedTestsFormat::Cabal => {
+            for (package, name) in &affected {
+                print_stdout(&format!("{package}:test:{name}"));
+            }
+        }
+    }
+
+    if let Some(changed) = &changed {
+        message!(
+            "{affected_count} of {total_count} test {suites} affected by {changed_count} changed {files}",
+            affected_count = affected.len().to_formatted_string(&Locale::en),
+            total_count = all_test_suites.len().to_formatted_string(&Locale::en),
+            suites = if all_test_suites.len() == 1 {
+                "suite"
+            } else {
+                "suites"
+            },
+            changed_count = changed.len().to_formatted_string(&Locale::en),
+            files = if changed.len() == 1 { "file" } else { "files" },
+        );
+    }
+
+    Ok(())
+}
+
+/// Names of the test suites in packages which match, with their package's name
+fn test_suites(packages: &[Package], matches: impl Fn(&Package) -> bool) -> BTreeSet<(&str, &str)> {
+    packages
+        .iter()
+        .filter(|package| matches(package))
+        .flat_map(|package| {
+            package
+                .components
+                .iter()
+                .filter(|component| component.kind == ComponentKind::TestSuite)
+                .map(|component| (package.name.as_str(), component.name.as_str()))
+        })
+        .collect()
+}
+
+/// Whether a path relative to the git root is a package's `package.yaml` or `.cabal` file
+fn is_package_file(package: &Package, path: &Utf8Path) -> bool {
+    path.parent() == Some(package.dir.as_path())
+        && (path.file_name() == Some("package.yaml") || path.extension() == Some("cabal"))
+}
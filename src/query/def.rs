@@ -0,0 +1,128 @@
+use crate::{
+    cli::query::DefArgs,
+    config::Pipeline,
+    events::message,
+    git,
+    io::{print_stdout, read_file},
+    lint::{haddock, parse},
+};
+use camino::Utf8PathBuf;
+use color_eyre::eyre;
+use globset::{Glob, GlobMatcher};
+use num_format::{Locale, ToFormattedString as _};
+use std::{collections::HashSet, sync::Arc};
+use tree_sitter::{Node, Tree};
+
+/// Print matching definitions as `path:line:column:kind name`, like `src/Foo.hs:12:1:data Config`
+#[tracing::instrument(skip_all)]
+pub async fn def(args: &DefArgs) -> eyre::Result<()> {
+    let matcher = Arc::new(Glob::new(&args.name)?.compile_matcher());
+
+    let paths = if args.changed {
+        git::changed_haskell_files(Pipeline::Lint).await?
+    } else if args.paths.is_empty() && args.packages.is_empty() {
+        git::all_haskell_files(Pipeline::Lint).await?
+    } else {
+        git::haskell_files(&args.paths, &args.packages, Pipeline::Lint).await?
+    };
+
+    let mut handles = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        let matcher = Arc::clone(&matcher);
+        handles.push(tokio::spawn(async move {
+            let (bytes, _) = read_file(&path).await?;
+            let (source_code, tree) = parse(&bytes)?;
+            let lines: Vec<String> = definitions(&source_code, &tree, &matcher)
+                .into_iter()
+                .map(|(line, column, kind, name)| format!("{path}:{line}:{column}:{kind} {name}"))
+                .collect();
+            eyre::Ok((path, lines))
+        }));
+    }
+
+    let mut modules: Vec<(Utf8PathBuf, Vec<String>)> = Vec::with_capacity(handles.len());
+    for handle in handles {
+        modules.push(handle.await??);
+    }
+    modules.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut definition_count = 0;
+    for (_, lines) in &modules {
+        for line in lines {
+            print_stdout(line);
+            definition_count += 1;
+        }
+    }
+
+    message!(
+        "Found {definition_count} {definitions} in {total_count} Haskell {modules}",
+        definition_count = definition_count.to_formatted_string(&Locale::en),
+        definitions = if definition_count == 1 {
+            "definition"
+        } else {
+            "definitions"
+        },
+        total_count = modules.len().to_formatted_string(&Locale::en),
+        modules = if modules.len() == 1 {
+            "module"
+        } else {
+            "modules"
+        },
+    );
+
+    Ok(())
+}
+
+/// Top-level definitions with a matching name, as their 1-based line and column, kind, and name.
+/// A function with several equations is only found once.
+fn definitions<'a>(
+    source_code: &'a str,
+    tree: &'a Tree,
+    matcher: &GlobMatcher,
+) -> Vec<(usize, usize, &'static str, &'a str)> {
+    let mut definitions = Vec::new();
+
+    let Some(declarations) = tree.root_node().child_by_field_name("declarations") else {
+        return definitions;
+    };
+
+    let mut seen = HashSet::new();
+    let mut cursor = declarations.walk();
+    for declaration in declarations.named_children(&mut cursor) {
+        let kind = match declaration.kind() {
+            "function" | "bind" => "function",
+            "data_type" => "data",
+            "newtype" => "newtype",
+            "type_synomym" => "type",
+            "class" => "class",
+            "type_family" => "type family",
+            "data_family" => "data family",
+            _ => continue,
+        };
+        let mut names = haddock::declaration_names(source_code, declaration);
+        if names.is_empty() && kind == "function" {
+            names.extend(infix_name(source_code, declaration));
+        }
+        for name in names {
+            if !matcher.is_match(name) || !seen.insert(name) {
+                continue;
+            }
+            let position = declaration.start_position();
+            definitions.push((position.row + 1, position.column + 1, kind, name));
+        }
+    }
+
+    definitions
+}
+
+/// Like `<+>` in ``a <+> b = …``, or `plus` in ``a `plus` b = …``, which don't have a `name`
+fn infix_name<'a>(source_code: &'a str, function: Node) -> Option<&'a str> {
+    let mut cursor = function.walk();
+    let infix = function
+        .children(&mut cursor)
+        .find(|child| child.kind() == "infix")?;
+    let operator = infix.child_by_field_name("operator")?;
+    let text = source_code.get(operator.byte_range())?;
+    Some(text.trim_matches('`').trim())
+}
@@ -0,0 +1,97 @@
+use crate::{
+    cli::query::ExtensionsArgs,
+    config::Pipeline,
+    context::cx,
+    events::message,
+    git,
+    io::{print_stdout, read_file},
+    lint::parse,
+    utils::table,
+};
+use camino::Utf8PathBuf;
+use color_eyre::eyre;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+/// Print each module's language extensions, then how many modules use each one
+#[tracing::instrument(skip_all)]
+pub async fn extensions(args: &ExtensionsArgs) -> eyre::Result<()> {
+    let cx = cx();
+
+    let paths = if args.paths.is_empty() && args.packages.is_empty() {
+        git::all_haskell_files(Pipeline::Lint).await?
+    } else {
+        git::haskell_files(&args.paths, &args.packages, Pipeline::Lint).await?
+    };
+
+    let defaults: HashSet<&str> = if args.not_in_defaults {
+        let (extensions, _) = cx.cache.fourmolu_extensions().await?;
+        extensions.iter().map(String::as_str).collect()
+    } else {
+        HashSet::new()
+    };
+
+    let mut handles = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        handles.push(tokio::spawn(async move {
+            let (bytes, _) = read_file(&path).await?;
+            eyre::Ok((path, module_extensions(&bytes)?))
+        }));
+    }
+
+    let mut modules: Vec<(Utf8PathBuf, BTreeSet<String>)> = Vec::with_capacity(handles.len());
+    for handle in handles {
+        modules.push(handle.await??);
+    }
+    modules.sort();
+
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for (path, extensions) in &modules {
+        for extension in extensions {
+            if defaults.contains(extension.as_str()) {
+                continue;
+            }
+            print_stdout(&format!("{path}:{extension}"));
+            *counts.entry(extension).or_default() += 1;
+        }
+    }
+
+    // Most used first, since those are the best candidates for default extensions
+    let mut rows: Vec<(&str, usize)> = counts.into_iter().collect();
+    rows.sort_by_key(|(extension, count)| (std::cmp::Reverse(*count), *extension));
+
+    if !rows.is_empty() {
+        message!("{}", table(&rows));
+    }
+
+    Ok(())
+}
+
+/// Extensions in a module's `{-# LANGUAGE #-}` pragmas
+fn module_extensions(bytes: &[u8]) -> eyre::Result<BTreeSet<String>> {
+    let (source_code, tree) = parse(bytes)?;
+    let mut extensions = BTreeSet::new();
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    // File-header pragmas come before the module header
+    for node in root.children(&mut cursor) {
+        if node.kind() != "pragma" {
+            continue;
+        }
+        let Some(pragma) = source_code
+            .get(node.byte_range())
+            .and_then(|text| text.strip_prefix("{-#"))
+            .and_then(|text| text.strip_suffix("#-}"))
+        else {
+            continue;
+        };
+        let mut words = pragma.split(|c: char| c == ',' || c.is_whitespace());
+        let is_language = words
+            .find(|word| !word.is_empty())
+            .is_some_and(|word| word.eq_ignore_ascii_case("LANGUAGE"));
+        if is_language {
+            extensions.extend(words.filter(|word| !word.is_empty()).map(String::from));
+        }
+    }
+    Ok(extensions)
+}
@@ -0,0 +1,478 @@
+use crate::{
+    cli::query::{CyclesArgs, DeadModulesArgs, GraphArgs, GraphFormat, ImportersArgs},
+    context::cx,
+    events::message,
+    exit::Findings,
+    io::print_stdout,
+    packages::{self, Component, ComponentKind},
+};
+use camino::Utf8Path;
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use serde::Serialize;
+use sqlx::sqlite::SqlitePool;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write as _,
+};
+use tokio::fs;
+
+/// The module graph from `be query index`, with modules identified by their path
+pub struct ModuleGraph {
+    /// Module names by path
+    pub names: BTreeMap<String, String>,
+    /// Paths of the modules each module imports
+    pub imports: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl ModuleGraph {
+    #[tracing::instrument(skip_all)]
+    pub async fn load(sqlite: &SqlitePool) -> eyre::Result<Self> {
+        let git_root = cx().cache.git_root().await?;
+
+        let names: BTreeMap<String, String> =
+            sqlx::query_as("select path, name from module_vertices where repo = $1")
+                .bind(git_root.as_str())
+                .fetch_all(sqlite)
+                .await?
+                .into_iter()
+                .collect();
+
+        if names.is_empty() {
+            eyre::bail!("No modules are indexed, run `be query index` first");
+        }
+
+        let edges: Vec<(String, String)> =
+            sqlx::query_as("select source, target from module_edges where repo = $1")
+                .bind(git_root.as_str())
+                .fetch_all(sqlite)
+                .await?;
+
+        let mut imports: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for (source, target) in edges {
+            imports.entry(source).or_default().insert(target);
+        }
+
+        Ok(Self { names, imports })
+    }
+
+    /// Paths of the modules with a name or path, since names aren't unique across packages
+    pub fn resolve(&self, module: &str) -> eyre::Result<BTreeSet<&str>> {
+        let module = module.strip_prefix("./").unwrap_or(module);
+        let paths: BTreeSet<&str> = self
+            .names
+            .iter()
+            .filter(|(path, name)| *path == module || *name == module)
+            .map(|(path, _)| path.as_str())
+            .collect();
+        if paths.is_empty() {
+            eyre::bail!("Module `{module}` isn't indexed");
+        }
+        Ok(paths)
+    }
+
+    /// Paths of the modules which import any of the modules, or import them indirectly
+    pub fn importers(&self, paths: &BTreeSet<&str>, transitive: bool) -> BTreeSet<&str> {
+        let mut importers = BTreeMap::<&str, Vec<&str>>::new();
+        for (source, targets) in &self.imports {
+            for target in targets {
+                importers.entry(target).or_default().push(source);
+            }
+        }
+
+        let mut found = BTreeSet::new();
+        let mut queue: Vec<&str> = paths.iter().copied().collect();
+        while let Some(path) = queue.pop() {
+            for importer in importers.get(path).into_iter().flatten() {
+                if found.insert(*importer) && transitive {
+                    queue.push(importer);
+                }
+            }
+        }
+
+        found
+    }
+
+    /// Paths of the modules and every module they import, directly or indirectly
+    pub fn dependencies<'a>(&'a self, paths: &BTreeSet<&'a str>) -> BTreeSet<&'a str> {
+        let mut found = paths.clone();
+        let mut queue: Vec<&str> = paths.iter().copied().collect();
+        while let Some(path) = queue.pop() {
+            for target in self.imports.get(path).into_iter().flatten() {
+                if found.insert(target) {
+                    queue.push(target);
+                }
+            }
+        }
+        found
+    }
+
+    /// Strongly connected components with more than one module, found with Tarjan's algorithm
+    pub fn cycles(&self) -> Vec<BTreeSet<&str>> {
+        let mut indices = BTreeMap::<&str, usize>::new();
+        let mut low_links = BTreeMap::<&str, usize>::new();
+        let mut stack = Vec::<&str>::new();
+        let mut on_stack = BTreeSet::<&str>::new();
+        let mut cycles = Vec::new();
+
+        for start in self.names.keys() {
+            if indices.contains_key(start.as_str()) {
+                continue;
+            }
+
+            // Iterative, since a deep import chain could overflow the call stack
+            let mut work: Vec<(&str, Vec<&str>)> = Vec::new();
+
+            let index = indices.len();
+            indices.insert(start, index);
+            low_links.insert(start, index);
+            stack.push(start);
+            on_stack.insert(start);
+            work.push((start, self.targets(start)));
+
+            while let Some((path, targets)) = work.last_mut() {
+                let path = *path;
+                if let Some(target) = targets.pop() {
+                    if !indices.contains_key(target) {
+                        let index = indices.len();
+                        indices.insert(target, index);
+                        low_links.insert(target, index);
+                        stack.push(target);
+                        on_stack.insert(target);
+                        work.push((target, self.targets(target)));
+                    } else if on_stack.contains(target) {
+                        let low_link = low_links[path].min(indices[target]);
+                        low_links.insert(path, low_link);
+                    }
+                    continue;
+                }
+
+                work.pop();
+
+                if let Some((parent, _)) = work.last() {
+                    let low_link = low_links[parent].min(low_links[path]);
+                    low_links.insert(parent, low_link);
+                }
+
+                if low_links[path] == indices[path] {
+                    let mut component = BTreeSet::new();
+                    while let Some(member) = stack.pop() {
+                        on_stack.remove(member);
+                        component.insert(member);
+                        if member == path {
+                            break;
+                        }
+                    }
+                    if component.len() > 1 {
+                        cycles.push(component);
+                    }
+                }
+            }
+        }
+
+        cycles
+    }
+
+    fn targets(&self, path: &str) -> Vec<&str> {
+        self.imports
+            .get(path)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn importers(sqlite: &SqlitePool, args: &ImportersArgs) -> eyre::Result<()> {
+    let graph = ModuleGraph::load(sqlite).await?;
+
+    let mut paths = BTreeSet::new();
+    for module in &args.modules {
+        paths.extend(graph.resolve(module)?);
+    }
+
+    for path in graph.importers(&paths, args.transitive) {
+        let name = &graph.names[path];
+        print_stdout(&format!("{path}:{name}"));
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Graph<'a> {
+    modules: Vec<Module<'a>>,
+    imports: Vec<Import<'a>>,
+}
+
+#[derive(Serialize)]
+struct Module<'a> {
+    path: &'a str,
+    name: &'a str,
+}
+
+#[derive(Serialize)]
+struct Import<'a> {
+    source: &'a str,
+    target: &'a str,
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn graph(sqlite: &SqlitePool, args: &GraphArgs) -> eyre::Result<()> {
+    let graph = ModuleGraph::load(sqlite).await?;
+
+    let mut paths: BTreeSet<&str> = if args.roots.is_empty() {
+        graph.names.keys().map(String::as_str).collect()
+    } else {
+        let mut roots = BTreeSet::new();
+        for module in &args.roots {
+            roots.extend(graph.resolve(module)?);
+        }
+        graph.dependencies(&roots)
+    };
+
+    if let Some(prefix) = &args.prefix {
+        let prefix = prefix.strip_prefix("./").unwrap_or(prefix);
+        paths.retain(|path| path.starts_with(prefix));
+    }
+
+    let modules: Vec<Module> = paths
+        .iter()
+        .map(|path| Module {
+            path,
+            name: &graph.names[*path],
+        })
+        .collect();
+
+    let imports: Vec<Import> = paths
+        .iter()
+        .flat_map(|source| {
+            graph
+                .imports
+                .get(*source)
+                .into_iter()
+                .flatten()
+                .filter(|target| paths.contains(target.as_str()))
+                .map(|target| Import { source, target })
+        })
+        .collect();
+
+    let output = match args.format {
+        GraphFormat::Dot => dot(&modules, &imports)?,
+        GraphFormat::Json => serde_json::to_string_pretty(&Graph { modules, imports })?,
+        GraphFormat::Mermaid => mermaid(&modules, &imports)?,
+    };
+
+    print_stdout(&output);
+
+    Ok(())
+}
+
+fn dot(modules: &[Module], imports: &[Import]) -> eyre::Result<String> {
+    let mut output = String::from("digraph modules {\n");
+    for Module { path, name } in modules {
+        writeln!(output, "  {path:?} [label={name:?}];")?;
+    }
+    for Import { source, target } in imports {
+        writeln!(output, "  {source:?} -> {target:?};")?;
+    }
+    output.push('}');
+    Ok(output)
+}
+
+fn mermaid(modules: &[Module], imports: &[Import]) -> eyre::Result<String> {
+    // Mermaid ids can't contain most punctuation, so number the modules instead
+    let ids: BTreeMap<&str, usize> = modules
+        .iter()
+        .enumerate()
+        .map(|(index, module)| (module.path, index))
+        .collect();
+    let mut output = String::from("flowchart LR");
+    for Module { path, name } in modules {
+        write!(output, "\n  m{}[\"{name}\"]", ids[path])?;
+    }
+    for Import { source, target } in imports {
+        write!(output, "\n  m{} --> m{}", ids[source], ids[target])?;
+    }
+    Ok(output)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn cycles(sqlite: &SqlitePool, args: &CyclesArgs) -> eyre::Result<()> {
+    let graph = ModuleGraph::load(sqlite).await?;
+
+    let mut allowed = BTreeSet::new();
+    if let Some(path) = &args.allow_list {
+        for line in fs::read_to_string(path).await?.lines() {
+            let names: BTreeSet<&str> = line.split_whitespace().collect();
+            if !names.is_empty() && !line.trim_start().starts_with('#') {
+                allowed.insert(names.into_iter().map(String::from).collect::<Vec<_>>());
+            }
+        }
+    }
+
+    let mut cycles: Vec<Vec<&str>> = graph
+        .cycles()
+        .into_iter()
+        .map(|paths| {
+            let names: BTreeSet<&str> = paths
+                .into_iter()
+                .map(|path| graph.names[path].as_str())
+                .collect();
+            names.into_iter().collect()
+        })
+        .collect();
+
+    cycles.sort();
+
+    let mut found_count = 0;
+
+    for cycle in &cycles {
+        if allowed.iter().any(|names| names == cycle) {
+            tracing::debug!("Ignoring allowed cycle: {}", cycle.join(" "));
+            continue;
+        }
+        print_stdout(&cycle.join(" "));
+        found_count += 1;
+    }
+
+    let allowed_count = cycles.len() - found_count;
+
+    if allowed_count > 0 {
+        message!(
+            "Ignored {allowed_count} allowed import {cycles}",
+            allowed_count = allowed_count.to_formatted_string(&Locale::en),
+            cycles = if allowed_count == 1 {
+                "cycle"
+            } else {
+                "cycles"
+            },
+        );
+    }
+
+    if found_count > 0 {
+        return Err(Findings(format!(
+            "Found {found_count} import {cycles}",
+            found_count = found_count.to_formatted_string(&Locale::en),
+            cycles = if found_count == 1 { "cycle" } else { "cycles" },
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn dead_modules(sqlite: &SqlitePool, args: &DeadModulesArgs) -> eyre::Result<()> {
+    let graph = ModuleGraph::load(sqlite).await?;
+    let packages = cx().cache.packages().await?;
+
+    let mut roots = BTreeSet::new();
+    for module in &args.roots {
+        roots.extend(graph.resolve(module)?);
+    }
+    for package in packages {
+        for component in &package.components {
+            roots.extend(component_roots(&graph, component));
+        }
+    }
+
+    let reachable = graph.dependencies(&roots);
+
+    let mut found_count = 0;
+
+    for (path, name) in &graph.names {
+        // `Main` modules can't be imported, and ones outside of packages might be scripts
+        if reachable.contains(path.as_str())
+            || name == "Main"
+            || packages::components_containing(packages, Utf8Path::new(path)).is_empty()
+        {
+            continue;
+        }
+        print_stdout(&format!("{path}:{name}"));
+        found_count += 1;
+    }
+
+    if found_count > 0 {
+        return Err(Findings(format!(
+            "Found {found_count} unreachable {modules} of {total_count} indexed modules",
+            found_count = found_count.to_formatted_string(&Locale::en),
+            modules = if found_count == 1 {
+                "module"
+            } else {
+                "modules"
+            },
+            total_count = graph.names.len().to_formatted_string(&Locale::en),
+        ))
+        .into());
+    }
+
+    message!(
+        "Found no unreachable modules of {total_count} indexed modules",
+        total_count = graph.names.len().to_formatted_string(&Locale::en),
+    );
+
+    Ok(())
+}
+
+/// Paths of the modules a component starts from: the modules a library exposes, or the `main` of
+/// anything else. Every module in its source directories when it's unknown.
+fn component_roots<'a>(graph: &'a ModuleGraph, component: &Component) -> BTreeSet<&'a str> {
+    let in_component = |path: &str| {
+        let path = Utf8Path::new(path);
+        component.source_dirs.iter().any(|dir| {
+            let module_path = if dir == "." {
+                Some(path)
+            } else {
+                path.strip_prefix(dir).ok()
+            };
+            module_path.and_then(packages::module_name).is_some()
+        })
+    };
+
+    let named = |modules: &[String]| -> BTreeSet<&'a str> {
+        graph
+            .names
+            .iter()
+            .filter(|(path, name)| modules.contains(name) && in_component(path))
+            .map(|(path, _)| path.as_str())
+            .collect()
+    };
+
+    let exposed = match component.kind {
+        ComponentKind::Library => component.exposed_modules.as_deref().map(named),
+        ComponentKind::Executable | ComponentKind::TestSuite | ComponentKind::Benchmark => {
+            component.main.as_deref().map(|main| {
+                let is_path = Utf8Path::new(main)
+                    .extension()
+                    .is_some_and(|extension| matches!(extension, "hs" | "lhs" | "hsc"));
+                if !is_path {
+                    return named(&[String::from(main)]);
+                }
+                component
+                    .source_dirs
+                    .iter()
+                    .map(|dir| {
+                        if dir == "." {
+                            Utf8Path::new(main).to_path_buf()
+                        } else {
+                            dir.join(main)
+                        }
+                    })
+                    .filter_map(|path| graph.names.get_key_value(path.as_str()))
+                    .map(|(path, _)| path.as_str())
+                    .collect()
+            })
+        }
+    };
+
+    exposed.unwrap_or_else(|| {
+        graph
+            .names
+            .keys()
+            .filter(|path| in_component(path))
+            .map(String::as_str)
+            .collect()
+    })
+}
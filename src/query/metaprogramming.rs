@@ -0,0 +1,148 @@
+use crate::{
+    cli::query::UsageArgs,
+    config::Pipeline,
+    events::message,
+    git,
+    io::{print_stdout, read_file},
+    lint::parse,
+};
+use camino::Utf8PathBuf;
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use tree_sitter::{Node, Tree};
+
+/// Where a module uses Template Haskell or CPP
+struct Usage {
+    line: usize,
+    column: usize,
+    /// The first line of it, like `#if MIN_VERSION_base(4,18,0)`
+    text: String,
+}
+
+/// Print Template Haskell splices and quasiquotes, as `path:line:column:text`
+#[tracing::instrument(skip_all)]
+pub async fn th(args: &UsageArgs) -> eyre::Result<()> {
+    report(args, ["splice", "splices"], th_usages).await
+}
+
+/// Print CPP conditionals, as `path:line:column:text`
+#[tracing::instrument(skip_all)]
+pub async fn cpp(args: &UsageArgs) -> eyre::Result<()> {
+    report(args, ["CPP conditional", "CPP conditionals"], cpp_usages).await
+}
+
+async fn report(
+    args: &UsageArgs,
+    [singular, plural]: [&str; 2],
+    usages: fn(&str, &Tree) -> Vec<Usage>,
+) -> eyre::Result<()> {
+    let paths = if args.paths.is_empty() && args.packages.is_empty() {
+        git::all_haskell_files(Pipeline::Lint).await?
+    } else {
+        git::haskell_files(&args.paths, &args.packages, Pipeline::Lint).await?
+    };
+
+    let mut handles = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        handles.push(tokio::spawn(async move {
+            let (bytes, _) = read_file(&path).await?;
+            let (source_code, tree) = parse(&bytes)?;
+            eyre::Ok((path, usages(&source_code, &tree)))
+        }));
+    }
+
+    let mut modules: Vec<(Utf8PathBuf, Vec<Usage>)> = Vec::with_capacity(handles.len());
+    for handle in handles {
+        modules.push(handle.await??);
+    }
+    modules.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut usage_count = 0;
+    let mut module_count = 0;
+    for (path, usages) in &modules {
+        if !usages.is_empty() {
+            module_count += 1;
+        }
+        for usage in usages {
+            print_stdout(&format!(
+                "{path}:{}:{}:{}",
+                usage.line, usage.column, usage.text
+            ));
+            usage_count += 1;
+        }
+    }
+
+    message!(
+        "Found {usage_count} {what} in {module_count} of {total_count} Haskell {modules}",
+        usage_count = usage_count.to_formatted_string(&Locale::en),
+        what = if usage_count == 1 { singular } else { plural },
+        module_count = module_count.to_formatted_string(&Locale::en),
+        total_count = modules.len().to_formatted_string(&Locale::en),
+        modules = if modules.len() == 1 {
+            "module"
+        } else {
+            "modules"
+        },
+    );
+
+    Ok(())
+}
+
+/// Splices, including top-level ones like `makeLenses ''Foo`, and quasiquotes. Ones inside
+/// another aren't counted again.
+fn th_usages(source_code: &str, tree: &Tree) -> Vec<Usage> {
+    let mut usages = Vec::new();
+    let mut nodes = vec![tree.root_node()];
+    while let Some(node) = nodes.pop() {
+        if matches!(node.kind(), "top_splice" | "splice" | "quasiquote") {
+            usages.push(usage(source_code, node));
+            continue;
+        }
+        let mut cursor = node.walk();
+        nodes.extend(node.children(&mut cursor));
+    }
+    usages.sort_by_key(|usage| (usage.line, usage.column));
+    usages
+}
+
+/// `#if`, `#ifdef`, `#ifndef`, and `#elif` directives, but not the `#else` and `#endif` which
+/// close them
+fn cpp_usages(source_code: &str, tree: &Tree) -> Vec<Usage> {
+    let mut usages = Vec::new();
+    let mut nodes = vec![tree.root_node()];
+    while let Some(node) = nodes.pop() {
+        if node.kind() == "cpp" {
+            let usage = usage(source_code, node);
+            let directive = usage
+                .text
+                .trim_start_matches('#')
+                .trim_start()
+                .split(|c: char| !c.is_ascii_alphabetic())
+                .next()
+                .unwrap_or_default();
+            if matches!(directive, "if" | "ifdef" | "ifndef" | "elif") {
+                usages.push(usage);
+            }
+            continue;
+        }
+        let mut cursor = node.walk();
+        nodes.extend(node.children(&mut cursor));
+    }
+    usages.sort_by_key(|usage| (usage.line, usage.column));
+    usages
+}
+
+fn usage(source_code: &str, node: Node) -> Usage {
+    let position = node.start_position();
+    let text = source_code
+        .get(node.byte_range())
+        .and_then(|text| text.lines().next())
+        .unwrap_or_default()
+        .trim();
+    Usage {
+        line: position.row + 1,
+        column: position.column + 1,
+        text: String::from(text),
+    }
+}
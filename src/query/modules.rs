@@ -0,0 +1,200 @@
+use crate::{
+    config::Pipeline,
+    context::cx,
+    events::{Outcome, message},
+    git,
+    io::read_file,
+    lint::{self, parse},
+    query::{self, Item},
+    queue,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use sqlx::sqlite::SqlitePool;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+/// A module which changed since it was last indexed
+struct Module {
+    path: String,
+    name: String,
+    source_hash: u64,
+    imports: Vec<String>,
+}
+
+impl Outcome for (String, Option<Module>) {
+    fn outcome(&self) -> &'static str {
+        if self.1.is_some() {
+            "indexed"
+        } else {
+            "cached"
+        }
+    }
+}
+
+/// Add every module in the repo to the `module_vertices` table, and every import between them to
+/// the `module_edges` table, parsing only modules which changed since they were last indexed
+#[tracing::instrument(skip_all)]
+pub async fn index(sqlite: &SqlitePool) -> eyre::Result<()> {
+    let cx = cx();
+
+    let git_root = cx.cache.git_root().await?;
+
+    let indexed: HashMap<String, String> =
+        sqlx::query_as("select path, source_hash from module_vertices where repo = $1")
+            .bind(git_root.as_str())
+            .fetch_all(sqlite)
+            .await?
+            .into_iter()
+            .collect();
+
+    let indexed = Arc::new(indexed);
+
+    let paths = git::all_haskell_files(Pipeline::Lint).await?;
+
+    let handles = queue::spawn(paths, |path| {
+        let indexed = indexed.clone();
+        async move { parse_module(&path, &indexed).await }
+    })
+    .await?;
+
+    let file_count = handles.len();
+    let mut parsed_count = 0;
+    let mut paths = HashSet::with_capacity(file_count);
+
+    let mut transaction = sqlite.begin().await?;
+
+    // Any module's name could change, so every import is resolved again after updating modules
+    sqlx::query("delete from module_edges where repo = $1")
+        .bind(git_root.as_str())
+        .execute(&mut *transaction)
+        .await?;
+
+    for handle in handles {
+        let (path, module) = handle.await??;
+        paths.insert(path);
+        let Some(module) = module else {
+            continue;
+        };
+        sqlx::query("delete from module_imports where repo = $1 and path = $2")
+            .bind(git_root.as_str())
+            .bind(&module.path)
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query("insert or replace into module_vertices values ($1, $2, $3, $4)")
+            .bind(git_root.as_str())
+            .bind(&module.path)
+            .bind(&module.name)
+            .bind(module.source_hash.to_string())
+            .execute(&mut *transaction)
+            .await?;
+        for import in &module.imports {
+            sqlx::query("insert or ignore into module_imports values ($1, $2, $3)")
+                .bind(git_root.as_str())
+                .bind(&module.path)
+                .bind(import)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        parsed_count += 1;
+    }
+
+    // Forget modules which were deleted, or are now excluded
+    for path in indexed.keys().filter(|path| !paths.contains(*path)) {
+        for table in ["module_imports", "module_vertices"] {
+            sqlx::query(&format!(
+                "delete from {table} where repo = $1 and path = $2"
+            ))
+            .bind(git_root.as_str())
+            .bind(path)
+            .execute(&mut *transaction)
+            .await?;
+        }
+    }
+
+    sqlx::query(
+        "
+        insert or ignore into module_edges
+        select imports.repo, imports.path, vertices.path
+        from module_imports as imports
+        join module_vertices as vertices
+          on vertices.repo = imports.repo
+          and vertices.name = imports.name
+        where imports.repo = $1
+          and imports.path != vertices.path
+        ",
+    )
+    .bind(git_root.as_str())
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    message!(
+        "Indexed {parsed_count} of {file_count} Haskell {modules}",
+        parsed_count = parsed_count.to_formatted_string(&Locale::en),
+        file_count = file_count.to_formatted_string(&Locale::en),
+        modules = if file_count == 1 { "module" } else { "modules" },
+    );
+
+    Ok(())
+}
+
+/// Paths of every indexed module relative to the git root, by module name. Packages can each have
+/// a module with the same name.
+pub async fn paths_by_name(sqlite: &SqlitePool) -> eyre::Result<HashMap<String, Vec<String>>> {
+    let git_root = cx().cache.git_root().await?;
+
+    let rows: Vec<(String, String)> =
+        sqlx::query_as("select name, path from module_vertices where repo = $1")
+            .bind(git_root.as_str())
+            .fetch_all(sqlite)
+            .await?;
+
+    let mut paths: HashMap<String, Vec<String>> = HashMap::new();
+    for (name, path) in rows {
+        paths.entry(name).or_default().push(path);
+    }
+
+    Ok(paths)
+}
+
+/// The module's path relative to the git root, and what it imports if it changed
+async fn parse_module(
+    path: &Utf8Path,
+    indexed: &HashMap<String, String>,
+) -> eyre::Result<(String, Option<Module>)> {
+    let git_root = cx().cache.git_root().await?;
+    let absolute_path = Utf8PathBuf::try_from(std::path::absolute(path)?)?;
+    let relative_path = absolute_path
+        .strip_prefix(git_root)
+        .unwrap_or(&absolute_path)
+        .to_string();
+
+    let (bytes, source_hash) = read_file(path).await?;
+
+    if indexed.get(&relative_path) == Some(&source_hash.to_string()) {
+        return Ok((relative_path, None));
+    }
+
+    let (source_code, tree) = parse(&bytes)?;
+
+    let name = lint::header_module(&source_code, tree.root_node()).unwrap_or("Main");
+
+    let imports = query::query_imports(&source_code, &tree)?
+        .into_iter()
+        .map(|Item { text, .. }| String::from(text))
+        .collect();
+
+    let module = Module {
+        path: relative_path.clone(),
+        name: String::from(name),
+        source_hash,
+        imports,
+    };
+
+    Ok((relative_path, Some(module)))
+}
@@ -0,0 +1,200 @@
+use crate::{
+    cli::query::NixInputsArgs,
+    config::Pipeline,
+    events::message,
+    git,
+    io::{print_stdout, read_file},
+    query::{Item, NIX_LANGUAGE, node_text},
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use std::str;
+use tree_sitter::{Node, Parser, Tree};
+
+#[tracing::instrument(skip_all)]
+pub async fn run_query_nix_inputs(args: &NixInputsArgs) -> eyre::Result<()> {
+    let paths = if args.paths.is_empty() && args.packages.is_empty() {
+        git::changed_nix_files(Pipeline::Lint).await?
+    } else {
+        git::nix_files(&args.paths, &args.packages, Pipeline::Lint).await?
+    };
+
+    let mut handles = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        handles.push(tokio::spawn(async move {
+            let (bytes, _) = read_file(&path).await?;
+            let source_code = str::from_utf8(&bytes)?;
+            let mut parser = Parser::new();
+            parser.set_language(&NIX_LANGUAGE)?;
+            let tree = parser.parse(source_code, None).unwrap();
+            let lines: Vec<String> = query_nix_inputs(&path, source_code, &tree)
+                .into_iter()
+                .map(|(reference, Item { line, column, text })| {
+                    let text = text.lines().next().unwrap_or_default();
+                    format!("{path}:{line}:{column}:{reference}:{text}")
+                })
+                .collect();
+            eyre::Ok((path, lines))
+        }));
+    }
+
+    let mut files: Vec<(Utf8PathBuf, Vec<String>)> = Vec::with_capacity(handles.len());
+    for handle in handles {
+        files.push(handle.await??);
+    }
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut reference_count = 0;
+    let mut file_count = 0;
+    for (_, lines) in &files {
+        if !lines.is_empty() {
+            file_count += 1;
+        }
+        for line in lines {
+            print_stdout(line);
+            reference_count += 1;
+        }
+    }
+
+    message!(
+        "Found {reference_count} {references} in {file_count} of {total_count} Nix {files}",
+        reference_count = reference_count.to_formatted_string(&Locale::en),
+        references = if reference_count == 1 {
+            "reference"
+        } else {
+            "references"
+        },
+        file_count = file_count.to_formatted_string(&Locale::en),
+        total_count = files.len().to_formatted_string(&Locale::en),
+        files = if files.len() == 1 { "file" } else { "files" },
+    );
+
+    Ok(())
+}
+
+/// What each `import` and `fetch*` call refers to, like `builtins.fetchGit` and its `url`, and
+/// the `url` and `follows` of each input in a `flake.nix`
+fn query_nix_inputs<'a>(
+    path: &Utf8Path,
+    source_code: &'a str,
+    tree: &'a Tree,
+) -> Vec<(String, Item<'a>)> {
+    let mut references = Vec::new();
+
+    if path.file_name() == Some("flake.nix")
+        && let Some(expression) = tree.root_node().child_by_field_name("expression")
+    {
+        for (attrs, expression) in bindings(source_code, expression) {
+            if attrs.first() == Some(&"inputs") {
+                flake_inputs(source_code, &attrs, expression, &mut references);
+            }
+        }
+    }
+
+    let mut nodes = vec![tree.root_node()];
+    while let Some(node) = nodes.pop() {
+        let mut cursor = node.walk();
+        nodes.extend(node.children(&mut cursor));
+        if node.kind() != "apply_expression" {
+            continue;
+        }
+        let (Some(function), Some(argument)) = (
+            node.child_by_field_name("function"),
+            node.child_by_field_name("argument"),
+        ) else {
+            continue;
+        };
+        // Like `fetchGit` in `builtins.fetchGit`
+        let name = match function.kind() {
+            "variable_expression" => function.child_by_field_name("name"),
+            "select_expression" => function
+                .child_by_field_name("attrpath")
+                .and_then(|attrpath| attrpath.named_child(attrpath.named_child_count() - 1)),
+            _ => None,
+        };
+        let Some(name) = name.and_then(|name| node_text(source_code, &name)) else {
+            continue;
+        };
+        if name != "import" && !name.starts_with("fetch") {
+            continue;
+        }
+        // The URL says more than the rest of its arguments
+        let target = bindings(source_code, argument)
+            .into_iter()
+            .find_map(|(attrs, expression)| (attrs == ["url"]).then_some(expression))
+            .unwrap_or(argument);
+        if let (Some(reference), Some(item)) =
+            (node_text(source_code, &function), item(source_code, target))
+        {
+            references.push((String::from(reference), item));
+        }
+    }
+
+    references.sort_by_key(|(_, item)| (item.line, item.column));
+    references
+}
+
+/// Bindings like `inputs.nixpkgs.url = "…"`, or ones nested in `inputs = { … }`, as the whole
+/// attribute path
+fn flake_inputs<'a>(
+    source_code: &'a str,
+    attrs: &[&str],
+    expression: Node,
+    references: &mut Vec<(String, Item<'a>)>,
+) {
+    if attrs.len() > 2 && matches!(attrs.last(), Some(&"url" | &"follows")) {
+        if let Some(item) = item(source_code, expression) {
+            references.push((attrs.join("."), item));
+        }
+        return;
+    }
+    for (nested, expression) in bindings(source_code, expression) {
+        let mut attrs = attrs.to_vec();
+        attrs.extend(nested);
+        flake_inputs(source_code, &attrs, expression, references);
+    }
+}
+
+/// The attribute paths and values of an attribute set's bindings, or nothing if it isn't one
+fn bindings<'a, 'tree>(
+    source_code: &'a str,
+    node: Node<'tree>,
+) -> Vec<(Vec<&'a str>, Node<'tree>)> {
+    if !matches!(node.kind(), "attrset_expression" | "rec_attrset_expression") {
+        return Vec::new();
+    }
+    let mut bindings = Vec::new();
+    let mut cursor = node.walk();
+    for binding_set in node.named_children(&mut cursor) {
+        if binding_set.kind() != "binding_set" {
+            continue;
+        }
+        let mut cursor = binding_set.walk();
+        for binding in binding_set.named_children(&mut cursor) {
+            let (Some(attrpath), Some(expression)) = (
+                binding.child_by_field_name("attrpath"),
+                binding.child_by_field_name("expression"),
+            ) else {
+                continue;
+            };
+            let mut cursor = attrpath.walk();
+            let attrs = attrpath
+                .children_by_field_name("attr", &mut cursor)
+                .filter_map(|attr| node_text(source_code, &attr))
+                .collect();
+            bindings.push((attrs, expression));
+        }
+    }
+    bindings
+}
+
+fn item<'a>(source_code: &'a str, node: Node) -> Option<Item<'a>> {
+    let position = node.start_position();
+    Some(Item {
+        line: position.row,
+        column: position.column,
+        text: node_text(source_code, &node)?,
+    })
+}
@@ -0,0 +1,260 @@
+use crate::{context::cx, exec::exec, packages::Package};
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre;
+use derive_more::Display;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env, str,
+};
+use tokio::fs;
+
+/// Where an imported module comes from
+#[derive(Display)]
+pub enum Origin<'a> {
+    /// Module in one of the repo's own packages
+    #[display("local:{_0}")]
+    Local(&'a str),
+    /// Module exposed by dependencies in the cabal build plan
+    #[display("external:{}", _0.iter().map(String::as_str).collect::<Vec<_>>().join(","))]
+    External(&'a BTreeSet<String>),
+    #[display("unknown")]
+    Unknown,
+}
+
+/// Package names for module names, from local packages and the cabal build plan
+pub struct Origins {
+    local: BTreeMap<String, String>,
+    external: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Origins {
+    #[tracing::instrument]
+    pub async fn load() -> eyre::Result<Self> {
+        let cx = cx();
+        let git_root = cx.cache.git_root().await?;
+        let packages = cx.cache.packages().await?;
+        let local = local_modules(git_root, packages).await?;
+        let external = match external_modules(git_root).await {
+            Ok(external) => external,
+            Err(error) => {
+                tracing::warn!("Failed to read cabal build plan: {error}");
+                BTreeMap::new()
+            }
+        };
+        Ok(Self { local, external })
+    }
+
+    pub fn resolve(&self, module: &str) -> Origin<'_> {
+        if let Some(package) = self.local.get(module) {
+            Origin::Local(package)
+        } else if let Some(packages) = self.external.get(module) {
+            Origin::External(packages)
+        } else {
+            Origin::Unknown
+        }
+    }
+}
+
+/// Module names of files under each package's source directories
+async fn local_modules(
+    git_root: &Utf8Path,
+    packages: &[Package],
+) -> eyre::Result<BTreeMap<String, String>> {
+    let git = cx().cache.which("git").await?;
+
+    let stdout = exec(
+        git,
+        [
+            "-C",
+            git_root.as_str(),
+            "ls-files",
+            "--cached",
+            "--others",
+            "--exclude-standard",
+            "--",
+            ":(glob)**/*.hs",
+            ":(glob)**/*.lhs",
+            ":(glob)**/*.hsc",
+        ],
+    )
+    .await?;
+
+    let mut modules = BTreeMap::new();
+
+    for path in str::from_utf8(&stdout)?.lines().map(Utf8Path::new) {
+        for package in packages {
+            let source_dirs = package
+                .components
+                .iter()
+                .flat_map(|component| &component.source_dirs);
+            for source_dir in source_dirs {
+                let relative = if source_dir == "." {
+                    Some(path)
+                } else {
+                    path.strip_prefix(source_dir).ok()
+                };
+                if let Some(module) = relative.and_then(module_name) {
+                    modules
+                        .entry(module)
+                        .or_insert_with(|| package.name.clone());
+                }
+            }
+        }
+    }
+
+    Ok(modules)
+}
+
+/// `Data/Map/Strict.hs` to `Data.Map.Strict`
+fn module_name(path: &Utf8Path) -> Option<String> {
+    let path = path.with_extension("");
+    let mut components = Vec::new();
+    for component in path.components() {
+        let component = component.as_str();
+        if !component.starts_with(|c: char| c.is_ascii_uppercase()) {
+            return None;
+        }
+        components.push(component);
+    }
+    (!components.is_empty()).then(|| components.join("."))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Plan {
+    compiler_id: String,
+    install_plan: Vec<PlanUnit>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct PlanUnit {
+    r#type: String,
+    id: String,
+    pkg_name: String,
+    style: Option<String>,
+}
+
+/// Exposed modules of every non-local unit in `dist-newstyle/cache/plan.json`, read from the
+/// package databases of the cabal store and GHC's global package database
+async fn external_modules(git_root: &Utf8Path) -> eyre::Result<BTreeMap<String, BTreeSet<String>>> {
+    let mut modules = BTreeMap::<String, BTreeSet<String>>::new();
+
+    let plan_path = git_root.join("dist-newstyle/cache/plan.json");
+
+    if !fs::try_exists(&plan_path).await? {
+        tracing::debug!("No cabal build plan at `{plan_path}`");
+        return Ok(modules);
+    }
+
+    let plan: Plan = serde_json::from_slice(&fs::read(&plan_path).await?)?;
+
+    let store_dbs = store_package_dbs(&plan.compiler_id).await;
+
+    let mut pre_existing = BTreeSet::new();
+
+    for unit in &plan.install_plan {
+        match (unit.r#type.as_str(), unit.style.as_deref()) {
+            (_, Some("local")) => {}
+            ("pre-existing", _) => {
+                pre_existing.insert(unit.pkg_name.as_str());
+            }
+            _ => {
+                for db in &store_dbs {
+                    let Ok(conf) = fs::read_to_string(db.join(format!("{}.conf", unit.id))).await
+                    else {
+                        continue;
+                    };
+                    for module in exposed_modules(&conf) {
+                        modules
+                            .entry(module)
+                            .or_default()
+                            .insert(unit.pkg_name.clone());
+                    }
+                    break;
+                }
+            }
+        }
+    }
+
+    if !pre_existing.is_empty() {
+        let ghc_pkg = cx().cache.which("ghc-pkg").await?;
+        let stdout = exec(ghc_pkg, ["dump", "--global", "--expand-pkgroot"]).await?;
+        for conf in str::from_utf8(&stdout)?.split("\n---\n") {
+            let Some(name) = conf_field(conf, "name").next() else {
+                continue;
+            };
+            if !pre_existing.contains(name) {
+                continue;
+            }
+            for module in exposed_modules(conf) {
+                modules
+                    .entry(module)
+                    .or_default()
+                    .insert(String::from(name));
+            }
+        }
+    }
+
+    Ok(modules)
+}
+
+/// Package databases in the cabal store for this compiler, like
+/// `~/.local/state/cabal/store/ghc-9.6.7/package.db`
+async fn store_package_dbs(compiler_id: &str) -> Vec<Utf8PathBuf> {
+    let mut stores = Vec::new();
+    if let Some(cabal_dir) = env::var_os("CABAL_DIR") {
+        stores.push(Utf8PathBuf::from(cabal_dir.to_string_lossy().as_ref()).join("store"));
+    }
+    if let Some(home) = env::var_os("HOME") {
+        let home = Utf8PathBuf::from(home.to_string_lossy().as_ref());
+        let state = env::var_os("XDG_STATE_HOME").map_or_else(
+            || home.join(".local/state"),
+            |state| Utf8PathBuf::from(state.to_string_lossy().as_ref()),
+        );
+        stores.push(state.join("cabal/store"));
+        stores.push(home.join(".cabal/store"));
+    }
+
+    let mut dbs = Vec::new();
+
+    for store in stores {
+        let Ok(mut entries) = fs::read_dir(&store).await else {
+            continue;
+        };
+        // Newer versions of cabal add an ABI hash suffix, like `ghc-9.6.7-3d5c`
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name == compiler_id || name.starts_with(&format!("{compiler_id}-")) {
+                dbs.push(store.join(name.as_ref()).join("package.db"));
+            }
+        }
+    }
+
+    dbs
+}
+
+fn exposed_modules(conf: &str) -> impl Iterator<Item = String> {
+    conf_field(conf, "exposed-modules").map(String::from)
+}
+
+/// Values of a field in `ghc-pkg` package description syntax, where values may continue on
+/// indented lines
+fn conf_field<'a>(conf: &'a str, field: &'a str) -> impl Iterator<Item = &'a str> {
+    let mut in_field = false;
+    conf.lines()
+        .filter_map(move |line| {
+            if line.starts_with(char::is_whitespace) {
+                in_field.then_some(line)
+            } else {
+                in_field = false;
+                let (key, value) = line.split_once(':')?;
+                in_field = key.trim() == field;
+                in_field.then_some(value)
+            }
+        })
+        .flat_map(|value| value.split(|c: char| c == ',' || c.is_whitespace()))
+        .filter(|value| !value.is_empty())
+}
@@ -0,0 +1,151 @@
+use crate::{
+    cli::query::OrphansArgs,
+    config::Pipeline,
+    events::message,
+    git,
+    io::{print_stdout, read_file},
+    lint::parse,
+};
+use camino::Utf8PathBuf;
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use std::collections::HashSet;
+use tree_sitter::{Node, Tree};
+
+/// An instance declaration which defines neither its class nor any of its types
+struct Orphan {
+    line: usize,
+    column: usize,
+    /// Like `Show Int`
+    head: String,
+}
+
+/// Print probable orphan instances, as `path:line:column:head`
+#[tracing::instrument(skip_all)]
+pub async fn orphans(args: &OrphansArgs) -> eyre::Result<()> {
+    let paths = if args.paths.is_empty() && args.packages.is_empty() {
+        git::all_haskell_files(Pipeline::Lint).await?
+    } else {
+        git::haskell_files(&args.paths, &args.packages, Pipeline::Lint).await?
+    };
+
+    let mut handles = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        handles.push(tokio::spawn(async move {
+            let (bytes, _) = read_file(&path).await?;
+            let (source_code, tree) = parse(&bytes)?;
+            eyre::Ok((path, module_orphans(&source_code, &tree)))
+        }));
+    }
+
+    let mut modules: Vec<(Utf8PathBuf, Vec<Orphan>)> = Vec::with_capacity(handles.len());
+    for handle in handles {
+        modules.push(handle.await??);
+    }
+    modules.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut orphan_count = 0;
+    let mut module_count = 0;
+    for (path, orphans) in &modules {
+        if !orphans.is_empty() {
+            module_count += 1;
+        }
+        for orphan in orphans {
+            print_stdout(&format!(
+                "{path}:{}:{}:{}",
+                orphan.line, orphan.column, orphan.head
+            ));
+            orphan_count += 1;
+        }
+    }
+
+    message!(
+        "Found {orphan_count} probable orphan {instances} in {module_count} of {total_count} \
+         Haskell {modules}",
+        orphan_count = orphan_count.to_formatted_string(&Locale::en),
+        instances = if orphan_count == 1 {
+            "instance"
+        } else {
+            "instances"
+        },
+        module_count = module_count.to_formatted_string(&Locale::en),
+        total_count = modules.len().to_formatted_string(&Locale::en),
+        modules = if modules.len() == 1 {
+            "module"
+        } else {
+            "modules"
+        },
+    );
+
+    Ok(())
+}
+
+/// Whether a module has any probable orphan instances
+pub fn has_orphans(source_code: &str, tree: &Tree) -> bool {
+    !module_orphans(source_code, tree).is_empty()
+}
+
+/// Instances and standalone deriving declarations whose class and types aren't declared in the
+/// module. Without resolving imports, a type declared elsewhere under the same name isn't noticed.
+fn module_orphans(source_code: &str, tree: &Tree) -> Vec<Orphan> {
+    let mut orphans = Vec::new();
+
+    let Some(declarations) = tree.root_node().child_by_field_name("declarations") else {
+        return orphans;
+    };
+
+    let text = |node: Node| source_code.get(node.byte_range()).unwrap_or_default();
+
+    let mut local = HashSet::new();
+    let mut cursor = declarations.walk();
+    for declaration in declarations.named_children(&mut cursor) {
+        if matches!(
+            declaration.kind(),
+            "data_type" | "newtype" | "class" | "type_synomym" | "type_family" | "data_family"
+        ) && let Some(name) = declaration.child_by_field_name("name")
+        {
+            local.insert(text(name));
+        }
+    }
+
+    let mut cursor = declarations.walk();
+    for declaration in declarations.named_children(&mut cursor) {
+        if !matches!(declaration.kind(), "instance" | "deriving_instance") {
+            continue;
+        }
+        let Some(class) = declaration.child_by_field_name("name") else {
+            continue;
+        };
+        let patterns = declaration.child_by_field_name("patterns");
+        // Qualified names are imported, so only unqualified ones can be local
+        let is_local = |node: Node| node.kind() == "name" && local.contains(text(node));
+        if is_local(class) || patterns.is_some_and(|patterns| any_node(patterns, &is_local)) {
+            continue;
+        }
+        let position = declaration.start_position();
+        let head = match patterns {
+            Some(patterns) => format!("{} {}", text(class), text(patterns)),
+            None => String::from(text(class)),
+        };
+        orphans.push(Orphan {
+            line: position.row + 1,
+            column: position.column + 1,
+            head: head.split_whitespace().collect::<Vec<_>>().join(" "),
+        });
+    }
+
+    orphans
+}
+
+fn any_node(node: Node, predicate: &impl Fn(Node) -> bool) -> bool {
+    if node.kind() == "qualified" {
+        return false;
+    }
+    if predicate(node) {
+        return true;
+    }
+    let mut cursor = node.walk();
+    node.children(&mut cursor)
+        .any(|child| any_node(child, predicate))
+}
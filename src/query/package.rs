@@ -0,0 +1,81 @@
+use crate::{
+    cli::query::PackageArgs,
+    context::cx,
+    io::print_stdout,
+    packages::{self, Component, Package},
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre;
+use tokio::fs;
+
+/// Print each module's components as `module:package:kind:component`, like the targets `cabal`
+/// takes
+#[tracing::instrument(skip_all)]
+pub async fn package(args: &PackageArgs) -> eyre::Result<()> {
+    let cx = cx();
+    let git_root = cx.cache.git_root().await?;
+    let packages = cx.cache.packages().await?;
+
+    for module in &args.modules {
+        let components = if is_path(module) {
+            let absolute_path = Utf8PathBuf::try_from(std::path::absolute(module)?)?;
+            let relative_path = absolute_path
+                .strip_prefix(git_root)
+                .unwrap_or(&absolute_path);
+            packages::components_containing(packages, relative_path)
+        } else {
+            module_components(git_root, packages, module).await?
+        };
+
+        if components.is_empty() {
+            tracing::warn!("`{module}` isn't in any package's source directories");
+        }
+
+        for (package, component) in components {
+            print_stdout(&format!(
+                "{module}:{}:{}:{}",
+                package.name, component.kind, component.name
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn is_path(module: &str) -> bool {
+    module.contains('/')
+        || Utf8Path::new(module)
+            .extension()
+            .is_some_and(|extension| matches!(extension, "hs" | "lhs" | "hsc"))
+}
+
+/// Components with a file for a module name in one of their source directories
+async fn module_components<'a>(
+    git_root: &Utf8Path,
+    packages: &'a [Package],
+    module: &str,
+) -> eyre::Result<Vec<(&'a Package, &'a Component)>> {
+    let module_path = Utf8PathBuf::from(module.replace('.', "/"));
+    let mut components = Vec::new();
+    for package in packages {
+        for component in &package.components {
+            for dir in &component.source_dirs {
+                let mut exists = false;
+                for extension in ["hs", "lhs", "hsc"] {
+                    let path = git_root
+                        .join(dir)
+                        .join(module_path.with_extension(extension));
+                    if fs::try_exists(&path).await? {
+                        exists = true;
+                        break;
+                    }
+                }
+                if exists {
+                    components.push((package, component));
+                    break;
+                }
+            }
+        }
+    }
+    Ok(components)
+}
@@ -0,0 +1,111 @@
+use crate::{
+    cli::query::RunArgs,
+    config::Pipeline,
+    events::message,
+    git,
+    io::{print_stdout, read_file},
+    lint::parse,
+    query::{LANGUAGE, captures},
+};
+use camino::Utf8PathBuf;
+use color_eyre::eyre::{self, WrapErr as _};
+use num_format::{Locale, ToFormattedString as _};
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::fs;
+
+#[derive(Serialize)]
+struct JsonCapture<'a> {
+    path: &'a str,
+    line: usize,
+    column: usize,
+    capture: &'a str,
+    text: &'a str,
+}
+
+/// Print every capture as `path:line:column:capture:text`, or as JSON, with 1-based lines and
+/// columns. Only the first line of the text is printed, unless it's JSON.
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &RunArgs) -> eyre::Result<()> {
+    let source = match (&args.query, &args.query_file) {
+        (Some(query), _) => query.clone(),
+        (None, Some(path)) => fs::read_to_string(path)
+            .await
+            .wrap_err_with(|| format!("Failed to read query file `{path}`"))?,
+        (None, None) => eyre::bail!("Missing a query"),
+    };
+
+    let query = Arc::new(tree_sitter::Query::new(&LANGUAGE, &source).wrap_err("Invalid query")?);
+
+    let paths = if args.paths.is_empty() && args.packages.is_empty() {
+        git::changed_haskell_files(Pipeline::Lint).await?
+    } else {
+        git::haskell_files(&args.paths, &args.packages, Pipeline::Lint).await?
+    };
+
+    let json = args.json;
+    let mut handles = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        let query = Arc::clone(&query);
+        handles.push(tokio::spawn(async move {
+            let (bytes, _) = read_file(&path).await?;
+            let (source_code, tree) = parse(&bytes)?;
+            let mut lines = Vec::new();
+            for capture in captures(&source_code, &tree, &query) {
+                let line = capture.line + 1;
+                let column = capture.column + 1;
+                lines.push(if json {
+                    serde_json::to_string(&JsonCapture {
+                        path: path.as_str(),
+                        line,
+                        column,
+                        capture: capture.name,
+                        text: capture.text,
+                    })?
+                } else {
+                    let text = capture.text.lines().next().unwrap_or_default();
+                    format!("{path}:{line}:{column}:{}:{text}", capture.name)
+                });
+            }
+            eyre::Ok((path, lines))
+        }));
+    }
+
+    let mut modules: Vec<(Utf8PathBuf, Vec<String>)> = Vec::with_capacity(handles.len());
+    for handle in handles {
+        modules.push(handle.await??);
+    }
+    modules.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut capture_count = 0;
+    let mut module_count = 0;
+    for (_, lines) in &modules {
+        if !lines.is_empty() {
+            module_count += 1;
+        }
+        for line in lines {
+            print_stdout(line);
+            capture_count += 1;
+        }
+    }
+
+    message!(
+        "Found {capture_count} {captures} in {module_count} of {total_count} Haskell {modules}",
+        capture_count = capture_count.to_formatted_string(&Locale::en),
+        captures = if capture_count == 1 {
+            "capture"
+        } else {
+            "captures"
+        },
+        module_count = module_count.to_formatted_string(&Locale::en),
+        total_count = modules.len().to_formatted_string(&Locale::en),
+        modules = if modules.len() == 1 {
+            "module"
+        } else {
+            "modules"
+        },
+    );
+
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use crate::{cli::query::ServeArgs, context::cx, events::message, packages::Package};
+use camino::Utf8Path;
+use color_eyre::eyre;
+use serde::Serialize;
+use sqlx::sqlite::SqlitePool;
+use std::net::Ipv4Addr;
+use tokio::{
+    io::{AsyncBufReadExt as _, AsyncWriteExt as _, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+const INDEX_HTML: &str = include_str!("serve.html");
+
+#[derive(Serialize)]
+struct Graph {
+    packages: Vec<String>,
+    modules: Vec<Module>,
+    imports: Vec<Import>,
+}
+
+#[derive(Serialize)]
+struct Module {
+    path: String,
+    name: String,
+    package: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Import {
+    source: String,
+    target: String,
+}
+
+/// Serve an interactive view of the module graph until interrupted
+#[tracing::instrument(skip_all)]
+pub async fn serve(sqlite: &SqlitePool, args: &ServeArgs) -> eyre::Result<()> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, args.port)).await?;
+
+    let address = listener.local_addr()?;
+
+    message!("Serving the module graph at http://{address}");
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let sqlite = sqlite.clone();
+        tokio::spawn(async move {
+            if let Err(error) = respond(&sqlite, stream).await {
+                tracing::warn!("Failed to respond to request: {error}");
+            }
+        });
+    }
+}
+
+#[tracing::instrument(skip_all)]
+async fn respond(sqlite: &SqlitePool, stream: TcpStream) -> eyre::Result<()> {
+    let mut stream = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    stream.read_line(&mut request_line).await?;
+
+    // Headers don't matter, but they have to be read before responding
+    loop {
+        let mut header = String::new();
+        if stream.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or_default();
+    let path = target.split('?').next().unwrap_or_default();
+
+    tracing::debug!("{method} {target}");
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/") => ("200 OK", "text/html; charset=utf-8", INDEX_HTML.into()),
+        ("GET", "/graph.json") => (
+            "200 OK",
+            "application/json",
+            serde_json::to_string(&graph(sqlite).await?)?,
+        ),
+        ("GET", _) => ("404 Not Found", "text/plain", String::from("Not found")),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            String::from("Method not allowed"),
+        ),
+    };
+
+    let stream = stream.get_mut();
+    stream
+        .write_all(
+            format!(
+                "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {length}\r\nConnection: close\r\n\r\n",
+                length = body.len(),
+            )
+            .as_bytes(),
+        )
+        .await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await?;
+
+    Ok(())
+}
+
+/// Read the graph on every request, so reindexing shows up after reloading the page
+#[tracing::instrument(skip_all)]
+async fn graph(sqlite: &SqlitePool) -> eyre::Result<Graph> {
+    let cx = cx();
+
+    let packages = cx.cache.packages().await?;
+
+    let git_root = cx.cache.git_root().await?;
+
+    let vertices: Vec<(String, String)> =
+        sqlx::query_as("select path, name from module_vertices where repo = $1 order by name")
+            .bind(git_root.as_str())
+            .fetch_all(sqlite)
+            .await?;
+
+    let imports: Vec<(String, String)> =
+        sqlx::query_as("select source, target from module_edges where repo = $1")
+            .bind(git_root.as_str())
+            .fetch_all(sqlite)
+            .await?;
+
+    let modules = vertices
+        .into_iter()
+        .map(|(path, name)| Module {
+            package: package_of(packages, Utf8Path::new(&path)).map(|package| package.name.clone()),
+            path,
+            name,
+        })
+        .collect();
+
+    let imports = imports
+        .into_iter()
+        .map(|(source, target)| Import { source, target })
+        .collect();
+
+    let mut package_names: Vec<_> = packages
+        .iter()
+        .map(|package| package.name.clone())
+        .collect();
+    package_names.sort();
+    package_names.dedup();
+
+    Ok(Graph {
+        packages: package_names,
+        modules,
+        imports,
+    })
+}
+
+/// The innermost package containing a path, relative to the git root
+fn package_of<'a>(packages: &'a [Package], path: &Utf8Path) -> Option<&'a Package> {
+    packages
+        .iter()
+        .filter(|package| path.starts_with(&package.dir))
+        .max_by_key(|package| package.dir.components().count())
+}
@@ -0,0 +1,238 @@
+use crate::{
+    cli::query::SearchArgs,
+    config::Pipeline,
+    context::cx,
+    events::{Outcome, message},
+    git,
+    io::{print_stdout, read_file},
+    lint::{self, haddock, parse},
+    queue,
+};
+use camino::Utf8Path;
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use sqlx::sqlite::SqlitePool;
+use std::collections::HashSet;
+use tree_sitter::{Node, Tree};
+
+/// A top-level declaration, as stored in the `symbols` table
+struct Symbol {
+    name: String,
+    kind: &'static str,
+    module: String,
+    path: String,
+    line: usize,
+    column: usize,
+    doc: String,
+}
+
+impl Outcome for Vec<Symbol> {
+    fn outcome(&self) -> &'static str {
+        "indexed"
+    }
+}
+
+/// Add every top-level declaration in the repo to the `symbols` table
+#[tracing::instrument(skip_all)]
+pub async fn index(sqlite: &SqlitePool) -> eyre::Result<()> {
+    let cx = cx();
+
+    let git_root = cx.cache.git_root().await?;
+
+    let paths = git::all_haskell_files(Pipeline::Lint).await?;
+
+    let handles = queue::spawn(paths, |path| async move {
+        let (bytes, _) = read_file(&path).await?;
+        let (source_code, tree) = parse(&bytes)?;
+        let git_root = cx.cache.git_root().await?;
+        let absolute_path = camino::Utf8PathBuf::try_from(std::path::absolute(&path)?)?;
+        let relative_path = absolute_path
+            .strip_prefix(git_root)
+            .unwrap_or(&absolute_path);
+        eyre::Ok(symbols(relative_path, &source_code, &tree))
+    })
+    .await?;
+
+    let file_count = handles.len();
+    let mut symbol_count = 0;
+
+    let mut transaction = sqlite.begin().await?;
+
+    sqlx::query("delete from symbols where repo = $1")
+        .bind(git_root.as_str())
+        .execute(&mut *transaction)
+        .await?;
+
+    for handle in handles {
+        for symbol in handle.await?? {
+            sqlx::query("insert into symbols values ($1, $2, $3, $4, $5, $6, $7, $8, $9)")
+                .bind(&symbol.name)
+                .bind(words(&symbol.name))
+                .bind(&symbol.module)
+                .bind(&symbol.doc)
+                .bind(symbol.kind)
+                .bind(&symbol.path)
+                .bind(i64::try_from(symbol.line)?)
+                .bind(i64::try_from(symbol.column)?)
+                .bind(git_root.as_str())
+                .execute(&mut *transaction)
+                .await?;
+            symbol_count += 1;
+        }
+    }
+
+    transaction.commit().await?;
+
+    message!(
+        "Indexed {symbol_count} {declarations} in {file_count} Haskell {files}",
+        symbol_count = symbol_count.to_formatted_string(&Locale::en),
+        declarations = if symbol_count == 1 {
+            "declaration"
+        } else {
+            "declarations"
+        },
+        file_count = file_count.to_formatted_string(&Locale::en),
+        files = if file_count == 1 { "file" } else { "files" },
+    );
+
+    Ok(())
+}
+
+fn symbols(path: &Utf8Path, source_code: &str, tree: &Tree) -> Vec<Symbol> {
+    let root = tree.root_node();
+
+    let module = lint::header_module(source_code, root).unwrap_or("Main");
+
+    let mut seen = HashSet::new();
+    let mut symbols = Vec::new();
+
+    let Some(declarations) = root.child_by_field_name("declarations") else {
+        return symbols;
+    };
+
+    let mut cursor = declarations.walk();
+    for declaration in declarations.named_children(&mut cursor) {
+        for name in haddock::declaration_names(source_code, declaration) {
+            // Keep the first declaration of a name, like its type signature, which has the haddock
+            if !seen.insert(name) {
+                continue;
+            }
+            let position = declaration.start_position();
+            symbols.push(Symbol {
+                name: String::from(name),
+                kind: kind(declaration),
+                module: String::from(module),
+                path: path.to_string(),
+                line: position.row + 1,
+                column: position.column + 1,
+                doc: haddock::haddock(declaration)
+                    .and_then(|comment| source_code.get(comment.byte_range()))
+                    .map(doc_text)
+                    .unwrap_or_default(),
+            });
+        }
+    }
+
+    symbols
+}
+
+fn kind(declaration: Node) -> &'static str {
+    match declaration.kind() {
+        "data_type" => "data",
+        "newtype" => "newtype",
+        "type_synomym" => "type",
+        "class" => "class",
+        "type_family" => "type family",
+        "data_family" => "data family",
+        _ => "value",
+    }
+}
+
+/// Haddock text without comment syntax
+fn doc_text(comment: &str) -> String {
+    let comment = comment.trim();
+    let comment = comment
+        .strip_prefix("{-")
+        .and_then(|comment| comment.strip_suffix("-}"))
+        .unwrap_or(comment);
+    let mut lines = Vec::new();
+    for line in comment.lines() {
+        let line = line.trim_start();
+        let line = line.strip_prefix("--").unwrap_or(line);
+        let line = line.trim_start();
+        let line = line.strip_prefix('|').unwrap_or(line);
+        lines.push(line.trim());
+    }
+    lines.join("\n").trim().to_string()
+}
+
+/// Words of a camel case or snake case name, so searching `module` finds `parseModuleName`
+fn words(name: &str) -> String {
+    let mut words = String::new();
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        if c == '_' || c == '\'' {
+            words.push(' ');
+        } else {
+            if c.is_uppercase() && previous.is_some_and(char::is_lowercase) {
+                words.push(' ');
+            }
+            words.push(c);
+        }
+        previous = Some(c);
+    }
+    words
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn search(sqlite: &SqlitePool, args: &SearchArgs) -> eyre::Result<()> {
+    let git_root = cx().cache.git_root().await?;
+
+    let is_indexed: bool =
+        sqlx::query_scalar("select exists(select 1 from symbols where repo = $1)")
+            .bind(git_root.as_str())
+            .fetch_one(sqlite)
+            .await?;
+
+    if !is_indexed {
+        tracing::info!("Building the query index, since this repo hasn't been indexed yet");
+        index(sqlite).await?;
+    }
+
+    // Quote terms so they're never parsed as FTS5 syntax, and match them as prefixes
+    let query = args
+        .terms
+        .iter()
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    // Names count for more than module names, which count for more than haddocks
+    let rows: Vec<(String, String, String, String, String, i64, i64)> = sqlx::query_as(
+        "
+        select name, module, doc, kind, path, start_line, start_column
+        from symbols
+        where symbols match $1
+          and repo = $2
+        order by bm25(symbols, 10.0, 5.0, 2.0, 1.0)
+        limit $3
+        ",
+    )
+    .bind(query)
+    .bind(git_root.as_str())
+    .bind(i64::try_from(args.limit)?)
+    .fetch_all(sqlite)
+    .await?;
+
+    for (name, module, doc, kind, path, line, column) in rows {
+        if let Some(summary) = doc.lines().next() {
+            print_stdout(&format!(
+                "{path}:{line}:{column}:{kind} {module}.{name}  -- {summary}"
+            ));
+        } else {
+            print_stdout(&format!("{path}:{line}:{column}:{kind} {module}.{name}"));
+        }
+    }
+
+    Ok(())
+}
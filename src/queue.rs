@@ -0,0 +1,85 @@
+use crate::{
+    context::cx,
+    events::{self, Outcome},
+};
+use camino::Utf8PathBuf;
+use color_eyre::eyre;
+use std::{sync::atomic::Ordering, time::Instant};
+use tokio::{fs, task::JoinHandle};
+
+/// Order in which files are handed to tools
+#[derive(Clone, Copy, Default, Debug, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Schedule {
+    /// Small files finish early, so progress is meaningful quickly while large files run in the
+    /// background
+    #[default]
+    SmallestFirst,
+    LargestFirst,
+    /// Use durations recorded in the cache, treating files without history as instant
+    FastestFirst,
+    /// Use durations recorded in the cache, so the slowest files don't end up running last
+    SlowestFirst,
+    /// Keep the order paths were given in
+    AsGiven,
+}
+
+/// Spawn a task per path, in the order chosen by the configured schedule. Permits are handed out
+/// first-come first-served, so spawn order is roughly the order in which tools run.
+#[tracing::instrument(skip_all)]
+pub async fn spawn<F, Fut, T>(
+    mut paths: Vec<Utf8PathBuf>,
+    f: F,
+) -> eyre::Result<Vec<JoinHandle<eyre::Result<T>>>>
+where
+    F: Fn(Utf8PathBuf) -> Fut,
+    Fut: Future<Output = eyre::Result<T>> + Send + 'static,
+    T: Outcome + Send + 'static,
+{
+    let schedule = cx().cache.config().await?.schedule;
+
+    tracing::trace!(?schedule, "Scheduling {} files", paths.len());
+
+    match schedule {
+        Schedule::SmallestFirst | Schedule::LargestFirst => {
+            let mut sized_paths = Vec::with_capacity(paths.len());
+            for path in paths {
+                let size = fs::metadata(&path)
+                    .await
+                    .map_or(0, |metadata| metadata.len());
+                sized_paths.push((size, path));
+            }
+            sized_paths.sort_by_key(|(size, _)| *size);
+            if let Schedule::LargestFirst = schedule {
+                sized_paths.reverse();
+            }
+            paths = sized_paths.into_iter().map(|(_, path)| path).collect();
+        }
+        Schedule::FastestFirst | Schedule::SlowestFirst => {
+            let durations = cx().cache.durations_by_path().await?;
+            paths.sort_by_key(|path| durations.get(path).copied().unwrap_or_default());
+            if let Schedule::SlowestFirst = schedule {
+                paths.reverse();
+            }
+        }
+        Schedule::AsGiven => {}
+    }
+
+    cx().files_spawned.fetch_add(paths.len(), Ordering::Relaxed);
+
+    let handles = paths
+        .into_iter()
+        .map(|path| {
+            let future = f(path.clone());
+            tokio::spawn(async move {
+                let start = Instant::now();
+                let result = future.await;
+                cx().files_finished.fetch_add(1, Ordering::Relaxed);
+                events::file(path.as_str(), &result, start.elapsed());
+                result
+            })
+        })
+        .collect();
+
+    Ok(handles)
+}
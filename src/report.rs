@@ -0,0 +1,360 @@
+use crate::{
+    cli::report::Args,
+    config::{Language, Pipeline},
+    context::cx,
+    format, git,
+    lint::{self, HlintHint},
+    state::LintRun,
+};
+use bytes::Bytes;
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre;
+use std::collections::{BTreeMap, BTreeSet};
+use tracing_indicatif::indicatif_println;
+
+/// How many hints with the largest change to show
+const TOP_HINT_COUNT: usize = 10;
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &Args) -> eyre::Result<()> {
+    if let Some(range) = &args.range {
+        run_report_range(range).await
+    } else {
+        run_report_trend(args.limit).await
+    }
+}
+
+#[tracing::instrument]
+async fn run_report_trend(limit: usize) -> eyre::Result<()> {
+    let cx = cx();
+    let git_root = cx.cache.git_root().await?;
+    let state = cx.state().await?;
+
+    let runs = state.lint_runs(git_root, limit).await?;
+
+    let (Some(first), Some(last)) = (runs.first(), runs.last()) else {
+        indicatif_println!("No lint runs recorded yet");
+        return Ok(());
+    };
+
+    let mut previous_total = None;
+
+    for run in &runs {
+        let total = total_count(run);
+        let delta = match previous_total {
+            Some(previous_total) => format!(" ({:+})", total - previous_total),
+            None => String::new(),
+        };
+        let severities = run
+            .severity_counts
+            .iter()
+            .map(|(severity, count)| format!("{count} {severity}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let severities = if severities.is_empty() {
+            severities
+        } else {
+            format!("  {severities}")
+        };
+        indicatif_println!(
+            "{timestamp}  {commit:.10}  {file_count:>6} files  {total:>6} findings{delta}{severities}",
+            timestamp = run.timestamp,
+            commit = run.commit,
+            file_count = run.file_count,
+        );
+        previous_total = Some(total);
+    }
+
+    if first.id == last.id {
+        return Ok(());
+    }
+
+    let first_counts = state.lint_run_hint_counts(first.id).await?;
+    let last_counts = state.lint_run_hint_counts(last.id).await?;
+
+    let hints: BTreeSet<&String> = first_counts.keys().chain(last_counts.keys()).collect();
+
+    let mut changes: Vec<(&String, i64)> = hints
+        .into_iter()
+        .map(|hint| {
+            let before = first_counts.get(hint).copied().unwrap_or(0);
+            let after = last_counts.get(hint).copied().unwrap_or(0);
+            (hint, after - before)
+        })
+        .filter(|(_, change)| *change != 0)
+        .collect();
+
+    changes.sort_by_key(|(_, change)| -change.abs());
+
+    if !changes.is_empty() {
+        indicatif_println!();
+        for (hint, change) in changes.into_iter().take(TOP_HINT_COUNT) {
+            indicatif_println!("{change:>+6}  {hint}");
+        }
+    }
+
+    Ok(())
+}
+
+fn total_count(run: &LintRun) -> i64 {
+    run.severity_counts.values().sum()
+}
+
+/// What a file looked like at either end of a commit range
+struct FileChange {
+    path: Utf8PathBuf,
+    /// Whether the file was formatted before and after, if it existed
+    formatted: (Option<bool>, Option<bool>),
+    /// Hints which appear more often after than before
+    introduced: Vec<(String, usize)>,
+    /// Hints which appear more often before than after
+    fixed: Vec<(String, usize)>,
+}
+
+/// The ends of a commit range like `A..B` or `A...B`, where a left-out end is `HEAD`
+#[derive(Debug, PartialEq, Eq)]
+struct Range<'a> {
+    from: &'a str,
+    to: &'a str,
+    /// Whether it's `A...B`, which starts from where `B` diverged from `A`
+    from_merge_base: bool,
+}
+
+impl<'a> Range<'a> {
+    fn parse(range: &'a str) -> Option<Self> {
+        let or_head = |revision: &'a str| {
+            if revision.is_empty() {
+                "HEAD"
+            } else {
+                revision
+            }
+        };
+        let (from, to, from_merge_base) = if let Some((from, to)) = range.split_once("...") {
+            (from, to, true)
+        } else {
+            let (from, to) = range.split_once("..")?;
+            (from, to, false)
+        };
+        Some(Self {
+            from: or_head(from),
+            to: or_head(to),
+            from_merge_base,
+        })
+    }
+}
+
+#[tracing::instrument]
+async fn run_report_range(range: &str) -> eyre::Result<()> {
+    let Some(range) = Range::parse(range) else {
+        eyre::bail!("Expected a commit range like `main..HEAD` or `main...HEAD`, got `{range}`");
+    };
+    let from = if range.from_merge_base {
+        // Like `git diff A...B`, only what changed on `B` since it diverged from `A`
+        git::merge_base(range.from, range.to).await?
+    } else {
+        String::from(range.from)
+    };
+    let to = String::from(range.to);
+
+    let config = cx().cache.config().await?;
+
+    let mut handles = Vec::new();
+
+    for (path, existed_before, exists_after) in git::changed_files_between(&from, &to).await? {
+        let language = if config.is_file_type(Language::Haskell, Pipeline::Format, &path)
+            || config.is_file_type(Language::Haskell, Pipeline::Lint, &path)
+        {
+            Language::Haskell
+        } else if config.is_file_type(Language::Nix, Pipeline::Format, &path) {
+            Language::Nix
+        } else if config.is_file_type(Language::Dhall, Pipeline::Format, &path) {
+            Language::Dhall
+        } else {
+            continue;
+        };
+        let from = from.clone();
+        let to = to.clone();
+        handles.push(tokio::spawn(async move {
+            let before = if existed_before {
+                Some(git::show_file(&from, &path).await?)
+            } else {
+                None
+            };
+            let after = if exists_after {
+                Some(git::show_file(&to, &path).await?)
+            } else {
+                None
+            };
+            file_change(path, language, before, after).await
+        }));
+    }
+
+    let mut changes = Vec::with_capacity(handles.len());
+    for handle in handles {
+        changes.push(handle.await??);
+    }
+
+    let became_formatted: Vec<_> = changes
+        .iter()
+        .filter(|change| matches!(change.formatted, (Some(false), Some(true))))
+        .collect();
+    let became_unformatted: Vec<_> = changes
+        .iter()
+        .filter(|change| matches!(change.formatted, (None | Some(true), Some(false))))
+        .collect();
+
+    print_section("Became formatted", became_formatted.len());
+    for change in &became_formatted {
+        indicatif_println!("  {}", change.path);
+    }
+
+    print_section("Became unformatted", became_unformatted.len());
+    for change in &became_unformatted {
+        indicatif_println!("  {}", change.path);
+    }
+
+    let introduced_count = changes
+        .iter()
+        .flat_map(|change| &change.introduced)
+        .map(|(_, count)| count)
+        .sum();
+    print_section("Introduced hints", introduced_count);
+    for change in &changes {
+        for (hint, count) in &change.introduced {
+            indicatif_println!("  {}: {hint} (x{count})", change.path);
+        }
+    }
+
+    let fixed_count = changes
+        .iter()
+        .flat_map(|change| &change.fixed)
+        .map(|(_, count)| count)
+        .sum();
+    print_section("Fixed hints", fixed_count);
+    for change in &changes {
+        for (hint, count) in &change.fixed {
+            indicatif_println!("  {}: {hint} (x{count})", change.path);
+        }
+    }
+
+    Ok(())
+}
+
+fn print_section(title: &str, count: usize) {
+    indicatif_println!("{title} ({count}):");
+    if count == 0 {
+        indicatif_println!("  (none)");
+    }
+}
+
+async fn file_change(
+    path: Utf8PathBuf,
+    language: Language,
+    before: Option<Bytes>,
+    after: Option<Bytes>,
+) -> eyre::Result<FileChange> {
+    let formatted_before = match &before {
+        Some(bytes) => Some(is_formatted(language, bytes.clone()).await?),
+        None => None,
+    };
+    let formatted_after = match &after {
+        Some(bytes) => Some(is_formatted(language, bytes.clone()).await?),
+        None => None,
+    };
+
+    let mut introduced = Vec::new();
+    let mut fixed = Vec::new();
+
+    if language == Language::Haskell {
+        let hints_before = hint_counts(&path, before).await?;
+        let hints_after = hint_counts(&path, after).await?;
+        let hints: BTreeSet<&String> = hints_before.keys().chain(hints_after.keys()).collect();
+        for hint in hints {
+            let count_before = hints_before.get(hint).copied().unwrap_or(0);
+            let count_after = hints_after.get(hint).copied().unwrap_or(0);
+            if count_after > count_before {
+                introduced.push((hint.clone(), count_after - count_before));
+            } else if count_before > count_after {
+                fixed.push((hint.clone(), count_before - count_after));
+            }
+        }
+    }
+
+    Ok(FileChange {
+        path,
+        formatted: (formatted_before, formatted_after),
+        introduced,
+        fixed,
+    })
+}
+
+async fn is_formatted(language: Language, bytes: Bytes) -> eyre::Result<bool> {
+    match language {
+        Language::Haskell => format::is_haskell_formatted(bytes).await,
+        Language::Nix => format::is_nix_formatted(bytes).await,
+        Language::Dhall => format::is_dhall_formatted(bytes).await,
+        Language::Shell => format::is_shell_formatted(bytes).await,
+    }
+}
+
+/// Counts of each `Severity: hint` in a file, or nothing if it doesn't exist
+async fn hint_counts(
+    path: &Utf8Path,
+    bytes: Option<Bytes>,
+) -> eyre::Result<BTreeMap<String, usize>> {
+    let mut counts = BTreeMap::new();
+    if let Some(bytes) = bytes {
+        let hints: Vec<HlintHint> = lint::haskell_hints(path, bytes).await?;
+        for hint in hints {
+            *counts
+                .entry(format!("{}: {}", hint.severity, hint.hint))
+                .or_default() += 1;
+        }
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Range;
+
+    fn range<'a>(from: &'a str, to: &'a str, from_merge_base: bool) -> Range<'a> {
+        Range {
+            from,
+            to,
+            from_merge_base,
+        }
+    }
+
+    #[test]
+    fn two_dots() {
+        assert_eq!(
+            Range::parse("main..HEAD"),
+            Some(range("main", "HEAD", false))
+        );
+        assert_eq!(Range::parse("main.."), Some(range("main", "HEAD", false)));
+        assert_eq!(
+            Range::parse("..feature"),
+            Some(range("HEAD", "feature", false))
+        );
+    }
+
+    #[test]
+    fn three_dots() {
+        assert_eq!(
+            Range::parse("main...HEAD"),
+            Some(range("main", "HEAD", true))
+        );
+        assert_eq!(Range::parse("main..."), Some(range("main", "HEAD", true)));
+        assert_eq!(
+            Range::parse("origin/main...feature"),
+            Some(range("origin/main", "feature", true)),
+        );
+    }
+
+    #[test]
+    fn not_a_range() {
+        assert_eq!(Range::parse("main"), None);
+        assert_eq!(Range::parse("main.HEAD"), None);
+    }
+}
@@ -0,0 +1,432 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{self, ContextCompat as _};
+use etcetera::app_strategy::{AppStrategy as _, AppStrategyArgs, Xdg};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqliteSynchronous};
+use std::{collections::BTreeMap, env, str::FromStr as _};
+use tokio::{fs, sync::OnceCell};
+use twox_hash::XxHash3_64;
+
+/// Number of runs per repo whose overwritten files are kept for `be undo`
+const JOURNAL_RUNS: i64 = 20;
+
+/// Long-lived data about past runs. Unlike `Cache`, this is never reset when `be` changes.
+pub struct State {
+    sqlite: SqlitePool,
+    /// Copies of overwritten files, in a directory per run
+    journal_dir: Utf8PathBuf,
+    /// This run's entry in the journal, created when it first writes a file
+    journal_run: OnceCell<i64>,
+}
+
+pub struct JournalRun {
+    pub id: i64,
+    pub timestamp: String,
+    pub command: String,
+    pub file_count: i64,
+    pub undone: bool,
+}
+
+pub struct JournalFile {
+    pub path: Utf8PathBuf,
+    /// Hash of the contents before the run, or `None` if the run created the file
+    pub previous_hash: Option<u64>,
+    /// Hash of the contents the run left, or `None` if the run removed the file
+    pub new_hash: Option<u64>,
+}
+
+pub struct LintRun {
+    pub id: i64,
+    pub timestamp: String,
+    pub commit: String,
+    pub file_count: i64,
+    pub severity_counts: BTreeMap<String, i64>,
+}
+
+impl State {
+    #[tracing::instrument]
+    pub async fn new() -> eyre::Result<Self> {
+        let xdg = Xdg::new(AppStrategyArgs {
+            top_level_domain: String::from("com"),
+            author: String::from("Evan Relf"),
+            app_name: String::from("Be"),
+        })?;
+
+        let xdg_state_dir = xdg.state_dir().unwrap_or_else(|| xdg.data_dir());
+
+        fs::create_dir_all(&xdg_state_dir).await?;
+
+        let xdg_state_dir = Utf8PathBuf::try_from(xdg_state_dir)?;
+
+        let sqlite_path = xdg_state_dir.join("state.sqlite");
+
+        let sqlite_url = format!("sqlite://{sqlite_path}");
+
+        let sqlite_opts = SqliteConnectOptions::from_str(&sqlite_url)?
+            .journal_mode(SqliteJournalMode::Wal)
+            .synchronous(SqliteSynchronous::Normal)
+            .create_if_missing(true);
+
+        let sqlite = SqlitePool::connect_with(sqlite_opts).await?;
+
+        sqlx::raw_sql(
+            "
+            create table if not exists lint_runs (
+                id integer primary key,
+                repo text not null,
+                timestamp text not null default (datetime('now')),
+                commit_hash text not null,
+                file_count integer not null
+            ) strict;
+
+            create table if not exists lint_run_counts (
+                run_id integer not null references lint_runs,
+                severity text not null,
+                hint text not null,
+                count integer not null,
+                unique (run_id, severity, hint)
+            ) strict;
+
+            create table if not exists journal_runs (
+                id integer primary key,
+                repo text not null,
+                timestamp text not null default (datetime('now')),
+                command text not null,
+                undone integer not null default 0,
+                undoes integer references journal_runs
+            ) strict;
+
+            create table if not exists journal_files (
+                run_id integer not null references journal_runs,
+                path text not null,
+                previous_hash text,
+                new_hash text,
+                unique (run_id, path)
+            ) strict;
+            ",
+        )
+        .execute(&sqlite)
+        .await?;
+
+        Ok(Self {
+            sqlite,
+            journal_dir: xdg_state_dir.join("journal"),
+            journal_run: OnceCell::new(),
+        })
+    }
+
+    /// Keep a copy of a file's contents before this run overwrites or removes it, so `be undo` can
+    /// restore it
+    #[tracing::instrument(skip(self, previous, new))]
+    pub async fn journal_write(
+        &self,
+        repo: &Utf8Path,
+        path: &Utf8Path,
+        previous: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> eyre::Result<()> {
+        let run_id = *self
+            .journal_run
+            .get_or_try_init(|| self.start_journal_run(repo))
+            .await?;
+
+        let previous_hash = previous.map(XxHash3_64::oneshot);
+        let new_hash = new.map(XxHash3_64::oneshot);
+
+        if let (Some(previous), Some(previous_hash)) = (previous, previous_hash) {
+            let run_dir = self.journal_dir.join(run_id.to_string());
+            fs::create_dir_all(&run_dir).await?;
+            fs::write(run_dir.join(previous_hash.to_string()), previous).await?;
+        }
+
+        let path = Utf8PathBuf::try_from(std::path::absolute(path)?)?;
+
+        // A file written more than once keeps its contents from before the run
+        sqlx::query(
+            "
+            insert into journal_files values ($1, $2, $3, $4)
+            on conflict (run_id, path) do update set new_hash = excluded.new_hash
+            ",
+        )
+        .bind(run_id)
+        .bind(path.as_str())
+        .bind(previous_hash.map(|hash| hash.to_string()))
+        .bind(new_hash.map(|hash| hash.to_string()))
+        .execute(&self.sqlite)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn start_journal_run(&self, repo: &Utf8Path) -> eyre::Result<i64> {
+        let mut command = String::from("be");
+        for arg in env::args().skip(1) {
+            command.push(' ');
+            command.push_str(&arg);
+        }
+
+        let run_id: i64 = sqlx::query_scalar(
+            "
+            insert into journal_runs (repo, command)
+            values ($1, $2)
+            returning id
+            ",
+        )
+        .bind(repo.as_str())
+        .bind(command)
+        .fetch_one(&self.sqlite)
+        .await?;
+
+        let old_run_ids: Vec<i64> = sqlx::query_scalar(
+            "
+            select id
+            from journal_runs
+            where repo = $1
+            order by id desc
+            limit -1 offset $2
+            ",
+        )
+        .bind(repo.as_str())
+        .bind(JOURNAL_RUNS)
+        .fetch_all(&self.sqlite)
+        .await?;
+
+        for old_run_id in old_run_ids {
+            tracing::debug!("Forgetting journal run {old_run_id}");
+            sqlx::query("delete from journal_files where run_id = $1")
+                .bind(old_run_id)
+                .execute(&self.sqlite)
+                .await?;
+            sqlx::query("update journal_runs set undoes = null where undoes = $1")
+                .bind(old_run_id)
+                .execute(&self.sqlite)
+                .await?;
+            sqlx::query("delete from journal_runs where id = $1")
+                .bind(old_run_id)
+                .execute(&self.sqlite)
+                .await?;
+            let run_dir = self.journal_dir.join(old_run_id.to_string());
+            if let Err(error) = fs::remove_dir_all(&run_dir).await
+                && error.kind() != std::io::ErrorKind::NotFound
+            {
+                return Err(error.into());
+            }
+        }
+
+        Ok(run_id)
+    }
+
+    /// Most recent runs in a repo which wrote files, newest first
+    #[tracing::instrument(skip(self))]
+    pub async fn journal_runs(&self, repo: &Utf8Path) -> eyre::Result<Vec<JournalRun>> {
+        let rows: Vec<(i64, String, String, i64, bool)> = sqlx::query_as(
+            "
+            select id, timestamp, command, (
+                select count(*) from journal_files where run_id = id
+            ), undone
+            from journal_runs
+            where repo = $1
+            order by id desc
+            ",
+        )
+        .bind(repo.as_str())
+        .fetch_all(&self.sqlite)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(id, timestamp, command, file_count, undone)| JournalRun {
+                id,
+                timestamp,
+                command,
+                file_count,
+                undone,
+            })
+            .collect())
+    }
+
+    /// Most recent run in a repo which hasn't been undone, and isn't itself an undo
+    #[tracing::instrument(skip(self))]
+    pub async fn last_journal_run(&self, repo: &Utf8Path) -> eyre::Result<Option<i64>> {
+        let run_id = sqlx::query_scalar(
+            "
+            select id
+            from journal_runs
+            where repo = $1
+              and undone = 0
+              and undoes is null
+            order by id desc
+            limit 1
+            ",
+        )
+        .bind(repo.as_str())
+        .fetch_optional(&self.sqlite)
+        .await?;
+
+        Ok(run_id)
+    }
+
+    /// Files a run in a repo wrote, or none if the run was in another repo
+    #[tracing::instrument(skip(self))]
+    pub async fn journal_files(
+        &self,
+        repo: &Utf8Path,
+        run_id: i64,
+    ) -> eyre::Result<Vec<JournalFile>> {
+        let rows: Vec<(String, Option<String>, Option<String>)> = sqlx::query_as(
+            "
+            select path, previous_hash, new_hash
+            from journal_files
+            join journal_runs on journal_runs.id = journal_files.run_id
+            where run_id = $1
+              and repo = $2
+            order by path
+            ",
+        )
+        .bind(run_id)
+        .bind(repo.as_str())
+        .fetch_all(&self.sqlite)
+        .await?;
+
+        let parse = |hash: Option<String>| hash.map(|hash| hash.parse()).transpose();
+
+        let mut files = Vec::with_capacity(rows.len());
+        for (path, previous_hash, new_hash) in rows {
+            files.push(JournalFile {
+                path: Utf8PathBuf::from(path),
+                previous_hash: parse(previous_hash)?,
+                new_hash: parse(new_hash)?,
+            });
+        }
+        Ok(files)
+    }
+
+    /// Contents of a file from before a run overwrote it
+    #[tracing::instrument(skip(self))]
+    pub async fn journal_contents(&self, run_id: i64, hash: u64) -> eyre::Result<Vec<u8>> {
+        let path = self
+            .journal_dir
+            .join(run_id.to_string())
+            .join(hash.to_string());
+        fs::read(&path)
+            .await
+            .ok()
+            .with_context(|| format!("Missing copy of overwritten file at `{path}`"))
+    }
+
+    /// Mark a run as undone, by this run if it wrote anything
+    #[tracing::instrument(skip(self))]
+    pub async fn mark_journal_run_undone(&self, run_id: i64) -> eyre::Result<()> {
+        sqlx::query("update journal_runs set undone = 1 where id = $1")
+            .bind(run_id)
+            .execute(&self.sqlite)
+            .await?;
+        if let Some(undo_run_id) = self.journal_run.get() {
+            sqlx::query("update journal_runs set undoes = $1 where id = $2")
+                .bind(run_id)
+                .bind(undo_run_id)
+                .execute(&self.sqlite)
+                .await?;
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self, counts))]
+    pub async fn record_lint_run(
+        &self,
+        repo: &Utf8Path,
+        commit: &str,
+        file_count: usize,
+        counts: &BTreeMap<(String, String), usize>,
+    ) -> eyre::Result<()> {
+        let mut transaction = self.sqlite.begin().await?;
+
+        let run_id: i64 = sqlx::query_scalar(
+            "
+            insert into lint_runs (repo, commit_hash, file_count)
+            values ($1, $2, $3)
+            returning id
+            ",
+        )
+        .bind(repo.as_str())
+        .bind(commit)
+        .bind(i64::try_from(file_count)?)
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        for ((severity, hint), count) in counts {
+            sqlx::query("insert into lint_run_counts values ($1, $2, $3, $4)")
+                .bind(run_id)
+                .bind(severity)
+                .bind(hint)
+                .bind(i64::try_from(*count)?)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    /// Most recent lint runs in a repo, oldest first
+    #[tracing::instrument(skip(self))]
+    pub async fn lint_runs(&self, repo: &Utf8Path, limit: usize) -> eyre::Result<Vec<LintRun>> {
+        let rows: Vec<(i64, String, String, i64)> = sqlx::query_as(
+            "
+            select id, timestamp, commit_hash, file_count
+            from lint_runs
+            where repo = $1
+            order by id desc
+            limit $2
+            ",
+        )
+        .bind(repo.as_str())
+        .bind(i64::try_from(limit)?)
+        .fetch_all(&self.sqlite)
+        .await?;
+
+        let mut runs = Vec::with_capacity(rows.len());
+
+        for (id, timestamp, commit, file_count) in rows.into_iter().rev() {
+            let severity_counts: Vec<(String, i64)> = sqlx::query_as(
+                "
+                select severity, sum(count)
+                from lint_run_counts
+                where run_id = $1
+                group by severity
+                ",
+            )
+            .bind(id)
+            .fetch_all(&self.sqlite)
+            .await?;
+
+            runs.push(LintRun {
+                id,
+                timestamp,
+                commit,
+                file_count,
+                severity_counts: severity_counts.into_iter().collect(),
+            });
+        }
+
+        Ok(runs)
+    }
+
+    /// Finding counts by hint for a lint run
+    #[tracing::instrument(skip(self))]
+    pub async fn lint_run_hint_counts(&self, run_id: i64) -> eyre::Result<BTreeMap<String, i64>> {
+        let rows: Vec<(String, i64)> = sqlx::query_as(
+            "
+            select hint, sum(count)
+            from lint_run_counts
+            where run_id = $1
+            group by hint
+            ",
+        )
+        .bind(run_id)
+        .fetch_all(&self.sqlite)
+        .await?;
+
+        Ok(rows.into_iter().collect())
+    }
+}
@@ -0,0 +1,37 @@
+use camino::Utf8PathBuf;
+use color_eyre::eyre;
+use std::sync::Mutex;
+use tempfile::{TempDir, tempdir};
+
+/// Temporary directories which live until `be` exits, like copies of tool configs that every
+/// process reads. `Context` is never dropped, so `main` calls `clear` before exiting.
+pub struct TempStore {
+    dirs: Mutex<Vec<TempDir>>,
+}
+
+impl TempStore {
+    pub fn new() -> Self {
+        Self {
+            dirs: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Create a directory which is removed by `clear`
+    pub fn dir(&self) -> eyre::Result<Utf8PathBuf> {
+        let temp_dir = tempdir()?;
+        let path = Utf8PathBuf::try_from(temp_dir.path().to_path_buf())?;
+        self.dirs.lock().unwrap().push(temp_dir);
+        Ok(path)
+    }
+
+    /// Remove every directory
+    pub fn clear(&self) {
+        let dirs = std::mem::take(&mut *self.dirs.lock().unwrap());
+        for dir in dirs {
+            let path = dir.path().to_path_buf();
+            if let Err(error) = dir.close() {
+                tracing::warn!("Failed to remove `{}`: {error}", path.display());
+            }
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{
+    cli::undo::Args,
+    context::cx,
+    events::message,
+    io::{read_file, remove_file, write_file},
+    state::JournalFile,
+};
+use bytes::Bytes;
+use color_eyre::eyre;
+use num_format::{Locale, ToFormattedString as _};
+use tokio::fs;
+use tracing_indicatif::indicatif_println;
+
+#[tracing::instrument(skip_all)]
+pub async fn run(args: &Args) -> eyre::Result<()> {
+    let cx = cx();
+
+    let git_root = cx.cache.git_root().await?;
+
+    let state = cx.state().await?;
+
+    if args.list {
+        for run in state.journal_runs(git_root).await? {
+            indicatif_println!(
+                "{id}  {timestamp}  {file_count} {files}  {command}{undone}",
+                id = run.id,
+                timestamp = run.timestamp,
+                file_count = run.file_count.to_formatted_string(&Locale::en),
+                files = if run.file_count == 1 { "file" } else { "files" },
+                command = run.command,
+                undone = if run.undone { " (undone)" } else { "" },
+            );
+        }
+        return Ok(());
+    }
+
+    let run_id = match args.run {
+        Some(run_id) => run_id,
+        None => match state.last_journal_run(git_root).await? {
+            Some(run_id) => run_id,
+            None => eyre::bail!("No runs to undo"),
+        },
+    };
+
+    let files = state.journal_files(git_root, run_id).await?;
+
+    if files.is_empty() {
+        eyre::bail!("Run {run_id} didn't write any files in this repo");
+    }
+
+    let total_count = files.len();
+    let mut restored_count = 0;
+    let mut skipped_count = 0;
+
+    for file in files {
+        if restore(run_id, &file, args.force).await? {
+            restored_count += 1;
+        } else {
+            skipped_count += 1;
+        }
+    }
+
+    state.mark_journal_run_undone(run_id).await?;
+
+    message!(
+        "Restored {restored_count} of {total_count} {files} from run {run_id}",
+        restored_count = restored_count.to_formatted_string(&Locale::en),
+        total_count = total_count.to_formatted_string(&Locale::en),
+        files = if total_count == 1 { "file" } else { "files" },
+    );
+
+    if skipped_count > 0 {
+        eyre::bail!(
+            "Skipped {skipped_count} {files} which changed after run {run_id} (use `--force` to restore anyway)",
+            skipped_count = skipped_count.to_formatted_string(&Locale::en),
+            files = if skipped_count == 1 { "file" } else { "files" },
+        );
+    }
+
+    Ok(())
+}
+
+/// Put back a file's contents from before the run. Returns whether it was restored, or skipped
+/// because it changed since the run.
+#[tracing::instrument(skip(file), fields(path = %file.path))]
+async fn restore(run_id: i64, file: &JournalFile, force: bool) -> eyre::Result<bool> {
+    let path = &file.path;
+
+    let current_hash = if fs::try_exists(path).await? {
+        Some(read_file(path).await?.1)
+    } else {
+        None
+    };
+
+    if current_hash == file.previous_hash {
+        tracing::debug!("Already restored `{path}`");
+        return Ok(true);
+    }
+
+    if current_hash != file.new_hash && !force {
+        tracing::warn!("Not restoring `{path}`, since it changed after run {run_id}");
+        return Ok(false);
+    }
+
+    match file.previous_hash {
+        Some(previous_hash) => {
+            let state = cx().state().await?;
+            let bytes = state.journal_contents(run_id, previous_hash).await?;
+            write_file(path, Bytes::from(bytes)).await?;
+        }
+        // The run created this file
+        None => remove_file(path).await?,
+    }
+
+    tracing::debug!("Restored `{path}`");
+
+    Ok(true)
+}
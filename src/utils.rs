@@ -1,3 +1,5 @@
+use num_format::{Locale, ToFormattedString as _};
+
 pub async fn flatten<T, E1, E2>(
     future: impl Future<Output = Result<Result<T, E2>, E1>>,
 ) -> Result<T, E2>
@@ -7,3 +9,26 @@ where
 {
     future.await?
 }
+
+/// A size for people to read, like `1,234 kB`
+pub fn kilobytes(bytes: u64) -> String {
+    format!(
+        "{} kB",
+        bytes.div_ceil(1000).to_formatted_string(&Locale::en)
+    )
+}
+
+/// Labels and counts in aligned columns, like an end-of-run summary
+pub fn table(rows: &[(&str, usize)]) -> String {
+    let counts: Vec<String> = rows
+        .iter()
+        .map(|(_, count)| count.to_formatted_string(&Locale::en))
+        .collect();
+    let label_width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+    let count_width = counts.iter().map(String::len).max().unwrap_or(0);
+    rows.iter()
+        .zip(&counts)
+        .map(|((label, _), count)| format!("{label:<label_width$}  {count:>count_width$}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
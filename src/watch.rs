@@ -0,0 +1,121 @@
+use crate::{context::cx, events::message, exit::Findings, git};
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre;
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    time::{Duration, SystemTime},
+};
+
+/// How often files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+/// How long files must stay unchanged before running again, so saving several files at once (or an
+/// editor writing a file in steps) only runs once
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// When each file or directory was modified, and its size
+type Snapshot = HashMap<Utf8PathBuf, (Option<SystemTime>, u64)>;
+
+/// Files in the repo, and the directories they're in
+struct Watched {
+    files: Vec<Utf8PathBuf>,
+    dirs: HashSet<Utf8PathBuf>,
+}
+
+/// Run a command, and then again whenever files in the repo change, until Ctrl-C.
+///
+/// Files are polled rather than watched with something like `inotify`, which works the same
+/// everywhere. The cost is a `stat` of every tracked and untracked file, and every directory in the
+/// repo containing them, each `POLL_INTERVAL`, which adds up in a large repo. Files are only listed
+/// again when a directory changes, which is the only way files are added, removed, or renamed. After the first run, changed files are only the ones which changed
+/// since the last run. Each run reuses `Context`, so tool versions and config are only looked up
+/// once, and unchanged files are cache hits. Changing `be.toml` needs a restart.
+pub async fn run<F, Fut>(mut run_once: F) -> eyre::Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = eyre::Result<()>>,
+{
+    let git_root = cx().cache.git_root().await?;
+
+    loop {
+        match run_once().await {
+            Ok(()) => {}
+            Err(error) if error.is::<Findings>() => message!("{error}"),
+            Err(error) => message!("Error: {error:?}"),
+        }
+
+        // Taken after running, so files `be` just wrote don't count as changes
+        let mut watched = list().await?;
+        let before = snapshot(&watched).await?;
+
+        message!("Watching for changes (press Ctrl-C to stop)");
+
+        let mut after = before.clone();
+        loop {
+            let is_settling = after != before;
+            tokio::time::sleep(if is_settling { DEBOUNCE } else { POLL_INTERVAL }).await;
+            let mut next = snapshot(&watched).await?;
+            if watched
+                .dirs
+                .iter()
+                .any(|dir| next.get(dir) != after.get(dir))
+            {
+                watched = list().await?;
+                next = snapshot(&watched).await?;
+            }
+            // Wait for files to settle
+            if is_settling && next == after {
+                break;
+            }
+            after = next;
+        }
+
+        let mut changed = Vec::new();
+        for path in &watched.files {
+            if after.get(path) != before.get(path) {
+                let path = Utf8PathBuf::try_from(std::path::absolute(path)?)?;
+                changed.push(relative(&path, git_root));
+            }
+        }
+        *cx().watched.lock().unwrap() = Some(changed);
+    }
+}
+
+async fn list() -> eyre::Result<Watched> {
+    let git_root = cx().cache.git_root().await?;
+    let files = git::tracked_and_untracked_files(std::slice::from_ref(git_root)).await?;
+    let mut dirs = HashSet::from([git_root.clone()]);
+    for file in &files {
+        let file = Utf8PathBuf::try_from(std::path::absolute(file)?)?;
+        dirs.extend(
+            file.ancestors()
+                .skip(1)
+                .take_while(|dir| dir.starts_with(git_root))
+                .map(Utf8Path::to_path_buf),
+        );
+    }
+    Ok(Watched { files, dirs })
+}
+
+async fn snapshot(watched: &Watched) -> eyre::Result<Snapshot> {
+    let paths: Vec<_> = watched.files.iter().chain(&watched.dirs).cloned().collect();
+    let snapshot = tokio::task::spawn_blocking(move || {
+        paths
+            .into_iter()
+            .map(|path| {
+                // Deleted files count as changed too
+                let stat = std::fs::metadata(&path).map_or((None, 0), |metadata| {
+                    (metadata.modified().ok(), metadata.len())
+                });
+                (path, stat)
+            })
+            .collect()
+    })
+    .await?;
+    Ok(snapshot)
+}
+
+fn relative(path: &Utf8Path, git_root: &Utf8Path) -> Utf8PathBuf {
+    path.strip_prefix(git_root).unwrap_or(path).to_path_buf()
+}